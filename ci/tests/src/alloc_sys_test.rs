@@ -0,0 +1,54 @@
+use alloc::alloc::{alloc, dealloc, Layout};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use psp::alloc_sys::{self, HeapError};
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_runner.check("alloc_sys_no_capacity", alloc_sys::capacity(), 0);
+    test_runner.check(
+        "alloc_sys_too_small",
+        alloc_sys::init(1024),
+        Err(HeapError::TooSmall(1024)),
+    );
+
+    // Allocated from the partition, and freed after the heap is set up.
+    let before = Box::new([7u8; 100]);
+
+    let size = alloc_sys::init(1024 * 1024).unwrap();
+    test_runner.check_true("alloc_sys_size", size > 1024 * 1024 - 64);
+    test_runner.check("alloc_sys_capacity", alloc_sys::capacity(), size);
+    test_runner.check("alloc_sys_all_free", alloc_sys::free(), size);
+    test_runner.check(
+        "alloc_sys_init_twice",
+        alloc_sys::init(1024 * 1024),
+        Err(HeapError::AlreadyInitialized),
+    );
+
+    test_runner.check("alloc_sys_old_box", before[99], 7);
+    drop(before);
+    test_runner.check("alloc_sys_old_box_freed", alloc_sys::free(), size);
+
+    let values: Vec<u32> = (0..1000).collect();
+    test_runner.check_true("alloc_sys_vec_allocated", alloc_sys::free() < size - 4000);
+    test_runner.check("alloc_sys_vec_values", values[999], 999);
+
+    unsafe {
+        let layout = Layout::from_size_align(100, 4096).unwrap();
+        let ptr = alloc(layout);
+        test_runner.check_true(
+            "alloc_sys_aligned",
+            !ptr.is_null() && ptr as usize % 4096 == 0,
+        );
+        dealloc(ptr, layout);
+    }
+
+    // Freed chunks are merged back together.
+    drop(values);
+    test_runner.check("alloc_sys_all_freed", alloc_sys::free(), size);
+
+    unsafe {
+        let layout = Layout::from_size_align(size + 1, 4).unwrap();
+        test_runner.check_true("alloc_sys_full", alloc(layout).is_null());
+    }
+}
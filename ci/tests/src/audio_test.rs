@@ -0,0 +1,321 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use psp::audio::atrac::{AtracError, Player};
+use psp::audio::capture::{CaptureError, Mic};
+use psp::audio::mp3::{self, Mp3Error};
+use psp::audio::wav::{self, WavError};
+use psp::audio::{
+    self, AudioError, Channel, Decoder, Format, Mixer, Sound, Stream, VoiceSettings, Waveform,
+    SAMPLE_RATE,
+};
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_runner.check(
+        "audio_block_too_small",
+        Channel::reserve(32, Format::Stereo16).err(),
+        Some(AudioError::InvalidBlockSize(32)),
+    );
+    test_runner.check(
+        "audio_block_unaligned",
+        Channel::reserve(100, Format::Mono16).err(),
+        Some(AudioError::InvalidBlockSize(100)),
+    );
+
+    // All 8 channels can be reserved, but not a 9th.
+    let channels: Vec<_> = (0..8)
+        .filter_map(|_| Channel::reserve(64, Format::Stereo16).ok())
+        .collect();
+    test_runner.check("audio_reserve_all", channels.len(), 8);
+    test_runner.check(
+        "audio_no_free_channel",
+        Channel::reserve(64, Format::Stereo16).err(),
+        Some(AudioError::NoFreeChannel),
+    );
+
+    // Dropping a channel releases it.
+    drop(channels);
+    let mut channel = Channel::reserve(64, Format::Mono16).unwrap();
+
+    test_runner.check(
+        "audio_wrong_block_length",
+        channel.output_blocking(&[0; 128]),
+        Err(AudioError::WrongBlockLength {
+            expected: 64,
+            actual: 128,
+        }),
+    );
+
+    channel.set_volume(0x1000, u16::MAX);
+    test_runner.check("audio_volume", channel.volume(), (0x1000, 0x8000));
+    test_runner.check("audio_output", channel.output_blocking(&[0; 64]), Ok(()));
+    drop(channel);
+
+    test_runner.check(
+        "audio_src_unsupported_rate",
+        Channel::reserve_src(12_345, 1024).err(),
+        Some(AudioError::UnsupportedRate {
+            rate: 12_345,
+            supported: audio::SRC_SAMPLE_RATES,
+        }),
+    );
+
+    // There is only one SRC channel.
+    let src = Channel::reserve_src(48_000, 1024).unwrap();
+    test_runner.check("audio_src_rate", src.sample_rate(), 48_000);
+    test_runner.check(
+        "audio_src_in_use",
+        Channel::reserve_src(22_050, 1024).err(),
+        Some(AudioError::SrcChannelInUse),
+    );
+    drop(src);
+    test_runner.check_true(
+        "audio_src_released",
+        Channel::reserve_src(22_050, 1024).is_ok(),
+    );
+
+    static FILLED: AtomicUsize = AtomicUsize::new(0);
+    static BLOCK_LEN: AtomicUsize = AtomicUsize::new(0);
+    let channel = Channel::reserve(64, Format::Stereo16).unwrap();
+    let mut stream = Stream::start(channel, |block| {
+        BLOCK_LEN.store(block.len(), Ordering::Relaxed);
+        FILLED.fetch_add(1, Ordering::Relaxed);
+    })
+    .unwrap();
+
+    // A few blocks' worth of frames.
+    for _ in 0..4 {
+        unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    }
+    test_runner.check_true("audio_stream_fills", FILLED.load(Ordering::Relaxed) > 0);
+    test_runner.check(
+        "audio_stream_block_len",
+        BLOCK_LEN.load(Ordering::Relaxed),
+        128,
+    );
+
+    stream.pause();
+    test_runner.check_true("audio_stream_paused", stream.is_paused());
+    stream.resume();
+    test_runner.check_true("audio_stream_resumed", !stream.is_paused());
+
+    // Stopping releases the channel, so all 8 can be reserved again.
+    stream.stop();
+    let channels: Vec<_> = (0..8)
+        .filter_map(|_| Channel::reserve(64, Format::Stereo16).ok())
+        .collect();
+    test_runner.check("audio_stream_released", channels.len(), 8);
+
+    // 16-bit stereo at the output rate is unchanged.
+    let samples = [1i16, -1, 1000, -1000];
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    let sound = wav::load(&wav_file(1, 2, 44_100, 16, &bytes));
+    test_runner.check(
+        "wav_stereo16",
+        sound.as_ref().map(Sound::samples),
+        Ok(&samples[..]),
+    );
+
+    // 8-bit mono at half the rate is doubled in both length and channels.
+    let sound = wav::load(&wav_file(1, 1, 22_050, 8, &[128, 192, 64, 128])).unwrap();
+    test_runner.check("wav_resampled_len", sound.len(), 8);
+    test_runner.check(
+        "wav_resampled_start",
+        &sound.samples()[..6],
+        &[0, 0, 0x2000, 0x2000, 0x4000, 0x4000][..],
+    );
+
+    let file = wav_file(1, 2, 44_100, 16, &bytes);
+    test_runner.check(
+        "wav_truncated",
+        wav::load(&file[..file.len() - 1]).err(),
+        Some(WavError::Truncated),
+    );
+    test_runner.check(
+        "wav_not_pcm",
+        wav::load(&wav_file(3, 2, 44_100, 32, &bytes)).err(),
+        Some(WavError::NotPcm(3)),
+    );
+    test_runner.check(
+        "wav_bits",
+        wav::load(&wav_file(1, 2, 44_100, 24, &bytes)).err(),
+        Some(WavError::UnsupportedBits(24)),
+    );
+    test_runner.check(
+        "wav_not_wav",
+        wav::load(b"not a wav file").err(),
+        Some(WavError::NotWav),
+    );
+
+    let beep = Sound::from_samples(alloc::vec![0; 4096]);
+    test_runner.check("audio_play_once", audio::play_once(&beep), Ok(()));
+
+    let tone = Duration::from_millis(50);
+    test_runner.check(
+        "audio_test_tone",
+        audio::test_tone(440, tone, Waveform::Sine, 0),
+        Ok(()),
+    );
+    test_runner.check(
+        "audio_test_tone_noise",
+        audio::test_tone(22_050, tone, Waveform::Noise, 0),
+        Ok(()),
+    );
+    test_runner.check(
+        "audio_test_tone_above_nyquist",
+        audio::test_tone(22_051, tone, Waveform::Square, 0),
+        Err(AudioError::InvalidFrequency(22_051)),
+    );
+    test_runner.check(
+        "audio_test_tone_zero",
+        audio::test_tone(0, tone, Waveform::Square, 0),
+        Err(AudioError::InvalidFrequency(0)),
+    );
+
+    test_runner.check(
+        "audio_volume_default",
+        audio::volume(),
+        (audio::MAX_VOLUME, audio::MAX_VOLUME),
+    );
+    audio::set_volume(-5, 0x10000);
+    test_runner.check(
+        "audio_volume_clamped",
+        audio::volume(),
+        (0, audio::MAX_VOLUME),
+    );
+    audio::set_volume(0x4000, 0x2000);
+    test_runner.check("audio_volume", audio::volume(), (0x4000, 0x2000));
+    test_runner.check(
+        "audio_volume_ducked_tone",
+        audio::test_tone(440, tone, Waveform::Sine, audio::MAX_VOLUME / 8),
+        Ok(()),
+    );
+    audio::set_volume(audio::MAX_VOLUME as i32, audio::MAX_VOLUME as i32);
+
+    // The emulator has nothing plugged in.
+    test_runner.check("audio_headphones", audio::headphones_connected(), false);
+
+    test_runner.check_true(
+        "atrac_missing_file",
+        matches!(
+            Player::open("host0:/missing.at3", false).err(),
+            Some(AtracError::Open(_))
+        ),
+    );
+    test_runner.check_true(
+        "mp3_missing_file",
+        matches!(
+            mp3::Player::open("host0:/missing.mp3", false).err(),
+            Some(Mp3Error::Open(_))
+        ),
+    );
+
+    let mut mixer = Mixer::start(2).unwrap();
+    let long = Sound::from_samples(alloc::vec![0; SAMPLE_RATE as usize * 2]);
+    let quiet = mixer.play(
+        &long,
+        VoiceSettings {
+            volume: 0x1000,
+            ..Default::default()
+        },
+    );
+    let loud = mixer.play(&long, VoiceSettings::default());
+    test_runner.check("mixer_playing", mixer.playing(), 2);
+
+    // With every slot in use, the quietest voice is replaced, then the
+    // oldest of the loudest.
+    let third = mixer.play(&long, VoiceSettings::default());
+    test_runner.check_true(
+        "mixer_steals_quietest",
+        !mixer.is_playing(quiet) && mixer.is_playing(loud) && mixer.is_playing(third),
+    );
+    let fourth = mixer.play(&long, VoiceSettings::default());
+    test_runner.check_true(
+        "mixer_steals_oldest",
+        !mixer.is_playing(loud) && mixer.is_playing(third) && mixer.is_playing(fourth),
+    );
+
+    mixer.stop(third);
+    test_runner.check("mixer_stopped", mixer.playing(), 1);
+
+    let short = mixer.play(
+        &Sound::from_samples(alloc::vec![0; 256]),
+        VoiceSettings::default(),
+    );
+    for _ in 0..4 {
+        unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    }
+    test_runner.check_true(
+        "mixer_voice_ends",
+        !mixer.is_playing(short) && mixer.is_playing(fourth),
+    );
+
+    test_runner.check(
+        "mixer_decoder_rate",
+        mixer.play_decoder(Silence, VoiceSettings::default()).err(),
+        Some(AudioError::UnsupportedRate {
+            rate: 48_000,
+            supported: &[SAMPLE_RATE],
+        }),
+    );
+
+    test_runner.check(
+        "mic_unsupported_rate",
+        Mic::open(48_000).err(),
+        Some(CaptureError::UnsupportedRate(48_000)),
+    );
+
+    // Either way, opening doesn't hang without a microphone.
+    match Mic::open(22_050) {
+        Ok(_mic) => test_runner.check(
+            "mic_in_use",
+            Mic::open(22_050).err(),
+            Some(CaptureError::InUse),
+        ),
+        Err(e) => test_runner.check("mic_no_device", e, CaptureError::NoDevice),
+    }
+}
+
+/// Endless silence at 48 kHz.
+struct Silence;
+
+impl Decoder for Silence {
+    fn decode(&mut self) -> Option<&[i16]> {
+        Some(&[0; 64])
+    }
+
+    fn sample_rate(&self) -> u32 {
+        48_000
+    }
+
+    fn rewind(&mut self) -> bool {
+        true
+    }
+}
+
+/// A WAV file with a `fmt ` chunk with these fields, and `data`.
+fn wav_file(format: u16, channels: u16, rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+    let block_align = channels * bits / 8;
+    let mut fmt = Vec::new();
+    fmt.extend_from_slice(&format.to_le_bytes());
+    fmt.extend_from_slice(&channels.to_le_bytes());
+    fmt.extend_from_slice(&rate.to_le_bytes());
+    fmt.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+    fmt.extend_from_slice(&block_align.to_le_bytes());
+    fmt.extend_from_slice(&bits.to_le_bytes());
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&(4 + 8 + fmt.len() as u32 + 8 + data.len() as u32).to_le_bytes());
+    file.extend_from_slice(b"WAVE");
+
+    for (id, body) in [(b"fmt ", &fmt[..]), (b"data", data)] {
+        file.extend_from_slice(id);
+        file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        file.extend_from_slice(body);
+    }
+
+    file
+}
@@ -0,0 +1,53 @@
+use psp::ctrl::{self, AnalogConfig, ResponseCurve, SamplingMode};
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // A worn stick resting off center.
+    let config = AnalogConfig {
+        center: (140.0, 110.0),
+        ..AnalogConfig::DEFAULT
+    };
+
+    test_runner.check("analog_rest", config.apply((140, 110)), (0.0, 0.0));
+    test_runner.check("analog_drift", config.apply((150, 102)), (0.0, 0.0));
+
+    test_runner.check("analog_right", config.apply((255, 110)), (1.0, 0.0));
+    test_runner.check("analog_left", config.apply((0, 110)), (-1.0, 0.0));
+    test_runner.check("analog_down", config.apply((140, 255)), (0.0, 1.0));
+    test_runner.check("analog_up", config.apply((140, 0)), (0.0, -1.0));
+
+    // Halfway between the deadzone and saturation.
+    let linear = AnalogConfig {
+        center: (0.0, 0.0),
+        deadzone: 0.2,
+        saturation: 0.6,
+        curve: ResponseCurve::Linear,
+    };
+    let squared = AnalogConfig {
+        curve: ResponseCurve::Squared,
+        ..linear
+    };
+    let (x, _) = linear.apply((102, 0));
+    test_runner.check_true("analog_linear", x > 0.49 && x < 0.51);
+    let (x, _) = squared.apply((102, 0));
+    test_runner.check_true("analog_squared", x > 0.24 && x < 0.26);
+
+    // Cycles outside the hardware's range are clamped, 0 is left alone.
+    let config = ctrl::configure(SamplingMode::Analog, 100);
+    test_runner.check("sampling_clamped", config.cycle_us, ctrl::MIN_CYCLE_US);
+    let config = ctrl::configure(SamplingMode::Analog, 0);
+    test_runner.check("sampling_vblank", config.cycle_us, 0);
+    test_runner.check("sampling_remembered", ctrl::sampling_config(), config);
+
+    // Nothing is pressed in the emulator, and a vblank takes a sample.
+    ctrl::read_latch();
+    unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    let latch = ctrl::read_latch();
+    test_runner.check_true("latch_samples", latch.samples > 0);
+    test_runner.check_true(
+        "latch_no_edges",
+        latch.made.is_empty() && latch.broken.is_empty(),
+    );
+    test_runner.check_true("latch_nothing_down", latch.down.is_empty());
+}
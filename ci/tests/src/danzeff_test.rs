@@ -0,0 +1,44 @@
+use alloc::vec::Vec;
+
+use psp::ctrl::danzeff::{Corner, Danzeff, Layer, BACKSPACE};
+use psp::ctrl::Button;
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut keyboard = Danzeff::new(Corner::BottomRight);
+    let none = Button::empty();
+
+    // Up and left selects the top-left cell.
+    let typed: Vec<char> = keyboard
+        .update_with((-1.0, -1.0), none, Button::TRIANGLE)
+        .collect();
+    test_runner.check("danzeff_top_left", keyboard.cell(), 0);
+    test_runner.check("danzeff_typed", typed, ['a'].to_vec());
+
+    let typed: Vec<char> = keyboard
+        .update_with(
+            (-1.0, -1.0),
+            Button::LTRIGGER,
+            Button::CIRCLE | Button::CROSS,
+        )
+        .collect();
+    test_runner.check("danzeff_shift", keyboard.layer(), Layer::Upper);
+    test_runner.check("danzeff_typed_shifted", typed, ['B', 'C'].to_vec());
+
+    // The stick at rest selects the center, with the special keys.
+    let typed: Vec<char> = keyboard
+        .update_with((0.1, -0.2), none, Button::TRIANGLE)
+        .collect();
+    test_runner.check("danzeff_center", keyboard.cell(), 4);
+    test_runner.check("danzeff_backspace", typed, [BACKSPACE].to_vec());
+
+    let typed: Vec<char> = keyboard
+        .update_with((1.0, 0.0), Button::RTRIGGER, Button::CROSS)
+        .collect();
+    test_runner.check("danzeff_digits", keyboard.layer(), Layer::Digits);
+    test_runner.check("danzeff_typed_digit", typed, [')'].to_vec());
+
+    // Nothing pressed, nothing typed.
+    let typed = keyboard.update_with((1.0, 1.0), Button::CROSS, none);
+    test_runner.check("danzeff_idle", typed.count(), 0);
+}
@@ -0,0 +1,326 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::fmt::Write;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use psp::debug::{self, Anchor, Config, ConfigError, Console, FontError, Level};
+use psp::test_runner::TestRunner;
+use psp::{BUF_WIDTH, SCREEN_HEIGHT};
+
+/// Words checked past the end of the console framebuffer.
+const GUARD_LEN: usize = BUF_WIDTH as usize * 16;
+const GUARD_WORD: u32 = 0xdead_beef;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let guard = unsafe {
+        let vram = psp::sys::uncached(psp::sys::sceGeEdramGetAddr().cast::<u32>());
+        core::slice::from_raw_parts_mut(
+            vram.add(BUF_WIDTH as usize * SCREEN_HEIGHT as usize),
+            GUARD_LEN,
+        )
+    };
+
+    guard.iter_mut().for_each(|w| *w = GUARD_WORD);
+
+    // Enough lines to scroll, so text is drawn on the very bottom row.
+    for i in 0..32 {
+        psp::dprintln!("bottom row clipping {} gjpqy", i);
+        psp::dputs!("bottom row clipping, no fmt\n");
+    }
+
+    test_runner.check_true(
+        "debug_bottom_row_clipped",
+        guard.iter().all(|w| *w == GUARD_WORD),
+    );
+
+    test_runner.check(
+        "debug_configure_too_wide",
+        debug::configure(Config {
+            display_width: 600,
+            ..Config::DEFAULT
+        }),
+        Err(ConfigError::InvalidSize),
+    );
+
+    test_runner.check(
+        "debug_configure_in_use",
+        debug::configure(Config::DEFAULT),
+        Err(ConfigError::InUse),
+    );
+
+    test_runner.check(
+        "debug_font_too_wide",
+        debug::load_font(&[0; 256 * 8], 9, 8),
+        Err(FontError::InvalidSize),
+    );
+    test_runner.check(
+        "debug_font_wrong_length",
+        debug::load_font(&[0; 256 * 8], 8, 10),
+        Err(FontError::WrongLength {
+            expected: 256 * 10,
+            actual: 256 * 8,
+        }),
+    );
+    test_runner.check(
+        "debug_font_in_use",
+        debug::load_font(&[0; 256 * 8], 8, 8),
+        Err(FontError::InUse),
+    );
+
+    test_runner.check(
+        "debug_proportional_font_advances_len",
+        debug::load_proportional_font(&[0; 256 * 8], &[4; 255], 8),
+        Err(FontError::InvalidAdvances),
+    );
+    test_runner.check(
+        "debug_proportional_font_too_wide",
+        debug::load_proportional_font(&[0; 256 * 8], &[9; 256], 8),
+        Err(FontError::InvalidAdvances),
+    );
+    test_runner.check(
+        "debug_proportional_font_in_use",
+        debug::load_proportional_font(&[0; 256 * 8], &[4; 256], 8),
+        Err(FontError::InUse),
+    );
+
+    // Scrolling is clamped to the top of the history and to the bottom.
+    debug::scroll(-10_000);
+    let top = debug::scroll_offset();
+    test_runner.check_true("debug_scroll_up", top > 0);
+
+    debug::scroll(-1);
+    test_runner.check("debug_scroll_clamped_top", debug::scroll_offset(), top);
+
+    // New output keeps the view in place while scrolled up.
+    debug::scroll(1);
+    psp::dputs!("scrolled\n");
+    test_runner.check("debug_scroll_holds", debug::scroll_offset(), top);
+
+    debug::scroll(10_000);
+    test_runner.check("debug_scroll_clamped_bottom", debug::scroll_offset(), 0);
+
+    debug::scroll(-1);
+    debug::scroll_to_bottom();
+    test_runner.check("debug_scroll_to_bottom", debug::scroll_offset(), 0);
+
+    // Shrinking the history keeps the newest lines, so the view can scroll
+    // back exactly one screen less than the new length.
+    let rows = debug::config().display_height / 10;
+    test_runner.check("debug_scrollback_set", debug::set_scrollback_lines(64), 64);
+    test_runner.check("debug_scrollback_get", debug::scrollback_lines(), 64);
+
+    debug::scroll(-10_000);
+    test_runner.check("debug_scrollback_depth", debug::scroll_offset(), 64 - rows);
+    debug::scroll_to_bottom();
+
+    test_runner.check(
+        "debug_scrollback_clamped",
+        debug::set_scrollback_lines(usize::MAX),
+        debug::MAX_SCROLLBACK_LINES,
+    );
+    debug::set_scrollback_lines(debug::DEFAULT_SCROLLBACK_LINES);
+
+    test_runner.check(
+        "debug_console_write",
+        write!(Console, "{} {:x}\n", "console", 0xbeefu32),
+        Ok(()),
+    );
+
+    // Batched output is still recorded right away, only the redraw waits.
+    let offset = debug::batch(|| {
+        debug::batch(|| {
+            for i in 0..32 {
+                psp::dprintln!("batched {}", i);
+            }
+        });
+
+        debug::scroll(-1);
+        debug::scroll_offset()
+    });
+    test_runner.check("debug_batch_nested", offset, 1);
+    debug::scroll_to_bottom();
+
+    // Progress bars redraw in place: only the first one adds a line, which
+    // moves a scrolled up view by one.
+    debug::scroll(-1);
+    debug::progress("loading", 0.0);
+    let offset = debug::scroll_offset();
+    for i in 0..=10 {
+        debug::progress("loading", i as f32 / 10.0);
+    }
+    debug::progress("clamped", 2.0);
+    debug::print_at(0, 4, "overwritten");
+    test_runner.check("debug_progress_in_place", debug::scroll_offset(), offset);
+    debug::scroll_to_bottom();
+    psp::dprintln!("after progress");
+
+    debug::set_wrap_marker(Some(b'\\'));
+    test_runner.check("debug_wrap_marker", debug::wrap_marker(), Some(b'\\'));
+    psp::dprintln!("{:-<300}", "wrapped with markers ");
+    debug::set_wrap_marker(None);
+
+    // Filtered messages don't reach the history, so they don't move a
+    // scrolled up view.
+    debug::set_min_level(Level::Warn);
+    debug::scroll(-1);
+    let offset = debug::scroll_offset();
+    psp::log!(Level::Info, "filtered {}", 1);
+    test_runner.check("debug_log_filtered", debug::scroll_offset(), offset);
+    psp::log!(Level::Error, "shown {}", 2);
+    test_runner.check("debug_log_shown", debug::scroll_offset(), offset + 1);
+    debug::set_min_level(Level::Trace);
+    debug::scroll_to_bottom();
+
+    let geometry = debug::geometry();
+    test_runner.check(
+        "debug_geometry_display",
+        (geometry.display_width, geometry.display_height),
+        (480, 272),
+    );
+    test_runner.check(
+        "debug_geometry_grid",
+        (geometry.rows, geometry.cols),
+        (272 / geometry.char_height, 480 / geometry.char_width),
+    );
+
+    debug::set_brightness(128);
+    psp::dprintln!("dimmed");
+    test_runner.check("debug_brightness", debug::brightness(), 128);
+    debug::set_brightness(255);
+
+    debug::set_mirror(true);
+    psp::dprintln!("mirrored");
+    test_runner.check("debug_mirror", debug::mirror(), true);
+    debug::set_mirror(false);
+
+    // Right-anchored text ends at the right edge of the grid, leaving the
+    // left of its row empty.
+    debug::set_anchor(Anchor::TopRight);
+    test_runner.check("debug_anchor", debug::anchor(), Anchor::TopRight);
+    psp::dprintln!("anchored");
+    let lit = |x0: usize, x1: usize| {
+        let y0 = (geometry.rows - 1) * geometry.char_height;
+        (y0..y0 + geometry.char_height).any(|y| {
+            (x0..x1).any(|x| unsafe {
+                let vram = psp::sys::uncached(psp::sys::sceGeEdramGetAddr().cast::<u32>());
+                *vram.add(x + y * BUF_WIDTH as usize) != 0
+            })
+        })
+    };
+    let right = geometry.cols * geometry.char_width;
+    test_runner.check_true(
+        "debug_anchor_right",
+        lit(right - 8 * geometry.char_width, right),
+    );
+    test_runner.check_true("debug_anchor_right_left_empty", !lit(0, right / 2));
+    test_runner.check_true(
+        "debug_anchor_capture_text",
+        debug::capture_text().ends_with("anchored"),
+    );
+
+    debug::set_anchor(Anchor::BottomRight);
+    for i in 0..32 {
+        psp::dprintln!("bottom right {} gjpqy", i);
+    }
+    test_runner.check_true(
+        "debug_anchor_clipped",
+        guard.iter().all(|w| *w == GUARD_WORD),
+    );
+    debug::set_anchor(Anchor::default());
+    test_runner.check("debug_anchor_default", debug::anchor(), Anchor::TopLeft);
+
+    // Shadows on the bottom row and right edge are clipped like the text.
+    debug::set_shadow(Some(0xff00_0000));
+    test_runner.check("debug_shadow", debug::shadow(), Some(0xff00_0000));
+    for i in 0..32 {
+        psp::dprintln!("shadow {} gjpqy {:x<2$}", i, "", 200);
+    }
+    test_runner.check_true(
+        "debug_shadow_clipped",
+        guard.iter().all(|w| *w == GUARD_WORD),
+    );
+    debug::set_shadow(None);
+    test_runner.check("debug_shadow_none", debug::shadow(), None);
+
+    psp::dprintln!("golden   ");
+    psp::dprintln!("output");
+    test_runner.check_true(
+        "debug_capture_text",
+        debug::capture_text().ends_with("golden\noutput"),
+    );
+
+    // A line one character too long wraps onto the next row.
+    let cols = debug::geometry().cols;
+    psp::dprintln!("{:x<1$}", "", cols + 1);
+    let expected = format!("{:x<1$}\nx", "", cols);
+    test_runner.check_true(
+        "debug_capture_text_wrapped",
+        debug::capture_text().ends_with(&expected),
+    );
+
+    // A box on the last three lines, which are empty.
+    for _ in 0..3 {
+        psp::dprintln!("");
+    }
+    debug::draw_box(1, debug::geometry().rows - 3, 5, 3);
+    let expected = format!(
+        "\n {}{}{}\n {}   {}\n {}{}{}",
+        debug::BOX_TOP_LEFT as char,
+        format!("{0}{0}{0}", debug::BOX_HORIZONTAL as char),
+        debug::BOX_TOP_RIGHT as char,
+        debug::BOX_VERTICAL as char,
+        debug::BOX_VERTICAL as char,
+        debug::BOX_BOTTOM_LEFT as char,
+        format!("{0}{0}{0}", debug::BOX_HORIZONTAL as char),
+        debug::BOX_BOTTOM_RIGHT as char,
+    );
+    test_runner.check_true("debug_draw_box", debug::capture_text().ends_with(&expected));
+
+    // Lines stay in order once the history has gone around twice, and the
+    // line count is brought back down.
+    let lines = debug::set_scrollback_lines(0);
+    debug::batch(|| {
+        for i in 0..2 * lines + 5 {
+            psp::dprintln!("line {}", i);
+        }
+    });
+    let expected = (2 * lines..2 * lines + 5)
+        .map(|i| format!("line {}", i))
+        .collect::<Vec<_>>()
+        .join("\n");
+    test_runner.check_true(
+        "debug_line_count_rebased",
+        debug::capture_text().ends_with(&expected),
+    );
+    debug::set_scrollback_lines(debug::DEFAULT_SCROLLBACK_LINES);
+
+    // Output past the queue for interrupt handlers is counted as dropped.
+    let dropped = debug::dropped_bytes();
+    unsafe {
+        let vblank = psp::sys::Interrupt::Vblank as i32;
+        psp::sys::sceKernelRegisterSubIntrHandler(vblank, 0, flood as *mut c_void, ptr::null_mut());
+        psp::sys::sceKernelEnableSubIntr(vblank, 0);
+        psp::sys::sceDisplayWaitVblankStart();
+        psp::sys::sceDisplayWaitVblankStart();
+        psp::sys::sceKernelDisableSubIntr(vblank, 0);
+        psp::sys::sceKernelReleaseSubIntrHandler(vblank, 0);
+    }
+    psp::dprintln!("");
+    test_runner.check_true(
+        "debug_dropped_bytes",
+        debug::dropped_bytes() >= dropped + FLOOD_LEN - 1024,
+    );
+}
+
+/// Bytes printed by `flood`, more than the interrupt output queue holds.
+const FLOOD_LEN: usize = 2048;
+
+static FLOODED: AtomicBool = AtomicBool::new(false);
+
+/// Vertical blank handler printing `FLOOD_LEN` bytes, once.
+extern "C" fn flood(_sub: i32, _arg: *mut c_void) {
+    if !FLOODED.swap(true, Ordering::Relaxed) {
+        psp::dprint!("{:x<1$}", "", FLOOD_LEN);
+    }
+}
@@ -0,0 +1,94 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use psp::display::{self, CaptureError};
+use psp::image::tga;
+use psp::sys::{self, IoOpenFlags};
+use psp::test_runner::TestRunner;
+
+const CAPTURE_PATH: &str = "host0:/capture_test.tga";
+const CAPTURE_LEN: usize = 18 + 480 * 272 * 3;
+
+static VBLANKS: AtomicU32 = AtomicU32::new(0);
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // Printing sets up the debug console's framebuffer, which takes effect
+    // on the next frame.
+    psp::dprintln!("display_test");
+    unsafe { sys::sceDisplayWaitVblankStart() };
+
+    let (top_addr, buffer_width, pixel_format) = display::get_frame_buf();
+    test_runner.check_true("get_frame_buf_addr", !top_addr.is_null());
+    test_runner.check("get_frame_buf_width", buffer_width, 512);
+    test_runner.check_true(
+        "get_frame_buf_format",
+        matches!(pixel_format, sys::DisplayPixelFormat::Psm8888),
+    );
+
+    display::set_vblank_callback(count_vblank);
+    wait_vblanks(4);
+    test_runner.check_true("vblank_callback", VBLANKS.load(Ordering::Relaxed) >= 2);
+
+    // Once cleared, the count stops.
+    display::clear_vblank_callback();
+    wait_vblanks(2);
+    let count = VBLANKS.load(Ordering::Relaxed);
+    wait_vblanks(2);
+    test_runner.check(
+        "vblank_callback_cleared",
+        VBLANKS.load(Ordering::Relaxed),
+        count,
+    );
+
+    test_runner.check(
+        "capture_invalid_path",
+        display::capture("host0:/bad\0.tga"),
+        Err(CaptureError::InvalidPath),
+    );
+
+    test_runner.check("capture", display::capture(CAPTURE_PATH), Ok(()));
+
+    // The capture loads back as a full screen image.
+    let data = read_file(CAPTURE_PATH);
+    test_runner.check("capture_len", data.len(), CAPTURE_LEN);
+
+    match tga::load(&data) {
+        Ok(texture) => test_runner.check(
+            "capture_size",
+            (texture.width(), texture.height()),
+            (480, 272),
+        ),
+        Err(e) => test_runner.fail("capture_size", &format!("{:?}", e)),
+    }
+}
+
+fn count_vblank() {
+    VBLANKS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn wait_vblanks(count: u32) {
+    for _ in 0..count {
+        unsafe { sys::sceDisplayWaitVblankStart() };
+    }
+}
+
+fn read_file(path: &str) -> Vec<u8> {
+    let path = format!("{}\0", path);
+    // One byte extra, to notice a file that is too long.
+    let mut data = alloc::vec![0; CAPTURE_LEN + 1];
+
+    unsafe {
+        let fd = sys::sceIoOpen(path.as_ptr(), IoOpenFlags::RD_ONLY, 0);
+        if fd.0 < 0 {
+            return Vec::new();
+        }
+
+        let read = sys::sceIoRead(fd, data.as_mut_ptr() as *mut c_void, data.len() as u32);
+        sys::sceIoClose(fd);
+        data.truncate(read.max(0) as usize);
+    }
+
+    data
+}
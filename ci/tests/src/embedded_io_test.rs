@@ -0,0 +1,82 @@
+use alloc::vec::Vec;
+use embedded_io::{BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
+use psp::io::{self, File, IoError};
+use psp::test_runner::TestRunner;
+
+const SRC_PATH: &str = "ms0:/embedded_io_src.bin";
+const DST_PATH: &str = "ms0:/embedded_io_dst.bin";
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i * 13) as u8).collect();
+
+    let mut src = File::create(SRC_PATH).unwrap();
+    test_runner.check(
+        "embedded_io_write_all",
+        Write::write_all(&mut src, &data),
+        Ok(()),
+    );
+    drop(src);
+
+    let mut src = File::open(SRC_PATH).unwrap();
+    let mut dst = File::create(DST_PATH).unwrap();
+    test_runner.check(
+        "embedded_io_copy",
+        copy(&mut src, &mut dst),
+        Ok(data.len() as u64),
+    );
+    drop(dst);
+
+    let mut copied = Vec::new();
+    File::open(DST_PATH)
+        .unwrap()
+        .read_to_end(&mut copied)
+        .unwrap();
+    test_runner.check_true("embedded_io_copy_data", copied == data);
+
+    test_runner.check(
+        "embedded_io_seek",
+        Seek::seek(&mut src, SeekFrom::End(-10)),
+        Ok(9_990),
+    );
+    let mut buf = [0; 10];
+    test_runner.check(
+        "embedded_io_read_exact",
+        src.read_exact(&mut buf).is_ok(),
+        true,
+    );
+    test_runner.check("embedded_io_read_exact_data", &buf[..], &data[9_990..]);
+
+    let mut reader = io::BufReader::new(File::open(SRC_PATH).unwrap());
+    test_runner.check(
+        "embedded_io_buf_read",
+        BufRead::fill_buf(&mut reader).map(|b| b.len()),
+        Ok(io::DEFAULT_BUF_LEN),
+    );
+
+    test_runner.check(
+        "embedded_io_stdout",
+        io::stdout().write_all(b"embedded_io_test\n"),
+        Ok(()),
+    );
+
+    test_runner.check(
+        "embedded_io_kind",
+        embedded_io::Error::kind(&IoError::NotFound),
+        ErrorKind::NotFound,
+    );
+}
+
+fn copy<R: Read, W: Write>(src: &mut R, dst: &mut W) -> Result<u64, ()> {
+    let mut buf = [0; 512];
+    let mut total = 0;
+
+    loop {
+        match src.read(&mut buf).map_err(|_| ())? {
+            0 => return Ok(total),
+            read => {
+                dst.write_all(&buf[..read]).map_err(|_| ())?;
+                total += read as u64;
+            }
+        }
+    }
+}
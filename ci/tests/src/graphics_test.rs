@@ -0,0 +1,146 @@
+use alloc::vec::Vec;
+use psp::graphics::{self, Dither, ImageRef};
+use psp::sys::TexturePixelFormat::{self, Psm4444, Psm5551, Psm5650, Psm8888};
+use psp::test_runner::TestRunner;
+
+const PAIRS: &[(&str, TexturePixelFormat, TexturePixelFormat)] = &[
+    ("convert_8888_5650", Psm8888, Psm5650),
+    ("convert_8888_5551", Psm8888, Psm5551),
+    ("convert_8888_4444", Psm8888, Psm4444),
+    ("convert_5650_8888", Psm5650, Psm8888),
+    ("convert_5650_5551", Psm5650, Psm5551),
+    ("convert_5650_4444", Psm5650, Psm4444),
+    ("convert_5551_8888", Psm5551, Psm8888),
+    ("convert_5551_5650", Psm5551, Psm5650),
+    ("convert_5551_4444", Psm5551, Psm4444),
+    ("convert_4444_8888", Psm4444, Psm8888),
+    ("convert_4444_5650", Psm4444, Psm5650),
+    ("convert_4444_5551", Psm4444, Psm5551),
+];
+
+const EXACT: &[(&str, TexturePixelFormat)] = &[
+    ("convert_5650_exact", Psm5650),
+    ("convert_5551_exact", Psm5551),
+    ("convert_4444_exact", Psm4444),
+];
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // Every channel value, with the channels out of step with each other.
+    let mut gradient = [0u8; 256 * 4];
+    for (i, p) in gradient.chunks_exact_mut(4).enumerate() {
+        p.copy_from_slice(&[i as u8, 255 - i as u8, (i * 7) as u8, (i * 3) as u8]);
+    }
+
+    // Round trips through both formats, compared as 8888.
+    for &(name, a, b) in PAIRS {
+        let src = to_format(&gradient, a);
+        let there = to_format_from(&src, a, b);
+        let back = to_format_from(&there, b, a);
+
+        let expected = to_format_from(&src, a, Psm8888);
+        let actual = to_format_from(&back, a, Psm8888);
+
+        let mut bounds = [0; 4];
+        for (c, bound) in bounds.iter_mut().enumerate() {
+            *bound = match (channel_bits(a, c), channel_bits(b, c)) {
+                (Some(x), Some(y)) => max_error(x.min(y)),
+                // Dropped alpha comes back opaque.
+                _ => 255,
+            };
+        }
+
+        let ok = expected
+            .chunks_exact(4)
+            .zip(actual.chunks_exact(4))
+            .all(|(e, a)| (0..4).all(|c| diff(e[c], a[c]) <= bounds[c]));
+
+        test_runner.check_true(name, ok);
+    }
+
+    // 16-bit pixels survive a trip through 8888 unchanged.
+    for &(name, format) in EXACT {
+        let all: Vec<u8> = (0..=u16::MAX).flat_map(|i| i.to_le_bytes()).collect();
+
+        let wide = to_format_from(&all, format, Psm8888);
+        let back = to_format_from(&wide, Psm8888, format);
+
+        test_runner.check_true(name, back[..] == all[..]);
+    }
+
+    // Dithering a flat color keeps every pixel within one step, and the
+    // average close to the original.
+    let flat = [[100u8, 100, 100, 255]; 16].concat();
+    let image = ImageRef::new(&flat, 4, 4, 16, Psm8888);
+    let dithered = graphics::convert_with(&image, Psm5650, Dither::Ordered);
+    let dithered = ImageRef::from_texture(&dithered);
+
+    let mut sum = 0u32;
+    let mut within_step = true;
+    for y in 0..4 {
+        let row = to_format_from(dithered.row(y), Psm5650, Psm8888);
+        for p in row.chunks_exact(4) {
+            within_step &= diff(p[0], 100) <= 8;
+            sum += p[0] as u32;
+        }
+    }
+
+    test_runner.check_true("convert_dither_within_step", within_step);
+    test_runner.check_true("convert_dither_average", diff((sum / 16) as u8, 100) <= 1);
+}
+
+fn to_format(rgba: &[u8], format: TexturePixelFormat) -> Vec<u8> {
+    to_format_from(rgba, Psm8888, format)
+}
+
+fn to_format_from(
+    src: &[u8],
+    src_format: TexturePixelFormat,
+    dst_format: TexturePixelFormat,
+) -> Vec<u8> {
+    let pixels = src.len() / bpp(src_format);
+    let mut dst = alloc::vec![0; pixels * bpp(dst_format)];
+    graphics::convert_row(src, src_format, &mut dst, dst_format, 0, Dither::None);
+    dst
+}
+
+fn bpp(format: TexturePixelFormat) -> usize {
+    match format {
+        Psm8888 => 4,
+        _ => 2,
+    }
+}
+
+/// Bits of channel `c` (RGBA order) stored by `format`, `None` if missing.
+fn channel_bits(format: TexturePixelFormat, c: usize) -> Option<u32> {
+    match (format, c) {
+        (Psm8888, _) => Some(8),
+        (Psm5650, 1) => Some(6),
+        (Psm5650, 3) => None,
+        (Psm5650, _) => Some(5),
+        (Psm5551, 3) => Some(1),
+        (Psm5551, _) => Some(5),
+        (Psm4444, _) => Some(4),
+        _ => unreachable!(),
+    }
+}
+
+/// Largest error a round trip through a `bits` wide channel can introduce.
+fn max_error(bits: u32) -> u8 {
+    match bits {
+        8 => 0,
+        6 => 2,
+        5 => 4,
+        // Half a step, plus one when rounding 6 bit values twice.
+        4 => 9,
+        1 => 127,
+        _ => unreachable!(),
+    }
+}
+
+fn diff(a: u8, b: u8) -> u8 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
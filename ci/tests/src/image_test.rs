@@ -0,0 +1,102 @@
+use psp::image::{png, tga, Texture};
+use psp::test_runner::TestRunner;
+
+const UNCOMPRESSED_24: &[u8] = include_bytes!("../assets/tga_uncompressed_24.tga");
+const RLE_32: &[u8] = include_bytes!("../assets/tga_rle_32.tga");
+const PALETTE: &[u8] = include_bytes!("../assets/tga_palette.tga");
+const PNG_RGB: &[u8] = include_bytes!("../assets/png_rgb_8.png");
+const PNG_RGBA: &[u8] = include_bytes!("../assets/png_rgba_8.png");
+const PNG_PALETTE: &[u8] = include_bytes!("../assets/png_palette_8.png");
+const PNG_GREY_ALPHA: &[u8] = include_bytes!("../assets/png_grey_alpha_8.png");
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let tex = tga::load(UNCOMPRESSED_24).unwrap();
+    test_runner.check(
+        "tga_uncompressed_24_size",
+        (tex.width(), tex.height()),
+        (5, 3),
+    );
+    test_runner.check("tga_uncompressed_24_checksum", checksum(&tex), 0x2c29fedf);
+
+    let mut tex = tga::load(RLE_32).unwrap();
+    test_runner.check("tga_rle_32_size", (tex.width(), tex.height()), (7, 4));
+    test_runner.check("tga_rle_32_checksum", checksum(&tex), 0x8777349d);
+
+    tex.swizzle();
+    tex.unswizzle();
+    test_runner.check("tga_swizzle_round_trip", checksum(&tex), 0x8777349d);
+
+    test_runner.check(
+        "tga_palette_unsupported",
+        tga::load(PALETTE).err(),
+        Some(tga::TgaError::ColorMapped),
+    );
+    test_runner.check(
+        "tga_truncated",
+        tga::load(&RLE_32[..RLE_32.len() - 1]).err(),
+        Some(tga::TgaError::Truncated),
+    );
+
+    // Stored, fixed Huffman, and dynamic Huffman compressed, with rows using
+    // each filter type in turn.
+    let tex = png::load(PNG_RGB).unwrap();
+    test_runner.check("png_rgb_size", (tex.width(), tex.height()), (5, 3));
+    test_runner.check("png_rgb_checksum", checksum(&tex), 0x4a3d5a88);
+
+    let tex = png::load(PNG_RGBA).unwrap();
+    test_runner.check("png_rgba_size", (tex.width(), tex.height()), (7, 4));
+    test_runner.check("png_rgba_checksum", checksum(&tex), 0x295c88ed);
+
+    let tex = png::load(PNG_PALETTE).unwrap();
+    test_runner.check("png_palette_size", (tex.width(), tex.height()), (6, 5));
+    test_runner.check("png_palette_checksum", checksum(&tex), 0xf7e2b83a);
+
+    let image = png::decode(PNG_GREY_ALPHA).unwrap();
+    test_runner.check(
+        "png_grey_alpha_size",
+        (image.width, image.height),
+        (100, 60),
+    );
+    let tex = Texture::from_rgba(image.width, image.height, &image.rgba, false);
+    test_runner.check("png_grey_alpha_checksum", checksum(&tex), 0x9faa8919);
+
+    test_runner.check(
+        "png_not_png",
+        png::decode(UNCOMPRESSED_24).err(),
+        Some(png::PngError::NotPng),
+    );
+    test_runner.check(
+        "png_truncated",
+        png::decode(&PNG_GREY_ALPHA[..PNG_GREY_ALPHA.len() / 2]).err(),
+        Some(png::PngError::Truncated),
+    );
+
+    // Too wide for a texture, but not for an `Image`.
+    let mut wide = PNG_RGB.to_vec();
+    wide[16..20].copy_from_slice(&1000u32.to_be_bytes());
+    test_runner.check(
+        "png_too_large",
+        png::load(&wide).err(),
+        Some(png::PngError::InvalidDimensions {
+            width: 1000,
+            height: 3,
+        }),
+    );
+}
+
+/// FNV-1a over the visible RGBA pixels, top row first.
+fn checksum(tex: &Texture) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+
+    for y in 0..tex.height() as usize {
+        let start = y * tex.stride();
+        let row = &tex.data()[start..start + tex.width() as usize * 4];
+
+        for byte in row {
+            hash ^= *byte as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+    }
+
+    hash
+}
@@ -0,0 +1,393 @@
+use alloc::vec::Vec;
+use psp::io::ctl::{self, CtlError};
+use psp::io::{
+    self, mem_stick, AsyncStatus, AtomicWriter, BufReader, BufWriter, File, IoError, SeekFrom,
+};
+use psp::test_runner::TestRunner;
+
+const PATH: &str = "host0:/io_test.bin";
+const ATOMIC_PATH: &str = "host0:/io_atomic_test.bin";
+const ATOMIC_TMP_PATH: &str = "host0:/io_atomic_test.bin.tmp";
+const NESTED_DIR: &str = "host0:/io_test_dir/a/b";
+const COPY_PATH: &str = "host0:/io_test_dir/a/b/copy.bin";
+const BUF_PATH: &str = "host0:/io_buf_test.bin";
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let data: Vec<u8> = (0..40_000u32).map(|i| (i * 7) as u8).collect();
+
+    let mut file = File::create(PATH).unwrap();
+    test_runner.check("io_write_all", file.write_all(&data), Ok(()));
+    drop(file);
+
+    test_runner.check(
+        "io_metadata_len",
+        io::metadata(PATH).map(|m| (m.len, m.is_dir)),
+        Ok((data.len() as u64, false)),
+    );
+
+    let mut file = File::open(PATH).unwrap();
+    let mut buf = [0; 4];
+    test_runner.check("io_read", file.read(&mut buf), Ok(4));
+    test_runner.check("io_read_data", buf, [0, 7, 14, 21]);
+
+    test_runner.check("io_seek_end", file.seek(SeekFrom::End(-2)), Ok(39_998));
+    test_runner.check(
+        "io_seek_current",
+        file.seek(SeekFrom::Current(-1)),
+        Ok(39_997),
+    );
+    test_runner.check("io_seek_start", file.seek(SeekFrom::Start(100)), Ok(100));
+
+    // Onto what's already there.
+    let mut read = alloc::vec![1, 2];
+    test_runner.check("io_read_to_end", file.read_to_end(&mut read), Ok(39_900));
+    test_runner.check_true(
+        "io_read_to_end_data",
+        read[..2] == [1, 2] && read[2..] == data[100..],
+    );
+    test_runner.check("io_read_eof", file.read(&mut buf), Ok(0));
+    test_runner.check(
+        "io_file_metadata",
+        file.metadata().map(|m| m.len),
+        Ok(data.len() as u64),
+    );
+
+    file.seek(SeekFrom::Start(0)).unwrap();
+    let mut read = file.read_async(alloc::vec![0; 50_000]);
+    let status = loop {
+        match read.poll() {
+            Ok(AsyncStatus::Pending) => unsafe { psp::sys::sceKernelDelayThread(1000) },
+            status => break status,
+        }
+    };
+    test_runner.check("io_read_async_poll", status, Ok(AsyncStatus::Done(40_000)));
+    test_runner.check_true("io_read_async_data", read.wait() == Ok(data.clone()));
+
+    // Abandoned reads are cancelled or waited for, leaving the file usable.
+    file.seek(SeekFrom::Start(0)).unwrap();
+    drop(file.read_async(alloc::vec![0; 40_000]));
+    test_runner.check(
+        "io_read_async_dropped",
+        file.seek(SeekFrom::Start(4)),
+        Ok(4),
+    );
+    drop(file);
+
+    let mut open = File::open_async(PATH).unwrap();
+    let opened = loop {
+        match open.poll() {
+            Ok(false) => unsafe { psp::sys::sceKernelDelayThread(1000) },
+            opened => break opened,
+        }
+    };
+    test_runner.check("io_open_async_poll", opened, Ok(true));
+    let mut file = open.wait().unwrap();
+    let mut head = [0; 4];
+    test_runner.check("io_open_async_read", file.read(&mut head), Ok(4));
+    test_runner.check_true("io_open_async_data", head[..] == data[..4]);
+    drop(file);
+
+    test_runner.check(
+        "io_open_async_not_found",
+        File::open_async("host0:/missing.bin")
+            .and_then(|open| open.wait())
+            .err(),
+        Some(IoError::NotFound),
+    );
+    test_runner.check(
+        "io_open_async_invalid_path",
+        File::open_async("host0:/bad\0.bin").err(),
+        Some(IoError::InvalidPath),
+    );
+    // Abandoned opens are cancelled or waited for, and the file closed.
+    drop(File::open_async(PATH).unwrap());
+
+    // Reopening for writing empties the file.
+    drop(File::create(PATH).unwrap());
+    test_runner.check(
+        "io_create_truncates",
+        io::metadata(PATH).map(|m| m.len),
+        Ok(0),
+    );
+
+    let entries: Vec<_> = io::read_dir("host0:/")
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+    test_runner.check_true(
+        "io_read_dir_no_dots",
+        entries
+            .iter()
+            .all(|e| e.name_bytes() != b"." && e.name_bytes() != b".."),
+    );
+    test_runner.check(
+        "io_read_dir_entry",
+        entries
+            .iter()
+            .find(|e| e.name() == Some("io_test.bin"))
+            .map(|e| (e.is_dir(), e.metadata().len)),
+        Some((false, 0)),
+    );
+
+    // Dropped before reaching the end.
+    let mut dir = io::read_dir("host0:/").unwrap();
+    test_runner.check_true("io_read_dir_partial", dir.next().is_some());
+    drop(dir);
+
+    test_runner.check(
+        "io_read_dir_not_found",
+        io::read_dir("host0:/missing_dir").err(),
+        Some(IoError::NotFound),
+    );
+
+    test_runner.check(
+        "io_not_found",
+        File::open("host0:/missing.bin").err(),
+        Some(IoError::NotFound),
+    );
+    test_runner.check(
+        "io_invalid_path",
+        File::open("host0:/bad\0.bin").err(),
+        Some(IoError::InvalidPath),
+    );
+
+    // The emulator always has a Memory Stick in.
+    test_runner.check_true("mem_stick_inserted", mem_stick::is_inserted());
+    test_runner.check("mem_stick_on_change", mem_stick::on_change(|_| {}), Ok(()));
+    test_runner.check(
+        "io_mem_stick_not_found",
+        File::open("ms0:/missing.bin").err(),
+        Some(IoError::NotFound),
+    );
+
+    let free = mem_stick::free_space().unwrap_or(0);
+    let total = mem_stick::total_space().unwrap_or(0);
+    let cluster_size = mem_stick::cluster_size().unwrap_or(0);
+    test_runner.check_true("mem_stick_total_space", total > 0);
+    test_runner.check_true("mem_stick_free_space", free <= total);
+    test_runner.check_true("mem_stick_cluster_size", cluster_size.is_power_of_two());
+    test_runner.check("mem_stick_whole_clusters", total % cluster_size as u64, 0);
+
+    // The same device, asked for by name.
+    test_runner.check("io_free_space", io::free_space("ms0:").ok(), Some(free));
+    test_runner.check(
+        "io_free_space_path",
+        io::free_space("ms0:/PSP/GAME").ok(),
+        Some(free),
+    );
+    test_runner.check(
+        "io_free_space_invalid",
+        io::free_space("ms0\0:"),
+        Err(IoError::InvalidPath),
+    );
+    test_runner.check_true("io_free_space_flash", io::free_space("flash0:").is_err());
+
+    // Over an existing file, and a stale temporary file from a crash.
+    File::create(ATOMIC_PATH)
+        .unwrap()
+        .write_all(b"old")
+        .unwrap();
+    File::create(ATOMIC_TMP_PATH)
+        .unwrap()
+        .write_all(b"stale")
+        .unwrap();
+    test_runner.check(
+        "io_write_atomic",
+        io::write_atomic(ATOMIC_PATH, b"new"),
+        Ok(()),
+    );
+    test_runner.check(
+        "io_write_atomic_data",
+        read_all(ATOMIC_PATH),
+        b"new".to_vec(),
+    );
+    test_runner.check(
+        "io_write_atomic_tmp_gone",
+        io::metadata(ATOMIC_TMP_PATH).err(),
+        Some(IoError::NotFound),
+    );
+
+    // Dropped without committing, the old contents stay.
+    let mut writer = AtomicWriter::create(ATOMIC_PATH).unwrap();
+    writer.write_all(b"abandoned").unwrap();
+    drop(writer);
+    test_runner.check(
+        "io_atomic_writer_dropped",
+        read_all(ATOMIC_PATH),
+        b"new".to_vec(),
+    );
+    test_runner.check(
+        "io_atomic_writer_dropped_tmp_gone",
+        io::metadata(ATOMIC_TMP_PATH).err(),
+        Some(IoError::NotFound),
+    );
+
+    test_runner.check("io_close", File::open(ATOMIC_PATH).unwrap().close(), Ok(()));
+
+    test_runner.check("io_create_dir_all", io::create_dir_all(NESTED_DIR), Ok(()));
+    test_runner.check(
+        "io_create_dir_all_is_dir",
+        io::metadata(NESTED_DIR).map(|m| m.is_dir),
+        Ok(true),
+    );
+    test_runner.check(
+        "io_create_dir_all_existing",
+        io::create_dir_all("host0:/io_test_dir//a/b/"),
+        Ok(()),
+    );
+
+    File::create(PATH).unwrap().write_all(&data).unwrap();
+    test_runner.check("io_copy", io::copy(PATH, COPY_PATH), Ok(data.len() as u64));
+    test_runner.check_true("io_copy_data", read_all(COPY_PATH) == data);
+    test_runner.check(
+        "io_copy_not_found",
+        io::copy("host0:/missing.bin", COPY_PATH).err(),
+        Some(IoError::NotFound),
+    );
+    test_runner.check(
+        "io_create_dir_all_over_file",
+        io::create_dir_all("host0:/io_test_dir/a/b/copy.bin/c").err(),
+        Some(IoError::AlreadyExists),
+    );
+
+    test_runner.check("io_remove_file", io::remove_file(COPY_PATH), Ok(()));
+    test_runner.check(
+        "io_remove_file_gone",
+        io::metadata(COPY_PATH).err(),
+        Some(IoError::NotFound),
+    );
+
+    // The tests are run with the PC's directory as `host0:/`.
+    test_runner.check_true("io_host_available", io::host_available());
+    let base = io::base_path();
+    test_runner.check_true("io_base_path_dir", base.is_empty() || base.ends_with('/'));
+    test_runner.check(
+        "io_resolve",
+        io::resolve("assets/a.bin"),
+        alloc::format!("{}assets/a.bin", base),
+    );
+    test_runner.check(
+        "io_resolve_device",
+        io::resolve("ms0:/a.bin"),
+        alloc::string::String::from("ms0:/a.bin"),
+    );
+
+    let mut file = File::open(PATH).unwrap();
+    test_runner.check_true("io_ctl_device_type", ctl::device_type(&file).is_ok());
+    test_runner.check(
+        "io_ctl_unsupported",
+        ctl::umd_start_sector(&file),
+        Err(CtlError::Unsupported),
+    );
+    test_runner.check(
+        "io_ctl_bad_buffer_len",
+        ctl::umd_read_sectors(&mut file, &mut [0; 100]),
+        Err(CtlError::BadBufferLen(100)),
+    );
+    test_runner.check(
+        "io_ctl_read_sectors",
+        ctl::umd_read_sectors(&mut file, &mut alloc::vec![0; ctl::SECTOR_LEN * 32]),
+        Ok(data.len()),
+    );
+
+    let mut writer = BufWriter::with_capacity(4096, File::create(BUF_PATH).unwrap());
+    for chunk in data.chunks(3) {
+        writer.write_all(chunk).unwrap();
+    }
+    // Flushed whenever the next 3 bytes don't fit, every 4095 bytes.
+    test_runner.check(
+        "io_buf_writer_buffered",
+        writer.buffer().len(),
+        data.len() % 4095,
+    );
+    test_runner.check(
+        "io_buf_writer_seek",
+        writer.seek(SeekFrom::End(0)),
+        Ok(data.len() as u64),
+    );
+    test_runner.check("io_buf_writer_flushed", writer.buffer().len(), 0);
+    writer.write_all(b"end").unwrap();
+    test_runner.check(
+        "io_buf_writer_into_inner",
+        writer.into_inner().map(drop),
+        Ok(()),
+    );
+    test_runner.check_true(
+        "io_buf_writer_data",
+        read_all(BUF_PATH) == [&data[..], &b"end"[..]].concat(),
+    );
+
+    let mut writer = BufWriter::new(File::create(BUF_PATH).unwrap());
+    let drop_error = writer.drop_error();
+    writer.write_all(b"dropped").unwrap();
+    drop(writer);
+    test_runner.check(
+        "io_buf_writer_drop",
+        read_all(BUF_PATH),
+        b"dropped".to_vec(),
+    );
+
+    // Writing to a file opened for reading fails on drop, and only that
+    // writer's handle gets the error.
+    let mut failing = BufWriter::new(File::open(PATH).unwrap());
+    let failing_error = failing.drop_error();
+    failing.write_all(b"x").unwrap();
+    drop(failing);
+    test_runner.check_true(
+        "io_buf_writer_drop_error",
+        failing_error.take_error().is_some(),
+    );
+    test_runner.check(
+        "io_buf_writer_drop_error_taken",
+        failing_error.take_error(),
+        None,
+    );
+    test_runner.check(
+        "io_buf_writer_drop_error_other",
+        drop_error.take_error(),
+        None,
+    );
+
+    let mut reader = BufReader::with_capacity(4096, File::open(PATH).unwrap());
+    let mut buf = [0; 3];
+    test_runner.check("io_buf_reader_read", reader.read(&mut buf), Ok(3));
+    test_runner.check("io_buf_reader_buffered", reader.buffer().len(), 4093);
+    test_runner.check(
+        "io_buf_reader_seek_back",
+        reader.seek(SeekFrom::Current(-2)),
+        Ok(1),
+    );
+    test_runner.check("io_buf_reader_kept", reader.buffer().len(), 4095);
+    test_runner.check(
+        "io_buf_reader_fill_buf",
+        reader.fill_buf().map(|b| b[0]),
+        Ok(data[1]),
+    );
+    reader.consume(100);
+    test_runner.check(
+        "io_buf_reader_seek_ahead",
+        reader.seek(SeekFrom::Current(5000)),
+        Ok(5101),
+    );
+    test_runner.check(
+        "io_buf_reader_read_after_seek",
+        reader.read(&mut buf).map(|_| buf),
+        Ok([data[5101], data[5102], data[5103]]),
+    );
+    test_runner.check(
+        "io_buf_reader_seek_end",
+        reader.seek(SeekFrom::End(-1)),
+        Ok(data.len() as u64 - 1),
+    );
+    test_runner.check(
+        "io_buf_reader_eof",
+        reader.read(&mut buf).and_then(|_| reader.read(&mut buf)),
+        Ok(0),
+    );
+}
+
+fn read_all(path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
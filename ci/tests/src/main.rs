@@ -7,19 +7,63 @@ extern crate alloc;
 
 use psp::test_runner::TestRunner;
 
+mod alloc_sys_test;
+mod audio_test;
 mod bmp_screenshot_test;
+mod ctrl_test;
+mod danzeff_test;
+mod debug_test;
+mod display_test;
+mod embedded_io_test;
+mod graphics_test;
+mod image_test;
+mod io_test;
 mod math_test;
+mod matrix_test;
+mod mem_test;
+mod patch_test;
+mod rand_test;
+mod sfo_test;
+mod skin_test;
+mod slots_test;
+mod swizzle_test;
+mod sync_test;
+mod thread_test;
+mod umd_test;
 mod vfpu_test;
 mod vram_test;
+mod zip_test;
 
 psp::module!("ci_tests", 1, 1);
 
 fn psp_main() {
     let tests = &[
         bmp_screenshot_test::test_main,
+        audio_test::test_main,
+        ctrl_test::test_main,
+        danzeff_test::test_main,
+        display_test::test_main,
+        embedded_io_test::test_main,
+        graphics_test::test_main,
+        image_test::test_main,
+        io_test::test_main,
         math_test::test_main,
+        matrix_test::test_main,
+        mem_test::test_main,
+        patch_test::test_main,
+        rand_test::test_main,
+        sfo_test::test_main,
+        skin_test::test_main,
+        slots_test::test_main,
+        swizzle_test::test_main,
+        sync_test::test_main,
+        thread_test::test_main,
+        umd_test::test_main,
         vfpu_test::test_main,
         vram_test::test_main,
+        zip_test::test_main,
+        debug_test::test_main,
+        alloc_sys_test::test_main,
     ];
 
     let mut runner = TestRunner::new_file_runner();
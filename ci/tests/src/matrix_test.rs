@@ -0,0 +1,68 @@
+use psp::gu::Matrix4;
+use psp::sys::{self, ScePspFVector3};
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let model_rows = [
+        [1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 2.0],
+        [0.0, 0.0, 1.0, 3.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+    let model = Matrix4::from_rows(model_rows);
+    test_runner.check("matrix_rows_round_trip", model.to_rows(), model_rows);
+    test_runner.check(
+        "matrix_translation_column",
+        [model.w.x, model.w.y, model.w.z, model.w.w],
+        [1.0, 2.0, 3.0, 1.0],
+    );
+    test_runner.check(
+        "matrix_identity",
+        (Matrix4::IDENTITY * model).to_rows(),
+        model_rows,
+    );
+
+    // The same translation, built by `sceGum`.
+    let mut gum = Matrix4::IDENTITY;
+    unsafe {
+        sys::sceGumLoadIdentity();
+        sys::sceGumTranslate(&ScePspFVector3 {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        });
+        sys::sceGumStoreMatrix(&mut gum);
+    }
+    test_runner.check("matrix_gum_layout", gum.to_rows(), model_rows);
+
+    let view = Matrix4::from_rows([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, -10.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    // A 90 degree field of view, square, from 1 to 100.
+    let (near, far) = (1.0, 100.0);
+    let projection = Matrix4::from_rows([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [
+            0.0,
+            0.0,
+            (far + near) / (near - far),
+            2.0 * far * near / (near - far),
+        ],
+        [0.0, 0.0, -1.0, 0.0],
+    ]);
+
+    // The origin is moved to (1, 2, -7) in view space.
+    let clip = (projection * view * model).transform([0.0, 0.0, 0.0, 1.0]);
+    let expected = [1.0, 2.0, 507.0 / 99.0, 7.0];
+    test_runner.check_true(
+        "matrix_mvp_clip",
+        clip.iter()
+            .zip(expected.iter())
+            .all(|(a, b)| a - b < 1e-4 && b - a < 1e-4),
+    );
+}
@@ -0,0 +1,91 @@
+use alloc::vec::Vec;
+use psp::test_runner::TestRunner;
+
+const WIDTH: usize = 512;
+const HEIGHT: usize = 272;
+const BENCH_ITERATIONS: usize = 20;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // Starts and lengths around line boundaries.
+    let mut words = alloc::vec![0u32; 100];
+    unsafe { psp::mem::fast_fill32(words.as_mut_ptr().add(3), 0xdead_beef, 90) };
+    test_runner.check_true(
+        "mem_fill32",
+        words[..3] == [0; 3]
+            && words[3..93].iter().all(|&w| w == 0xdead_beef)
+            && words[93..] == [0; 7],
+    );
+
+    let src: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    let mut aligned = alloc::vec![0u8; 300];
+    unsafe { psp::mem::fast_copy(aligned.as_mut_ptr().add(1), src.as_ptr().add(1), 290) };
+    test_runner.check_true(
+        "mem_copy_aligned",
+        aligned[0] == 0 && aligned[1..291] == src[1..291] && aligned[291..].iter().all(|&b| b == 0),
+    );
+
+    let mut misaligned = alloc::vec![0u8; 300];
+    unsafe { psp::mem::fast_copy(misaligned.as_mut_ptr().add(2), src.as_ptr().add(1), 297) };
+    test_runner.check_true("mem_copy_misaligned", misaligned[2..299] == src[1..298]);
+
+    unsafe { psp::mem::fast_copy(aligned.as_mut_ptr(), src.as_ptr(), 0) };
+    test_runner.check("mem_copy_empty", aligned[0], 0);
+
+    // The top of VRAM, past the framebuffers and depth buffers of other
+    // tests, through the uncached mirror.
+    let vram = (0x4420_0000 - WIDTH * HEIGHT * 4) as *mut u32;
+    let count = WIDTH * HEIGHT;
+
+    let naive = psp::benchmark(
+        || unsafe {
+            let mut ptr = vram;
+            for _ in 0..count {
+                *ptr = 0xff00_00ff;
+                ptr = ptr.offset(1);
+            }
+        },
+        BENCH_ITERATIONS,
+    );
+    let fast = psp::benchmark(
+        || unsafe { psp::mem::fast_fill32(vram, 0xff00_ff00, count) },
+        BENCH_ITERATIONS,
+    );
+    test_runner.check_true("mem_fill32_vram", unsafe {
+        *vram == 0xff00_ff00 && *vram.add(count - 1) == 0xff00_ff00
+    });
+    test_runner.dbg(
+        "mem_fill32_bench",
+        &alloc::format!(
+            "512x272 fill: loop {:?}, fast_fill32 {:?}, {:.2}x",
+            naive,
+            fast,
+            naive.as_micros() as f32 / fast.as_micros().max(1) as f32
+        ),
+    );
+
+    let pixels = alloc::vec![0x8080_8080u32; count];
+    let naive = psp::benchmark(
+        || unsafe {
+            for (i, &pixel) in pixels.iter().enumerate() {
+                *vram.add(i) = pixel;
+            }
+        },
+        BENCH_ITERATIONS,
+    );
+    let fast = psp::benchmark(
+        || unsafe { psp::mem::fast_copy(vram as *mut u8, pixels.as_ptr() as *const u8, count * 4) },
+        BENCH_ITERATIONS,
+    );
+    test_runner.check_true("mem_copy_vram", unsafe {
+        *vram.add(count / 2) == 0x8080_8080
+    });
+    test_runner.dbg(
+        "mem_copy_bench",
+        &alloc::format!(
+            "512x272 copy: loop {:?}, fast_copy {:?}, {:.2}x",
+            naive,
+            fast,
+            naive.as_micros() as f32 / fast.as_micros().max(1) as f32
+        ),
+    );
+}
@@ -0,0 +1,49 @@
+use psp::gu::{PatchError, PatchMesh, SpriteVertex, Vertex};
+use psp::sys::VertexType;
+use psp::test_runner::TestRunner;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct ColorOnly {
+    _color: u32,
+}
+
+unsafe impl Vertex for ColorOnly {
+    const FORMAT: VertexType = VertexType::COLOR_8888;
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let points = [SpriteVertex::default(); 7 * 5];
+
+    test_runner.check_true(
+        "patch_bezier_4x4",
+        PatchMesh::bezier(&points[..16], 4, 4).is_ok(),
+    );
+    test_runner.check_true(
+        "patch_bezier_7x4",
+        PatchMesh::bezier(&points[..28], 7, 4).is_ok(),
+    );
+    test_runner.check_true("patch_spline_5x7", PatchMesh::spline(&points, 5, 7).is_ok());
+
+    test_runner.check(
+        "patch_bezier_5x4",
+        PatchMesh::bezier(&points[..20], 5, 4).err(),
+        Some(PatchError::InvalidSize),
+    );
+    test_runner.check(
+        "patch_spline_3x4",
+        PatchMesh::spline(&points[..12], 3, 4).err(),
+        Some(PatchError::InvalidSize),
+    );
+    test_runner.check(
+        "patch_count_mismatch",
+        PatchMesh::bezier(&points[..17], 4, 4).err(),
+        Some(PatchError::CountMismatch),
+    );
+
+    let colors = [ColorOnly::default(); 16];
+    test_runner.check(
+        "patch_no_positions",
+        PatchMesh::bezier(&colors, 4, 4).err(),
+        Some(PatchError::NoPositions),
+    );
+}
@@ -0,0 +1,29 @@
+use psp::rand::Rng;
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut a = Rng::with_seed(1234);
+    let mut b = Rng::with_seed(1234);
+    let same = (0..100).all(|_| a.next_u32() == b.next_u32());
+    test_runner.check_true("rand_same_seed", same);
+
+    let mut c = Rng::with_seed(4321);
+    let differs = (0..100).any(|_| a.next_u32() != c.next_u32());
+    test_runner.check_true("rand_different_seed", differs);
+
+    // A clone continues the same sequence.
+    let mut d = a.clone();
+    test_runner.check("rand_clone", d.next_u32(), a.next_u32());
+
+    let in_range = (0..1000).all(|_| (-5..5).contains(&a.next_range(-5, 5)));
+    test_runner.check_true("rand_range", in_range);
+    test_runner.check("rand_empty_range", a.next_range(3, 3), 3);
+    test_runner.check(
+        "rand_full_range",
+        a.next_range(i32::MIN, i32::MAX) < i32::MAX,
+        true,
+    );
+
+    let in_unit = (0..1000).all(|_| (0.0..1.0).contains(&a.next_f32()));
+    test_runner.check_true("rand_f32", in_unit);
+}
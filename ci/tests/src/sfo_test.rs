@@ -0,0 +1,140 @@
+use psp::sfo::{self, Sfo, SfoError, Value};
+use psp::test_runner::TestRunner;
+
+// Written by `mksfo`, and laid out like a game's and a save's PARAM.SFO,
+// with space to spare after most values.
+const MKSFO: &[u8] = include_bytes!("../assets/sfo_mksfo.sfo");
+const GAME: &[u8] = include_bytes!("../assets/sfo_game.sfo");
+const SAVEDATA: &[u8] = include_bytes!("../assets/sfo_savedata.sfo");
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    for &(name, data) in &[
+        ("sfo_mksfo_round_trip", MKSFO),
+        ("sfo_game_round_trip", GAME),
+        ("sfo_savedata_round_trip", SAVEDATA),
+    ] {
+        test_runner.check(
+            name,
+            sfo::parse(data).map(|sfo| sfo.to_bytes() == data),
+            Ok(true),
+        );
+    }
+
+    let sfo = sfo::parse(MKSFO).unwrap();
+    test_runner.check("sfo_mksfo_title", sfo.get_str("TITLE"), Some("Hello World"));
+    test_runner.check(
+        "sfo_mksfo_parental_level",
+        sfo.get_int("PARENTAL_LEVEL"),
+        Some(1),
+    );
+    test_runner.check("sfo_mksfo_keys", sfo.iter().count(), 8);
+
+    let game = sfo::parse(GAME).unwrap();
+    test_runner.check(
+        "sfo_game_title",
+        game.get_str("TITLE"),
+        Some("Ünïcode Test Game"),
+    );
+    test_runner.check(
+        "sfo_game_disc_id",
+        game.get_str("DISC_ID"),
+        Some("ULUS12345"),
+    );
+    test_runner.check("sfo_game_region", game.get_int("REGION"), Some(0x8000));
+    test_runner.check("sfo_game_int_as_str", game.get_str("REGION"), None);
+    test_runner.check("sfo_game_missing", game.get("SAVEDATA_TITLE"), None);
+
+    let save = sfo::parse(SAVEDATA).unwrap();
+    test_runner.check(
+        "sfo_savedata_detail",
+        save.get_str("SAVEDATA_DETAIL"),
+        Some("Chapter 3\nForest"),
+    );
+    test_runner.check(
+        "sfo_savedata_params",
+        save.get("SAVEDATA_PARAMS")
+            .map(|v| matches!(v, Value::Binary(b) if b.len() == 128 && b[0] == 1)),
+        Some(true),
+    );
+
+    // Existing strings keep their space, so the file stays the same size.
+    let mut edited = game.clone();
+    test_runner.check(
+        "sfo_set_str_overlong",
+        edited.set_str("DISC_ID", "ULUS123456789012"),
+        Err(SfoError::Overlong { max_len: 16 }),
+    );
+    test_runner.check(
+        "sfo_set_str",
+        edited.set_str("DISC_ID", "ULES00001"),
+        Ok(()),
+    );
+    test_runner.check("sfo_set_int", edited.set_int("PARENTAL_LEVEL", 9), Ok(()));
+    test_runner.check("sfo_set_same_len", edited.to_bytes().len(), GAME.len());
+    test_runner.check(
+        "sfo_set_round_trip",
+        sfo::parse(&edited.to_bytes()),
+        Ok(edited.clone()),
+    );
+    test_runner.check(
+        "sfo_set_nul",
+        edited.set_str("TITLE", "a\0b"),
+        Err(SfoError::InvalidString),
+    );
+
+    // New keys go in sorted order, as the firmware expects.
+    let mut new = Sfo::new();
+    new.set_str("TITLE", "Hello World").unwrap();
+    new.set_str("CATEGORY", "MG").unwrap();
+    new.set_int("BOOTABLE", 1).unwrap();
+    test_runner.check(
+        "sfo_new_order",
+        new.iter()
+            .map(|(key, _)| key)
+            .collect::<alloc::vec::Vec<_>>(),
+        alloc::vec!["BOOTABLE", "CATEGORY", "TITLE"],
+    );
+    test_runner.check(
+        "sfo_new_round_trip",
+        sfo::parse(&new.to_bytes()),
+        Ok(new.clone()),
+    );
+
+    test_runner.check(
+        "sfo_not_sfo",
+        sfo::parse(b"\x89PNG\r\n\x1a\n and then some more").err(),
+        Some(SfoError::NotSfo),
+    );
+    test_runner.check(
+        "sfo_truncated_header",
+        sfo::parse(&GAME[..12]).err(),
+        Some(SfoError::Truncated),
+    );
+    test_runner.check(
+        "sfo_truncated_data",
+        sfo::parse(&GAME[..GAME.len() - 1]).err(),
+        Some(SfoError::Truncated),
+    );
+    test_runner.check(
+        "sfo_truncated_anywhere",
+        (0..MKSFO.len()).all(|len| sfo::parse(&MKSFO[..len]).is_err()),
+        true,
+    );
+
+    // The first entry is BOOTABLE, an integer, then CATEGORY, a string.
+    let mut bad = GAME.to_vec();
+    bad[23] = 0x09;
+    test_runner.check(
+        "sfo_unknown_format",
+        sfo::parse(&bad).err(),
+        Some(SfoError::UnknownFormat(0x0904)),
+    );
+
+    let mut bad = GAME.to_vec();
+    bad[40] = 5;
+    test_runner.check(
+        "sfo_overlong_str",
+        sfo::parse(&bad).err(),
+        Some(SfoError::Overlong { max_len: 4 }),
+    );
+}
@@ -0,0 +1,39 @@
+use psp::sys::VertexType;
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let plain = VertexType::VERTEX_32BITF;
+    test_runner.check("vertex_plain_weights", plain.weight_count(), 0);
+    test_runner.check("vertex_plain_morphs", plain.morph_count(), 1);
+
+    // A weight count without a weight format means no weights.
+    test_runner.check(
+        "vertex_weights_no_format",
+        plain.with_weights(3).weight_count(),
+        0,
+    );
+
+    for n in 1..=8 {
+        let skinned = (plain | VertexType::WEIGHT_16BIT).with_weights(n);
+        let morphed = plain.with_morph_targets(n);
+
+        test_runner.check("vertex_weight_count", skinned.weight_count(), n as usize);
+        test_runner.check("vertex_morph_count", morphed.morph_count(), n as usize);
+        test_runner.check(
+            "vertex_weights_replaced",
+            skinned.with_weights(1).weight_count(),
+            1,
+        );
+    }
+
+    test_runner.check(
+        "vertex_weights_constant",
+        (VertexType::WEIGHT_8BIT | VertexType::WEIGHTS4).weight_count(),
+        4,
+    );
+    test_runner.check(
+        "vertex_morphs_constant",
+        VertexType::VERTICES5.morph_count(),
+        5,
+    );
+}
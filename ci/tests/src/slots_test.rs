@@ -0,0 +1,91 @@
+use psp::io::slots::{SlotError, SlotStore};
+use psp::io::{self, File};
+use psp::test_runner::TestRunner;
+
+const DIR: &str = "host0:/slots_test/saves/";
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let slots = SlotStore::new(DIR, 3);
+
+    test_runner.check("slots_save", slots.save(1, b"level 4"), Ok(()));
+    test_runner.check("slots_load", slots.load(1), Ok(Some(b"level 4".to_vec())));
+    test_runner.check(
+        "slots_path",
+        slots.path(1),
+        Ok(alloc::string::String::from(
+            "host0:/slots_test/saves/slot1.sav",
+        )),
+    );
+    test_runner.check(
+        "slots_overwrite",
+        slots.save(1, b"level 5").and_then(|()| slots.load(1)),
+        Ok(Some(b"level 5".to_vec())),
+    );
+    test_runner.check(
+        "slots_empty_payload",
+        slots.save(0, b"").and_then(|()| slots.load(0)),
+        Ok(Some(alloc::vec::Vec::new())),
+    );
+    test_runner.check("slots_missing", slots.load(2), Ok(None));
+    test_runner.check(
+        "slots_invalid",
+        slots.save(3, b"x"),
+        Err(SlotError::InvalidSlot(3)),
+    );
+
+    let path = slots.path(1).unwrap();
+    let mut saved = read_all(&path);
+
+    // A flipped bit in the payload.
+    let last = saved.len() - 1;
+    saved[last] ^= 1;
+    File::create(&path).unwrap().write_all(&saved).unwrap();
+    test_runner.check("slots_corrupt_crc", slots.load(1), Err(SlotError::Corrupt));
+
+    // Cut short.
+    File::create(&path)
+        .unwrap()
+        .write_all(&saved[..10])
+        .unwrap();
+    test_runner.check(
+        "slots_corrupt_truncated",
+        slots.load(1),
+        Err(SlotError::Corrupt),
+    );
+
+    saved[last] ^= 1;
+    saved[4] = 99;
+    File::create(&path).unwrap().write_all(&saved).unwrap();
+    test_runner.check(
+        "slots_unsupported_version",
+        slots.load(1),
+        Err(SlotError::UnsupportedVersion(99)),
+    );
+
+    // Only the temporary file is left, as after a save interrupted just
+    // before renaming it.
+    saved[4] = 1;
+    File::create(&alloc::format!("{}.tmp", path))
+        .unwrap()
+        .write_all(&saved)
+        .unwrap();
+    io::remove_file(&path).unwrap();
+    test_runner.check(
+        "slots_tmp_fallback",
+        slots.load(1),
+        Ok(Some(b"level 5".to_vec())),
+    );
+
+    test_runner.check(
+        "slots_delete",
+        slots.delete(1).and_then(|()| slots.load(1)),
+        Ok(None),
+    );
+    test_runner.check("slots_delete_empty", slots.delete(2), Ok(()));
+}
+
+fn read_all(path: &str) -> alloc::vec::Vec<u8> {
+    let mut data = alloc::vec::Vec::new();
+    File::open(path).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
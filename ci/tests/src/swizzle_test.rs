@@ -0,0 +1,55 @@
+use alloc::vec::Vec;
+use psp::gu;
+use psp::image::Texture;
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let sizes = [
+        ("swizzle_round_trip_16x8x1", 16, 8, 1),
+        ("swizzle_round_trip_32x16x4", 32, 16, 4),
+        ("swizzle_round_trip_64x8x2", 64, 8, 2),
+        ("swizzle_round_trip_128x24x4", 128, 24, 4),
+        ("swizzle_round_trip_512x512x4", 512, 512, 4),
+    ];
+
+    for &(name, width, height, bpp) in &sizes {
+        let src = pattern(width as usize * height as usize * bpp);
+        let swizzled = gu::swizzle(&src, width, height, bpp);
+        test_runner.check_true(name, gu::unswizzle(&swizzled, width, height, bpp) == src);
+    }
+
+    // Two blocks side by side: block 0 holds the first 16 bytes of each of
+    // the 8 rows, then block 1 the rest.
+    let src = pattern(32 * 8);
+    let swizzled = gu::swizzle(&src, 32, 8, 1);
+    test_runner.check(
+        "swizzle_block_rows",
+        &swizzled[..32],
+        &[&src[..16], &src[32..48]].concat()[..],
+    );
+    test_runner.check("swizzle_second_block", &swizzled[128..144], &src[16..32]);
+
+    // A texture swizzles its whole padded buffer the same way.
+    let rgba = pattern(20 * 10 * 4);
+    let linear = Texture::from_rgba(20, 10, &rgba, false);
+    let swizzled = Texture::from_rgba(20, 10, &rgba, true);
+    test_runner.check(
+        "swizzle_texture_flag",
+        (linear.is_swizzled(), swizzled.is_swizzled()),
+        (false, true),
+    );
+    test_runner.check_true(
+        "swizzle_texture_matches",
+        swizzled.data()
+            == &gu::swizzle(
+                linear.data(),
+                linear.buffer_width(),
+                linear.buffer_height(),
+                4,
+            )[..],
+    );
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+}
@@ -0,0 +1,252 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use psp::sync::{
+    self, EventFlag, Lazy, Mutex, Once, RecvError, RecvTimeoutError, RwLock, SendError,
+    SendTimeoutError, TryRecvError, WaitError,
+};
+use psp::test_runner::TestRunner;
+use psp::thread;
+
+const INCREMENTS: u32 = 1000;
+
+static COUNTER: Mutex<u32> = Mutex::new(0);
+static CONFIG: RwLock<u32> = RwLock::new(0);
+static ONCE: Once = Once::new();
+static EVENTS: EventFlag = EventFlag::new(0b0001);
+static INIT_CALLS: AtomicU32 = AtomicU32::new(0);
+static TABLE: Lazy<alloc::vec::Vec<u32>> = Lazy::new(|| {
+    INIT_CALLS.fetch_add(1, Ordering::Relaxed);
+    // Long enough that the racing thread finds it running.
+    thread::delay(10_000);
+    (0..4).map(|i| i * i).collect()
+});
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // The first lock creates the semaphore.
+    *COUNTER.lock() += 1;
+    test_runner.check("sync_lock", *COUNTER.lock(), 1);
+
+    {
+        let _guard = COUNTER.lock();
+        test_runner.check_true("sync_try_lock_held", COUNTER.try_lock().is_none());
+    }
+    test_runner.check(
+        "sync_try_lock_released",
+        COUNTER.try_lock().map(|c| *c),
+        Some(1),
+    );
+
+    // Each worker yields while holding the lock, so the other finds it taken.
+    let workers = [0, 1].map(|_| {
+        thread::spawn(|| {
+            for _ in 0..INCREMENTS {
+                let mut counter = COUNTER.lock();
+                thread::yield_now();
+                *counter += 1;
+            }
+        })
+    });
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    test_runner.check("sync_contended", *COUNTER.lock(), 1 + 2 * INCREMENTS);
+
+    // A worker of higher priority waits for this thread to release the lock.
+    let guard = COUNTER.lock();
+    let worker = thread::Builder::new()
+        .priority(16)
+        .spawn(|| *COUNTER.lock() += 1)
+        .unwrap();
+    test_runner.check_true("sync_waiting", !worker.is_finished());
+    drop(guard);
+    test_runner.check_true("sync_woken", worker.is_finished());
+    worker.join().unwrap();
+    test_runner.check("sync_woken_count", *COUNTER.lock(), 2 + 2 * INCREMENTS);
+
+    let mut local = Mutex::new(alloc::vec![1, 2]);
+    local.get_mut().push(3);
+    test_runner.check("sync_into_inner", local.into_inner(), alloc::vec![1, 2, 3]);
+
+    // Readers share the lock, and keep writers out.
+    let first = CONFIG.read();
+    let second = CONFIG.try_read();
+    test_runner.check_true("rwlock_readers_share", second.is_some());
+    test_runner.check_true(
+        "rwlock_readers_exclude_writer",
+        CONFIG.try_write().is_none(),
+    );
+    drop((first, second));
+
+    {
+        let mut config = CONFIG.write();
+        *config = 7;
+        test_runner.check_true("rwlock_writer_excludes_reader", CONFIG.try_read().is_none());
+        test_runner.check_true(
+            "rwlock_writer_excludes_writer",
+            CONFIG.try_write().is_none(),
+        );
+    }
+    test_runner.check("rwlock_write", *CONFIG.read(), 7);
+
+    // A waiting writer goes before readers that come after it.
+    let reading = CONFIG.read();
+    let writer = thread::Builder::new()
+        .priority(16)
+        .spawn(|| *CONFIG.write() += 1)
+        .unwrap();
+    test_runner.check_true("rwlock_writer_waiting", !writer.is_finished());
+    test_runner.check_true("rwlock_writer_priority", CONFIG.try_read().is_none());
+    drop(reading);
+    writer.join().unwrap();
+    test_runner.check("rwlock_writer_woken", *CONFIG.read(), 8);
+
+    // Readers waiting on a writer are all woken when it is done.
+    let writing = CONFIG.write();
+    let readers = [0, 1].map(|_| {
+        thread::Builder::new()
+            .priority(16)
+            .spawn(|| *CONFIG.read())
+            .unwrap()
+    });
+    test_runner.check_true(
+        "rwlock_readers_waiting",
+        readers.iter().all(|r| !r.is_finished()),
+    );
+    drop(writing);
+    let read = readers.map(|r| r.join().ok());
+    test_runner.check("rwlock_readers_woken", read, [Some(8), Some(8)]);
+
+    let mut calls = 0;
+    ONCE.call_once(|| calls += 1);
+    ONCE.call_once(|| calls += 1);
+    test_runner.check("once_call_once", calls, 1);
+    test_runner.check_true("once_completed", ONCE.is_completed());
+
+    // Both threads get the value, built once.
+    let racer = thread::spawn(|| TABLE[3]);
+    test_runner.check("lazy_value", TABLE[2], 4);
+    test_runner.check("lazy_racer_value", racer.join().ok(), Some(9));
+    test_runner.check("lazy_init_once", INIT_CALLS.load(Ordering::Relaxed), 1);
+
+    test_runner.check(
+        "event_flag_initial",
+        EVENTS.wait_any(0b0011, None),
+        Ok(0b0001),
+    );
+    test_runner.check(
+        "event_flag_all_timeout",
+        EVENTS.wait_all(0b0011, Some(Duration::from_millis(5))),
+        Err(WaitError::Timeout),
+    );
+    EVENTS.set(0b0110);
+    test_runner.check("event_flag_set", EVENTS.wait_all(0b0011, None), Ok(0b0111));
+    EVENTS.clear(0b0101);
+    test_runner.check("event_flag_clear", EVENTS.wait_any(!0, None), Ok(0b0010));
+    test_runner.check(
+        "event_flag_no_bits",
+        EVENTS.wait_any(0, None).is_err(),
+        true,
+    );
+
+    // Two threads wait for different bits, and are woken by one `set`.
+    let waiters = [0b1000u32, 0b1_0000].map(|bit| {
+        thread::Builder::new()
+            .priority(16)
+            .spawn(move || EVENTS.wait_any(bit, Some(Duration::from_secs(1))))
+            .unwrap()
+    });
+    test_runner.check_true(
+        "event_flag_waiting",
+        waiters.iter().all(|w| !w.is_finished()),
+    );
+    EVENTS.set(0b1_1000);
+    let woken = waiters.map(|w| w.join().ok());
+    test_runner.check(
+        "event_flag_woken",
+        woken,
+        [Some(Ok(0b1_1010)), Some(Ok(0b1_1010))],
+    );
+
+    // Deleted while a thread waits on it through its UID.
+    let flag = EventFlag::new(0);
+    let id = flag.id();
+    let waiter = thread::Builder::new()
+        .priority(16)
+        .spawn(move || unsafe {
+            let mut bits = 0;
+            psp::sys::sceKernelWaitEventFlag(
+                psp::sys::SceUid(id),
+                1,
+                psp::sys::EventFlagWaitTypes::OR,
+                &mut bits,
+                core::ptr::null_mut(),
+            )
+        })
+        .unwrap();
+    drop(flag);
+    test_runner.check(
+        "event_flag_deleted",
+        waiter.join().ok(),
+        Some(0x8002_01b5u32 as i32),
+    );
+
+    let (sender, receiver) = sync::channel(2);
+    test_runner.check(
+        "channel_empty",
+        receiver.try_recv(),
+        Err(TryRecvError::Empty),
+    );
+    sender.send(alloc::vec![1, 2]).unwrap();
+    sender.send(alloc::vec![3]).unwrap();
+    test_runner.check_true(
+        "channel_full",
+        matches!(
+            sender.send_timeout(alloc::vec![4], Duration::from_millis(5)),
+            Err(SendTimeoutError::Timeout(_))
+        ),
+    );
+    test_runner.check("channel_recv", receiver.recv(), Ok(alloc::vec![1, 2]));
+    test_runner.check("channel_try_recv", receiver.try_recv(), Ok(alloc::vec![3]));
+    test_runner.check(
+        "channel_recv_timeout",
+        receiver.recv_timeout(Duration::from_millis(5)),
+        Err(RecvTimeoutError::Timeout),
+    );
+
+    // Values from a loader thread arrive in order, and the receiver sees
+    // the disconnection once they are all received.
+    let loader = thread::spawn(move || {
+        for i in 0..10u32 {
+            sender.send(alloc::vec![i; 100]).unwrap();
+        }
+    });
+    let received: alloc::vec::Vec<u32> = core::iter::from_fn(|| receiver.recv().ok())
+        .map(|v| v[99])
+        .collect();
+    loader.join().unwrap();
+    test_runner.check("channel_order", received, (0..10).collect());
+    test_runner.check("channel_disconnected", receiver.recv(), Err(RecvError));
+    test_runner.check(
+        "channel_try_recv_disconnected",
+        receiver.try_recv(),
+        Err(TryRecvError::Disconnected),
+    );
+
+    // A sender waiting for room fails once the receiver is dropped, and
+    // the values still queued are dropped with it.
+    let (sender, receiver) = sync::channel(1);
+    let queued = alloc::sync::Arc::new(());
+    sender.send(queued.clone()).unwrap();
+    let blocked = thread::Builder::new()
+        .priority(16)
+        .spawn(move || matches!(sender.send(alloc::sync::Arc::new(())), Err(SendError(_))))
+        .unwrap();
+    test_runner.check_true("channel_send_waiting", !blocked.is_finished());
+    drop(receiver);
+    test_runner.check("channel_send_disconnected", blocked.join().ok(), Some(true));
+    test_runner.check(
+        "channel_queued_dropped",
+        alloc::sync::Arc::strong_count(&queued),
+        1,
+    );
+}
@@ -0,0 +1,134 @@
+use core::time::Duration;
+use psp::test_runner::TestRunner;
+use psp::thread::{self, ThreadStatus};
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // The tests run on the thread started by `module!`.
+    let info = thread::current_info();
+    test_runner.check("thread_name", info.name.as_str(), "main_thread");
+    test_runner.check("thread_status", info.status, ThreadStatus::RUNNING);
+    test_runner.check("thread_priority", info.priority, 32);
+    test_runner.check("thread_stack_size", info.stack_size, 256 * 1024);
+    test_runner.check_true(
+        "thread_stack_free",
+        info.stack_free > 0 && info.stack_free < info.stack_size,
+    );
+
+    // Using more stack lowers the mark.
+    let before = thread::stack_free();
+    let used = use_stack();
+    test_runner.check_true(
+        "thread_stack_free_lowered",
+        used != 0 && thread::stack_free() < before,
+    );
+
+    let start = unsafe { psp::sys::sceKernelGetSystemTimeLow() };
+    thread::delay(10_000);
+    let slept = unsafe { psp::sys::sceKernelGetSystemTimeLow() }.wrapping_sub(start);
+    test_runner.check_true("thread_delay", slept >= 10_000);
+
+    let start = unsafe { psp::sys::sceKernelGetSystemTimeLow() };
+    thread::delay_cb(10_000);
+    let slept = unsafe { psp::sys::sceKernelGetSystemTimeLow() }.wrapping_sub(start);
+    test_runner.check_true("thread_delay_cb", slept >= 10_000);
+
+    let start = unsafe { psp::sys::sceKernelGetSystemTimeWide() };
+    thread::sleep(Duration::from_micros(10_500));
+    let slept = unsafe { psp::sys::sceKernelGetSystemTimeWide() } - start;
+    test_runner.check_true("thread_sleep", slept >= 10_500);
+
+    // Part of a microsecond still sleeps for one.
+    let start = unsafe { psp::sys::sceKernelGetSystemTimeWide() };
+    thread::sleep(Duration::from_nanos(1));
+    let slept = unsafe { psp::sys::sceKernelGetSystemTimeWide() } - start;
+    test_runner.check_true("thread_sleep_rounded_up", slept >= 1);
+
+    let start = unsafe { psp::sys::sceKernelGetSystemTimeWide() };
+    thread::sleep_us(10_000);
+    let slept = unsafe { psp::sys::sceKernelGetSystemTimeWide() } - start;
+    test_runner.check_true("thread_sleep_us", slept >= 10_000);
+
+    let start = unsafe { psp::sys::sceKernelGetSystemTimeWide() };
+    thread::delay_precise(Duration::from_micros(50));
+    let waited = unsafe { psp::sys::sceKernelGetSystemTimeWide() } - start;
+    test_runner.check_true("thread_delay_precise", (50..1_000).contains(&waited));
+
+    // Nothing else of the same priority is ready, so this returns at once.
+    thread::yield_now();
+
+    let handle = thread::spawn(|| 6 * 7);
+    let id = handle.id();
+    test_runner.check("thread_spawn_join", handle.join().ok(), Some(42));
+    test_runner.check_true("thread_spawn_deleted", !thread_exists(id));
+
+    let handle = thread::Builder::new()
+        .name("loader")
+        .priority(40)
+        .stack_size(0x4000)
+        .spawn(|| {
+            let info = thread::current_info();
+            (info.name, info.priority, info.stack_size)
+        })
+        .unwrap();
+    let (name, priority, stack_size) = handle.join().unwrap();
+    test_runner.check("thread_builder_name", name.as_str(), "loader");
+    test_runner.check("thread_builder_priority", priority, 40);
+    test_runner.check("thread_builder_stack_size", stack_size, 0x4000);
+
+    // Higher priority than this thread, so it has finished before `join`.
+    let handle = thread::Builder::new()
+        .priority(16)
+        .spawn(|| alloc::vec![1u8, 2, 3])
+        .unwrap();
+    test_runner.check_true("thread_is_finished", handle.is_finished());
+    test_runner.check(
+        "thread_join_finished",
+        handle.join().ok(),
+        Some(alloc::vec![1u8, 2, 3]),
+    );
+
+    // A detached thread deletes itself when it ends.
+    let handle = thread::Builder::new()
+        .priority(40)
+        .spawn(|| thread::delay(1_000))
+        .unwrap();
+    let id = handle.id();
+    drop(handle);
+    test_runner.check_true("thread_detached_running", thread_exists(id));
+    thread::delay(20_000);
+    test_runner.check_true("thread_detached_deleted", !thread_exists(id));
+
+    // One that finished before being detached is deleted by the handle.
+    let handle = thread::Builder::new().priority(16).spawn(|| ()).unwrap();
+    let id = handle.id();
+    drop(handle);
+    test_runner.check_true("thread_detached_finished_deleted", !thread_exists(id));
+
+    test_runner.check(
+        "thread_spawn_invalid_name",
+        thread::Builder::new().name("a\0b").spawn(|| ()).err(),
+        Some(thread::SpawnError::InvalidName),
+    );
+    test_runner.check_true(
+        "thread_spawn_invalid_priority",
+        matches!(
+            thread::Builder::new().priority(1000).spawn(|| ()),
+            Err(thread::SpawnError::Kernel(_))
+        ),
+    );
+}
+
+fn thread_exists(id: i32) -> bool {
+    let mut info: psp::sys::SceKernelThreadInfo = unsafe { core::mem::zeroed() };
+    info.size = core::mem::size_of::<psp::sys::SceKernelThreadInfo>();
+    unsafe { psp::sys::sceKernelReferThreadStatus(psp::sys::SceUid(id), &mut info) >= 0 }
+}
+
+#[inline(never)]
+fn use_stack() -> u8 {
+    let buf = [1u8; 64 * 1024];
+    core::hint::black_box(&buf)
+        .iter()
+        .fold(0, |a, &b| a.wrapping_add(b))
+        | 1
+}
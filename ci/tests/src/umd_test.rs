@@ -0,0 +1,24 @@
+use psp::test_runner::TestRunner;
+use psp::umd::{self, DiscKind, UmdError};
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    // Without a disc, waiting fails right away rather than hanging.
+    if !umd::is_present() {
+        test_runner.check("umd_no_disc", umd::wait_ready(), Err(UmdError::NoDisc));
+        test_runner.check(
+            "umd_mount_no_disc",
+            umd::mount().err(),
+            Some(UmdError::NoDisc),
+        );
+    } else {
+        test_runner.check("umd_ready", umd::wait_ready(), Ok(()));
+
+        match umd::mount() {
+            Ok(disc) => {
+                test_runner.check("umd_mount_kind", disc.disc_info().kind, DiscKind::Game);
+                test_runner.check_true("umd_mount_open", disc.open("PSP_GAME/PARAM.SFO").is_ok());
+            }
+            Err(e) => test_runner.check("umd_mount", Err(e), Ok(())),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use core::ptr::null_mut;
 use psp::test_runner::TestRunner;
-use psp::vram_alloc::get_vram_allocator;
+use psp::vram_alloc::{self, get_vram_allocator, VramOutOfMemoryError};
 
 pub fn test_main(test_runner: &mut TestRunner) {
     let mut alloc = get_vram_allocator().unwrap();
@@ -60,4 +60,39 @@ pub fn test_main(test_runner: &mut TestRunner) {
         muh_item[15] = 42;
         test_runner.check("vram_storage_integrity2", muh_item[15], 42);
     }
+
+    test_runner.check("vram_total", vram_alloc::total(), unsafe {
+        psp::sys::sceGeEdramGetSize()
+    });
+
+    let remaining = vram_alloc::remaining();
+    let chunk = alloc.alloc(64);
+    test_runner.check("vram_remaining", vram_alloc::remaining(), remaining - 64);
+    drop(chunk);
+
+    test_runner.check(
+        "vram_out_of_memory",
+        alloc.try_alloc(remaining as u32).err(),
+        Some(VramOutOfMemoryError {
+            requested: remaining,
+            remaining: remaining - 64,
+        }),
+    );
+
+    let remaining = vram_alloc::remaining();
+    let depth = alloc.alloc_depth_buffer(512, 272);
+    test_runner.check("vram_depth_buffer_len", depth.len(), 512 * 272 * 2);
+    test_runner.check(
+        "vram_depth_buffer_remaining",
+        vram_alloc::remaining(),
+        remaining - 512 * 272 * 2,
+    );
+    drop(depth);
+
+    alloc.free_all();
+    test_runner.check(
+        "vram_free_all",
+        vram_alloc::remaining(),
+        vram_alloc::total(),
+    );
 }
@@ -0,0 +1,106 @@
+use alloc::vec::Vec;
+use psp::image::png;
+use psp::io::File;
+use psp::test_runner::TestRunner;
+use psp::zip::{Archive, ZipError};
+
+// A stored text file, a directory, and deflated copies of a PNG and of
+// 129013 bytes of text.
+const ASSETS: &[u8] = include_bytes!("../assets/zip_assets.zip");
+const ZIP64: &[u8] = include_bytes!("../assets/zip_zip64.zip");
+const PNG_RGBA: &[u8] = include_bytes!("../assets/png_rgba_8.png");
+
+const PATH: &str = "host0:/zip_test.zip";
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut archive = open(ASSETS).unwrap();
+    test_runner.check(
+        "zip_names",
+        archive.names().collect::<Vec<_>>(),
+        alloc::vec!["readme.txt", "textures/", "textures/rgba.png", "lorem.txt"],
+    );
+
+    let mut readme = Vec::new();
+    test_runner.check(
+        "zip_read_stored",
+        archive
+            .by_name("readme.txt")
+            .and_then(|mut e| e.read_to_end(&mut readme)),
+        Ok(16),
+    );
+    test_runner.check(
+        "zip_read_stored_data",
+        &readme[..],
+        &b"hello from a zip"[..],
+    );
+
+    let mut texture = Vec::new();
+    test_runner.check(
+        "zip_read_deflated",
+        archive
+            .by_name("textures/rgba.png")
+            .and_then(|mut e| e.read_to_end(&mut texture)),
+        Ok(PNG_RGBA.len()),
+    );
+    test_runner.check_true("zip_read_deflated_data", texture == PNG_RGBA);
+    test_runner.check(
+        "zip_load_texture",
+        png::load(&texture).map(|t| (t.width(), t.height())),
+        Ok((7, 4)),
+    );
+
+    // Much more than fits in one read, or in the inflater's window.
+    let mut lorem = archive.by_name("lorem.txt").unwrap();
+    let mut buf = [0; 100];
+    let mut total = 0;
+    let streamed = loop {
+        match lorem.read(&mut buf) {
+            Ok(0) => break Ok(total),
+            Ok(len) => total += len,
+            Err(e) => break Err(e),
+        }
+    };
+    test_runner.check("zip_read_streaming", streamed, Ok(129_013));
+    test_runner.check("zip_entry_size", lorem.size(), 129_013);
+    drop(lorem);
+
+    test_runner.check(
+        "zip_by_name_missing",
+        archive.by_name("missing.txt").err(),
+        Some(ZipError::NotFound),
+    );
+    test_runner.check(
+        "zip_read_dir",
+        archive
+            .by_name("textures/")
+            .and_then(|mut e| e.read(&mut buf)),
+        Ok(0),
+    );
+    drop(archive);
+
+    // The stored text is in the file as is, so damaging it fails the
+    // checksum.
+    let mut damaged = ASSETS.to_vec();
+    let at = damaged
+        .windows(16)
+        .position(|w| w == b"hello from a zip")
+        .unwrap();
+    damaged[at] ^= 0x20;
+    let mut archive = open(&damaged).unwrap();
+    test_runner.check(
+        "zip_read_damaged",
+        archive
+            .by_name("readme.txt")
+            .and_then(|mut e| e.read_to_end(&mut Vec::new())),
+        Err(ZipError::Corrupt),
+    );
+    drop(archive);
+
+    test_runner.check("zip_zip64", open(ZIP64).err(), Some(ZipError::Zip64));
+    test_runner.check("zip_not_zip", open(PNG_RGBA).err(), Some(ZipError::NotZip));
+}
+
+fn open(data: &[u8]) -> Result<Archive, ZipError> {
+    File::create(PATH).unwrap().write_all(data).unwrap();
+    Archive::open(File::open(PATH).unwrap())
+}
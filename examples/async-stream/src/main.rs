@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use psp::io::{self, AsyncStatus, File};
+
+psp::module!("sample_async_stream", 1, 1);
+
+const CHUNK_LEN: usize = 64 * 1024;
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    // Any file will do, and the program's own is always there.
+    let path = io::resolve("EBOOT.PBP");
+    let mut frames = 0;
+
+    let mut open = File::open_async(&path).unwrap();
+    while !open.poll().unwrap() {
+        frames += wait_frame();
+    }
+    let mut file = open.wait().unwrap();
+
+    let mut buf = alloc::vec![0; CHUNK_LEN];
+    let mut total = 0;
+    let mut checksum = 0u32;
+
+    loop {
+        // The game loop keeps going while the chunk is read.
+        let mut read = file.read_async(buf);
+        while read.poll().unwrap() == AsyncStatus::Pending {
+            frames += wait_frame();
+        }
+
+        // The buffer comes back shortened to what was read.
+        buf = read.wait().unwrap();
+        if buf.is_empty() {
+            break;
+        }
+
+        total += buf.len();
+        checksum = buf
+            .iter()
+            .fold(checksum, |sum, &b| sum.wrapping_add(b as u32));
+        buf.resize(CHUNK_LEN, 0);
+    }
+
+    psp::dprintln!(
+        "Read {} bytes (sum {:#010x}) while {} frames went by.",
+        total,
+        checksum,
+        frames
+    );
+}
+
+/// Stands in for drawing a frame.
+fn wait_frame() -> u32 {
+    unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    1
+}
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use psp::audio::{Mixer, Sound, VoiceSettings, SAMPLE_RATE};
+use psp::ctrl::{Button, Input};
+
+psp::module!("sample_audio_mixer", 1, 1);
+
+const VOICES: usize = 16;
+
+/// A one second triangle wave at `freq` Hz, fading out.
+fn tone(freq: u32) -> Sound {
+    let len = SAMPLE_RATE as usize;
+    let step = freq * 0x1_0000 / SAMPLE_RATE;
+    let mut samples = Vec::with_capacity(len * 2);
+
+    for i in 0..len {
+        // The position within the wave, in 16.16 fixed point.
+        let phase = (i as u32 * step) & 0xffff;
+        let triangle = match phase {
+            p if p < 0x8000 => p as i32 - 0x4000,
+            p => 0xc000 - p as i32,
+        };
+
+        let value = (triangle * 4000 / 0x4000) * (len - i) as i32 / len as i32;
+        samples.push(value as i16);
+        samples.push(value as i16);
+    }
+
+    Sound::from_samples(samples)
+}
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    let tones: Vec<Sound> = (0..VOICES as u32).map(|i| tone(220 + i * 40)).collect();
+    let mut mixer = Mixer::start(VOICES).unwrap();
+    let mut input = Input::new();
+
+    psp::dprintln!("A tone starts every 4 frames, so all 16 voices overlap.");
+    psp::dprintln!("Press Cross to start 16 more at once.");
+
+    let mut frame = 0;
+    let mut underruns = 0;
+
+    loop {
+        input.update();
+
+        if frame % 4 == 0 {
+            let i = frame / 4 % VOICES;
+            mixer.play(
+                &tones[i],
+                VoiceSettings {
+                    // From left to right, and back.
+                    pan: (i as f32 / (VOICES - 1) as f32) * 2.0 - 1.0,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // Each takes the slot of the oldest voice.
+        if input.pressed(Button::CROSS) {
+            for tone in &tones {
+                mixer.play(tone, VoiceSettings::default());
+            }
+        }
+
+        if mixer.underruns() != underruns {
+            underruns = mixer.underruns();
+            psp::dprintln!("Underruns: {}", underruns);
+        }
+
+        frame += 1;
+        unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    }
+}
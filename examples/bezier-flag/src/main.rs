@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+use psp::gu::{Gu, PatchMesh, Vertex};
+use psp::sys::{self, MatrixMode, ScePspFVector3, VertexType};
+
+psp::module!("sample_bezier_flag", 1, 1);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct FlagVertex {
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+unsafe impl Vertex for FlagVertex {
+    const FORMAT: VertexType = VertexType::from_bits_truncate(
+        VertexType::COLOR_8888.bits()
+            | VertexType::VERTEX_32BITF.bits()
+            | VertexType::TRANSFORM_3D.bits(),
+    );
+}
+
+/// Three stripes, one per pair of control point rows.
+const STRIPES: [u32; 4] = [0xff00_00ff, 0xffff_ffff, 0xffff_ffff, 0xffff_0000];
+
+/// A 4x4 grid of control points, rippling along X as `time` advances. The
+/// edge at the pole (X = -2) stays still.
+fn control_points(time: f32) -> impl ExactSizeIterator<Item = FlagVertex> {
+    (0..16).map(move |i| {
+        let (u, v) = ((i % 4) as f32, (i / 4) as f32);
+        let x = u * 4.0 / 3.0 - 2.0;
+        let wave = unsafe { psp::math::sinf(time + u * 1.5) };
+
+        FlagVertex {
+            color: STRIPES[i / 4],
+            x,
+            y: 1.5 - v,
+            z: wave * 0.25 * u,
+        }
+    })
+}
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    let mut gu = Gu::init().unwrap();
+    let mut time = 0.0f32;
+
+    loop {
+        gu.frame(|frame| {
+            frame.clear(0xff55_4433);
+
+            unsafe {
+                sys::sceGumMatrixMode(MatrixMode::Projection);
+                sys::sceGumLoadIdentity();
+                sys::sceGumPerspective(75.0, 16.0 / 9.0, 0.5, 1000.0);
+
+                sys::sceGumMatrixMode(MatrixMode::View);
+                sys::sceGumLoadIdentity();
+
+                sys::sceGumMatrixMode(MatrixMode::Model);
+                sys::sceGumLoadIdentity();
+                sys::sceGumTranslate(&ScePspFVector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -3.5,
+                });
+                sys::sceGumRotateY(0.4);
+
+                // `draw_bezier` goes straight to the GE, so the matrices have
+                // to be uploaded first.
+                sys::sceGumUpdateMatrix();
+            }
+
+            let points = frame.push_vertices(control_points(time));
+            let mesh = PatchMesh::bezier(points, 4, 4).unwrap();
+            frame.draw_bezier(&mesh, 16);
+        });
+
+        time += 0.05;
+    }
+}
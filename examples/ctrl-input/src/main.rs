@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+use psp::ctrl::{self, Button, IdleKind, Input};
+
+psp::module!("sample_ctrl_input", 1, 1);
+
+const BUTTONS: &[(Button, &str)] = &[
+    (Button::CROSS, "Cross"),
+    (Button::CIRCLE, "Circle"),
+    (Button::SQUARE, "Square"),
+    (Button::TRIANGLE, "Triangle"),
+    (Button::LTRIGGER, "L"),
+    (Button::RTRIGGER, "R"),
+    (Button::UP, "Up"),
+    (Button::DOWN, "Down"),
+    (Button::LEFT, "Left"),
+    (Button::RIGHT, "Right"),
+    (Button::START, "Start"),
+    (Button::SELECT, "Select"),
+];
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    let mut input = Input::new();
+
+    // Don't dim the screen while only the stick is used.
+    ctrl::cancel_idle(IdleKind::AnalogMotion { threshold: 8 });
+
+    // Catch taps shorter than a frame, too.
+    input.set_latched(true);
+    let reset = input.register_combo(&[Button::LTRIGGER, Button::RTRIGGER, Button::START]);
+    psp::dprintln!("Press some buttons, or hold L + R to track the stick.");
+
+    loop {
+        input.update();
+
+        for &(button, name) in BUTTONS {
+            if input.pressed(button) {
+                psp::dprintln!("{} pressed", name);
+            }
+
+            if input.released(button) {
+                psp::dprintln!("{} released", name);
+            }
+        }
+
+        if input.combo_triggered(reset) {
+            let held = input.held_for(Button::LTRIGGER | Button::RTRIGGER);
+            psp::dprintln!(
+                "Start pressed after holding L + R for {} ms",
+                held.as_millis()
+            );
+        }
+
+        if input.held(Button::LTRIGGER | Button::RTRIGGER) {
+            let (x, _) = input.analog();
+            psp::debug::progress("stick x", (x + 1.0) / 2.0);
+        }
+
+        unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    }
+}
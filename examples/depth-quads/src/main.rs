@@ -0,0 +1,92 @@
+//! Two overlapping quads, with the nearer one drawn first. With depth
+//! testing, it still ends up in front.
+
+#![no_std]
+#![no_main]
+
+use psp::display;
+use psp::gu::{Gu, Vertex};
+use psp::sys::{self, DepthFunc, GuPrimitive, VertexType};
+
+psp::module!("sample_depth_quads", 1, 1);
+
+const NEAR_COLOR: u32 = 0xff00_00ff;
+const FAR_COLOR: u32 = 0xffff_0000;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ColorVertex {
+    color: u32,
+    x: i16,
+    y: i16,
+    /// Depth, from 0 at the far plane to 65535 at the near plane.
+    z: u16,
+    _pad: i16,
+}
+
+unsafe impl Vertex for ColorVertex {
+    const FORMAT: VertexType = VertexType::from_bits_truncate(
+        VertexType::COLOR_8888.bits()
+            | VertexType::VERTEX_16BIT.bits()
+            | VertexType::TRANSFORM_2D.bits(),
+    );
+}
+
+/// A square sprite with its top-left corner at `x`, `y`.
+fn quad(x: i16, y: i16, size: i16, z: u16, color: u32) -> [ColorVertex; 2] {
+    let corner = |x, y| ColorVertex {
+        color,
+        x,
+        y,
+        z,
+        _pad: 0,
+    };
+
+    [corner(x, y), corner(x + size, y + size)]
+}
+
+/// The pixel at `x`, `y` of the buffer on screen.
+fn read_pixel(x: usize, y: usize) -> u32 {
+    let (buf, stride, _) = display::get_frame_buf();
+
+    // Uncached, as the GE wrote it without going through the data cache.
+    let uncached = (buf as usize | 0x4000_0000) as *const u32;
+    unsafe { uncached.add(y * stride + x).read_volatile() }
+}
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    let mut gu = Gu::init().unwrap();
+
+    let near = quad(140, 56, 120, 50_000, NEAR_COLOR);
+    let far = quad(220, 96, 120, 10_000, FAR_COLOR);
+    let mut checked = false;
+
+    loop {
+        let mut frame = gu.start_frame();
+        frame.clear(0xff55_4433);
+        frame.set_depth_test(Some(DepthFunc::GreaterOrEqual));
+
+        for quad in &[near, far] {
+            let vertices = frame.push_vertices(quad.iter().copied());
+            frame.draw_array(GuPrimitive::Sprites, vertices);
+        }
+
+        frame.finish();
+        gu.swap_buffers(true);
+
+        if !checked {
+            checked = true;
+
+            // Let the swap take effect, then look where the quads overlap.
+            unsafe { sys::sceDisplayWaitVblankStart() };
+            let pixel = read_pixel(240, 136);
+
+            match pixel {
+                NEAR_COLOR => psp::dprintln!("The nearer quad is in front."),
+                _ => psp::dprintln!("Expected {:#010x}, found {:#010x}", NEAR_COLOR, pixel),
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+#![no_std]
+#![no_main]
+
+use core::f32::consts::PI;
+use psp::gu::{Attenuation, Gu, Light, Vertex};
+use psp::sys::{self, DepthFunc, GuPrimitive, MatrixMode, ScePspFVector3, VertexType};
+
+psp::module!("sample_lit_cube", 1, 1);
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct LitVertex {
+    normal: [f32; 3],
+    pos: [f32; 3],
+}
+
+unsafe impl Vertex for LitVertex {
+    const FORMAT: VertexType = VertexType::from_bits_truncate(
+        VertexType::NORMAL_32BITF.bits()
+            | VertexType::VERTEX_32BITF.bits()
+            | VertexType::TRANSFORM_3D.bits(),
+    );
+}
+
+/// The six faces of a unit cube, as two triangles each.
+fn cube() -> impl ExactSizeIterator<Item = LitVertex> {
+    const AXES: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    const CORNERS: [(f32, f32); 6] = [
+        (-1.0, -1.0),
+        (1.0, -1.0),
+        (1.0, 1.0),
+        (-1.0, -1.0),
+        (1.0, 1.0),
+        (-1.0, 1.0),
+    ];
+
+    (0..36).map(|i| {
+        let face = i / 6;
+        let sign = if face % 2 == 0 { 1.0 } else { -1.0 };
+        let n = AXES[face / 2];
+        let u = AXES[(face / 2 + 1) % 3];
+        let v = AXES[(face / 2 + 2) % 3];
+        let (a, b) = CORNERS[i % 6];
+
+        LitVertex {
+            normal: [0, 1, 2].map(|c| n[c] * sign),
+            pos: [0, 1, 2].map(|c| n[c] * sign + u[c] * a * sign + v[c] * b),
+        }
+    })
+}
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    let mut gu = Gu::init().unwrap();
+
+    let sun = Light::directional([0.0, 1.0, 1.0], 0x0080_8080);
+    let spot = Light::spot(
+        [0.0, 0.0, 3.0],
+        [0.0, 0.0, -1.0],
+        0x0000_ffff,
+        Attenuation::NONE,
+        8.0,
+        PI / 8.0,
+    );
+
+    let mut val = 0.0f32;
+
+    loop {
+        // A red light circling the cube.
+        let angle = val * PI / 90.0;
+        let orbit = unsafe {
+            [
+                3.0 * psp::math::cosf(angle),
+                0.0,
+                3.0 * psp::math::sinf(angle),
+            ]
+        };
+        let lamp = Light::point(
+            orbit,
+            0x0000_00ff,
+            Attenuation {
+                constant: 0.0,
+                linear: 0.4,
+                quadratic: 0.0,
+            },
+        )
+        .with_specular(0x00ff_ffff);
+
+        gu.frame(|frame| {
+            frame.clear(0xff55_4433);
+            frame.set_depth_test(Some(DepthFunc::GreaterOrEqual));
+
+            frame.set_lighting(true);
+            frame.set_ambient(0xff20_2020);
+            frame.set_light(0, &sun).unwrap();
+            frame.set_light(1, &lamp).unwrap();
+            frame.set_light(2, &spot).unwrap();
+            for i in 0..3 {
+                frame.enable_light(i, true).unwrap();
+            }
+
+            unsafe {
+                sys::sceGuModelColor(0, 0xffff_ffff, 0xffff_ffff, 0xffff_ffff);
+                sys::sceGuSpecular(12.0);
+
+                sys::sceGumMatrixMode(MatrixMode::Projection);
+                sys::sceGumLoadIdentity();
+                sys::sceGumPerspective(75.0, 16.0 / 9.0, 0.5, 1000.0);
+
+                sys::sceGumMatrixMode(MatrixMode::View);
+                sys::sceGumLoadIdentity();
+
+                sys::sceGumMatrixMode(MatrixMode::Model);
+                sys::sceGumLoadIdentity();
+                sys::sceGumTranslate(&ScePspFVector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -3.5,
+                });
+                sys::sceGumRotateXYZ(&ScePspFVector3 {
+                    x: val * 0.79 * (PI / 180.0),
+                    y: val * 0.98 * (PI / 180.0),
+                    z: val * 1.32 * (PI / 180.0),
+                });
+
+                // `draw_array` goes straight to the GE, so the matrices have
+                // to be uploaded first.
+                sys::sceGumUpdateMatrix();
+            }
+
+            let vertices = frame.push_vertices(cube());
+            frame.draw_array(GuPrimitive::Triangles, vertices);
+        });
+
+        val += 1.0;
+    }
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec;
+use psp::audio::capture::{CaptureError, Mic};
+use psp::audio::{self, Sound, SAMPLE_RATE};
+use psp::ctrl::{Button, Input};
+
+psp::module!("sample_mic_echo", 1, 1);
+
+const SECONDS: usize = 2;
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    // Recorded at the output rate, so it plays back as is.
+    let mut mic = match Mic::open(SAMPLE_RATE) {
+        Ok(mic) => mic,
+        Err(CaptureError::NoDevice) => {
+            psp::dprintln!("No microphone. Plug in a headset with one.");
+            return;
+        }
+        Err(e) => panic!("Can't open the microphone: {:?}", e),
+    };
+
+    let mut input = Input::new();
+    let mut recording = vec![0; SAMPLE_RATE as usize * SECONDS];
+
+    psp::dprintln!("Press Cross to record {} seconds.", SECONDS);
+
+    loop {
+        input.update();
+
+        if input.pressed(Button::CROSS) {
+            psp::dprintln!("Recording...");
+            let len = mic.read(&mut recording);
+
+            // The recording is mono, and sounds are stereo.
+            let samples = recording[..len].iter().flat_map(|&s| [s, s]).collect();
+            audio::play_once(&Sound::from_samples(samples)).unwrap();
+            psp::dprintln!("Playing back. Press Cross to record again.");
+        }
+
+        unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    }
+}
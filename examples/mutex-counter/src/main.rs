@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+use psp::sync::Mutex;
+use psp::thread;
+
+psp::module!("sample_mutex_counter", 1, 1);
+
+const INCREMENTS: u32 = 10_000;
+
+static COUNTER: Mutex<u32> = Mutex::new(0);
+
+fn count() {
+    for _ in 0..INCREMENTS {
+        *COUNTER.lock() += 1;
+    }
+}
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    // Uncontended: only this thread takes the lock.
+    count();
+    psp::dprintln!("One thread: {}", *COUNTER.lock());
+
+    // Contended: two threads of the same priority, each handing the CPU to
+    // the other while holding the lock, so each often finds it taken.
+    let workers: [_; 2] = [0, 1].map(|_| {
+        thread::spawn(|| {
+            for _ in 0..INCREMENTS {
+                let mut counter = COUNTER.lock();
+                thread::yield_now();
+                *counter += 1;
+            }
+        })
+    });
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    psp::dprintln!(
+        "Two threads: {}, expected {}",
+        *COUNTER.lock(),
+        3 * INCREMENTS
+    );
+}
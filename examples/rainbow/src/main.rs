@@ -15,7 +15,7 @@ fn psp_main() {
         sys::sceDisplaySetMode(sys::DisplayMode::Lcd, SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
 
         // Cache-through address
-        VRAM = (0x4000_0000u32 | sys::sceGeEdramGetAddr() as u32) as *mut u32;
+        VRAM = sys::uncached(sys::sceGeEdramGetAddr().cast::<u32>());
 
         sys::sceDisplaySetFrameBuf(
             VRAM as *const u8,
@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+
+use psp::gu::{Gu, Matrix4, Vertex};
+use psp::sys::{self, GuPrimitive, MatrixMode, ScePspFVector3, ScePspFVector4, VertexType};
+
+psp::module!("sample_skinned_arm", 1, 1);
+
+/// An arm two units long, bending at its middle.
+const SEGMENTS: usize = 8;
+const ELBOW: f32 = 1.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct ArmVertex {
+    /// How much the upper and lower arm bones move this vertex.
+    weights: [f32; 2],
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+unsafe impl Vertex for ArmVertex {
+    const FORMAT: VertexType = VertexType::from_bits_truncate(
+        VertexType::WEIGHT_32BITF.with_weights(2).bits()
+            | VertexType::COLOR_8888.bits()
+            | VertexType::VERTEX_32BITF.bits()
+            | VertexType::TRANSFORM_3D.bits(),
+    );
+}
+
+/// A triangle strip along X, blending between the bones around the elbow.
+fn arm() -> impl ExactSizeIterator<Item = ArmVertex> {
+    (0..(SEGMENTS + 1) * 2).map(|i| {
+        let x = (i / 2) as f32 * 2.0 / SEGMENTS as f32;
+        let lower = ((x - ELBOW + 0.25) / 0.5).max(0.0).min(1.0);
+
+        ArmVertex {
+            weights: [1.0 - lower, lower],
+            color: if lower < 0.5 {
+                0xff40_80ff
+            } else {
+                0xffff_8040
+            },
+            x,
+            y: if i % 2 == 0 { 0.15 } else { -0.15 },
+            z: 0.0,
+        }
+    })
+}
+
+/// A rotation by `angle` around the Z axis, followed by a translation.
+fn rotation(angle: f32, tx: f32, ty: f32) -> Matrix4 {
+    let (s, c) = unsafe { (psp::math::sinf(angle), psp::math::cosf(angle)) };
+    let v = |x, y, z, w| ScePspFVector4 { x, y, z, w };
+
+    // Columns, as `sceGuBoneMatrix` expects them.
+    Matrix4 {
+        x: v(c, s, 0.0, 0.0),
+        y: v(-s, c, 0.0, 0.0),
+        z: v(0.0, 0.0, 1.0, 0.0),
+        w: v(tx, ty, 0.0, 1.0),
+    }
+}
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    let mut gu = Gu::init().unwrap();
+    let mut time = 0.0f32;
+
+    loop {
+        let shoulder = unsafe { psp::math::sinf(time) } * 0.6;
+        let elbow = unsafe { psp::math::sinf(time * 1.7) } * 1.2 + 1.0;
+
+        // The lower arm turns around the elbow, then moves with the upper
+        // arm: p' = R(shoulder) * (R(elbow) * (p - e) + e).
+        let (s, c) = unsafe { (psp::math::sinf(elbow), psp::math::cosf(elbow)) };
+        let (ex, ey) = (ELBOW - c * ELBOW, -s * ELBOW);
+        let (s0, c0) = unsafe { (psp::math::sinf(shoulder), psp::math::cosf(shoulder)) };
+
+        let upper = rotation(shoulder, 0.0, 0.0);
+        let lower = rotation(shoulder + elbow, c0 * ex - s0 * ey, s0 * ex + c0 * ey);
+
+        gu.frame(|frame| {
+            frame.clear(0xff55_4433);
+            frame.set_bone_matrix(0, &upper);
+            frame.set_bone_matrix(1, &lower);
+
+            unsafe {
+                sys::sceGumMatrixMode(MatrixMode::Projection);
+                sys::sceGumLoadIdentity();
+                sys::sceGumPerspective(75.0, 16.0 / 9.0, 0.5, 1000.0);
+
+                sys::sceGumMatrixMode(MatrixMode::View);
+                sys::sceGumLoadIdentity();
+
+                sys::sceGumMatrixMode(MatrixMode::Model);
+                sys::sceGumLoadIdentity();
+                sys::sceGumTranslate(&ScePspFVector3 {
+                    x: -1.0,
+                    y: 0.0,
+                    z: -3.0,
+                });
+
+                // `draw_array` goes straight to the GE, so the matrices have
+                // to be uploaded first.
+                sys::sceGumUpdateMatrix();
+            }
+
+            let vertices = frame.push_vertices(arm());
+            frame.draw_array(GuPrimitive::TriangleStrip, vertices);
+        });
+
+        time += 0.03;
+    }
+}
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+use psp::audio::{self, wav};
+use psp::ctrl::{Button, Input};
+
+psp::module!("sample_sound_effect", 1, 1);
+
+const BEEP: &[u8] = include_bytes!("../assets/beep.wav");
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    // An 8-bit mono file at 22.05 kHz, converted on load.
+    let beep = wav::load(BEEP).unwrap();
+    let mut input = Input::new();
+
+    psp::dprintln!("Press Cross to beep.");
+
+    loop {
+        input.update();
+
+        if input.pressed(Button::CROSS) {
+            // Fails while all channels are still playing earlier beeps.
+            if let Err(e) = audio::play_once(&beep) {
+                psp::dprintln!("Can't play: {:?}", e);
+            }
+        }
+
+        unsafe { psp::sys::sceDisplayWaitVblankStart() };
+    }
+}
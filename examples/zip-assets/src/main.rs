@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use psp::gu::{Gu, SpriteVertex};
+use psp::image::png;
+use psp::io::File;
+use psp::sys::GuPrimitive;
+use psp::zip::Archive;
+use psp::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+psp::module!("sample_zip_assets", 1, 1);
+
+fn psp_main() {
+    psp::enable_home_button();
+
+    // Copy `assets/data.zip` next to EBOOT.PBP, which is where relative
+    // paths start from.
+    let mut archive = Archive::open(File::open("data.zip").unwrap()).unwrap();
+
+    let mut ferris = Vec::new();
+    archive
+        .by_name("ferris.png")
+        .unwrap()
+        .read_to_end(&mut ferris)
+        .unwrap();
+    let texture = png::load(&ferris).unwrap();
+
+    let mut gu = Gu::init().unwrap();
+
+    let (w, h) = (texture.width() as i16, texture.height() as i16);
+    let x = (SCREEN_WIDTH as i16 - w) / 2;
+    let y = (SCREEN_HEIGHT as i16 - h) / 2;
+    let sprite = [
+        SpriteVertex::new(0, 0, 0xffff_ffff, x, y),
+        SpriteVertex::new(w, h, 0xffff_ffff, x + w, y + h),
+    ];
+
+    loop {
+        let mut frame = gu.start_frame();
+        frame.clear(0xff55_4433);
+        frame.bind_texture(&texture);
+
+        let vertices = frame.push_vertices(sprite.iter().copied());
+        frame.draw_array(GuPrimitive::Sprites, vertices);
+        frame.finish();
+
+        gu.swap_buffers(true);
+    }
+}
@@ -3,10 +3,17 @@ use alloc::alloc::{GlobalAlloc, Layout};
 use core::{mem, ptr};
 
 /// An allocator that hooks directly into the PSP OS memory allocator.
+///
+/// Once `alloc_sys::init` has been called, allocations come from its heap
+/// instead.
 struct SystemAlloc;
 
 unsafe impl GlobalAlloc for SystemAlloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if let Some(ptr) = crate::alloc_sys::alloc(layout) {
+            return ptr;
+        }
+
         let size = layout.size()
             // We need to store the memory block ID.
             + mem::size_of::<SceUid>()
@@ -40,6 +47,10 @@ unsafe impl GlobalAlloc for SystemAlloc {
 
     #[inline(never)]
     unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        if crate::alloc_sys::dealloc(ptr) {
+            return;
+        }
+
         let align_padding = *ptr.sub(1);
 
         let id = *ptr.sub(align_padding as usize).cast::<SceUid>().offset(-1);
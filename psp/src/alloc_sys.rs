@@ -0,0 +1,263 @@
+//! A heap in one block of the user partition, used by the global allocator.
+//!
+//! By default, every allocation is its own block from
+//! `sceKernelAllocPartitionMemory`. That is a kernel call per allocation,
+//! and small allocations waste memory, since the kernel rounds blocks up.
+//! `init` instead reserves one block up front, and allocations are served
+//! from it with a free list:
+//!
+//! ```no_run
+//! // 16 MiB, or as much as is free.
+//! let size = psp::alloc_sys::init(16 * 1024 * 1024).unwrap();
+//! ```
+//!
+//! Anything allocated before `init` is still freed correctly afterwards.
+//! Once the heap is full, allocations fail rather than fall back to the
+//! partition.
+//!
+//! Thread stacks, loaded modules and the firmware's decoder buffers are
+//! allocated from the user partition outside of the heap, so leave room for
+//! them.
+//!
+//! # VRAM
+//!
+//! The heap is main RAM only. VRAM is a separate pool, handed out by
+//! `vram_alloc`, and the two never share memory: a `Vec` is never placed in
+//! VRAM, and freeing VRAM doesn't make room on the heap. Pixels copied into
+//! VRAM can be dropped from the heap once the copy is written back (see
+//! `cpu::dcache_writeback_range`).
+
+use crate::sys::{self, SceSysMemBlockTypes, SceSysMemPartitionId};
+use core::alloc::Layout;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Granularity of the heap. Every chunk starts and ends on a multiple of it.
+const UNIT: usize = 16;
+
+/// Smaller heaps aren't worth a block.
+const MIN_SIZE: usize = 4 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapError {
+    /// `init` has already been called.
+    AlreadyInitialized,
+    /// Less than 4 KiB was asked for, or is free, with the size that would
+    /// have been reserved.
+    TooSmall(usize),
+    /// Reserving the block failed, with the error code from
+    /// `sceKernelAllocPartitionMemory`.
+    Kernel(i32),
+}
+
+/// A free chunk, stored at its start.
+struct FreeChunk {
+    len: usize,
+    next: *mut FreeChunk,
+}
+
+/// Stored just before each allocation, for freeing it.
+struct Header {
+    /// The chunk containing the allocation, which may start before the
+    /// header to meet its alignment.
+    start: usize,
+    len: usize,
+}
+
+struct Heap {
+    start: usize,
+    end: usize,
+    /// Sorted by address, with no two chunks adjacent.
+    free: *mut FreeChunk,
+    free_len: usize,
+}
+
+/// Set once `init` has started, so that it only runs once.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Empty until `init`, so that nothing is allocated from it or freed to it.
+static mut HEAP: Heap = Heap {
+    start: 0,
+    end: 0,
+    free: ptr::null_mut(),
+    free_len: 0,
+};
+
+/// Reserve a block of `max_bytes`, or all of the largest free block of the
+/// user partition if smaller, and allocate from it from now on. Returns the
+/// size of the heap.
+pub fn init(max_bytes: usize) -> Result<usize, HeapError> {
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return Err(HeapError::AlreadyInitialized);
+    }
+
+    let size = max_bytes.min(unsafe { sys::sceKernelMaxFreeMemSize() }) & !(UNIT - 1);
+    if size < MIN_SIZE {
+        STARTED.store(false, Ordering::Relaxed);
+        return Err(HeapError::TooSmall(size));
+    }
+
+    let id = unsafe {
+        sys::sceKernelAllocPartitionMemory(
+            SceSysMemPartitionId::SceKernelPrimaryUserPartition,
+            &b"heap\0"[0],
+            SceSysMemBlockTypes::Low,
+            size as u32,
+            ptr::null_mut(),
+        )
+    };
+
+    if id.0 < 0 {
+        STARTED.store(false, Ordering::Relaxed);
+        return Err(HeapError::Kernel(id.0));
+    }
+
+    // The block is never freed, so the ID isn't kept.
+    let head = unsafe { sys::sceKernelGetBlockHeadAddr(id) } as usize;
+    let start = align_up(head, UNIT);
+    let end = (head + size) & !(UNIT - 1);
+
+    with_heap(|heap| unsafe {
+        let chunk = start as *mut FreeChunk;
+        chunk.write(FreeChunk {
+            len: end - start,
+            next: ptr::null_mut(),
+        });
+
+        *heap = Heap {
+            start,
+            end,
+            free: chunk,
+            free_len: end - start,
+        };
+    });
+
+    Ok(end - start)
+}
+
+/// Size of the heap in bytes, or 0 before `init`.
+pub fn capacity() -> usize {
+    with_heap(|heap| heap.end - heap.start)
+}
+
+/// Bytes not allocated, though not necessarily in one piece. Each
+/// allocation also takes a few bytes more than its size.
+pub fn free() -> usize {
+    with_heap(|heap| heap.free_len)
+}
+
+/// Allocate from the heap, or `None` before `init`. The pointer is null
+/// when there's no room.
+pub(crate) fn alloc(layout: Layout) -> Option<*mut u8> {
+    with_heap(|heap| {
+        if heap.start == heap.end {
+            None
+        } else {
+            Some(unsafe { heap.alloc(layout) })
+        }
+    })
+}
+
+/// Free `ptr` if it was allocated from the heap, returning whether it was.
+pub(crate) unsafe fn dealloc(ptr: *mut u8) -> bool {
+    with_heap(|heap| {
+        let contains = (heap.start..heap.end).contains(&(ptr as usize));
+        if contains {
+            heap.dealloc(ptr);
+        }
+
+        contains
+    })
+}
+
+impl Heap {
+    /// First fit: the first free chunk that the allocation fits in.
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let align = layout.align().max(UNIT);
+        let mut link: *mut *mut FreeChunk = &mut self.free;
+
+        while !(*link).is_null() {
+            let chunk = *link;
+            let start = chunk as usize;
+            let end = start + (*chunk).len;
+
+            let data = align_up(start + core::mem::size_of::<Header>(), align);
+            let used_end = match data.checked_add(layout.size()).map(|e| align_up(e, UNIT)) {
+                Some(used_end) if used_end <= end => used_end,
+                _ => {
+                    link = &mut (*chunk).next;
+                    continue;
+                }
+            };
+
+            // Keep the rest of the chunk free.
+            let next = (*chunk).next;
+            let used_end = if end - used_end >= UNIT {
+                let rest = used_end as *mut FreeChunk;
+                rest.write(FreeChunk {
+                    len: end - used_end,
+                    next,
+                });
+                *link = rest;
+                used_end
+            } else {
+                *link = next;
+                end
+            };
+
+            (data as *mut Header).sub(1).write(Header {
+                start,
+                len: used_end - start,
+            });
+            self.free_len -= used_end - start;
+
+            return data as *mut u8;
+        }
+
+        ptr::null_mut()
+    }
+
+    /// Return the allocation's chunk to the free list, merging it with the
+    /// chunks either side when they touch.
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        let Header { start, mut len } = (ptr as *mut Header).sub(1).read();
+        self.free_len += len;
+
+        let mut prev: *mut FreeChunk = ptr::null_mut();
+        let mut next = self.free;
+        while !next.is_null() && (next as usize) < start {
+            prev = next;
+            next = (*next).next;
+        }
+
+        if !next.is_null() && start + len == next as usize {
+            len += (*next).len;
+            next = (*next).next;
+        }
+
+        if !prev.is_null() && prev as usize + (*prev).len == start {
+            (*prev).len += len;
+            (*prev).next = next;
+            return;
+        }
+
+        let chunk = start as *mut FreeChunk;
+        chunk.write(FreeChunk { len, next });
+
+        if prev.is_null() {
+            self.free = chunk;
+        } else {
+            (*prev).next = chunk;
+        }
+    }
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+/// Run `f` with the heap, with interrupts suspended to keep out other
+/// threads.
+fn with_heap<R, F: FnOnce(&mut Heap) -> R>(f: F) -> R {
+    crate::critical(|| f(unsafe { &mut *ptr::addr_of_mut!(HEAP) }))
+}
@@ -0,0 +1,230 @@
+//! Playing ATRAC3 and ATRAC3plus (.at3) files with the hardware decoder.
+//!
+//! ```no_run
+//! use psp::audio::atrac::Player;
+//!
+//! let mut music = Player::open("ms0:/PSP/MUSIC/theme.at3", true).unwrap();
+//!
+//! // Plays in the background until dropped.
+//! music.pause();
+//! music.resume();
+//! ```
+//!
+//! Files are streamed from disk through a small buffer, rather than loaded
+//! whole. The codec modules are loaded on first use.
+
+use super::{AudioError, Decoder};
+use crate::sys::{self, IoOpenFlags, IoWhence, Module, SceUid};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+
+/// Bytes of the file held in memory at a time.
+const BUFFER_LEN: usize = 64 * 1024;
+
+/// Returned when all ATRAC IDs are in use.
+const ERROR_NO_ATRAC_ID: i32 = 0x8063_0003_u32 as i32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtracError {
+    /// The path contains a NUL byte.
+    InvalidPath,
+    /// The file could not be opened, with the error code from `sceIoOpen`.
+    Open(i32),
+    /// Reading the file failed, with the error code from `sceIoRead`.
+    Read(i32),
+    /// Loading the codec modules failed, with the error code.
+    LoadModule(i32),
+    /// All ATRAC IDs are in use by other players.
+    NoFreeId,
+    /// The firmware rejected the file, with its error code. This is usually
+    /// a file that isn't ATRAC3 or ATRAC3plus.
+    Rejected(i32),
+    /// No channel could be set up for output.
+    Audio(AudioError),
+}
+
+impl From<AudioError> for AtracError {
+    fn from(e: AudioError) -> Self {
+        AtracError::Audio(e)
+    }
+}
+
+/// An .at3 file playing on its own channel, stopped and released on drop.
+pub type Player = super::Player<AtracDecoder>;
+
+impl Player {
+    /// Start playing the file at `path`, such as `"ms0:/music.at3"`, from
+    /// the beginning. With `looping`, it starts over when it ends.
+    pub fn open(path: &str, looping: bool) -> Result<Self, AtracError> {
+        let decoder = AtracDecoder::open(path)?;
+        Ok(Self::start(decoder, looping)?)
+    }
+}
+
+/// An .at3 file being decoded, holding an ATRAC ID until dropped.
+pub struct AtracDecoder {
+    id: i32,
+    fd: SceUid,
+    /// Read by the decoder until the ID is released.
+    _data: Vec<u8>,
+    frame: Vec<i16>,
+    /// The last frame has been decoded.
+    ended: bool,
+}
+
+impl AtracDecoder {
+    /// Open the file at `path`, loading the codec modules if needed.
+    pub fn open(path: &str) -> Result<Self, AtracError> {
+        super::load_modules(&[Module::AvCodec, Module::AvAtrac3Plus])
+            .map_err(AtracError::LoadModule)?;
+
+        if path.contains('\0') {
+            return Err(AtracError::InvalidPath);
+        }
+
+        let mut c_path = Vec::with_capacity(path.len() + 1);
+        c_path.extend_from_slice(path.as_bytes());
+        c_path.push(0);
+
+        let fd = unsafe { sys::sceIoOpen(c_path.as_ptr(), IoOpenFlags::RD_ONLY, 0) };
+        if fd.0 < 0 {
+            return Err(AtracError::Open(fd.0));
+        }
+
+        let close = |e| {
+            unsafe { sys::sceIoClose(fd) };
+            Err(e)
+        };
+
+        // The whole file, when it fits.
+        let file_len = unsafe { sys::sceIoLseek(fd, 0, IoWhence::End) };
+        unsafe { sys::sceIoLseek(fd, 0, IoWhence::Set) };
+        let mut data = vec![0; (file_len.max(0) as usize).min(BUFFER_LEN)];
+
+        let read =
+            unsafe { sys::sceIoRead(fd, data.as_mut_ptr() as *mut c_void, data.len() as u32) };
+        if read < 0 {
+            return close(AtracError::Read(read));
+        }
+
+        // Given less than the whole file, the decoder asks for the rest as it
+        // goes.
+        let id = unsafe {
+            sys::sceAtracSetDataAndGetID(data.as_mut_ptr() as *mut c_void, read as usize)
+        };
+        match id {
+            ERROR_NO_ATRAC_ID => return close(AtracError::NoFreeId),
+            id if id < 0 => return close(AtracError::Rejected(id)),
+            _ => {}
+        }
+
+        let mut max_samples = 0;
+        let ret = unsafe { sys::sceAtracGetMaxSample(id, &mut max_samples) };
+        if ret < 0 {
+            unsafe { sys::sceAtracReleaseAtracID(id) };
+            return close(AtracError::Rejected(ret));
+        }
+
+        Ok(Self {
+            id,
+            fd,
+            _data: data,
+            // Decoded as stereo.
+            frame: vec![0; max_samples as usize * 2],
+            ended: false,
+        })
+    }
+
+    /// Read as much of the file as the decoder has room for.
+    fn refill(&mut self) {
+        let mut remain = 0;
+        unsafe { sys::sceAtracGetRemainFrame(self.id, &mut remain) };
+
+        // Negative when the rest of the file is already in memory.
+        if remain < 0 {
+            return;
+        }
+
+        let (mut ptr, mut available, mut offset) = (core::ptr::null_mut(), 0, 0);
+        unsafe { sys::sceAtracGetStreamDataInfo(self.id, &mut ptr, &mut available, &mut offset) };
+
+        if available > 0 {
+            unsafe {
+                sys::sceIoLseek(self.fd, offset as i64, IoWhence::Set);
+                let read = sys::sceIoRead(self.fd, ptr as *mut c_void, available);
+                if read > 0 {
+                    sys::sceAtracAddStreamData(self.id, read as u32);
+                }
+            }
+        }
+    }
+}
+
+impl Decoder for AtracDecoder {
+    fn decode(&mut self) -> Option<&[i16]> {
+        if self.ended {
+            return None;
+        }
+
+        self.refill();
+
+        let (mut samples, mut end, mut remain) = (0, 0, 0);
+        let ret = unsafe {
+            sys::sceAtracDecodeData(
+                self.id,
+                self.frame.as_mut_ptr() as *mut u16,
+                &mut samples,
+                &mut end,
+                &mut remain,
+            )
+        };
+
+        // Corrupt data, or data not read in time. Either way, stop.
+        if ret < 0 {
+            return None;
+        }
+
+        self.ended = end != 0;
+        Some(&self.frame[..samples.max(0) as usize * 2])
+    }
+
+    /// Go back to the first sample, refilling the buffer from the file if
+    /// needed.
+    fn rewind(&mut self) -> bool {
+        let mut info = unsafe { core::mem::zeroed::<sys::Atrac3BufferInfo>() };
+        if unsafe { sys::sceAtracGetBufferInfoForReseting(self.id, 0, &mut info) } < 0 {
+            return false;
+        }
+
+        let mut read = 0;
+        if info.ui_writable_byte_first_buf > 0 {
+            unsafe {
+                sys::sceIoLseek(
+                    self.fd,
+                    info.ui_read_position_first_buf as i64,
+                    IoWhence::Set,
+                );
+                read = sys::sceIoRead(
+                    self.fd,
+                    info.puc_write_position_first_buf as *mut c_void,
+                    info.ui_writable_byte_first_buf,
+                )
+                .max(0);
+            }
+        }
+
+        let ret = unsafe { sys::sceAtracResetPlayPosition(self.id, 0, read as u32, 0) };
+        self.ended = ret < 0;
+        !self.ended
+    }
+}
+
+impl Drop for AtracDecoder {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceAtracReleaseAtracID(self.id);
+            sys::sceIoClose(self.fd);
+        }
+    }
+}
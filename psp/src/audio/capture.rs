@@ -0,0 +1,176 @@
+//! Recording from the microphone.
+//!
+//! ```no_run
+//! use psp::audio::capture::Mic;
+//!
+//! let mut mic = Mic::open(22_050).unwrap();
+//! let mut samples = [0i16; 1024];
+//! let read = mic.read(&mut samples);
+//! ```
+//!
+//! Samples are mono. The microphone is either built in, or on a headset
+//! plugged into the remote port, depending on the model. Without one,
+//! `Mic::open` fails with `CaptureError::NoDevice`.
+
+use crate::sys::{self, AudioInputFrequency};
+use core::ffi::c_void;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Sample rates the microphone records at.
+pub const SAMPLE_RATES: &[u32] = &[11_025, 22_050, 44_100];
+
+/// The gain used until `Mic::set_gain`.
+pub const DEFAULT_GAIN: i32 = 0x4000;
+
+/// Samples per input request. Reads are whole multiples of it.
+const CHUNK_SAMPLES: usize = 64;
+/// Samples read at most per request, so that a long read isn't one call.
+const MAX_READ_SAMPLES: usize = 4096;
+
+/// How long to wait for the first samples before deciding that there is no
+/// microphone. They take under 6 ms at the lowest rate.
+const PROBE_TIMEOUT_US: u32 = 100_000;
+
+/// Whether a `Mic` is open. There is only one input.
+static OPEN: AtomicBool = AtomicBool::new(false);
+
+/// Written by the probe in `open`. Static, since the input may still be
+/// pending when `open` gives up, and be written long after.
+static mut PROBE: [i16; CHUNK_SAMPLES] = [0; CHUNK_SAMPLES];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureError {
+    /// The microphone doesn't record at this rate. See `SAMPLE_RATES`.
+    UnsupportedRate(u32),
+    /// There is no microphone, or it didn't respond.
+    NoDevice,
+    /// Another `Mic` is open.
+    InUse,
+    /// The kernel returned an error code.
+    Kernel(i32),
+}
+
+/// The microphone, open for recording until dropped.
+#[derive(Debug)]
+pub struct Mic {
+    sample_rate: u32,
+    gain: i32,
+}
+
+impl Mic {
+    /// Open the microphone to record at `sample_rate`, one of
+    /// `SAMPLE_RATES`, with `DEFAULT_GAIN`.
+    ///
+    /// A few samples are recorded to check that the microphone is there,
+    /// since reading from a missing one never returns.
+    pub fn open(sample_rate: u32) -> Result<Self, CaptureError> {
+        let freq = frequency(sample_rate).ok_or(CaptureError::UnsupportedRate(sample_rate))?;
+
+        if OPEN.swap(true, Ordering::Acquire) {
+            return Err(CaptureError::InUse);
+        }
+
+        let mut mic = Self {
+            sample_rate,
+            gain: DEFAULT_GAIN,
+        };
+
+        // From here on, dropping `mic` clears `OPEN`.
+        mic.set_gain(DEFAULT_GAIN)?;
+        probe(freq)?;
+
+        Ok(mic)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Set the input gain. The firmware doesn't document a range, but
+    /// higher is louder.
+    pub fn set_gain(&mut self, gain: i32) -> Result<(), CaptureError> {
+        let ret = unsafe { sys::sceAudioInputInit(0, gain, 0) };
+        if ret < 0 {
+            return Err(CaptureError::Kernel(ret));
+        }
+
+        self.gain = gain;
+        Ok(())
+    }
+
+    pub fn gain(&self) -> i32 {
+        self.gain
+    }
+
+    /// Fill `buf` with samples, blocking until they have been recorded.
+    /// Returns how many were read.
+    ///
+    /// Samples are read in multiples of 64, so up to 63 at the end of
+    /// `buf` are left as they were. Fewer are read if recording fails.
+    pub fn read(&mut self, buf: &mut [i16]) -> usize {
+        let len = buf.len() - buf.len() % CHUNK_SAMPLES;
+
+        for (i, chunk) in buf[..len].chunks_mut(MAX_READ_SAMPLES).enumerate() {
+            let ret = unsafe {
+                sys::sceAudioInputBlocking(
+                    chunk.len() as i32,
+                    // Checked in `open`.
+                    frequency(self.sample_rate).unwrap(),
+                    chunk.as_mut_ptr() as *mut c_void,
+                )
+            };
+
+            if ret < 0 {
+                return i * MAX_READ_SAMPLES;
+            }
+        }
+
+        len
+    }
+}
+
+impl Drop for Mic {
+    fn drop(&mut self) {
+        OPEN.store(false, Ordering::Release);
+    }
+}
+
+fn frequency(sample_rate: u32) -> Option<AudioInputFrequency> {
+    match sample_rate {
+        11_025 => Some(AudioInputFrequency::Khz11_025),
+        22_050 => Some(AudioInputFrequency::Khz22_05),
+        44_100 => Some(AudioInputFrequency::Khz44_1),
+        _ => None,
+    }
+}
+
+/// Record one chunk without blocking, and wait for it for a limited time.
+fn probe(freq: AudioInputFrequency) -> Result<(), CaptureError> {
+    let ret = unsafe {
+        sys::sceAudioInput(
+            CHUNK_SAMPLES as i32,
+            freq,
+            ptr::addr_of_mut!(PROBE) as *mut c_void,
+        )
+    };
+
+    // Also returned while an earlier probe is still waiting.
+    if ret < 0 {
+        return Err(CaptureError::NoDevice);
+    }
+
+    let mut waited = 0;
+    while waited < PROBE_TIMEOUT_US {
+        match unsafe { sys::sceAudioPollInputEnd() } {
+            0 => return Ok(()),
+            e if e < 0 => return Err(CaptureError::Kernel(e)),
+            _ => {}
+        }
+
+        unsafe { sys::sceKernelDelayThread(1000) };
+        waited += 1000;
+    }
+
+    Err(CaptureError::NoDevice)
+}
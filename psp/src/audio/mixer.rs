@@ -0,0 +1,474 @@
+//! Mixing sounds and music in software, onto one channel.
+
+use super::player::Feeder;
+use super::{AudioError, Channel, Decoder, Format, Sound, Stream, MAX_VOLUME, SAMPLE_RATE};
+use crate::critical;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Samples per block of the output channel. Shorter than a `Player`'s, so
+/// that sounds start sooner.
+const BLOCK_SAMPLES: usize = 512;
+
+/// How a voice is played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiceSettings {
+    /// From 0 to `MAX_VOLUME`.
+    pub volume: u16,
+    /// From -1.0, left only, to 1.0, right only.
+    pub pan: f32,
+    /// Start over at the end, until stopped.
+    pub looping: bool,
+}
+
+impl Default for VoiceSettings {
+    /// Full volume, centered, played once.
+    fn default() -> Self {
+        Self {
+            volume: MAX_VOLUME,
+            pan: 0.0,
+            looping: false,
+        }
+    }
+}
+
+/// A voice started by a `Mixer`, for changing or stopping it later.
+///
+/// Once the voice ends, or its slot is taken by another, the handle does
+/// nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Voice {
+    slot: usize,
+    generation: u32,
+}
+
+/// Mixes up to a fixed number of voices onto one channel, played from a
+/// thread. Stopped and released on drop.
+///
+/// ```no_run
+/// use psp::audio::{wav, Mixer, VoiceSettings};
+///
+/// # let data: &[u8] = &[];
+/// let beep = wav::load(data).unwrap();
+/// let mut mixer = Mixer::start(16).unwrap();
+///
+/// let voice = mixer.play(&beep, VoiceSettings {
+///     pan: -0.5,
+///     ..Default::default()
+/// });
+/// mixer.set_volume(voice, 0x4000);
+/// ```
+///
+/// Voices free their slot when they end. Starting a voice with every slot
+/// in use takes the slot of the quietest voice, the oldest of them if
+/// several are as quiet, so the choice only depends on what was played.
+///
+/// Voices are summed at full precision and clamped once, so loud voices
+/// overlapping clip rather than wrap around.
+pub struct Mixer {
+    stream: Stream,
+    shared: Arc<Shared>,
+    slots: Vec<Slot>,
+    /// The order of the next voice started, for finding the oldest.
+    next_order: u64,
+}
+
+/// The `Mixer`'s view of a slot.
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    /// Of the last voice started in the slot.
+    generation: u32,
+    volume: u16,
+    pan: f32,
+    order: u64,
+}
+
+/// State shared between the `Mixer` and its output thread.
+struct Shared {
+    /// Per slot, the generation of the last voice to end in it. The slot is
+    /// free when it matches the last started.
+    ended: Box<[AtomicU32]>,
+    /// Commands not yet taken by the output thread, oldest first. Only used
+    /// with interrupts suspended.
+    queue: UnsafeCell<Queue>,
+}
+
+// Commands are only reached through the queue, with interrupts suspended.
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+struct Queue {
+    head: *mut Command,
+    tail: *mut Command,
+}
+
+struct Command {
+    slot: usize,
+    generation: u32,
+    kind: CommandKind,
+    next: *mut Command,
+}
+
+enum CommandKind {
+    Play(Source, Gains),
+    SetGains(Gains),
+    Stop,
+}
+
+/// Volumes for each side, out of `MAX_VOLUME`.
+#[derive(Clone, Copy)]
+struct Gains {
+    left: i32,
+    right: i32,
+}
+
+enum Source {
+    Sound {
+        sound: Sound,
+        pos: usize,
+        looping: bool,
+    },
+    Decoder(Feeder<Box<dyn Decoder>>),
+}
+
+/// Owned by the output thread.
+struct Mix {
+    shared: Arc<Shared>,
+    voices: Vec<Option<Active>>,
+    /// A voice's samples for the current block.
+    scratch: Vec<i16>,
+    sums: Vec<i32>,
+}
+
+struct Active {
+    generation: u32,
+    source: Source,
+    gains: Gains,
+}
+
+impl Mixer {
+    /// Start mixing on a new channel, with room for `polyphony` voices at
+    /// once, or 1 if 0.
+    pub fn start(polyphony: usize) -> Result<Self, AudioError> {
+        let polyphony = polyphony.max(1);
+        let channel = Channel::reserve(BLOCK_SAMPLES, Format::Stereo16)?;
+
+        let shared = Arc::new(Shared {
+            ended: (0..polyphony).map(|_| AtomicU32::new(0)).collect(),
+            queue: UnsafeCell::new(Queue {
+                head: ptr::null_mut(),
+                tail: ptr::null_mut(),
+            }),
+        });
+
+        let mut mix = Mix {
+            shared: shared.clone(),
+            voices: (0..polyphony).map(|_| None).collect(),
+            scratch: vec![0; BLOCK_SAMPLES * 2],
+            sums: vec![0; BLOCK_SAMPLES * 2],
+        };
+
+        let stream = Stream::start(channel, move |block| mix.fill(block))?;
+
+        Ok(Self {
+            stream,
+            shared,
+            slots: vec![Slot::default(); polyphony],
+            next_order: 0,
+        })
+    }
+
+    /// Start playing `sound`.
+    pub fn play(&mut self, sound: &Sound, settings: VoiceSettings) -> Voice {
+        let source = Source::Sound {
+            sound: sound.clone(),
+            pos: 0,
+            looping: settings.looping,
+        };
+
+        self.start_voice(source, settings)
+    }
+
+    /// Start playing from `decoder`, such as music from an
+    /// `atrac::AtracDecoder`.
+    ///
+    /// This fails with `AudioError::UnsupportedRate` for a decoder not at
+    /// `SAMPLE_RATE`, since voices aren't resampled.
+    pub fn play_decoder<D: Decoder>(
+        &mut self,
+        decoder: D,
+        settings: VoiceSettings,
+    ) -> Result<Voice, AudioError> {
+        match decoder.sample_rate() {
+            SAMPLE_RATE => {}
+            rate => {
+                return Err(AudioError::UnsupportedRate {
+                    rate,
+                    supported: &[SAMPLE_RATE],
+                })
+            }
+        }
+
+        let decoder: Box<dyn Decoder> = Box::new(decoder);
+        let source = Source::Decoder(Feeder::new(decoder, settings.looping));
+
+        Ok(self.start_voice(source, settings))
+    }
+
+    /// Stop `voice` after the current block, freeing its slot.
+    pub fn stop(&mut self, voice: Voice) {
+        if !self.is_playing(voice) {
+            return;
+        }
+
+        self.shared.ended[voice.slot].store(voice.generation, Ordering::Release);
+        self.send(voice, CommandKind::Stop);
+    }
+
+    /// Set the volume of `voice`, from 0 to `MAX_VOLUME`.
+    pub fn set_volume(&mut self, voice: Voice, volume: u16) {
+        if self.is_playing(voice) {
+            self.slots[voice.slot].volume = volume;
+            self.update_gains(voice);
+        }
+    }
+
+    /// Set the pan of `voice`, from -1.0, left only, to 1.0, right only.
+    pub fn set_pan(&mut self, voice: Voice, pan: f32) {
+        if self.is_playing(voice) {
+            self.slots[voice.slot].pan = pan;
+            self.update_gains(voice);
+        }
+    }
+
+    /// Whether `voice` hasn't ended, been stopped, or lost its slot.
+    pub fn is_playing(&self, voice: Voice) -> bool {
+        match self.slots.get(voice.slot) {
+            Some(slot) => slot.generation == voice.generation && !self.is_free(voice.slot),
+            None => false,
+        }
+    }
+
+    /// Voices currently playing.
+    pub fn playing(&self) -> usize {
+        (0..self.slots.len()).filter(|&i| !self.is_free(i)).count()
+    }
+
+    pub fn polyphony(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Stop mixing after the current block, until `resume`.
+    pub fn pause(&mut self) {
+        self.stream.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.stream.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.stream.is_paused()
+    }
+
+    /// Blocks that started playing late, because mixing took longer than a
+    /// block.
+    pub fn underruns(&self) -> usize {
+        self.stream.underruns()
+    }
+
+    fn is_free(&self, slot: usize) -> bool {
+        self.shared.ended[slot].load(Ordering::Acquire) == self.slots[slot].generation
+    }
+
+    fn start_voice(&mut self, source: Source, settings: VoiceSettings) -> Voice {
+        // A free slot, or else the quietest voice, oldest first.
+        let slot = (0..self.slots.len())
+            .find(|&i| self.is_free(i))
+            .or_else(|| {
+                (0..self.slots.len()).min_by_key(|&i| (self.slots[i].volume, self.slots[i].order))
+            })
+            .unwrap_or(0);
+
+        let generation = self.slots[slot].generation.wrapping_add(1);
+        self.slots[slot] = Slot {
+            generation,
+            volume: settings.volume,
+            pan: settings.pan,
+            order: self.next_order,
+        };
+        self.next_order += 1;
+
+        let voice = Voice { slot, generation };
+        let gains = gains(settings.volume, settings.pan);
+        self.send(voice, CommandKind::Play(source, gains));
+
+        voice
+    }
+
+    fn update_gains(&mut self, voice: Voice) {
+        let slot = &self.slots[voice.slot];
+        let gains = gains(slot.volume, slot.pan);
+        self.send(voice, CommandKind::SetGains(gains));
+    }
+
+    fn send(&mut self, voice: Voice, kind: CommandKind) {
+        // Allocated before suspending interrupts.
+        let command = Box::into_raw(Box::new(Command {
+            slot: voice.slot,
+            generation: voice.generation,
+            kind,
+            next: ptr::null_mut(),
+        }));
+
+        self.shared.with_queue(|queue| unsafe {
+            match queue.tail.as_mut() {
+                Some(tail) => tail.next = command,
+                None => queue.head = command,
+            }
+
+            queue.tail = command;
+        });
+    }
+}
+
+impl Shared {
+    /// Run `f` with the queue, with interrupts suspended to keep out the
+    /// other thread.
+    fn with_queue<R, F: FnOnce(&mut Queue) -> R>(&self, f: F) -> R {
+        critical(|| f(unsafe { &mut *self.queue.get() }))
+    }
+
+    /// Take the queued commands, oldest first.
+    fn take_commands(&self) -> *mut Command {
+        self.with_queue(|queue| {
+            queue.tail = ptr::null_mut();
+            core::mem::replace(&mut queue.head, ptr::null_mut())
+        })
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let mut next = self.take_commands();
+
+        while !next.is_null() {
+            let command = unsafe { Box::from_raw(next) };
+            next = command.next;
+        }
+    }
+}
+
+impl Mix {
+    fn fill(&mut self, block: &mut [i16]) {
+        self.apply_commands();
+        self.sums.fill(0);
+
+        for (slot, entry) in self.voices.iter_mut().enumerate() {
+            let voice = match entry {
+                Some(voice) => voice,
+                None => continue,
+            };
+
+            let len = voice.source.read(&mut self.scratch);
+            let Gains { left, right } = voice.gains;
+
+            for (sum, values) in self
+                .sums
+                .chunks_exact_mut(2)
+                .zip(self.scratch[..len].chunks_exact(2))
+            {
+                sum[0] = sum[0].saturating_add((values[0] as i32 * left) >> 15);
+                sum[1] = sum[1].saturating_add((values[1] as i32 * right) >> 15);
+            }
+
+            if len < self.scratch.len() {
+                self.shared.ended[slot].store(voice.generation, Ordering::Release);
+                *entry = None;
+            }
+        }
+
+        for (value, &sum) in block.iter_mut().zip(&self.sums) {
+            *value = sum.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+    }
+
+    fn apply_commands(&mut self) {
+        let mut next = self.shared.take_commands();
+
+        while !next.is_null() {
+            let command = unsafe { *Box::from_raw(next) };
+            next = command.next;
+
+            let entry = &mut self.voices[command.slot];
+            let current = matches!(entry, Some(voice) if voice.generation == command.generation);
+
+            match command.kind {
+                // Replaces any voice whose slot was taken.
+                CommandKind::Play(source, gains) => {
+                    *entry = Some(Active {
+                        generation: command.generation,
+                        source,
+                        gains,
+                    })
+                }
+                // Otherwise, the voice has already ended.
+                _ if !current => {}
+                CommandKind::SetGains(gains) => {
+                    if let Some(voice) = entry {
+                        voice.gains = gains;
+                    }
+                }
+                CommandKind::Stop => *entry = None,
+            }
+        }
+    }
+}
+
+impl Source {
+    /// Fill `out` with the next samples, returning how many values were
+    /// written. Fewer than `out.len()` means the source has ended.
+    fn read(&mut self, out: &mut [i16]) -> usize {
+        match self {
+            Source::Sound {
+                sound,
+                pos,
+                looping,
+            } => {
+                let samples = sound.samples();
+                let mut written = 0;
+
+                while written < out.len() && *pos < samples.len() {
+                    let n = (samples.len() - *pos).min(out.len() - written);
+                    out[written..written + n].copy_from_slice(&samples[*pos..*pos + n]);
+                    *pos += n;
+                    written += n;
+
+                    if *pos == samples.len() && *looping {
+                        *pos = 0;
+                    }
+                }
+
+                written
+            }
+            Source::Decoder(feeder) => feeder.fill(out),
+        }
+    }
+}
+
+/// Split `volume` between the sides by `pan`, keeping the nearer side at
+/// full `volume`.
+fn gains(volume: u16, pan: f32) -> Gains {
+    let volume = volume.min(MAX_VOLUME) as f32;
+    let pan = pan.clamp(-1.0, 1.0);
+
+    Gains {
+        left: (volume * (1.0 - pan.max(0.0))) as i32,
+        right: (volume * (1.0 + pan.min(0.0))) as i32,
+    }
+}
@@ -0,0 +1,380 @@
+//! Audio output through the hardware channels.
+//!
+//! ```no_run
+//! use psp::audio::{Channel, Format};
+//!
+//! let mut channel = Channel::reserve(1024, Format::Stereo16).unwrap();
+//! let block = [0i16; 1024 * 2];
+//!
+//! loop {
+//!     channel.output_blocking(&block).unwrap();
+//! }
+//! ```
+//!
+//! Samples are signed 16-bit, with stereo samples interleaved left first. A
+//! channel plays one block at a time, of the size it was reserved with.
+//!
+//! The 8 hardware channels play at `SAMPLE_RATE`. One more channel, reserved
+//! with `Channel::reserve_src`, converts other sample rates in hardware.
+//!
+//! For sound effects, `wav::load` decodes a WAV file into a `Sound`, which
+//! `play_once` plays in the background. Music is best kept in ATRAC3 files,
+//! streamed by `atrac::Player`. MP3 files are streamed by `mp3::Player`,
+//! and Ogg Vorbis files by `vorbis::Player`, with the `vorbis` feature.
+//!
+//! A `Mixer` plays many sounds and a music decoder together on one channel,
+//! for games that would otherwise run out of channels.
+//!
+//! `capture::Mic` records from the microphone.
+//!
+//! `test_tone` plays a tone using nothing but a `Channel`, to check that
+//! output works when nothing else does.
+//!
+//! The hardware has no master volume a program can set, only one per
+//! channel. `set_volume` scales every channel's volume instead, such as to
+//! duck the game's audio while `headphones_connected` is false.
+
+pub mod atrac;
+pub mod capture;
+mod mixer;
+pub mod mp3;
+mod player;
+mod sound;
+mod stream;
+mod tone;
+#[cfg(feature = "vorbis")]
+pub mod vorbis;
+pub mod wav;
+
+use crate::sys::{
+    self, AudioFormat, AudioOutputFrequency, AUDIO_CHANNEL_MAX, AUDIO_SAMPLE_MAX, AUDIO_SAMPLE_MIN,
+};
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+pub use mixer::{Mixer, Voice, VoiceSettings};
+pub use player::{Decoder, Player};
+pub use sound::{play_once, Sound};
+pub use stream::Stream;
+pub use tone::{test_tone, Waveform};
+
+/// The loudest volume, which plays samples unchanged.
+pub const MAX_VOLUME: u16 = sys::AUDIO_VOLUME_MAX as u16;
+
+/// Samples per second played by the hardware channels.
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Sample rates the SRC channel converts from.
+pub const SRC_SAMPLE_RATES: &[u32] = &[
+    8_000, 11_025, 12_000, 16_000, 22_050, 24_000, 32_000, 44_100, 48_000,
+];
+
+/// The number the kernel gives the SRC channel, after the hardware channels.
+const SRC_CHANNEL: i32 = AUDIO_CHANNEL_MAX as i32;
+/// Samples per block the SRC channel accepts.
+const SRC_SAMPLES_MIN: usize = 17;
+const SRC_SAMPLES_MAX: usize = 4111;
+
+/// Above the default priority of 32 of the main thread, so output isn't held
+/// up by it.
+const THREAD_PRIORITY: i32 = 0x12;
+const STACK_SIZE: i32 = 64 * 1024;
+
+/// Returned when loading a module that is already loaded.
+const ERROR_ALREADY_LOADED: i32 = 0x8011_1102_u32 as i32;
+
+/// Channels currently reserved through `Channel`.
+static RESERVED: AtomicU32 = AtomicU32::new(0);
+/// Whether the SRC channel is reserved.
+static SRC_RESERVED: AtomicBool = AtomicBool::new(false);
+/// The volume set with `set_volume`, left in the high half.
+static MASTER_VOLUME: AtomicU32 = AtomicU32::new((MAX_VOLUME as u32) << 16 | MAX_VOLUME as u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Stereo16,
+    Mono16,
+}
+
+impl Format {
+    /// Values per sample.
+    pub fn channels(self) -> usize {
+        match self {
+            Format::Stereo16 => 2,
+            Format::Mono16 => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioError {
+    /// The block size is not a multiple of 64 from 64 to 65472 samples, or
+    /// for the SRC channel, from 17 to 4111 samples.
+    InvalidBlockSize(usize),
+    /// All 8 hardware channels are reserved.
+    NoFreeChannel,
+    /// The SRC channel is already reserved. There is only one.
+    SrcChannelInUse,
+    /// The SRC channel can't convert from `rate`. It supports the rates in
+    /// `supported`, which is `SRC_SAMPLE_RATES`.
+    UnsupportedRate {
+        rate: u32,
+        supported: &'static [u32],
+    },
+    /// The frequency is 0, or too high to play at `SAMPLE_RATE`.
+    InvalidFrequency(u32),
+    /// The data passed is not one block: `expected` values, for the
+    /// channel's block size and format.
+    WrongBlockLength { expected: usize, actual: usize },
+    /// The kernel returned an error code.
+    Kernel(i32),
+}
+
+/// A reserved hardware output channel, released on drop.
+#[derive(Debug)]
+pub struct Channel {
+    id: i32,
+    samples_per_block: usize,
+    format: Format,
+    volume: (u16, u16),
+    sample_rate: u32,
+}
+
+impl Channel {
+    /// Reserve the first free channel, playing blocks of `samples_per_block`
+    /// samples in `format`. The volume starts at `MAX_VOLUME`.
+    pub fn reserve(samples_per_block: usize, format: Format) -> Result<Self, AudioError> {
+        let valid = (AUDIO_SAMPLE_MIN as usize..=AUDIO_SAMPLE_MAX as usize)
+            .contains(&samples_per_block)
+            && samples_per_block % 64 == 0;
+
+        if !valid {
+            return Err(AudioError::InvalidBlockSize(samples_per_block));
+        }
+
+        if RESERVED.fetch_add(1, Ordering::Relaxed) >= AUDIO_CHANNEL_MAX {
+            RESERVED.fetch_sub(1, Ordering::Relaxed);
+            return Err(AudioError::NoFreeChannel);
+        }
+
+        let sys_format = match format {
+            Format::Stereo16 => AudioFormat::Stereo,
+            Format::Mono16 => AudioFormat::Mono,
+        };
+
+        let id = unsafe {
+            sys::sceAudioChReserve(
+                sys::AUDIO_NEXT_CHANNEL,
+                samples_per_block as i32,
+                sys_format,
+            )
+        };
+
+        if id < 0 {
+            RESERVED.fetch_sub(1, Ordering::Relaxed);
+            return Err(AudioError::Kernel(id));
+        }
+
+        Ok(Self {
+            id,
+            samples_per_block,
+            format,
+            volume: (MAX_VOLUME, MAX_VOLUME),
+            sample_rate: SAMPLE_RATE,
+        })
+    }
+
+    /// Reserve the SRC channel, playing stereo blocks of `samples_per_block`
+    /// samples at `sample_rate`, which is one of `SRC_SAMPLE_RATES`.
+    ///
+    /// There is only one SRC channel. It is free again once this is dropped.
+    pub fn reserve_src(sample_rate: u32, samples_per_block: usize) -> Result<Self, AudioError> {
+        let freq = match sample_rate {
+            48_000 => AudioOutputFrequency::Khz48,
+            44_100 => AudioOutputFrequency::Khz44_1,
+            32_000 => AudioOutputFrequency::Khz32,
+            24_000 => AudioOutputFrequency::Khz24,
+            22_050 => AudioOutputFrequency::Khz22_05,
+            16_000 => AudioOutputFrequency::Khz16,
+            12_000 => AudioOutputFrequency::Khz12,
+            11_025 => AudioOutputFrequency::Khz11_025,
+            8_000 => AudioOutputFrequency::Khz8,
+            rate => {
+                return Err(AudioError::UnsupportedRate {
+                    rate,
+                    supported: SRC_SAMPLE_RATES,
+                })
+            }
+        };
+
+        if !(SRC_SAMPLES_MIN..=SRC_SAMPLES_MAX).contains(&samples_per_block) {
+            return Err(AudioError::InvalidBlockSize(samples_per_block));
+        }
+
+        if SRC_RESERVED.swap(true, Ordering::Acquire) {
+            return Err(AudioError::SrcChannelInUse);
+        }
+
+        let ret = unsafe { sys::sceAudioSRCChReserve(samples_per_block as i32, freq, 2) };
+
+        if ret < 0 {
+            SRC_RESERVED.store(false, Ordering::Release);
+            return Err(AudioError::Kernel(ret));
+        }
+
+        Ok(Self {
+            id: SRC_CHANNEL,
+            samples_per_block,
+            format: Format::Stereo16,
+            volume: (MAX_VOLUME, MAX_VOLUME),
+            sample_rate,
+        })
+    }
+
+    /// The hardware channel number, from 0 to 7, or 8 for the SRC channel.
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn is_src(&self) -> bool {
+        self.id == SRC_CHANNEL
+    }
+
+    /// Samples per second, which is `SAMPLE_RATE` except on the SRC channel.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn samples_per_block(&self) -> usize {
+        self.samples_per_block
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Set the volume of each side, up to `MAX_VOLUME`. Louder values are
+    /// clamped. This also applies to the block playing now.
+    ///
+    /// The SRC channel has a single volume, so it plays both sides at the
+    /// louder of the two, from the next block.
+    ///
+    /// The channel plays at this volume scaled by the master volume, from
+    /// `audio::set_volume`.
+    pub fn set_volume(&mut self, left: u16, right: u16) {
+        self.volume = (left.min(MAX_VOLUME), right.min(MAX_VOLUME));
+
+        if !self.is_src() {
+            let (left, right) = self.output_volume();
+            unsafe { sys::sceAudioChangeChannelVolume(self.id, left, right) };
+        }
+    }
+
+    pub fn volume(&self) -> (u16, u16) {
+        self.volume
+    }
+
+    /// The volume of each side, scaled by the master volume.
+    fn output_volume(&self) -> (i32, i32) {
+        let (left, right) = volume();
+        let scale =
+            |volume: u16, master: u16| (volume as u32 * master as u32 / MAX_VOLUME as u32) as i32;
+
+        (scale(self.volume.0, left), scale(self.volume.1, right))
+    }
+
+    /// Play one block, first waiting for the channel to be done with the
+    /// previous one.
+    ///
+    /// `block` holds `samples_per_block` samples, so twice as many values
+    /// for stereo.
+    pub fn output_blocking(&mut self, block: &[i16]) -> Result<(), AudioError> {
+        let expected = self.samples_per_block * self.format.channels();
+        if block.len() != expected {
+            return Err(AudioError::WrongBlockLength {
+                expected,
+                actual: block.len(),
+            });
+        }
+
+        // The buffer is only read, despite the `*mut`.
+        let buf = block.as_ptr() as *mut c_void;
+        let (left, right) = self.output_volume();
+        let ret = if self.is_src() {
+            unsafe { sys::sceAudioSRCOutputBlocking(left.max(right), buf) }
+        } else {
+            unsafe { sys::sceAudioOutputPannedBlocking(self.id, left, right, buf) }
+        };
+
+        match ret {
+            ret if ret < 0 => Err(AudioError::Kernel(ret)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Samples of the previous block not played yet. `output_blocking`
+    /// doesn't wait when this is 0.
+    pub fn rest_len(&self) -> usize {
+        // The SRC channel is the same as the `sceAudioOutput2` one.
+        let rest = if self.is_src() {
+            unsafe { sys::sceAudioOutput2GetRestSample() }
+        } else {
+            unsafe { sys::sceAudioGetChannelRestLen(self.id) }
+        };
+
+        rest.max(0) as usize
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        // A channel can't be released while it is playing.
+        while self.rest_len() > 0 {
+            unsafe { sys::sceKernelDelayThread(1000) };
+        }
+
+        if self.is_src() {
+            unsafe { sys::sceAudioSRCChRelease() };
+            SRC_RESERVED.store(false, Ordering::Release);
+        } else {
+            unsafe { sys::sceAudioChRelease(self.id) };
+            RESERVED.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Set the master volume of each side, from 0 to `MAX_VOLUME`, which is
+/// 0x8000. Values outside that are clamped.
+///
+/// The hardware only has a volume per channel, so this scales the volume
+/// of every `Channel`, and everything playing through one, from the next
+/// block each plays. It starts at `MAX_VOLUME`, which leaves channels as
+/// they are. The system volume, set with the PSP's buttons, applies on top.
+pub fn set_volume(left: i32, right: i32) {
+    let clamp = |volume: i32| volume.max(0).min(MAX_VOLUME as i32) as u32;
+    MASTER_VOLUME.store(clamp(left) << 16 | clamp(right), Ordering::Relaxed);
+}
+
+/// The master volume of each side, set with `set_volume`.
+pub fn volume() -> (u16, u16) {
+    let volume = MASTER_VOLUME.load(Ordering::Relaxed);
+    ((volume >> 16) as u16, volume as u16)
+}
+
+/// Whether headphones are plugged in, rather than playing through the
+/// speakers.
+pub fn headphones_connected() -> bool {
+    unsafe { sys::sceHprmIsHeadphoneExist() == 1 }
+}
+
+/// Load codec `modules` in order, skipping those already loaded.
+fn load_modules(modules: &[sys::Module]) -> Result<(), i32> {
+    for &module in modules {
+        match unsafe { sys::sceUtilityLoadModule(module) } {
+            ret if ret < 0 && ret != ERROR_ALREADY_LOADED => return Err(ret),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,257 @@
+//! Playing MP3 files with the firmware's decoder.
+//!
+//! ```no_run
+//! use psp::audio::mp3::Player;
+//!
+//! let mut music = Player::open("ms0:/PSP/MUSIC/theme.mp3", true).unwrap();
+//!
+//! // Plays in the background until dropped.
+//! music.pause();
+//! music.resume();
+//! ```
+//!
+//! Files are streamed from disk, and may be variable bitrate. An ID3v2 tag
+//! at the start of the file is skipped. Files at rates other than
+//! `SAMPLE_RATE`, such as 48 kHz, play on the SRC channel, so only one of
+//! them can play at a time. The `sceMp3` module needs firmware 3.00 or
+//! later, and is loaded on first use.
+
+use super::{AudioError, Decoder};
+use crate::sys::{self, IoOpenFlags, IoWhence, Module, Mp3Handle, SceMp3InitArg, SceUid};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{ptr, slice};
+
+/// Bytes of the file held in memory at a time. This includes 1472 bytes the
+/// decoder uses as workspace.
+const STREAM_LEN: usize = 16 * 1024;
+/// Room for two frames of 1152 stereo samples.
+const PCM_LEN: usize = 1152 * 2 * 2;
+
+/// Returned by `sceMp3Decode` once the whole stream is decoded.
+const ERROR_DECODE_END: i32 = 0x8067_1402_u32 as i32;
+
+/// Whether `sceMp3InitResource` has been called.
+static RESOURCES: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mp3Error {
+    /// The path contains a NUL byte.
+    InvalidPath,
+    /// The file could not be opened, with the error code from `sceIoOpen`.
+    Open(i32),
+    /// Reading the file failed, with the error code from `sceIoRead`.
+    Read(i32),
+    /// Loading the codec modules failed, with the error code.
+    LoadModule(i32),
+    /// No decoder handle could be reserved, usually because all are in use
+    /// by other players.
+    NoFreeHandle(i32),
+    /// The firmware rejected the file, with its error code. This is usually
+    /// a file that isn't MP3.
+    Rejected(i32),
+    /// No channel could be set up for output.
+    Audio(AudioError),
+}
+
+impl From<AudioError> for Mp3Error {
+    fn from(e: AudioError) -> Self {
+        Mp3Error::Audio(e)
+    }
+}
+
+/// An MP3 file playing on its own channel, stopped and released on drop.
+pub type Player = super::Player<Mp3Decoder>;
+
+impl Player {
+    /// Start playing the file at `path`, such as `"ms0:/music.mp3"`, from
+    /// the beginning. With `looping`, it starts over when it ends.
+    pub fn open(path: &str, looping: bool) -> Result<Self, Mp3Error> {
+        let decoder = Mp3Decoder::open(path)?;
+        Ok(Self::start(decoder, looping)?)
+    }
+}
+
+/// Buffers shared with the decoder, which wants them cache line aligned.
+#[repr(C, align(64))]
+struct Buffers {
+    stream: [u8; STREAM_LEN],
+    pcm: [i16; PCM_LEN],
+}
+
+/// An MP3 file being decoded, holding a decoder handle until dropped.
+pub struct Mp3Decoder {
+    handle: Mp3Handle,
+    fd: SceUid,
+    /// Read by the decoder until the handle is released.
+    _buffers: Box<Buffers>,
+    sample_rate: u32,
+    /// The whole stream has been decoded.
+    ended: bool,
+}
+
+impl Mp3Decoder {
+    /// Open the file at `path`, loading the codec modules if needed.
+    pub fn open(path: &str) -> Result<Self, Mp3Error> {
+        super::load_modules(&[Module::AvCodec, Module::AvMp3]).map_err(Mp3Error::LoadModule)?;
+
+        if !RESOURCES.swap(true, Ordering::Relaxed) {
+            unsafe { sys::sceMp3InitResource() };
+        }
+
+        if path.contains('\0') {
+            return Err(Mp3Error::InvalidPath);
+        }
+
+        let mut c_path = Vec::with_capacity(path.len() + 1);
+        c_path.extend_from_slice(path.as_bytes());
+        c_path.push(0);
+
+        let fd = unsafe { sys::sceIoOpen(c_path.as_ptr(), IoOpenFlags::RD_ONLY, 0) };
+        if fd.0 < 0 {
+            return Err(Mp3Error::Open(fd.0));
+        }
+
+        let close = |e| {
+            unsafe { sys::sceIoClose(fd) };
+            Err(e)
+        };
+
+        let file_len = unsafe { sys::sceIoLseek(fd, 0, IoWhence::End) };
+        unsafe { sys::sceIoLseek(fd, 0, IoWhence::Set) };
+
+        let mut header = [0; 10];
+        let read =
+            unsafe { sys::sceIoRead(fd, header.as_mut_ptr() as *mut c_void, header.len() as u32) };
+        if read < 0 {
+            return close(Mp3Error::Read(read));
+        }
+
+        let mut buffers = Box::new(Buffers {
+            stream: [0; STREAM_LEN],
+            pcm: [0; PCM_LEN],
+        });
+
+        let mut args = SceMp3InitArg {
+            mp3_stream_start: id3v2_len(&header[..read as usize]),
+            unk1: 0,
+            mp3_stream_end: file_len.max(0) as u32,
+            unk2: 0,
+            mp3_buf: buffers.stream.as_mut_ptr() as *mut c_void,
+            mp3_buf_size: STREAM_LEN as i32,
+            pcm_buf: buffers.pcm.as_mut_ptr() as *mut c_void,
+            pcm_buf_size: (PCM_LEN * 2) as i32,
+        };
+
+        let handle = unsafe { sys::sceMp3ReserveMp3Handle(&mut args) };
+        if handle < 0 {
+            return close(Mp3Error::NoFreeHandle(handle));
+        }
+
+        let mut decoder = Self {
+            handle: Mp3Handle(handle),
+            fd,
+            _buffers: buffers,
+            sample_rate: 0,
+            ended: false,
+        };
+
+        // The first data must be in before `sceMp3Init` parses it. From here
+        // on, dropping the decoder releases the handle and closes the file.
+        decoder.refill()?;
+
+        let ret = unsafe { sys::sceMp3Init(decoder.handle) };
+        if ret < 0 {
+            return Err(Mp3Error::Rejected(ret));
+        }
+
+        decoder.sample_rate = unsafe { sys::sceMp3GetSamplingRate(decoder.handle) } as u32;
+        Ok(decoder)
+    }
+
+    /// Read as much of the file as the decoder has room for, if it needs it.
+    fn refill(&mut self) -> Result<(), Mp3Error> {
+        if unsafe { sys::sceMp3CheckStreamDataNeeded(self.handle) } <= 0 {
+            return Ok(());
+        }
+
+        let (mut dst, mut len, mut pos) = (ptr::null_mut(), 0, 0);
+        let ret =
+            unsafe { sys::sceMp3GetInfoToAddStreamData(self.handle, &mut dst, &mut len, &mut pos) };
+        if ret < 0 {
+            return Err(Mp3Error::Rejected(ret));
+        }
+
+        let read = unsafe {
+            sys::sceIoLseek(self.fd, pos as i64, IoWhence::Set);
+            sys::sceIoRead(self.fd, dst as *mut c_void, len.max(0) as u32)
+        };
+        if read < 0 {
+            return Err(Mp3Error::Read(read));
+        }
+
+        // Adding 0 bytes at the end of the file tells the decoder to drain
+        // what it has buffered.
+        unsafe { sys::sceMp3NotifyAddStreamData(self.handle, read) };
+        Ok(())
+    }
+}
+
+impl Decoder for Mp3Decoder {
+    fn decode(&mut self) -> Option<&[i16]> {
+        if self.ended || self.refill().is_err() {
+            return None;
+        }
+
+        let mut out = ptr::null_mut();
+        let bytes = unsafe { sys::sceMp3Decode(self.handle, &mut out) };
+
+        match bytes {
+            // Points into the PCM buffer. The last frames still come after
+            // the whole file has been read, until the stream is drained.
+            n if n > 0 => Some(unsafe { slice::from_raw_parts(out, n as usize / 2) }),
+            0 | ERROR_DECODE_END => {
+                self.ended = true;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn rewind(&mut self) -> bool {
+        let ret = unsafe { sys::sceMp3ResetPlayPosition(self.handle) };
+        self.ended = ret < 0;
+        !self.ended
+    }
+}
+
+impl Drop for Mp3Decoder {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceMp3ReleaseMp3Handle(self.handle);
+            sys::sceIoClose(self.fd);
+        }
+    }
+}
+
+/// Length of the ID3v2 tag that `header` starts, or 0 without one.
+fn id3v2_len(header: &[u8]) -> u32 {
+    if header.len() < 10 || &header[..3] != b"ID3" {
+        return 0;
+    }
+
+    // Stored as 7 bits per byte, excluding the 10 byte header, and the 10
+    // byte footer if the flag for it is set.
+    let size = header[6..10]
+        .iter()
+        .fold(0, |size, &b| (size << 7) | (b & 0x7f) as u32);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+
+    10 + size + footer
+}
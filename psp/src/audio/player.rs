@@ -0,0 +1,182 @@
+//! Playing compressed audio from a thread, through a `Decoder`.
+
+use super::{AudioError, Channel, Format, Stream, SAMPLE_RATE};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Samples per block of the output channel.
+const BLOCK_SAMPLES: usize = 1024;
+
+/// A source of frames for a `Player`, such as a file being decoded.
+pub trait Decoder: Send + 'static {
+    /// Decode the next frame, as stereo samples at `sample_rate`, interleaved
+    /// left first. Returns `None` at the end, or on an error.
+    ///
+    /// An empty frame is skipped.
+    fn decode(&mut self) -> Option<&[i16]>;
+
+    /// Samples per second of the decoded frames. Rates other than
+    /// `SAMPLE_RATE` are played on the SRC channel.
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    /// Go back to the start, returning false if that isn't possible.
+    fn rewind(&mut self) -> bool;
+}
+
+impl<D: Decoder + ?Sized> Decoder for Box<D> {
+    fn decode(&mut self) -> Option<&[i16]> {
+        (**self).decode()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn rewind(&mut self) -> bool {
+        (**self).rewind()
+    }
+}
+
+/// A decoder playing on its own channel, stopped and released on drop.
+///
+/// Each format's module has a `Player` opening files of that format, such
+/// as `atrac::Player`. Any `Decoder` can be played with `start`.
+pub struct Player<D> {
+    stream: Stream,
+    finished: Arc<AtomicBool>,
+    _decoder: PhantomData<fn() -> D>,
+}
+
+impl<D: Decoder> Player<D> {
+    /// Start playing from `decoder` on a new channel. With `looping`, it is
+    /// rewound whenever it ends.
+    ///
+    /// This fails with `AudioError::SrcChannelInUse` for a decoder not at
+    /// `SAMPLE_RATE` while another such decoder is playing.
+    pub fn start(decoder: D, looping: bool) -> Result<Self, AudioError> {
+        let channel = match decoder.sample_rate() {
+            SAMPLE_RATE => Channel::reserve(BLOCK_SAMPLES, Format::Stereo16)?,
+            rate => Channel::reserve_src(rate, BLOCK_SAMPLES)?,
+        };
+
+        let finished = Arc::new(AtomicBool::new(false));
+        let mut feeder = Feeder::new(decoder, looping);
+        let ended = finished.clone();
+
+        let stream = Stream::start(channel, move |block| {
+            if feeder.fill(block) < block.len() {
+                ended.store(true, Ordering::Release);
+            }
+        })?;
+
+        Ok(Self {
+            stream,
+            finished,
+            _decoder: PhantomData,
+        })
+    }
+
+    pub fn pause(&mut self) {
+        self.stream.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.stream.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.stream.is_paused()
+    }
+
+    /// Whether the decoder has ended, or failed. Never true when looping,
+    /// unless rewinding fails.
+    ///
+    /// The last block may still be playing.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Blocks that started playing late, usually because reading the file
+    /// took too long.
+    pub fn underruns(&self) -> usize {
+        self.stream.underruns()
+    }
+}
+
+/// Copies frames from a decoder into blocks, on the output thread.
+pub(super) struct Feeder<D> {
+    decoder: D,
+    /// The last frame, with `pos` values of it already output.
+    frame: Vec<i16>,
+    pos: usize,
+    looping: bool,
+    /// The decoder has ended, or failed.
+    ended: bool,
+}
+
+impl<D: Decoder> Feeder<D> {
+    pub(super) fn new(decoder: D, looping: bool) -> Self {
+        Self {
+            decoder,
+            frame: Vec::new(),
+            pos: 0,
+            looping,
+            ended: false,
+        }
+    }
+
+    /// Fill `block`, returning how many values were written. Fewer than the
+    /// block's length means the decoder has ended, and the rest is left as
+    /// it was. The end of the last frame is output in full, even though it
+    /// doesn't line up with a block.
+    pub(super) fn fill(&mut self, block: &mut [i16]) -> usize {
+        let mut written = 0;
+
+        while written < block.len() && !self.ended {
+            if self.pos == self.frame.len() && !self.next_frame() {
+                self.ended = true;
+                break;
+            }
+
+            let n = (self.frame.len() - self.pos).min(block.len() - written);
+            block[written..written + n].copy_from_slice(&self.frame[self.pos..self.pos + n]);
+            self.pos += n;
+            written += n;
+        }
+
+        written
+    }
+
+    fn next_frame(&mut self) -> bool {
+        // Only rewind once, in case the decoder ends right away.
+        let mut rewound = false;
+
+        loop {
+            let decoded = match self.decoder.decode() {
+                Some(frame) if !frame.is_empty() => {
+                    self.frame.clear();
+                    self.frame.extend_from_slice(frame);
+                    true
+                }
+                Some(_) => continue,
+                None => false,
+            };
+
+            if decoded {
+                self.pos = 0;
+                return true;
+            }
+
+            if !self.looping || rewound || !self.decoder.rewind() {
+                return false;
+            }
+
+            rewound = true;
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Decoded sounds, played in the background.
+
+use super::{AudioError, Channel, Format, STACK_SIZE, THREAD_PRIORITY};
+use crate::sys::{self, ThreadAttributes};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::time::Duration;
+use core::{mem, ptr};
+
+/// Samples per block played by `play_once`.
+const BLOCK_SAMPLES: usize = 1024;
+
+/// Stereo samples at `SAMPLE_RATE`, ready to play.
+///
+/// Cloning a sound is cheap, as the samples are shared.
+#[derive(Debug, Clone)]
+pub struct Sound {
+    samples: Arc<[i16]>,
+}
+
+impl Sound {
+    /// A sound from stereo values at `SAMPLE_RATE`, interleaved left first.
+    /// A trailing unpaired value is dropped.
+    pub fn from_samples(mut samples: Vec<i16>) -> Self {
+        samples.truncate(samples.len() & !1);
+
+        Self {
+            samples: samples.into(),
+        }
+    }
+
+    /// The stereo values, interleaved left first.
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Number of samples, counting a left and right value as one.
+    pub fn len(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(self.len() as u64 * 1_000_000 / super::SAMPLE_RATE as u64)
+    }
+}
+
+/// Start playing `sound` on a free channel, without waiting for it to end.
+///
+/// The channel is released once the sound is over. This fails with
+/// `AudioError::NoFreeChannel` if all channels are in use, such as by
+/// several sounds already playing.
+pub fn play_once(sound: &Sound) -> Result<(), AudioError> {
+    let channel = Channel::reserve(BLOCK_SAMPLES, Format::Stereo16)?;
+    let playback = Box::into_raw(Box::new((channel, sound.samples.clone())));
+
+    unsafe {
+        let thread = sys::sceKernelCreateThread(
+            b"audio_play_once\0".as_ptr(),
+            play_thread,
+            THREAD_PRIORITY,
+            STACK_SIZE,
+            ThreadAttributes::USER,
+            ptr::null_mut(),
+        );
+
+        if thread.0 < 0 {
+            drop(Box::from_raw(playback));
+            return Err(AudioError::Kernel(thread.0));
+        }
+
+        // The kernel copies the pointer onto the thread's stack.
+        let ret = sys::sceKernelStartThread(
+            thread,
+            mem::size_of::<*mut (Channel, Arc<[i16]>)>(),
+            &playback as *const *mut (Channel, Arc<[i16]>) as *mut c_void,
+        );
+
+        if ret < 0 {
+            sys::sceKernelDeleteThread(thread);
+            drop(Box::from_raw(playback));
+            return Err(AudioError::Kernel(ret));
+        }
+    }
+
+    Ok(())
+}
+
+unsafe extern "C" fn play_thread(_args: usize, argp: *mut c_void) -> i32 {
+    let playback = Box::from_raw(*(argp as *mut *mut (Channel, Arc<[i16]>)));
+    let (mut channel, samples) = *playback;
+    let mut last = [0; BLOCK_SAMPLES * 2];
+
+    for block in samples.chunks(BLOCK_SAMPLES * 2) {
+        // The last block is padded with silence.
+        let block = match block.len() {
+            len if len == last.len() => block,
+            len => {
+                last[..len].copy_from_slice(block);
+                &last[..]
+            }
+        };
+
+        let _ = channel.output_blocking(block);
+    }
+
+    // Releases the channel once the last block has played.
+    drop(channel);
+    drop(samples);
+    sys::sceKernelExitDeleteThread(0)
+}
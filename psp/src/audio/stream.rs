@@ -0,0 +1,189 @@
+//! Continuous output from a thread, for music.
+
+use super::{AudioError, Channel, STACK_SIZE, THREAD_PRIORITY};
+use crate::sys::{self, SceUid, ThreadAttributes};
+use alloc::boxed::Box;
+use alloc::vec;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use core::{mem, ptr};
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const STOPPING: u8 = 2;
+
+/// A callback filling blocks of samples.
+type Fill = Box<dyn FnMut(&mut [i16]) + Send>;
+
+/// State shared between the `Stream` and its output thread.
+struct Shared {
+    state: AtomicU8,
+    underruns: AtomicUsize,
+}
+
+/// Owned by the output thread.
+struct Output {
+    channel: Channel,
+    fill: Fill,
+    shared: *const Shared,
+}
+
+/// A channel played from a thread, which calls back for each block.
+///
+/// ```no_run
+/// use psp::audio::{Channel, Format, Stream};
+///
+/// let channel = Channel::reserve(1024, Format::Stereo16).unwrap();
+/// let mut phase = 0u32;
+///
+/// let stream = Stream::start(channel, move |block| {
+///     for sample in block.chunks_mut(2) {
+///         // A square wave, on both sides.
+///         let value = if phase % 100 < 50 { 4000 } else { -4000 };
+///         sample.fill(value);
+///         phase += 1;
+///     }
+/// });
+/// ```
+///
+/// The callback fills one block while the other plays. Blocks start out
+/// silent, so a callback that doesn't write all of a block leaves silence
+/// rather than old samples.
+pub struct Stream {
+    /// Freed once the output thread has ended.
+    shared: *mut Shared,
+    thread: SceUid,
+}
+
+impl Stream {
+    /// Start playing `channel`, with blocks filled by `fill`.
+    ///
+    /// `fill` is passed `samples_per_block` samples to fill, so twice as
+    /// many values for stereo. It runs on the output thread.
+    pub fn start<F>(channel: Channel, fill: F) -> Result<Self, AudioError>
+    where
+        F: FnMut(&mut [i16]) + Send + 'static,
+    {
+        let shared = Box::into_raw(Box::new(Shared {
+            state: AtomicU8::new(RUNNING),
+            underruns: AtomicUsize::new(0),
+        }));
+        let output = Box::into_raw(Box::new(Output {
+            channel,
+            fill: Box::new(fill),
+            shared,
+        }));
+
+        unsafe {
+            let thread = sys::sceKernelCreateThread(
+                b"audio_stream\0".as_ptr(),
+                output_thread,
+                THREAD_PRIORITY,
+                STACK_SIZE,
+                ThreadAttributes::USER | ThreadAttributes::VFPU,
+                ptr::null_mut(),
+            );
+
+            if thread.0 < 0 {
+                drop(Box::from_raw(output));
+                drop(Box::from_raw(shared));
+                return Err(AudioError::Kernel(thread.0));
+            }
+
+            // The kernel copies the pointer onto the thread's stack.
+            let ret = sys::sceKernelStartThread(
+                thread,
+                mem::size_of::<*mut Output>(),
+                &output as *const *mut Output as *mut c_void,
+            );
+
+            if ret < 0 {
+                sys::sceKernelDeleteThread(thread);
+                drop(Box::from_raw(output));
+                drop(Box::from_raw(shared));
+                return Err(AudioError::Kernel(ret));
+            }
+
+            Ok(Self { shared, thread })
+        }
+    }
+
+    /// Stop calling back after the current block, until `resume`.
+    pub fn pause(&mut self) {
+        self.shared().state.store(PAUSED, Ordering::Release);
+    }
+
+    pub fn resume(&mut self) {
+        self.shared().state.store(RUNNING, Ordering::Release);
+        unsafe { sys::sceKernelWakeupThread(self.thread) };
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared().state.load(Ordering::Acquire) == PAUSED
+    }
+
+    /// Blocks that started playing late, because the callback took longer
+    /// than a block to fill the next one. The channel plays silence in the
+    /// meantime.
+    pub fn underruns(&self) -> usize {
+        self.shared().underruns.load(Ordering::Relaxed)
+    }
+
+    /// Stop playing, wait for the output thread to end, and release the
+    /// channel. This also happens on drop.
+    pub fn stop(self) {}
+
+    fn shared(&self) -> &Shared {
+        unsafe { &*self.shared }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.shared().state.store(STOPPING, Ordering::Release);
+
+        unsafe {
+            sys::sceKernelWakeupThread(self.thread);
+            sys::sceKernelWaitThreadEnd(self.thread, ptr::null_mut());
+            sys::sceKernelDeleteThread(self.thread);
+            drop(Box::from_raw(self.shared));
+        }
+    }
+}
+
+unsafe extern "C" fn output_thread(_args: usize, argp: *mut c_void) -> i32 {
+    let mut output = Box::from_raw(*(argp as *mut *mut Output));
+    let shared = &*output.shared;
+    let len = output.channel.samples_per_block() * output.channel.format().channels();
+    let mut blocks = [vec![0; len], vec![0; len]];
+    let mut started = false;
+
+    for i in (0..2).cycle() {
+        match shared.state.load(Ordering::Acquire) {
+            STOPPING => break,
+            PAUSED => {
+                // Woken by `resume`, or to stop.
+                sys::sceKernelSleepThread();
+                started = false;
+                continue;
+            }
+            _ => {}
+        }
+
+        let block = &mut blocks[i];
+        block.fill(0);
+        (output.fill)(block);
+
+        if started && output.channel.rest_len() == 0 {
+            shared.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Only fails on a bad channel, which `Channel` rules out.
+        let _ = output.channel.output_blocking(block);
+        started = true;
+    }
+
+    // Releases the channel once its last block has played.
+    drop(output);
+    0
+}
@@ -0,0 +1,116 @@
+//! Test tones, for checking that audio output works at all.
+
+use super::{AudioError, Channel, Format, SAMPLE_RATE};
+use core::f32::consts::TAU;
+use core::time::Duration;
+
+/// Samples per block played by `test_tone`.
+const BLOCK_SAMPLES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    /// White noise, at a new random level every half period, so lower
+    /// frequencies sound darker.
+    Noise,
+}
+
+/// Play a `waveform` at `freq_hz` for `duration`, blocking until it has
+/// played. `volume` goes up to `MAX_VOLUME`, which is very loud in
+/// headphones: `MAX_VOLUME / 8` is plenty to hear.
+///
+/// A channel is reserved for the tone, and released after. Nothing else is
+/// used, not even a thread, so this still works when the mixer or streaming
+/// don't.
+///
+/// Fails with `AudioError::InvalidFrequency` for 0 Hz, or above half of
+/// `SAMPLE_RATE`, the highest frequency that can be played.
+pub fn test_tone(
+    freq_hz: u32,
+    duration: Duration,
+    waveform: Waveform,
+    volume: u16,
+) -> Result<(), AudioError> {
+    if freq_hz == 0 || freq_hz > SAMPLE_RATE / 2 {
+        return Err(AudioError::InvalidFrequency(freq_hz));
+    }
+
+    let mut channel = Channel::reserve(BLOCK_SAMPLES, Format::Stereo16)?;
+    channel.set_volume(volume, volume);
+
+    let mut oscillator = Oscillator::new(freq_hz, waveform);
+    let mut left = (duration.as_micros() * SAMPLE_RATE as u128 / 1_000_000) as u64;
+    let mut block = [0i16; BLOCK_SAMPLES * 2];
+
+    while left > 0 {
+        let len = left.min(BLOCK_SAMPLES as u64) as usize;
+
+        // Silence pads out the last block.
+        for (i, sample) in block.chunks_exact_mut(2).enumerate() {
+            let value = if i < len { oscillator.next() } else { 0 };
+            sample.copy_from_slice(&[value, value]);
+        }
+
+        channel.output_blocking(&block)?;
+        left -= len as u64;
+    }
+
+    // Dropping the channel waits for the last block to play.
+    Ok(())
+}
+
+struct Oscillator {
+    waveform: Waveform,
+    /// Where in the period the next sample is, as a fraction of 2^32.
+    phase: u32,
+    step: u32,
+    /// State of the xorshift generator for `Noise`, never 0.
+    random: u32,
+    level: i16,
+}
+
+impl Oscillator {
+    fn new(freq_hz: u32, waveform: Waveform) -> Self {
+        let mut oscillator = Self {
+            waveform,
+            phase: 0,
+            // Fits, as `freq_hz` is at most half the rate.
+            step: (((freq_hz as u64) << 32) / SAMPLE_RATE as u64) as u32,
+            random: 0x2545_f491,
+            level: 0,
+        };
+
+        oscillator.level = oscillator.next_random();
+        oscillator
+    }
+
+    fn next(&mut self) -> i16 {
+        let phase = self.phase;
+        self.phase = phase.wrapping_add(self.step);
+
+        match self.waveform {
+            Waveform::Sine => {
+                let angle = (phase >> 8) as f32 * (TAU / (1 << 24) as f32);
+                (libm::sinf(angle) * i16::MAX as f32) as i16
+            }
+            Waveform::Square if phase < 1 << 31 => i16::MAX,
+            Waveform::Square => -i16::MAX,
+            Waveform::Noise => {
+                // Crossing into the other half of the period.
+                if (phase ^ self.phase) >> 31 != 0 {
+                    self.level = self.next_random();
+                }
+
+                self.level
+            }
+        }
+    }
+
+    fn next_random(&mut self) -> i16 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random >> 16) as i16
+    }
+}
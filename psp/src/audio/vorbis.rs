@@ -0,0 +1,294 @@
+//! Playing Ogg Vorbis files, with the `vorbis` feature.
+//!
+//! ```no_run
+//! use psp::audio::vorbis::{Player, VorbisDecoder};
+//!
+//! let music = Player::open("ms0:/PSP/MUSIC/theme.ogg", true).unwrap();
+//!
+//! // An intro, then a loop from 1.5 s at 44.1 kHz to the loop's end.
+//! let mut decoder = VorbisDecoder::open("ms0:/PSP/MUSIC/boss.ogg").unwrap();
+//! decoder.set_loop(66_150, 1_234_567);
+//! let boss = Player::start(decoder, true).unwrap();
+//! ```
+//!
+//! Files are decoded in software by `lewton`. It needs `std`, so the feature
+//! enables the `std` feature, which needs a toolchain with `std` for the
+//! PSP.
+//!
+//! Files are streamed from disk through a small buffer, rather than loaded
+//! whole. Mono files are played on both sides, and files at rates other
+//! than `SAMPLE_RATE` play on the SRC channel.
+//!
+//! A corrupted page is skipped, leaving a short gap, rather than ending
+//! playback.
+
+use super::{AudioError, Decoder};
+use crate::sys::{self, IoOpenFlags, IoWhence, SceUid};
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::time::Duration;
+use lewton::inside_ogg::OggStreamReader;
+use lewton::{OggReadError, VorbisError as LewtonError};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+/// Bytes of the file held in memory at a time.
+const BUFFER_LEN: usize = 16 * 1024;
+
+/// Bad packets in a row after which the file is taken to be unreadable.
+const MAX_BAD_PACKETS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VorbisError {
+    /// The path contains a NUL byte.
+    InvalidPath,
+    /// The file could not be opened, with the error code from `sceIoOpen`.
+    Open(i32),
+    /// The file doesn't start with Vorbis headers, or they are corrupted.
+    NotVorbis,
+    /// More than two channels.
+    UnsupportedChannels(u8),
+    /// Seeking failed, usually because the file is corrupted there.
+    Seek,
+    /// No channel could be set up for output.
+    Audio(AudioError),
+}
+
+impl From<AudioError> for VorbisError {
+    fn from(e: AudioError) -> Self {
+        VorbisError::Audio(e)
+    }
+}
+
+/// An Ogg Vorbis file playing on its own channel, stopped and released on
+/// drop.
+pub type Player = super::Player<VorbisDecoder>;
+
+impl Player {
+    /// Start playing the file at `path`, such as `"ms0:/music.ogg"`, from
+    /// the beginning. With `looping`, it starts over when it ends.
+    pub fn open(path: &str, looping: bool) -> Result<Self, VorbisError> {
+        let decoder = VorbisDecoder::open(path)?;
+        Ok(Self::start(decoder, looping)?)
+    }
+}
+
+type Reader = OggStreamReader<BufReader<File>>;
+
+/// An Ogg Vorbis file being decoded.
+pub struct VorbisDecoder {
+    reader: Reader,
+    /// Stereo samples of the last packet decoded.
+    frame: Vec<i16>,
+    /// Samples per channel decoded up to the end of `frame`.
+    position: u64,
+    /// `frame` holds the rest of the packet seeked into, to output next.
+    seeked: bool,
+    /// Where `rewind` goes back to, and where decoding stops.
+    loop_start: u64,
+    loop_end: Option<u64>,
+}
+
+impl VorbisDecoder {
+    /// Open the file at `path`, and read its headers.
+    pub fn open(path: &str) -> Result<Self, VorbisError> {
+        let file = BufReader::with_capacity(BUFFER_LEN, File::open(path)?);
+        let reader = OggStreamReader::new(file).map_err(|_| VorbisError::NotVorbis)?;
+
+        let channels = reader.ident_hdr.audio_channels;
+        if channels > 2 {
+            return Err(VorbisError::UnsupportedChannels(channels));
+        }
+
+        Ok(Self {
+            reader,
+            frame: Vec::new(),
+            position: 0,
+            seeked: false,
+            loop_start: 0,
+            loop_end: None,
+        })
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.reader.ident_hdr.audio_channels
+    }
+
+    /// Samples per channel decoded so far, counting from the start of the
+    /// file.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Continue from `time` into the file, or the end if it's shorter.
+    pub fn seek(&mut self, time: Duration) -> Result<(), VorbisError> {
+        let rate = self.reader.ident_hdr.audio_sample_rate as u128;
+        self.seek_sample((time.as_micros() * rate / 1_000_000) as u64)
+    }
+
+    /// Continue from the sample at `position`, per channel.
+    ///
+    /// The file can only be seeked to the start of a page, so the samples
+    /// from there up to `position` are decoded and dropped.
+    pub fn seek_sample(&mut self, position: u64) -> Result<(), VorbisError> {
+        self.reader
+            .seek_absgp_pg(position)
+            .map_err(|_| VorbisError::Seek)?;
+        self.frame.clear();
+        self.seeked = true;
+
+        // Positions are only known again at the end of a page, so packets
+        // are kept until then.
+        while read_packet(&mut self.reader, &mut self.frame).is_some() {
+            let end = match self.reader.get_last_absgp() {
+                Some(end) => end,
+                None => continue,
+            };
+
+            if end > position {
+                let start = end.saturating_sub(self.frame.len() as u64 / 2);
+                let skip = position.saturating_sub(start) as usize * 2;
+                self.frame.drain(..skip.min(self.frame.len()));
+                self.position = end;
+
+                return Ok(());
+            }
+
+            self.frame.clear();
+        }
+
+        // Past the end.
+        self.position = position;
+        Ok(())
+    }
+
+    /// Loop between two sample positions, per channel: `rewind` goes back
+    /// to `start`, and decoding ends at `end`, rather than the file's
+    /// start and end.
+    ///
+    /// Played by a looping `Player`, everything up to `end` plays first,
+    /// then the loop repeats without a gap.
+    pub fn set_loop(&mut self, start: u64, end: u64) {
+        self.loop_start = start;
+        self.loop_end = Some(end.max(start));
+    }
+}
+
+impl Decoder for VorbisDecoder {
+    fn decode(&mut self) -> Option<&[i16]> {
+        if !core::mem::replace(&mut self.seeked, false) {
+            if matches!(self.loop_end, Some(end) if self.position >= end) {
+                return None;
+            }
+
+            self.frame.clear();
+            let len = read_packet(&mut self.reader, &mut self.frame)? as u64;
+            self.position = match self.reader.get_last_absgp() {
+                Some(end) => end,
+                None => self.position + len,
+            };
+        }
+
+        // Only the part before the loop's end.
+        if let Some(end) = self.loop_end {
+            let over = self.position.saturating_sub(end) as usize * 2;
+            self.frame.truncate(self.frame.len().saturating_sub(over));
+        }
+
+        Some(&self.frame)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.ident_hdr.audio_sample_rate
+    }
+
+    fn rewind(&mut self) -> bool {
+        self.seek_sample(self.loop_start).is_ok()
+    }
+}
+
+/// Decode the next packet onto the end of `out`, as stereo, returning its
+/// samples per channel. Bad packets are skipped.
+fn read_packet(reader: &mut Reader, out: &mut Vec<i16>) -> Option<usize> {
+    let mut bad = 0;
+
+    let samples = loop {
+        match reader.read_dec_packet_itl() {
+            Ok(Some(samples)) => break samples,
+            Ok(None) => return None,
+            // Not worth skipping, as the rest of the file can't be read.
+            Err(LewtonError::OggError(OggReadError::ReadError(_))) => return None,
+            Err(_) if bad < MAX_BAD_PACKETS => bad += 1,
+            Err(_) => return None,
+        }
+    };
+
+    match reader.ident_hdr.audio_channels {
+        1 => {
+            out.extend(samples.iter().flat_map(|&s| [s, s]));
+            Some(samples.len())
+        }
+        _ => {
+            out.extend_from_slice(&samples);
+            Some(samples.len() / 2)
+        }
+    }
+}
+
+/// A file read through `sceIo`, closed on drop.
+struct File {
+    fd: SceUid,
+}
+
+impl File {
+    fn open(path: &str) -> Result<Self, VorbisError> {
+        if path.contains('\0') {
+            return Err(VorbisError::InvalidPath);
+        }
+
+        let mut c_path = Vec::with_capacity(path.len() + 1);
+        c_path.extend_from_slice(path.as_bytes());
+        c_path.push(0);
+
+        let fd = unsafe { sys::sceIoOpen(c_path.as_ptr(), IoOpenFlags::RD_ONLY, 0) };
+        if fd.0 < 0 {
+            return Err(VorbisError::Open(fd.0));
+        }
+
+        Ok(Self { fd })
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read =
+            unsafe { sys::sceIoRead(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len() as u32) };
+        if read < 0 {
+            return Err(io::Error::other("sceIoRead failed"));
+        }
+
+        Ok(read as usize)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (offset as i64, IoWhence::Set),
+            SeekFrom::Current(offset) => (offset, IoWhence::Cur),
+            SeekFrom::End(offset) => (offset, IoWhence::End),
+        };
+
+        let pos = unsafe { sys::sceIoLseek(self.fd, offset, whence) };
+        if pos < 0 {
+            return Err(io::Error::other("sceIoLseek failed"));
+        }
+
+        Ok(pos as u64)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        unsafe { sys::sceIoClose(self.fd) };
+    }
+}
@@ -0,0 +1,170 @@
+//! Loading WAV files.
+//!
+//! ```no_run
+//! use psp::audio::{self, wav};
+//!
+//! # let data: &[u8] = &[];
+//! // Such as `include_bytes!("beep.wav")`.
+//! let beep = wav::load(data).unwrap();
+//! audio::play_once(&beep).unwrap();
+//! ```
+//!
+//! Only uncompressed PCM is supported, with 8 or 16 bits and one or two
+//! channels. Sounds are converted to stereo and resampled to `SAMPLE_RATE`
+//! with linear interpolation.
+
+use super::{Sound, SAMPLE_RATE};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+
+/// Sample rates accepted, in Hz.
+const MIN_RATE: u32 = 1_000;
+const MAX_RATE: u32 = 192_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavError {
+    /// The data doesn't start with a RIFF WAVE header.
+    NotWav,
+    /// The data ends within a chunk, such as a file cut short.
+    Truncated,
+    /// There is no `fmt ` chunk before the `data` chunk.
+    MissingFormat,
+    /// There is no `data` chunk.
+    MissingData,
+    /// The samples are compressed, with this format tag. PCM is 1.
+    NotPcm(u16),
+    /// Bits per sample other than 8 or 16.
+    UnsupportedBits(u16),
+    /// Channel counts other than 1 or 2.
+    UnsupportedChannels(u16),
+    /// A sample rate outside 1 to 192 kHz.
+    UnsupportedRate(u32),
+}
+
+/// The `fmt ` chunk's fields that matter for PCM.
+#[derive(Debug, Clone, Copy)]
+struct Spec {
+    channels: u16,
+    rate: u32,
+    bits: u16,
+}
+
+/// Decode a WAV file into a `Sound`.
+pub fn load(data: &[u8]) -> Result<Sound, WavError> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(WavError::NotWav);
+    }
+
+    let mut spec = None;
+    let mut rest = &data[12..];
+
+    while !rest.is_empty() {
+        if rest.len() < 8 {
+            return Err(WavError::Truncated);
+        }
+
+        let id = &rest[0..4];
+        let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        let body = rest[8..].get(..len).ok_or(WavError::Truncated)?;
+
+        match id {
+            b"fmt " => spec = Some(parse_spec(body)?),
+            b"data" => {
+                let spec = spec.ok_or(WavError::MissingFormat)?;
+                return Ok(Sound::from_samples(convert(body, spec)));
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even length, though the padding byte is
+        // often left out of the last one.
+        rest = rest.get(8 + len + len % 2..).unwrap_or(&[]);
+    }
+
+    Err(WavError::MissingData)
+}
+
+fn parse_spec(body: &[u8]) -> Result<Spec, WavError> {
+    if body.len() < 16 {
+        return Err(WavError::Truncated);
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+    let format = u16_at(0);
+    let spec = Spec {
+        channels: u16_at(2),
+        rate: u32::from_le_bytes(body[4..8].try_into().unwrap()),
+        bits: u16_at(14),
+    };
+
+    if format != WAVE_FORMAT_PCM {
+        Err(WavError::NotPcm(format))
+    } else if spec.bits != 8 && spec.bits != 16 {
+        Err(WavError::UnsupportedBits(spec.bits))
+    } else if spec.channels != 1 && spec.channels != 2 {
+        Err(WavError::UnsupportedChannels(spec.channels))
+    } else if !(MIN_RATE..=MAX_RATE).contains(&spec.rate) {
+        Err(WavError::UnsupportedRate(spec.rate))
+    } else {
+        Ok(spec)
+    }
+}
+
+/// Convert PCM samples to interleaved stereo at `SAMPLE_RATE`.
+fn convert(body: &[u8], spec: Spec) -> Vec<i16> {
+    let bytes = spec.bits as usize / 8;
+    let frame_len = bytes * spec.channels as usize;
+
+    // 8-bit samples are unsigned.
+    let value = |b: &[u8]| match bytes {
+        1 => (b[0] as i16 - 128) << 8,
+        _ => i16::from_le_bytes([b[0], b[1]]),
+    };
+
+    // A partial frame at the end is dropped.
+    let frames: Vec<(i16, i16)> = body
+        .chunks_exact(frame_len)
+        .map(|frame| {
+            let left = value(frame);
+            match spec.channels {
+                1 => (left, left),
+                _ => (left, value(&frame[bytes..])),
+            }
+        })
+        .collect();
+
+    if spec.rate == SAMPLE_RATE {
+        return frames.iter().flat_map(|&(l, r)| [l, r]).collect();
+    }
+
+    resample(&frames, spec.rate)
+}
+
+/// Linearly interpolate `frames` at `rate` to `SAMPLE_RATE`.
+fn resample(frames: &[(i16, i16)], rate: u32) -> Vec<i16> {
+    let last = match frames.last() {
+        Some(&last) => last,
+        None => return Vec::new(),
+    };
+
+    let len = (frames.len() as u64 * SAMPLE_RATE as u64 / rate as u64) as usize;
+    let mut out = Vec::with_capacity(len * 2);
+
+    for i in 0..len as u64 {
+        // The source position, in 16.16 fixed point.
+        let pos = (i * rate as u64 * 0x1_0000) / SAMPLE_RATE as u64;
+        let index = (pos >> 16) as usize;
+        let t = (pos & 0xffff) as i32;
+
+        let (l0, r0) = frames[index];
+        let (l1, r1) = frames.get(index + 1).copied().unwrap_or(last);
+        let lerp = |a: i16, b: i16| (a as i32 + (((b as i32 - a as i32) * t) >> 16)) as i16;
+
+        out.push(lerp(l0, l1));
+        out.push(lerp(r0, r1));
+    }
+
+    out
+}
@@ -0,0 +1,38 @@
+//! CPU data cache maintenance.
+//!
+//! The GE and other hardware read and write main memory directly, without
+//! going through the CPU's data cache. Data the CPU writes through a cached
+//! address may still sit in the cache, so it must be written back before the
+//! GE reads it:
+//!
+//! - Textures, CLUTs and vertices in cached memory need a writeback after
+//!   they are last written, and before the list drawing them is finished
+//!   with `sceGuFinish`. The GE may start on a list before it is finished, so
+//!   data written mid-frame must be written back before the commands using
+//!   it are sent.
+//! - Memory from `sceGuGetMemory`, VRAM and addresses made uncached with
+//!   `sys::uncached` bypass the cache, and need nothing.
+//!
+//! When the GE or DMA writes to cached memory, such as a texture rendered to
+//! main memory, the CPU can keep reading stale cached data. Use
+//! `dcache_writeback_invalidate_all` after `sceGuSync` before reading it.
+
+use crate::sys;
+use core::ffi::c_void;
+
+/// Write `len` bytes from `ptr` back from the data cache to memory.
+///
+/// The range is widened to whole 64 byte cache lines, so neighbouring data
+/// is written back too, which is harmless.
+pub fn dcache_writeback_range<T>(ptr: *const T, len: usize) {
+    unsafe { sys::sceKernelDcacheWritebackRange(ptr as *const c_void, len as u32) };
+}
+
+/// Write the whole data cache back to memory, and invalidate it.
+///
+/// Afterwards, memory written by the GE or DMA is read fresh. This is slower
+/// than writing back a range, so prefer `dcache_writeback_range` when only
+/// CPU writes need to reach the GE.
+pub fn dcache_writeback_invalidate_all() {
+    unsafe { sys::sceKernelDcacheWritebackInvalidateAll() };
+}
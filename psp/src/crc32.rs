@@ -0,0 +1,40 @@
+//! CRC-32, with the polynomial of ZIP and PNG.
+
+/// Lookup table for the polynomial.
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+/// Continue an inverted CRC-32, which starts at `!0`, over `data`.
+pub(crate) fn update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    crc
+}
+
+/// The CRC-32 of `data`.
+pub(crate) fn checksum(data: &[u8]) -> u32 {
+    !update(!0, data)
+}
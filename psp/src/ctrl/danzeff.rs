@@ -0,0 +1,278 @@
+//! A compact on-screen keyboard, in the style of Danzeff.
+//!
+//! The keyboard is a 3x3 grid of cells with four characters each. The analog
+//! stick selects a cell, and Triangle, Circle, Cross and Square type the
+//! character at the top, right, bottom and left of it. Holding L switches to
+//! upper case, and holding R to digits and symbols.
+//!
+//! ```no_run
+//! use psp::ctrl::danzeff::{Corner, Danzeff, BACKSPACE, ENTER};
+//! use psp::ctrl::Input;
+//!
+//! let mut input = Input::new();
+//! let mut keyboard = Danzeff::new(Corner::BottomRight);
+//! let mut line = alloc::string::String::new();
+//!
+//! loop {
+//!     input.update();
+//!
+//!     for c in keyboard.update(&input) {
+//!         match c {
+//!             BACKSPACE => {
+//!                 line.pop();
+//!             }
+//!             ENTER => line.clear(),
+//!             c => line.push(c),
+//!         }
+//!     }
+//!
+//!     // Draw with `keyboard.draw(&mut frame, &font)`.
+//! }
+//! ```
+//!
+//! The cell at the center, with the stick at rest, holds the space, backspace
+//! and enter keys in every layer.
+
+use super::{Button, Input};
+use crate::font::{self, BmFont};
+use crate::gu::{Blend, Frame, SpriteVertex};
+use crate::sys::GuPrimitive;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use alloc::vec::Vec;
+
+/// Typed by the backspace key.
+pub const BACKSPACE: char = '\u{8}';
+/// Typed by the enter key.
+pub const ENTER: char = '\n';
+
+/// The face buttons, in the order of the characters in a cell: top, right,
+/// bottom and left.
+const FACE_BUTTONS: [Button; 4] = [
+    Button::TRIANGLE,
+    Button::CIRCLE,
+    Button::CROSS,
+    Button::SQUARE,
+];
+
+const CENTER: usize = 4;
+const CENTER_KEYS: [char; 4] = [BACKSPACE, ENTER, ' ', '.'];
+
+/// The characters of the outer cells, four per cell, row by row, skipping
+/// the center.
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz,!?-'\"";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ,!?-'\"";
+const DIGITS: &str = "1234567890.,+-*/=()%@#$&_:;!?[]~";
+
+/// Stick distance along an axis needed to select an outer cell.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Size of a cell, and the distance of the keyboard from the screen edges,
+/// in pixels.
+pub const CELL_SIZE: i32 = 36;
+const MARGIN: i32 = 8;
+
+const BACKGROUND_COLOR: u32 = 0xc020_2020;
+const SELECTED_COLOR: u32 = 0xc080_5020;
+const TEXT_COLOR: u32 = 0xffff_ffff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Lower,
+    Upper,
+    Digits,
+}
+
+impl Layer {
+    /// The characters of cell `cell`, in `FACE_BUTTONS` order.
+    pub fn cell(self, cell: usize) -> [char; 4] {
+        assert!(cell < 9, "cell {} out of range", cell);
+
+        if cell == CENTER {
+            return CENTER_KEYS;
+        }
+
+        let chars = match self {
+            Layer::Lower => LOWER,
+            Layer::Upper => UPPER,
+            Layer::Digits => DIGITS,
+        };
+
+        // All the layouts are ASCII.
+        let outer = if cell > CENTER { cell - 1 } else { cell };
+        let bytes = &chars.as_bytes()[outer * 4..outer * 4 + 4];
+        [0, 1, 2, 3].map(|i| bytes[i] as char)
+    }
+}
+
+/// The keyboard's state.
+#[derive(Debug, Clone, Copy)]
+pub struct Danzeff {
+    corner: Corner,
+    layer: Layer,
+    /// The selected cell, row by row from the top left.
+    cell: usize,
+}
+
+impl Danzeff {
+    pub fn new(corner: Corner) -> Self {
+        Self {
+            corner,
+            layer: Layer::Lower,
+            cell: CENTER,
+        }
+    }
+
+    pub fn corner(&self) -> Corner {
+        self.corner
+    }
+
+    pub fn set_corner(&mut self, corner: Corner) {
+        self.corner = corner;
+    }
+
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    /// The selected cell, from 0 to 8, row by row from the top left.
+    pub fn cell(&self) -> usize {
+        self.cell
+    }
+
+    /// Update the selection from `input`, and return the characters typed
+    /// since the previous update.
+    pub fn update(&mut self, input: &Input) -> Typed {
+        self.update_with(input.analog(), input.buttons(), input.made)
+    }
+
+    /// Like `update`, from a stick position in -1.0..1.0, the buttons held
+    /// and those just pressed.
+    pub fn update_with(&mut self, stick: (f32, f32), held: Button, pressed: Button) -> Typed {
+        self.layer = if held.contains(Button::RTRIGGER) {
+            Layer::Digits
+        } else if held.contains(Button::LTRIGGER) {
+            Layer::Upper
+        } else {
+            Layer::Lower
+        };
+
+        let step = |v: f32| match v {
+            v if v <= -STICK_THRESHOLD => 0,
+            v if v >= STICK_THRESHOLD => 2,
+            _ => 1,
+        };
+        self.cell = step(stick.1) * 3 + step(stick.0);
+
+        let keys = self.layer.cell(self.cell);
+        let mut typed = Typed {
+            chars: ['\0'; 4],
+            len: 0,
+            pos: 0,
+        };
+
+        for (&button, &c) in FACE_BUTTONS.iter().zip(&keys) {
+            if pressed.contains(button) {
+                typed.chars[typed.len] = c;
+                typed.len += 1;
+            }
+        }
+
+        typed
+    }
+
+    /// Draw the keyboard, with the selected cell highlighted.
+    ///
+    /// This binds `font`'s page texture, and draws with alpha blending.
+    pub fn draw<'a>(&self, frame: &mut Frame<'a>, font: &'a BmFont) {
+        let (x0, y0) = self.origin();
+        let mut frame = frame.scoped_blend(Some(Blend::ALPHA));
+        frame.unbind_texture();
+
+        let cells = (0..9).flat_map(|cell| {
+            let (x, y) = (
+                x0 + cell as i32 % 3 * CELL_SIZE,
+                y0 + cell as i32 / 3 * CELL_SIZE,
+            );
+            let color = match cell == self.cell {
+                true => SELECTED_COLOR,
+                false => BACKGROUND_COLOR,
+            };
+
+            // Without the cell's last row and column, to leave a grid.
+            let (x1, y1) = ((x + CELL_SIZE - 1) as i16, (y + CELL_SIZE - 1) as i16);
+            [
+                SpriteVertex::new(0, 0, color, x as i16, y as i16),
+                SpriteVertex::new(0, 0, color, x1, y1),
+            ]
+        });
+        let vertices = frame.push_vertices(cells.collect::<Vec<_>>());
+        frame.draw_array(GuPrimitive::Sprites, vertices);
+
+        let line_height = font.line_height() as i32;
+        for cell in 0..9 {
+            let (cx, cy) = (
+                x0 + cell as i32 % 3 * CELL_SIZE + CELL_SIZE / 2,
+                y0 + cell as i32 / 3 * CELL_SIZE + CELL_SIZE / 2,
+            );
+
+            // Top, right, bottom and left of the cell's center.
+            let offsets = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+            for (&c, &(dx, dy)) in self.layer.cell(cell).iter().zip(&offsets) {
+                let mut buf = [0; 4];
+                let label = match c {
+                    BACKSPACE => "del",
+                    ENTER => "ret",
+                    ' ' => "sp",
+                    c => c.encode_utf8(&mut buf),
+                };
+
+                let x = cx + dx * CELL_SIZE / 3 - font.measure(label) / 2;
+                let y = cy + dy * CELL_SIZE / 3 - line_height / 2;
+                font::draw_text(&mut frame, font, x, y, TEXT_COLOR, label);
+            }
+        }
+    }
+
+    /// Top-left corner of the keyboard on screen.
+    fn origin(&self) -> (i32, i32) {
+        let size = CELL_SIZE * 3;
+        let right = SCREEN_WIDTH as i32 - size - MARGIN;
+        let bottom = SCREEN_HEIGHT as i32 - size - MARGIN;
+
+        match self.corner {
+            Corner::TopLeft => (MARGIN, MARGIN),
+            Corner::TopRight => (right, MARGIN),
+            Corner::BottomLeft => (MARGIN, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// The characters typed on one update, in `FACE_BUTTONS` order.
+#[derive(Debug, Clone)]
+pub struct Typed {
+    chars: [char; 4],
+    len: usize,
+    pos: usize,
+}
+
+impl Iterator for Typed {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.pos < self.len {
+            self.pos += 1;
+            Some(self.chars[self.pos - 1])
+        } else {
+            None
+        }
+    }
+}
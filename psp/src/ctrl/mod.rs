@@ -0,0 +1,578 @@
+//! Controller input, with edge detection.
+//!
+//! ```no_run
+//! use psp::ctrl::{Button, Input};
+//!
+//! let mut input = Input::new();
+//!
+//! loop {
+//!     input.update();
+//!
+//!     if input.pressed(Button::CROSS) {
+//!         psp::dprintln!("jump");
+//!     }
+//!
+//!     unsafe { psp::sys::sceDisplayWaitVblankStart() };
+//! }
+//! ```
+//!
+//! By default, `pressed` and `released` compare this frame's snapshot with the
+//! previous one, so a tap that starts and ends between two updates is
+//! missed. `Input::set_latched` switches to the controller's latch instead,
+//! which records every transition. For individual events, including those
+//! during long blocking operations, use [`latch_events`].
+//!
+//! The latch is kept by the kernel, which updates it on every sample, and
+//! covers the time since it was last read rather than a frame. A simple
+//! menu that only needs to know what was pressed can call [`read_latch`]
+//! whenever it is ready, without an `Input` at all.
+//!
+//! Reading the latch resets it. Only one consumer, either a latched `Input`,
+//! `latch_events` or `read_latch`, should read it, and share what it reads:
+//! a program should have one `Input`, update it once per frame, and hand it
+//! to everything that needs input by reference.
+
+pub mod danzeff;
+
+use crate::sys::{self, CtrlMode, PowerTick, SceCtrlData, SceCtrlLatch};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::time::Duration;
+
+/// Samples taken by the controller, as counted by latch reads.
+static SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+/// The sampling configuration last set with `configure`. The kernel starts
+/// out digital, sampling on every vertical blank.
+static ANALOG: AtomicBool = AtomicBool::new(false);
+static CYCLE_US: AtomicU32 = AtomicU32::new(0);
+
+/// Shortest and longest sampling cycles the hardware supports, in
+/// microseconds.
+pub const MIN_CYCLE_US: u32 = 5555;
+pub const MAX_CYCLE_US: u32 = 20000;
+
+/// A button, or a set of buttons.
+pub type Button = sys::CtrlButtons;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingMode {
+    /// Buttons only. The analog stick reads as centered.
+    Digital,
+    /// Buttons and the analog stick.
+    Analog,
+}
+
+/// How the controller is sampled, see `configure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplingConfig {
+    pub mode: SamplingMode,
+    /// Time between samples in microseconds, or 0 to sample on every vertical
+    /// blank.
+    pub cycle_us: u32,
+}
+
+/// Set how the controller is sampled. Returns the configuration applied.
+///
+/// A `cycle_us` other than 0 outside `MIN_CYCLE_US..=MAX_CYCLE_US` is clamped
+/// to that range, with a warning in debug builds.
+///
+/// `Input::new` configures analog sampling on every vertical blank. Call this
+/// afterwards to change it.
+pub fn configure(mode: SamplingMode, cycle_us: u32) -> SamplingConfig {
+    let clamped = match cycle_us {
+        0 => 0,
+        _ => cycle_us.clamp(MIN_CYCLE_US, MAX_CYCLE_US),
+    };
+
+    if cfg!(debug_assertions) && clamped != cycle_us {
+        crate::log!(
+            crate::debug::Level::Warn,
+            "ctrl: sampling cycle {} us clamped to {} us",
+            cycle_us,
+            clamped
+        );
+    }
+
+    let ctrl_mode = match mode {
+        SamplingMode::Digital => CtrlMode::Digital,
+        SamplingMode::Analog => CtrlMode::Analog,
+    };
+
+    unsafe {
+        sys::sceCtrlSetSamplingCycle(clamped as i32);
+        sys::sceCtrlSetSamplingMode(ctrl_mode);
+    }
+
+    ANALOG.store(mode == SamplingMode::Analog, Ordering::Relaxed);
+    CYCLE_US.store(clamped, Ordering::Relaxed);
+
+    sampling_config()
+}
+
+/// The configuration last set with `configure`.
+pub fn sampling_config() -> SamplingConfig {
+    SamplingConfig {
+        mode: match ANALOG.load(Ordering::Relaxed) {
+            true => SamplingMode::Analog,
+            false => SamplingMode::Digital,
+        },
+        cycle_us: CYCLE_US.load(Ordering::Relaxed),
+    }
+}
+
+/// What to keep from going idle, see `cancel_idle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleKind {
+    /// Keep the display on and the PSP from suspending.
+    All,
+    /// Keep the display on.
+    Display,
+    /// Keep the PSP from suspending. The display may still turn off.
+    Suspend,
+    /// Let analog stick movement reset the idle timer, like button presses
+    /// do. `threshold` is the movement needed on either axis, 1 to 128.
+    AnalogMotion { threshold: u8 },
+}
+
+/// Reset the system idle timer.
+///
+/// `All`, `Display` and `Suspend` reset it once, and need to be repeated
+/// regularly, such as once per frame. `AnalogMotion` stays in effect until
+/// set again, so games played with the stick alone don't dim the backlight.
+pub fn cancel_idle(kind: IdleKind) {
+    let tick = match kind {
+        IdleKind::All => PowerTick::All,
+        IdleKind::Display => PowerTick::Display,
+        IdleKind::Suspend => PowerTick::Suspend,
+        IdleKind::AnalogMotion { threshold } => {
+            let threshold = threshold.clamp(1, 128) as i32;
+            unsafe { sys::sceCtrlSetIdleCancelThreshold(threshold, threshold) };
+            return;
+        }
+    };
+
+    unsafe { sys::scePowerTick(tick) };
+}
+
+/// How the analog stick's distance from the center maps to its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseCurve {
+    Linear,
+    /// Squares the distance, for finer control close to the center.
+    Squared,
+}
+
+/// Corrections applied to the raw analog stick position by `Input::analog`.
+///
+/// Distances are measured after scaling both sides of each axis to -1.0..1.0,
+/// so a stick whose center is off still reaches ±1.0 at both extremes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalogConfig {
+    /// Raw rest position of the stick.
+    pub center: (f32, f32),
+    /// Distance from the center within which the stick reads as centered.
+    pub deadzone: f32,
+    /// Distance from the center at which the stick reads as pushed all the
+    /// way.
+    pub saturation: f32,
+    pub curve: ResponseCurve,
+}
+
+impl AnalogConfig {
+    pub const DEFAULT: AnalogConfig = AnalogConfig {
+        center: (127.5, 127.5),
+        deadzone: 0.15,
+        saturation: 0.95,
+        curve: ResponseCurve::Linear,
+    };
+
+    /// Sample the stick's rest position over `frames` frames, and use it as
+    /// `input`'s center. Returns the updated configuration.
+    ///
+    /// The stick must not be touched meanwhile. This waits for a vertical
+    /// blank after each sample, and updates `input`, so edges seen during
+    /// calibration are lost.
+    pub fn calibrate(input: &mut Input, frames: u32) -> AnalogConfig {
+        let frames = frames.max(1);
+        let (mut x, mut y) = (0, 0);
+
+        for _ in 0..frames {
+            input.update();
+
+            let (lx, ly) = input.analog_raw();
+            x += lx as u32;
+            y += ly as u32;
+
+            unsafe { sys::sceDisplayWaitVblankStart() };
+        }
+
+        input.analog_config.center = (x as f32 / frames as f32, y as f32 / frames as f32);
+        input.analog_config
+    }
+
+    /// Map a raw stick position to -1.0..1.0 on each axis.
+    pub fn apply(&self, (lx, ly): (u8, u8)) -> (f32, f32) {
+        let x = axis(lx, self.center.0);
+        let y = axis(ly, self.center.1);
+
+        let distance = libm::sqrtf(x * x + y * y);
+        if distance <= self.deadzone {
+            return (0.0, 0.0);
+        }
+
+        let range = (self.saturation - self.deadzone).max(f32::EPSILON);
+        let mut scaled = ((distance - self.deadzone) / range).min(1.0);
+
+        if self.curve == ResponseCurve::Squared {
+            scaled *= scaled;
+        }
+
+        (x / distance * scaled, y / distance * scaled)
+    }
+}
+
+impl Default for AnalogConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Identifies a combo registered with `Input::register_combo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ComboId(usize);
+
+/// The controller's state for the current frame.
+#[derive(Debug, Clone)]
+pub struct Input {
+    data: SceCtrlData,
+    previous: Button,
+    /// Edges since the previous update, from the latch or from the snapshots.
+    made: Button,
+    broken: Button,
+    latched: bool,
+    analog_config: AnalogConfig,
+    /// System time of the last update, in microseconds.
+    now: u64,
+    /// When each button (by bit) last went down.
+    down_since: [u64; 32],
+    combos: Vec<Button>,
+    /// Which combos triggered on the last update, by `ComboId`.
+    triggered: Vec<bool>,
+}
+
+impl Input {
+    /// Enable analog sampling, and start with nothing held or pressed.
+    pub fn new() -> Self {
+        let mut input = Self {
+            data: SceCtrlData::default(),
+            previous: Button::empty(),
+            made: Button::empty(),
+            broken: Button::empty(),
+            latched: false,
+            analog_config: AnalogConfig::DEFAULT,
+            now: 0,
+            down_since: [0; 32],
+            combos: Vec::new(),
+            triggered: Vec::new(),
+        };
+
+        configure(SamplingMode::Analog, 0);
+
+        // Buttons already down when the input is created aren't presses, but
+        // count as held from now on.
+        input.update();
+        input.made = Button::empty();
+        input
+    }
+
+    /// Take a new snapshot of the controller. Call this once per frame.
+    pub fn update(&mut self) {
+        self.previous = self.data.buttons;
+        unsafe { sys::sceCtrlPeekBufferPositive(&mut self.data, 1) };
+
+        if self.latched {
+            let latch = read_latch();
+            self.made = latch.made;
+            self.broken = latch.broken;
+        } else {
+            self.made = self.data.buttons - self.previous;
+            self.broken = self.previous - self.data.buttons;
+        }
+
+        self.now = unsafe { sys::sceKernelGetSystemTimeWide() } as u64;
+
+        // Including buttons released and pressed again within the frame.
+        let started = (self.made | (self.data.buttons - self.previous)) & self.data.buttons;
+        for bit in bits(started) {
+            self.down_since[bit] = self.now;
+        }
+
+        self.update_combos();
+    }
+
+    fn update_combos(&mut self) {
+        let (held, made) = (self.data.buttons, self.made);
+
+        for (triggered, &combo) in self.triggered.iter_mut().zip(&self.combos) {
+            *triggered = held.contains(combo) && made.intersects(combo);
+        }
+
+        // A combo contained in a longer one that triggered doesn't.
+        for i in 0..self.combos.len() {
+            let combo = self.combos[i];
+            let shadowed = self
+                .combos
+                .iter()
+                .zip(&self.triggered)
+                .any(|(&other, &t)| t && other != combo && other.contains(combo));
+
+            if shadowed {
+                self.triggered[i] = false;
+            }
+        }
+    }
+
+    /// Take edges from the controller's latch rather than from comparing
+    /// snapshots, so that no press is missed. Off by default.
+    ///
+    /// The latch is reset when this is turned on, dropping the edges
+    /// recorded until then.
+    pub fn set_latched(&mut self, latched: bool) {
+        if latched && !self.latched {
+            read_raw_latch();
+        }
+
+        self.latched = latched;
+    }
+
+    /// Whether all of `buttons` are held down.
+    pub fn held(&self, buttons: Button) -> bool {
+        self.data.buttons.contains(buttons)
+    }
+
+    /// How long all of `buttons` have been held down together, as of the
+    /// last update. Zero if any of them is up.
+    pub fn held_for(&self, buttons: Button) -> Duration {
+        if buttons.is_empty() || !self.held(buttons) {
+            return Duration::ZERO;
+        }
+
+        let since = bits(buttons)
+            .map(|bit| self.down_since[bit])
+            .max()
+            .unwrap_or(self.now);
+        Duration::from_micros(self.now.saturating_sub(since))
+    }
+
+    /// Register a combo of buttons pressed together, such as
+    /// `&[Button::LTRIGGER, Button::RTRIGGER, Button::START]`.
+    ///
+    /// The combo triggers on an update where all of its buttons are down, and
+    /// at least one of them was just pressed. If a combo is part of a longer
+    /// one that triggers on the same update, only the longer one does.
+    pub fn register_combo(&mut self, buttons: &[Button]) -> ComboId {
+        let combo = buttons.iter().fold(Button::empty(), |a, &b| a | b);
+
+        self.combos.push(combo);
+        self.triggered.push(false);
+        ComboId(self.combos.len() - 1)
+    }
+
+    /// Whether combo `id` triggered on the last update.
+    pub fn combo_triggered(&self, id: ComboId) -> bool {
+        self.triggered[id.0]
+    }
+
+    /// Whether any of `buttons` went down since the previous update.
+    pub fn pressed(&self, buttons: Button) -> bool {
+        self.made.intersects(buttons)
+    }
+
+    /// Whether any of `buttons` went up since the previous update.
+    pub fn released(&self, buttons: Button) -> bool {
+        self.broken.intersects(buttons)
+    }
+
+    /// All buttons held down.
+    pub fn buttons(&self) -> Button {
+        self.data.buttons
+    }
+
+    /// The analog stick position, from -1.0 to 1.0 on each axis, corrected
+    /// with the analog configuration. Positive Y is down.
+    pub fn analog(&self) -> (f32, f32) {
+        self.analog_config.apply(self.analog_raw())
+    }
+
+    /// The raw analog stick position, from 0 to 255 on each axis.
+    pub fn analog_raw(&self) -> (u8, u8) {
+        (self.data.lx, self.data.ly)
+    }
+
+    pub fn analog_config(&self) -> AnalogConfig {
+        self.analog_config
+    }
+
+    pub fn set_analog_config(&mut self, config: AnalogConfig) {
+        self.analog_config = config;
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEventKind {
+    Pressed,
+    Released,
+}
+
+/// A button going down or up, from `latch_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonEvent {
+    /// A single button.
+    pub button: Button,
+    pub kind: ButtonEventKind,
+    /// The number of controller samples taken before the latch was read,
+    /// counting from the first read. The latch doesn't record when within
+    /// that time a transition happened, so all events from one call share
+    /// this.
+    pub sample: u32,
+}
+
+/// Every button press and release since the previous call, read from the
+/// controller's latch.
+///
+/// A button that went both down and up since then gets both events, in the
+/// order that leaves it in its current state.
+pub fn latch_events() -> LatchEvents {
+    let (latch, _) = read_raw_latch();
+
+    LatchEvents {
+        latch,
+        sample: SAMPLES.load(Ordering::Relaxed),
+        bit: 0,
+        pending: None,
+    }
+}
+
+/// Iterator returned by `latch_events`.
+#[derive(Debug, Clone)]
+pub struct LatchEvents {
+    latch: SceCtrlLatch,
+    sample: u32,
+    /// Next bit to look at.
+    bit: u32,
+    /// Second event for the previous bit.
+    pending: Option<ButtonEvent>,
+}
+
+impl Iterator for LatchEvents {
+    type Item = ButtonEvent;
+
+    fn next(&mut self) -> Option<ButtonEvent> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+
+        while self.bit < 32 {
+            let mask = 1 << self.bit;
+            self.bit += 1;
+
+            let button = match Button::from_bits(mask) {
+                Some(button) => button,
+                None => continue,
+            };
+
+            let made = self.latch.ui_make & mask != 0;
+            let broken = self.latch.ui_break & mask != 0;
+            let sample = self.sample;
+            let event = |kind| ButtonEvent {
+                button,
+                kind,
+                sample,
+            };
+
+            let (first, second) = match (made, broken) {
+                (false, false) => continue,
+                (true, false) => (ButtonEventKind::Pressed, None),
+                (false, true) => (ButtonEventKind::Released, None),
+                (true, true) if self.latch.ui_press & mask != 0 => {
+                    (ButtonEventKind::Released, Some(ButtonEventKind::Pressed))
+                }
+                (true, true) => (ButtonEventKind::Pressed, Some(ButtonEventKind::Released)),
+            };
+
+            self.pending = second.map(event);
+            return Some(event(first));
+        }
+
+        None
+    }
+}
+
+/// Button transitions recorded by the controller's latch, from
+/// `read_latch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CtrlLatch {
+    /// Buttons that went down since the latch was last read (the kernel's
+    /// make bits).
+    pub made: Button,
+    /// Buttons that went up since the latch was last read (break bits).
+    pub broken: Button,
+    /// Buttons down as of the last sample (press bits).
+    pub down: Button,
+    /// Buttons up as of the last sample (release bits).
+    pub up: Button,
+    /// Samples the controller took since the latch was last read.
+    pub samples: u32,
+}
+
+/// Read and reset the controller's latch.
+///
+/// Unlike `Input`, which compares snapshots taken once per update, the
+/// kernel checks every sample, so a button that went down and up again
+/// since the last read is in both `made` and `broken`. There is no
+/// snapshot to keep, which makes this the cheapest way to poll for
+/// presses.
+pub fn read_latch() -> CtrlLatch {
+    let (latch, samples) = read_raw_latch();
+
+    CtrlLatch {
+        made: Button::from_bits_truncate(latch.ui_make),
+        broken: Button::from_bits_truncate(latch.ui_break),
+        down: Button::from_bits_truncate(latch.ui_press),
+        up: Button::from_bits_truncate(latch.ui_release),
+        samples,
+    }
+}
+
+/// Read and reset the latch, counting the samples since the last read.
+fn read_raw_latch() -> (SceCtrlLatch, u32) {
+    let mut latch = SceCtrlLatch::default();
+    let samples = unsafe { sys::sceCtrlReadLatch(&mut latch) }.max(0) as u32;
+
+    SAMPLES.fetch_add(samples, Ordering::Relaxed);
+    (latch, samples)
+}
+
+/// Indices of the bits set in `buttons`.
+fn bits(buttons: Button) -> impl Iterator<Item = usize> {
+    (0..32).filter(move |bit| buttons.bits() & (1 << bit) != 0)
+}
+
+/// Scale `raw` to -1.0..1.0, each side of `center` separately.
+fn axis(raw: u8, center: f32) -> f32 {
+    let offset = raw as f32 - center;
+
+    let extent = if offset < 0.0 { center } else { 255.0 - center };
+    if extent <= 0.0 {
+        return 0.0;
+    }
+
+    (offset / extent).clamp(-1.0, 1.0)
+}
@@ -1,15 +1,33 @@
 //! Debug support.
 //!
-//! You should use the `dprintln!` and `dprint!` macros.
+//! You should use the `dprintln!` and `dprint!` macros, or `dputs!` for plain
+//! string literals.
+//!
+//! Printing from an interrupt handler, such as a GE callback, is allowed. The
+//! output is queued and only added once a thread prints (or the console is
+//! otherwise updated), so it may show up a frame late. Up to 1 KiB is queued,
+//! anything past that is dropped and counted by `dropped_bytes`.
+//!
+//! Without the `debug-console` feature, which is on by default, `dprint!`,
+//! `dprintln!`, `dputs!` and `log!` expand to nothing: their arguments are
+//! type checked, but not evaluated. A program that doesn't otherwise draw
+//! the console then doesn't contain the console's font either. The functions
+//! in this module still work when called directly.
 
+use crate::critical;
+use crate::sync::Once;
 use crate::sys;
-use core::fmt;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use core::{fmt, mem, ptr};
 
 /// Like `println!`, but prints to the PSP screen.
 #[macro_export]
 macro_rules! dprintln {
     () => {
-        $crate::dprint("\n")
+        $crate::dprint!("\n")
     };
     ($($arg:tt)*) => {{
         $crate::dprint!($($arg)*);
@@ -18,6 +36,7 @@ macro_rules! dprintln {
 }
 
 /// Like `print!`, but prints to the PSP screen.
+#[cfg(feature = "debug-console")]
 #[macro_export]
 macro_rules! dprint {
     ($($arg:tt)*) => {{
@@ -25,97 +44,719 @@ macro_rules! dprint {
     }}
 }
 
-// TODO: Wrap this in some kind of a mutex.
+/// Like `print!`, but prints to the PSP screen.
+#[cfg(not(feature = "debug-console"))]
+#[macro_export]
+macro_rules! dprint {
+    ($($arg:tt)*) => {{
+        if false {
+            let _ = core::format_args!($($arg)*);
+        }
+    }}
+}
+
+/// Prints a string literal to the PSP screen, without going through
+/// `core::fmt`.
+#[cfg(feature = "debug-console")]
+#[macro_export]
+macro_rules! dputs {
+    ($s:literal) => {
+        $crate::debug::write_bytes($s.as_bytes())
+    };
+}
+
+/// Prints a string literal to the PSP screen, without going through
+/// `core::fmt`.
+#[cfg(not(feature = "debug-console"))]
+#[macro_export]
+macro_rules! dputs {
+    ($s:literal) => {{
+        let _: &str = $s;
+    }};
+}
+
+/// Only accessed through `with_chars`.
 static mut CHARS: CharBuffer = CharBuffer::new();
+static mut CONFIG: Config = Config::DEFAULT;
+
+/// The largest display the console can be configured for, the PSP's TV out
+/// resolution.
+pub const MAX_DISPLAY_WIDTH: usize = 720;
+pub const MAX_DISPLAY_HEIGHT: usize = 480;
+
+/// Framebuffer layout used by the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Framebuffer stride, in pixels.
+    pub buffer_width: usize,
+    /// Visible width, in pixels.
+    pub display_width: usize,
+    /// Visible height, in pixels.
+    pub display_height: usize,
+}
+
+impl Config {
+    /// The PSP's LCD: 480x272, with a 512 pixel stride.
+    pub const DEFAULT: Config = Config {
+        buffer_width: 512,
+        display_width: 480,
+        display_height: 272,
+    };
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The console has already been used with the previous configuration,
+    /// and its framebuffer reserved for it.
+    InUse,
+    /// The display is empty, larger than `MAX_DISPLAY_WIDTH` x
+    /// `MAX_DISPLAY_HEIGHT`, or wider than the buffer.
+    InvalidSize,
+}
+
+/// Set the framebuffer layout used by the console.
+///
+/// This must be called before the console is first used, which reserves
+/// VRAM for the layout.
+pub fn configure(config: Config) -> Result<(), ConfigError> {
+    if config.display_width == 0
+        || config.display_height == 0
+        || config.display_width > MAX_DISPLAY_WIDTH
+        || config.display_height > MAX_DISPLAY_HEIGHT
+        || config.display_width > config.buffer_width
+    {
+        return Err(ConfigError::InvalidSize);
+    }
+
+    with_chars(|chars| {
+        if !chars.is_empty() || VRAM_RESERVED.is_completed() {
+            return Err(ConfigError::InUse);
+        }
+
+        unsafe { CONFIG = config };
+        chars.set_layout(config, chars.font);
+
+        Ok(())
+    })
+}
+
+/// The current framebuffer layout.
+pub fn config() -> Config {
+    unsafe { CONFIG }
+}
+
+/// The console's screen layout and text grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    /// Visible width, in pixels.
+    pub display_width: usize,
+    /// Visible height, in pixels.
+    pub display_height: usize,
+    /// Framebuffer stride, in pixels.
+    pub buffer_width: usize,
+    /// Text rows and columns on screen.
+    pub rows: usize,
+    pub cols: usize,
+    /// Size of a character cell, in pixels. Row `r`, column `c` starts at
+    /// `(c * char_width, r * char_height)`.
+    ///
+    /// With a proportional font, `char_width` is the widest character, and
+    /// `cols` how many of the narrowest fit.
+    pub char_width: usize,
+    pub char_height: usize,
+}
 
-/// Update the screen.
+/// The current screen layout, for drawing aligned with the console's text.
+pub fn geometry() -> Geometry {
+    let config = config();
+    let (rows, cols, font) = with_chars(|chars| (chars.rows, chars.cols, chars.font));
+
+    Geometry {
+        display_width: config.display_width,
+        display_height: config.display_height,
+        buffer_width: config.buffer_width,
+        rows,
+        cols,
+        char_width: font.char_width,
+        char_height: font.char_height,
+    }
+}
+
+/// Run `f` with exclusive access to the line buffer.
+///
+/// Interrupts are suspended while `f` runs, which keeps out both other
+/// threads and interrupt handlers. Keep `f` short.
+fn with_chars<R, F: FnOnce(&mut CharBuffer) -> R>(f: F) -> R {
+    critical(|| f(unsafe { &mut *ptr::addr_of_mut!(CHARS) }))
+}
+
+/// Update the screen, or mark it as needing an update if a batch is running.
+///
+/// Output queued by interrupt handlers is added first.
 fn update() {
+    drain_deferred();
+
+    let deferred = with_chars(|chars| {
+        if chars.batch_depth > 0 {
+            chars.dirty = true;
+        }
+
+        chars.dirty
+    });
+
+    if !deferred {
+        redraw();
+    }
+}
+
+/// Run `f`, redrawing the screen only once at the end instead of after every
+/// print.
+///
+/// ```no_run
+/// psp::debug::batch(|| {
+///     for i in 0..20 {
+///         psp::dprintln!("line {}", i);
+///     }
+/// });
+/// ```
+///
+/// Nested batches are part of the outermost one, which does the redraw. The
+/// screen is not redrawn if nothing was printed. While a batch runs, output
+/// from other threads is held back as well.
+pub fn batch<R, F: FnOnce() -> R>(f: F) -> R {
+    struct Batch;
+
+    impl Drop for Batch {
+        fn drop(&mut self) {
+            let redraw_now = with_chars(|chars| {
+                chars.batch_depth -= 1;
+                chars.batch_depth == 0 && mem::replace(&mut chars.dirty, false)
+            });
+
+            if redraw_now {
+                redraw();
+            }
+        }
+    }
+
+    with_chars(|chars| chars.batch_depth += 1);
+
+    // Ends the batch even if `f` panics, so the console keeps updating.
+    let _batch = Batch;
+    f()
+}
+
+/// Draw the visible lines.
+fn redraw() {
     unsafe {
         init();
         clear_screen(0);
+    }
+
+    let (font, rows, shown, cursor) = with_chars(|chars| {
+        let visible = chars.visible();
+        let cursor = chars.cursor_visible.then(|| chars.cursor()).flatten();
 
-        for (i, line) in CHARS.lines().enumerate() {
-            put_str::<MsxFont>(
+        (chars.font, chars.rows, visible.end - visible.pos, cursor)
+    });
+
+    let anchor = anchor();
+    let cursor_width = font.advance(b'_');
+
+    // A bottom anchor moves the lines down the grid, leaving room for the
+    // cursor if it starts a new line.
+    let used = cursor.map_or(shown, |(row, _)| shown.max(row + 1));
+    let top = match anchor.is_bottom() {
+        true => rows.saturating_sub(used),
+        false => 0,
+    };
+
+    // A right anchor ends each line at the right edge of the text grid, or
+    // the cursor's line just before the cursor.
+    let line_x = |width: usize, row: usize| match anchor.is_right() {
+        true => {
+            let reserved = match cursor {
+                Some((cursor_row, _)) if cursor_row == row => cursor_width,
+                _ => 0,
+            };
+
+            font.line_width(unsafe { CONFIG.display_width })
+                .saturating_sub(width + reserved)
+        }
+        false => 0,
+    };
+
+    // Lines are copied out one at a time, so the lock isn't held while
+    // drawing.
+    let mut i = 0;
+    while let Some(line) = with_chars(|chars| chars.visible().nth(i)) {
+        unsafe {
+            put_str(
+                &font,
                 &line.chars[0..line.len],
-                0,
-                i * MsxFont::CHAR_HEIGHT,
-                0xffff_ffff,
-            )
+                line_x(line.width, i),
+                (top + i) * font.char_height,
+                line.color,
+            );
         }
+
+        i += 1;
+    }
+
+    if let Some((row, x)) = cursor {
+        let on = unsafe { sys::sceKernelGetSystemTimeLow() } / CURSOR_BLINK_US % 2 == 0;
+
+        if on {
+            put_glyph(
+                &font,
+                char_x(&font, line_x(x, row) + x, cursor_width),
+                (top + row) * font.char_height,
+                apply_brightness(0xffff_ffff),
+                b'_',
+            );
+        }
+    }
+}
+
+/// The text on screen, as lines joined with `\n`, for checking output in
+/// tests.
+///
+/// Trailing spaces and blank lines are left out. Colors, the cursor,
+/// mirroring and the anchor are not represented.
+pub fn capture_text() -> String {
+    let mut text = String::new();
+
+    let mut i = 0;
+    while let Some(line) = with_chars(|chars| chars.visible().nth(i)) {
+        text.extend(line.chars[..line.len].iter().map(|&b| b as char));
+
+        let trimmed = text.trim_end_matches(' ').len();
+        text.truncate(trimmed);
+        text.push('\n');
+
+        i += 1;
+    }
+
+    let trimmed = text.trim_end().len();
+    text.truncate(trimmed);
+    text
+}
+
+/// How long the cursor stays on, then off, in microseconds.
+const CURSOR_BLINK_US: u32 = 500_000;
+
+/// Show or hide a blinking cursor where the next character will go.
+///
+/// The cursor is drawn when the console is redrawn, so it only blinks as
+/// long as something keeps printing or calling `update_cursor`. It is hidden
+/// while the view is scrolled up.
+pub fn set_cursor_visible(visible: bool) {
+    with_chars(|chars| chars.cursor_visible = visible);
+    update();
+}
+
+/// Redraw the console if the cursor is visible, to make it blink. Call this
+/// once per frame while waiting for input.
+pub fn update_cursor() {
+    if with_chars(|chars| chars.cursor_visible) {
+        update();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    /// Text has already been printed with the previous font.
+    InUse,
+    /// The width is not 1 to 8 pixels, or the height is 0.
+    InvalidSize,
+    /// The advances are not 256 widths of up to 8 pixels, with at least one
+    /// that isn't 0.
+    InvalidAdvances,
+    /// The glyphs are not `256 * char_height` bytes.
+    WrongLength { expected: usize, actual: usize },
+}
+
+/// Use a monospaced bitmap font of your own instead of the built-in one.
+///
+/// `glyphs` holds the 256 characters in order, each one byte per row for
+/// `char_height` rows, with the leftmost pixel in the top bit. Only the
+/// top `char_width` bits of each row are drawn, and characters are placed
+/// right next to each other, so the glyphs should include any spacing.
+///
+/// Like `configure`, this must be called before anything is printed. The
+/// text grid is no larger than it is with the built-in font, so a smaller
+/// font may not fill a TV sized display.
+pub fn load_font(
+    glyphs: &'static [u8],
+    char_width: usize,
+    char_height: usize,
+) -> Result<(), FontError> {
+    if !(1..=8).contains(&char_width) || char_height == 0 {
+        return Err(FontError::InvalidSize);
+    }
+
+    let expected = 256 * char_height;
+    if glyphs.len() != expected {
+        return Err(FontError::WrongLength {
+            expected,
+            actual: glyphs.len(),
+        });
+    }
+
+    set_font(Font {
+        glyphs,
+        advances: None,
+        glyph_width: char_width,
+        glyph_height: char_height,
+        char_width,
+        char_height,
+        narrowest: char_width,
+    })
+}
+
+/// Use a proportional bitmap font of your own: like `load_font`, but each
+/// character is as wide as its entry in `advances`, up to 8 pixels.
+/// Characters of width 0 aren't drawn.
+///
+/// Lines wrap once the next character doesn't fit, so more narrow
+/// characters fit on a line than wide ones, up to the 120 a line holds.
+/// `print_at` columns count characters, not cells of a fixed width.
+pub fn load_proportional_font(
+    glyphs: &'static [u8],
+    advances: &'static [u8],
+    char_height: usize,
+) -> Result<(), FontError> {
+    let widest = advances.iter().copied().max().unwrap_or(0) as usize;
+    let narrowest = advances.iter().copied().filter(|&a| a > 0).min();
+
+    let narrowest = match narrowest {
+        Some(narrowest) if advances.len() == 256 && widest <= 8 => narrowest as usize,
+        _ => return Err(FontError::InvalidAdvances),
+    };
+
+    if char_height == 0 {
+        return Err(FontError::InvalidSize);
     }
+
+    let expected = 256 * char_height;
+    if glyphs.len() != expected {
+        return Err(FontError::WrongLength {
+            expected,
+            actual: glyphs.len(),
+        });
+    }
+
+    set_font(Font {
+        glyphs,
+        advances: Some(advances),
+        glyph_width: widest,
+        glyph_height: char_height,
+        char_width: widest,
+        char_height,
+        narrowest,
+    })
 }
 
-trait Font {
-    const CHAR_WIDTH: usize;
-    const CHAR_HEIGHT: usize;
+fn set_font(font: Font) -> Result<(), FontError> {
+    with_chars(|chars| {
+        if !chars.is_empty() {
+            return Err(FontError::InUse);
+        }
+
+        chars.set_layout(config(), font);
+        Ok(())
+    })
+}
 
-    fn put_char(x: usize, y: usize, color: u32, c: u8);
+/// A bitmap font, one byte per glyph row.
+#[derive(Clone, Copy)]
+struct Font {
+    glyphs: &'static [u8],
+    /// How far each character moves the next one along, for a proportional
+    /// font.
+    advances: Option<&'static [u8]>,
+    /// Columns and rows drawn of each glyph.
+    glyph_width: usize,
+    glyph_height: usize,
+    /// Size of a character cell. For a proportional font, the width is that
+    /// of the widest character.
+    char_width: usize,
+    char_height: usize,
+    /// Width of the narrowest character, other than those of width 0.
+    narrowest: usize,
 }
 
-struct MsxFont;
+/// The built-in font, with 8x8 glyphs in 6x10 cells. ASCII glyphs only use
+/// the left 6 of their 8 columns.
+const MSX: Font = Font {
+    glyphs: &MSX_FONT,
+    advances: None,
+    glyph_width: 8,
+    glyph_height: 8,
+    char_width: 6,
+    char_height: 10,
+    narrowest: 6,
+};
 
-impl Font for MsxFont {
-    const CHAR_HEIGHT: usize = 10;
-    const CHAR_WIDTH: usize = 6;
+impl Font {
+    /// Width of `c`, including the space after it.
+    fn advance(&self, c: u8) -> usize {
+        match self.advances {
+            Some(advances) => advances[c as usize] as usize,
+            None => self.char_width,
+        }
+    }
+
+    fn text_width(&self, text: &[u8]) -> usize {
+        text.iter().map(|&c| self.advance(c)).sum()
+    }
+
+    /// Width of the area lines are drawn in, which for a monospaced font is
+    /// a whole number of cells.
+    fn line_width(&self, display_width: usize) -> usize {
+        match self.advances {
+            Some(_) => display_width,
+            None => display_width / self.char_width * self.char_width,
+        }
+    }
+
+    fn put_char(&self, x: usize, y: usize, color: u32, c: u8) {
+        let glyph = &self.glyphs[c as usize * self.glyph_height..][..self.glyph_height];
+        let mirror = MIRROR.load(Ordering::Relaxed);
 
-    fn put_char(x: usize, y: usize, color: u32, c: u8) {
         unsafe {
-            let mut ptr = VRAM_BASE.add(x + y * BUFFER_WIDTH);
+            let Config {
+                buffer_width,
+                display_width,
+                display_height,
+            } = CONFIG;
+            let mut ptr = vram_base().add(x + y * buffer_width);
+
+            for (i, &bits) in glyph.iter().enumerate() {
+                // Clip glyphs straddling the bottom edge, so rows past the
+                // display don't spill into the memory after the framebuffer.
+                if y + i >= display_height {
+                    break;
+                }
+
+                for j in 0..self.glyph_width {
+                    // Mirrored glyphs are flipped within their width, and
+                    // whatever is drawn past it is dropped.
+                    let column = match mirror {
+                        true => self.advance(c).checked_sub(j + 1),
+                        false => Some(j),
+                    };
 
-            for i in 0..8 {
-                for j in 0..8 {
-                    if MSX_FONT[c as usize * 8 + i] & (0b1000_0000 >> j) != 0 {
-                        *ptr = color;
+                    // Skip pixels past the right edge rather than letting them
+                    // wrap around onto the next scanline.
+                    if let Some(column) = column {
+                        if x + j < display_width && bits & (0x80 >> column) != 0 {
+                            *ptr = color;
+                        }
                     }
 
                     ptr = ptr.offset(1);
                 }
 
-                ptr = ptr.add(BUFFER_WIDTH - 8);
+                ptr = ptr.add(buffer_width - self.glyph_width);
             }
         }
     }
 }
 
-const BUFFER_WIDTH: usize = 512;
-const DISPLAY_HEIGHT: usize = 272;
-const DISPLAY_WIDTH: usize = 480;
-static mut VRAM_BASE: *mut u32 = 0 as *mut u32;
+/// Reserves the console's framebuffer, sized for `CONFIG`, on first use.
+static VRAM_RESERVED: Once = Once::new();
+/// The framebuffer, set by `init` through `VRAM_RESERVED`.
+static VRAM_BASE: AtomicPtr<u32> = AtomicPtr::new(ptr::null_mut());
+
+fn vram_base() -> *mut u32 {
+    VRAM_BASE.load(Ordering::Acquire)
+}
 
 unsafe fn clear_screen(color: u32) {
-    let mut ptr = VRAM_BASE;
+    crate::mem::fast_fill32(
+        vram_base(),
+        color,
+        CONFIG.buffer_width * CONFIG.display_height,
+    );
+}
+
+static MIRROR: AtomicBool = AtomicBool::new(false);
+
+/// Draw text mirrored horizontally: each glyph is flipped, and lines run
+/// right to left from the right edge of the text grid. Off by default.
+pub fn set_mirror(mirror: bool) {
+    MIRROR.store(mirror, Ordering::Relaxed);
+    update();
+}
+
+pub fn mirror() -> bool {
+    MIRROR.load(Ordering::Relaxed)
+}
+
+/// Horizontal position of a character `width` pixels wide, `x` pixels into
+/// a line.
+fn char_x(font: &Font, x: usize, width: usize) -> usize {
+    match mirror() {
+        true => font
+            .line_width(unsafe { CONFIG.display_width })
+            .saturating_sub(x + width),
+        false => x,
+    }
+}
+
+/// Where the console's text sits on the screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// Lines fill the screen from the top, starting at the left edge. The
+    /// default.
+    #[default]
+    TopLeft,
+    /// The newest line is on the bottom row, and older ones scroll up off
+    /// the top.
+    BottomLeft,
+    /// Lines fill the screen from the top, ending at the right edge.
+    TopRight,
+    /// The newest line is on the bottom row, ending at the right edge.
+    BottomRight,
+}
+
+impl Anchor {
+    fn is_bottom(self) -> bool {
+        matches!(self, Anchor::BottomLeft | Anchor::BottomRight)
+    }
 
-    for _ in 0..(BUFFER_WIDTH * DISPLAY_HEIGHT) {
-        *ptr = color;
-        ptr = ptr.offset(1);
+    fn is_right(self) -> bool {
+        matches!(self, Anchor::TopRight | Anchor::BottomRight)
     }
 }
 
-unsafe fn put_str<T: Font>(s: &[u8], x: usize, y: usize, color: u32) {
-    if y > DISPLAY_HEIGHT {
+static ANCHOR: AtomicU8 = AtomicU8::new(Anchor::TopLeft as u8);
+
+/// Stick the console's text to a corner of the screen. `Anchor::TopLeft` by
+/// default.
+///
+/// Lines stay on the rows of the text grid, so a bottom anchor leaves any
+/// pixels below the last whole row empty. A right anchor lines each line's
+/// last character up with the right edge of the grid, using each
+/// character's own width with a proportional font. With `set_mirror`, the
+/// text is then flipped, ending at the left edge instead.
+pub fn set_anchor(anchor: Anchor) {
+    ANCHOR.store(anchor as u8, Ordering::Relaxed);
+    update();
+}
+
+pub fn anchor() -> Anchor {
+    match ANCHOR.load(Ordering::Relaxed) {
+        1 => Anchor::BottomLeft,
+        2 => Anchor::TopRight,
+        3 => Anchor::BottomRight,
+        _ => Anchor::TopLeft,
+    }
+}
+
+/// Whether `SHADOW_COLOR` is drawn.
+static SHADOW: AtomicBool = AtomicBool::new(false);
+static SHADOW_COLOR: AtomicU32 = AtomicU32::new(0);
+
+/// Draw a shadow of each character, one pixel down and to the right, in
+/// `color`, to keep text readable over a bright background. `None`, the
+/// default, draws no shadow.
+///
+/// Drawing each character twice makes drawing text about twice as slow.
+pub fn set_shadow(color: Option<u32>) {
+    SHADOW_COLOR.store(color.unwrap_or(0), Ordering::Relaxed);
+    SHADOW.store(color.is_some(), Ordering::Relaxed);
+    update();
+}
+
+pub fn shadow() -> Option<u32> {
+    SHADOW
+        .load(Ordering::Relaxed)
+        .then(|| SHADOW_COLOR.load(Ordering::Relaxed))
+}
+
+/// Draw `c` at `x`, `y`, over its shadow if there is one. Both are clipped
+/// to the display.
+fn put_glyph(font: &Font, x: usize, y: usize, color: u32, c: u8) {
+    if let Some(shadow) = shadow() {
+        font.put_char(x + 1, y + 1, apply_brightness(shadow), c);
+    }
+
+    font.put_char(x, y, color, c);
+}
+
+/// Scale applied to the text's color channels, 255 for full brightness.
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(255);
+
+/// Dim the console's text, from 255 for full brightness (the default) down
+/// to 0 for black. This only changes the colors drawn, not the backlight.
+pub fn set_brightness(level: u8) {
+    BRIGHTNESS.store(level, Ordering::Relaxed);
+    update();
+}
+
+pub fn brightness() -> u8 {
+    BRIGHTNESS.load(Ordering::Relaxed)
+}
+
+/// Scale the color channels of `color` by the brightness, keeping alpha.
+fn apply_brightness(color: u32) -> u32 {
+    let level = brightness() as u32;
+    if level == 255 {
+        return color;
+    }
+
+    let channel = |shift: u32| ((color >> shift & 0xff) * level / 255) << shift;
+    color & 0xff00_0000 | channel(16) | channel(8) | channel(0)
+}
+
+unsafe fn put_str(font: &Font, s: &[u8], x: usize, y: usize, color: u32) {
+    if y >= CONFIG.display_height {
         return;
     }
 
-    for (i, c) in s.iter().enumerate() {
-        if i >= (DISPLAY_WIDTH / T::CHAR_WIDTH) {
+    let color = apply_brightness(color);
+    let mut x = x;
+
+    for &c in s {
+        // Only whole characters are drawn.
+        let advance = font.advance(c);
+        if x + advance > CONFIG.display_width {
             break;
         }
 
-        if *c as u32 <= 255 && *c != b'\0' {
-            T::put_char(T::CHAR_WIDTH * i + x, y, color, *c);
+        if c != b'\0' && advance > 0 {
+            put_glyph(font, char_x(font, x, advance), y, color, c);
         }
+
+        x += advance;
     }
 }
 
 unsafe fn init() {
-    // The OR operation here specifies the address bypasses cache.
-    VRAM_BASE = (0x4000_0000u32 | sys::sceGeEdramGetAddr() as u32) as *mut u32;
+    VRAM_RESERVED.call_once(|| {
+        let size = CONFIG.buffer_width * CONFIG.display_height * 4;
+        let offset = crate::vram_alloc::reserve_console(size);
+
+        let vram = sys::uncached(sys::sceGeEdramGetAddr().cast::<u32>());
+        VRAM_BASE.store(vram.add(offset / 4), Ordering::Release);
+    });
 
-    // TODO: Change sys types to usize.
-    sys::sceDisplaySetMode(sys::DisplayMode::Lcd, DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    sys::sceDisplaySetMode(
+        sys::DisplayMode::Lcd,
+        CONFIG.display_width,
+        CONFIG.display_height,
+    );
     sys::sceDisplaySetFrameBuf(
-        VRAM_BASE as *const u8,
-        BUFFER_WIDTH,
+        vram_base() as *const u8,
+        CONFIG.buffer_width,
         sys::DisplayPixelFormat::Psm8888,
         sys::DisplaySetBufSync::NextFrame,
     );
@@ -125,59 +766,763 @@ unsafe fn init() {
 pub fn print_args(arguments: core::fmt::Arguments<'_>) {
     use fmt::Write;
 
-    unsafe {
-        let _ = write!(CHARS, "{}", arguments);
+    if in_interrupt() {
+        let _ = write!(Deferred, "{}", arguments);
+        return;
     }
 
+    ensure_history();
+
+    with_chars(|chars| {
+        // Only a failing `Display` impl can make this fail, what it wrote
+        // so far is kept.
+        let _ = write!(chars, "{}", arguments);
+    });
+
     update();
 }
 
+/// Text color, as `0xAABBGGRR`.
+const DEFAULT_COLOR: u32 = 0xffff_ffff;
+
+/// Like `dprintln!`, for a message at a `debug::Level`.
+///
+/// ```no_run
+/// use psp::debug::Level;
+/// psp::log!(Level::Warn, "{} retries left", 3);
+/// ```
+///
+/// Messages are prefixed with their level, shown in the level's color, and
+/// always start on a line of their own. Messages below the level set with
+/// `debug::set_min_level` are dropped.
+#[cfg(feature = "debug-console")]
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::debug::log_args($level, core::format_args!($($arg)*))
+    };
+}
+
+/// Like `dprintln!`, for a message at a `debug::Level`.
+#[cfg(not(feature = "debug-console"))]
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {{
+        if false {
+            let _: $crate::debug::Level = $level;
+            let _ = core::format_args!($($arg)*);
+        }
+    }};
+}
+
+/// Severity of a `log!` message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn prefix(self) -> &'static str {
+        match self {
+            Level::Trace => "[TRACE] ",
+            Level::Debug => "[DEBUG] ",
+            Level::Info => "[INFO] ",
+            Level::Warn => "[WARN] ",
+            Level::Error => "[ERROR] ",
+        }
+    }
+
+    fn color(self) -> u32 {
+        match self {
+            Level::Trace => 0xff80_8080,
+            Level::Debug => 0xffff_ff00,
+            Level::Info => DEFAULT_COLOR,
+            Level::Warn => 0xff00_ffff,
+            Level::Error => 0xff40_40ff,
+        }
+    }
+}
+
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(Level::Trace as u8);
+
+/// Drop `log!` messages below `level`. All levels are shown by default.
+pub fn set_min_level(level: Level) {
+    MIN_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Drop all `log!` messages, for `LevelFilter::Off` in `log_compat`.
+#[cfg(feature = "log-compat")]
+pub(crate) fn set_log_off() {
+    MIN_LEVEL.store(Level::Error as u8 + 1, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are shown.
+pub(crate) fn level_enabled(level: Level) -> bool {
+    level as u8 >= MIN_LEVEL.load(Ordering::Relaxed)
+}
+
+#[doc(hidden)]
+pub fn log_args(level: Level, arguments: fmt::Arguments<'_>) {
+    use fmt::Write;
+
+    if !level_enabled(level) {
+        return;
+    }
+
+    if in_interrupt() {
+        let _ = writeln!(Deferred, "{}{}", level.prefix(), arguments);
+        return;
+    }
+
+    ensure_history();
+
+    with_chars(|chars| {
+        if !chars.advance_next && !chars.lines.is_empty() && chars.current_line().len > 0 {
+            chars.add(b'\n');
+        }
+
+        chars.color = level.color();
+        let _ = writeln!(chars, "{}{}", level.prefix(), arguments);
+        chars.color = DEFAULT_COLOR;
+    });
+
+    update();
+}
+
+/// The console, as a `core::fmt::Write` target.
+///
+/// ```no_run
+/// use core::fmt::Write;
+/// let _ = write!(psp::debug::Console, "{:08x}", 0xdead_beefu32);
+/// ```
+///
+/// A `write!` redraws the screen once, after all of its arguments have been
+/// written, like `dprint!`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Console;
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if in_interrupt() {
+            return Deferred.write_str(s);
+        }
+
+        ensure_history();
+        with_chars(|chars| chars.write_str(s))?;
+        update();
+
+        Ok(())
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        print_args(args);
+        Ok(())
+    }
+}
+
+/// Print raw bytes to the screen.
+///
+/// Unlike `dprint!`, this skips `core::fmt` entirely, which makes it cheaper
+/// for printing fixed strings in hot loops. Bytes are drawn as MSX font
+/// characters, no UTF-8 decoding is done.
+pub fn write_bytes(bytes: &[u8]) {
+    if in_interrupt() {
+        bytes.iter().for_each(|&b| defer(b));
+        return;
+    }
+
+    ensure_history();
+    with_chars(|chars| {
+        for &b in bytes {
+            chars.add(b);
+        }
+    });
+
+    update();
+}
+
+/// Size of the queue for output from interrupt handlers, in bytes.
+const DEFERRED_LEN: usize = 1024;
+
+/// Output from interrupt handlers, waiting for the next `update` on a thread.
+///
+/// Interrupt handlers can't safely draw, allocate or wait, so they only
+/// append here. Handlers don't interrupt each other, which leaves a single
+/// writer (whichever handler is running). Threads read in `update`, each
+/// byte taken inside `with_chars`, so no two threads take the same one.
+/// `DEFERRED_HEAD` and `DEFERRED_TAIL` count bytes written and read, the
+/// queue is full when they are `DEFERRED_LEN` apart.
+static mut DEFERRED_BUF: [u8; DEFERRED_LEN] = [0; DEFERRED_LEN];
+static DEFERRED_HEAD: AtomicUsize = AtomicUsize::new(0);
+static DEFERRED_TAIL: AtomicUsize = AtomicUsize::new(0);
+static DROPPED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+fn in_interrupt() -> bool {
+    unsafe { sys::sceKernelIsIntrContext() != 0 }
+}
+
+/// Queue `b` for the next `update`. Dropped if the queue is full.
+fn defer(b: u8) {
+    let head = DEFERRED_HEAD.load(Ordering::Relaxed);
+
+    if head.wrapping_sub(DEFERRED_TAIL.load(Ordering::Acquire)) < DEFERRED_LEN {
+        unsafe { DEFERRED_BUF[head % DEFERRED_LEN] = b };
+        DEFERRED_HEAD.store(head.wrapping_add(1), Ordering::Release);
+    } else {
+        DROPPED_BYTES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Bytes printed from interrupt handlers that were dropped because the queue
+/// was full, since startup.
+///
+/// This is the only way output is lost: printing from a thread always
+/// reaches the history.
+pub fn dropped_bytes() -> usize {
+    DROPPED_BYTES.load(Ordering::Relaxed)
+}
+
+/// Move queued interrupt output into the line buffer.
+fn drain_deferred() {
+    if DEFERRED_HEAD.load(Ordering::Acquire) == DEFERRED_TAIL.load(Ordering::Relaxed) {
+        return;
+    }
+
+    ensure_history();
+
+    // A byte at a time, to keep interrupts suspended only briefly.
+    loop {
+        let drained = with_chars(|chars| {
+            let tail = DEFERRED_TAIL.load(Ordering::Relaxed);
+            if tail == DEFERRED_HEAD.load(Ordering::Acquire) {
+                return true;
+            }
+
+            chars.add(unsafe { DEFERRED_BUF[tail % DEFERRED_LEN] });
+            DEFERRED_TAIL.store(tail.wrapping_add(1), Ordering::Release);
+            false
+        });
+
+        if drained {
+            break;
+        }
+    }
+}
+
+/// Writes into the interrupt output queue.
+struct Deferred;
+
+impl fmt::Write for Deferred {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.chars().for_each(|c| defer(to_byte(c)));
+        Ok(())
+    }
+}
+
 // TODO: Move to font.
-const ROWS: usize = DISPLAY_HEIGHT / MsxFont::CHAR_HEIGHT;
-const COLS: usize = DISPLAY_WIDTH / MsxFont::CHAR_WIDTH;
+/// Capacity of the line buffer. The rows and columns actually used depend on
+/// the configured display size.
+const MAX_ROWS: usize = MAX_DISPLAY_HEIGHT / MSX.char_height;
+const MAX_COLS: usize = MAX_DISPLAY_WIDTH / MSX.char_width;
+
+/// Default number of lines kept for scrolling back, including the visible
+/// ones.
+pub const DEFAULT_SCROLLBACK_LINES: usize = 256;
+
+/// Upper limit for `set_scrollback_lines`.
+pub const MAX_SCROLLBACK_LINES: usize = 4096;
+
+/// Set how many lines of output are kept for scrolling back, including the
+/// visible ones. Returns the number actually used, after clamping to
+/// `MAX_ROWS..=MAX_SCROLLBACK_LINES`.
+///
+/// Each line costs `MAX_COLS` (120) bytes for its characters, plus a `usize`
+/// each for its length and width and a `u32` for its color: 132 bytes in
+/// total, or 33 KiB for the default 256 lines. The history is allocated on
+/// the heap the first time the console is used, and reallocated by this
+/// function. The most recent lines are kept.
+pub fn set_scrollback_lines(n: usize) -> usize {
+    let n = n.clamp(MAX_ROWS, MAX_SCROLLBACK_LINES);
+
+    // Allocate outside of the lock, interrupts are disabled while it is held.
+    let mut lines = vec![Line::new(); n];
+
+    with_chars(|chars| {
+        chars.capacity = n;
+
+        if !chars.lines.is_empty() {
+            chars.move_to(&mut lines);
+        }
+
+        mem::swap(&mut chars.lines, &mut lines);
+    });
+
+    // `lines` is now the old history, freed here rather than under the lock.
+    drop(lines);
+    n
+}
+
+/// The number of lines kept for scrolling back, see `set_scrollback_lines`.
+pub fn scrollback_lines() -> usize {
+    with_chars(|chars| chars.capacity)
+}
+
+/// Allocate the history if it hasn't been yet.
+fn ensure_history() {
+    let capacity = with_chars(|chars| chars.lines.is_empty().then_some(chars.capacity));
+
+    if let Some(capacity) = capacity {
+        let mut lines = vec![Line::new(); capacity];
+
+        with_chars(|chars| {
+            // Unless another thread got here first.
+            if chars.lines.is_empty() {
+                mem::swap(&mut chars.lines, &mut lines);
+            }
+        });
+    }
+}
+
+/// Scroll the console by `delta` lines: negative values go back through
+/// older output, positive values towards the newest.
+///
+/// The view is kept between the oldest line still in the history and the
+/// bottom. While scrolled up, new output does not move the view.
+pub fn scroll(delta: isize) {
+    ensure_history();
+    with_chars(|chars| chars.scroll(delta));
+    update();
+}
+
+/// Scroll back down to the newest output.
+pub fn scroll_to_bottom() {
+    with_chars(|chars| chars.scroll = 0);
+    update();
+}
+
+/// Mark lines broken because they were too long: `marker` is drawn in the
+/// last column of the broken line, and the first column of its continuation.
+///
+/// Off (`None`) by default. The marker takes up a column on both lines, so
+/// wrapped lines hold one character less. Only affects output from now on.
+pub fn set_wrap_marker(marker: Option<u8>) {
+    with_chars(|chars| chars.wrap_marker = marker);
+}
+
+/// The wrap marker, see `set_wrap_marker`.
+pub fn wrap_marker() -> Option<u8> {
+    with_chars(|chars| chars.wrap_marker)
+}
+
+/// How many lines the console is scrolled up from the bottom.
+pub fn scroll_offset() -> usize {
+    with_chars(|chars| chars.scroll)
+}
+
+/// Write `s` over what is on screen, starting at `col` in visible `row`,
+/// without moving the cursor or scrolling.
+///
+/// Rows count from the topmost line shown, which with a bottom anchor isn't
+/// the top of the screen until the lines fill it, and follow the view when
+/// it is scrolled. Text past the end of a line is cut off, and rows without
+/// a line yet are left alone.
+pub fn print_at(row: usize, col: usize, s: &str) {
+    ensure_history();
+    with_chars(|chars| {
+        if let Some(index) = chars.visible_index(row) {
+            chars.put(index, col, s.chars().map(to_byte));
+        }
+    });
+
+    update();
+}
+
+// Box-drawing characters of the default font, which are control
+// characters in ASCII. Fonts from `load_font` or `load_proportional_font`
+// may draw anything for them.
+
+/// `─`, a line across the middle of the cell.
+pub const BOX_HORIZONTAL: u8 = 0x17;
+/// `│`, a line down the middle of the cell.
+pub const BOX_VERTICAL: u8 = 0x16;
+/// `┌`
+pub const BOX_TOP_LEFT: u8 = 0x18;
+/// `┐`
+pub const BOX_TOP_RIGHT: u8 = 0x19;
+/// `└`
+pub const BOX_BOTTOM_LEFT: u8 = 0x1a;
+/// `┘`
+pub const BOX_BOTTOM_RIGHT: u8 = 0x1b;
+/// `├`
+pub const BOX_TEE_RIGHT: u8 = 0x14;
+/// `┤`
+pub const BOX_TEE_LEFT: u8 = 0x13;
+/// `┬`
+pub const BOX_TEE_DOWN: u8 = 0x12;
+/// `┴`
+pub const BOX_TEE_UP: u8 = 0x11;
+/// `┼`
+pub const BOX_CROSS: u8 = 0x15;
+
+/// Draw the frame of a box `w` columns wide and `h` rows high, with its
+/// top left corner at `col` in visible `row`, using `print_at`.
+///
+/// ```no_run
+/// use psp::debug;
+///
+/// // Lines for the box to go on.
+/// for _ in 0..4 {
+///     psp::dprintln!();
+/// }
+///
+/// debug::draw_box(2, 0, 20, 4);
+/// debug::print_at(1, 4, "Score: 100");
+/// debug::print_at(2, 4, "Lives: 3");
+/// ```
+///
+/// Only the frame is drawn, so text already inside stays. As with
+/// `print_at`, rows without a line yet are left alone, so print the lines
+/// first. Boxes narrower or lower than 2 aren't drawn.
+///
+/// With a monospaced font, such as the default one, the top and bottom
+/// edges join up. The sides don't quite: the default font's glyphs are 8
+/// pixels high, in rows 10 pixels apart, so there is a 2 pixel gap between
+/// each row's piece of a side.
+pub fn draw_box(col: usize, row: usize, w: usize, h: usize) {
+    if w < 2 || h < 2 {
+        return;
+    }
+
+    let edge = |left: u8, right: u8| -> String {
+        let middle = (0..w - 2).map(|_| BOX_HORIZONTAL);
+        core::iter::once(left)
+            .chain(middle)
+            .chain(core::iter::once(right))
+            .map(char::from)
+            .collect()
+    };
+    let mut buf = [0; 4];
+    let vertical = char::from(BOX_VERTICAL).encode_utf8(&mut buf);
+
+    batch(|| {
+        print_at(row, col, &edge(BOX_TOP_LEFT, BOX_TOP_RIGHT));
+
+        for side_row in row + 1..row + h - 1 {
+            print_at(side_row, col, vertical);
+            print_at(side_row, col + w - 1, vertical);
+        }
+
+        print_at(row + h - 1, col, &edge(BOX_BOTTOM_LEFT, BOX_BOTTOM_RIGHT));
+    });
+}
+
+/// Show a progress bar like `label [####......]  40%`, filling a line of its
+/// own.
+///
+/// Repeated calls redraw the same line in place, until something else is
+/// printed. `fraction` is clamped to `0.0..=1.0`, and the bar takes up
+/// whatever width the label leaves.
+pub fn progress(label: &str, fraction: f32) {
+    ensure_history();
+    with_chars(|chars| {
+        if let Some(index) = chars.reserve_progress_line() {
+            // As many columns as fit at the widest character.
+            let cols = chars.cols.min(chars.width / chars.font.char_width);
+            let bar = progress_bar(label, fraction, cols);
+            chars.put(index, 0, bar.chars[..bar.len].iter().copied());
+        }
+    });
+
+    update();
+}
+
+fn progress_bar(label: &str, fraction: f32, cols: usize) -> Line {
+    // `clamp` keeps NaN, which is taken as 0.
+    let fraction = if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    };
+    let label_len = label.chars().count();
+
+    // "label " + "[" + bar + "] " + "100%"
+    let used = if label_len > 0 { label_len + 1 } else { 0 } + 7;
+    let width = cols.saturating_sub(used);
+    let filled = (fraction * width as f32) as usize;
+    let percent = (fraction * 100.0) as usize;
+
+    let mut line = Line::new();
+    let mut push = |c: u8| {
+        if line.len < cols {
+            line.chars[line.len] = c;
+            line.len += 1;
+        }
+    };
+
+    if label_len > 0 {
+        label.chars().map(to_byte).for_each(&mut push);
+        push(b' ');
+    }
+
+    push(b'[');
+    (0..width).for_each(|i| push(if i < filled { b'#' } else { b'.' }));
+    push(b']');
+    push(b' ');
+
+    for &place in &[100, 10, 1] {
+        push(if percent >= place || place == 1 {
+            b'0' + (percent / place % 10) as u8
+        } else {
+            b' '
+        });
+    }
+    push(b'%');
+
+    line
+}
+
+/// The MSX font character for `c`, which covers Latin-1.
+fn to_byte(c: char) -> u8 {
+    match c as u32 {
+        0..=255 => c as u8,
+        _ => 0,
+    }
+}
 
 #[derive(Copy, Clone)]
 struct Line {
-    chars: [u8; COLS],
+    chars: [u8; MAX_COLS],
     len: usize,
+    /// Width of the characters, in pixels.
+    width: usize,
+    color: u32,
 }
 
 impl Line {
     const fn new() -> Self {
         Self {
-            chars: [0; COLS],
+            chars: [0; MAX_COLS],
             len: 0,
+            width: 0,
+            color: DEFAULT_COLOR,
         }
     }
 }
 
+/// A ring of the most recent lines.
 struct CharBuffer {
-    lines: [Line; ROWS],
+    /// Empty until allocated by `ensure_history`.
+    lines: Vec<Line>,
+    /// Length `lines` has once allocated.
+    capacity: usize,
+    font: Font,
+    rows: usize,
+    /// The most characters that fit on a line.
+    cols: usize,
+    /// Width of a line, in pixels.
+    width: usize,
+    /// Index of the line being written to, counting from the very first
+    /// until the ring is full. After that it is kept below twice the ring's
+    /// length, at the same position in the ring, so it can't overflow.
     written: usize,
+    /// Lines scrolled up from the bottom.
+    scroll: usize,
     advance_next: bool,
+    /// Number of nested `batch` calls running.
+    batch_depth: usize,
+    /// Whether an update was held back by a batch.
+    dirty: bool,
+    /// The line `progress` draws on, while nothing else has been printed
+    /// after it.
+    progress_line: Option<usize>,
+    cursor_visible: bool,
+    wrap_marker: Option<u8>,
+    /// Color for new lines.
+    color: u32,
 }
 
 impl CharBuffer {
     const fn new() -> Self {
         Self {
-            lines: [Line::new(); ROWS],
+            lines: Vec::new(),
+            capacity: DEFAULT_SCROLLBACK_LINES,
+            font: MSX,
+            rows: Config::DEFAULT.display_height / MSX.char_height,
+            cols: Config::DEFAULT.display_width / MSX.char_width,
+            width: Config::DEFAULT.display_width,
             written: 0,
+            scroll: 0,
             advance_next: false,
+            batch_depth: 0,
+            dirty: false,
+            progress_line: None,
+            cursor_visible: false,
+            wrap_marker: None,
+            color: DEFAULT_COLOR,
         }
     }
 
+    fn is_empty(&self) -> bool {
+        self.written == 0 && self.lines.first().map_or(0, |l| l.len) == 0 && !self.advance_next
+    }
+
+    /// Lay out lines for the display in `config`, in `font`. Only valid
+    /// while empty.
+    fn set_layout(&mut self, config: Config, font: Font) {
+        self.font = font;
+        self.rows = (config.display_height / font.char_height).min(MAX_ROWS);
+        self.cols = (config.display_width / font.narrowest).min(MAX_COLS);
+        self.width = config.display_width;
+    }
+
+    /// Number of lines in the history, including the current one.
+    fn len(&self) -> usize {
+        core::cmp::min(self.written + 1, self.lines.len())
+    }
+
+    /// Copy as many of the most recent lines as fit into `lines`, and make it
+    /// the start of a new ring.
+    fn move_to(&mut self, lines: &mut [Line]) {
+        let skip = self.len().saturating_sub(lines.len());
+        let mut copied = 0;
+
+        for (dst, src) in lines.iter_mut().zip(self.lines().skip(skip)) {
+            *dst = src;
+            copied += 1;
+        }
+
+        self.written = copied - 1;
+        self.scroll = core::cmp::min(self.scroll, copied.saturating_sub(self.rows));
+        self.progress_line = None;
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.len().saturating_sub(self.rows)
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let scroll = self.scroll as isize - delta;
+        self.scroll = core::cmp::min(scroll.max(0) as usize, self.max_scroll());
+    }
+
     fn advance(&mut self) {
         self.written += 1;
-        if self.written >= ROWS {
-            *self.current_line() = Line::new();
+        self.rebase();
+        *self.current_line() = Line::new();
+
+        // Keep the same lines in view if the user has scrolled up.
+        if self.scroll > 0 {
+            self.scroll = core::cmp::min(self.scroll + 1, self.max_scroll());
+        }
+    }
+
+    /// Bring `written` back below twice the ring's length, once the ring
+    /// has gone around twice. Indices keep their place in the ring.
+    fn rebase(&mut self) {
+        let len = self.lines.len();
+
+        if self.written >= 2 * len {
+            let shift = self.written - (len + self.written % len);
+            self.written -= shift;
+            self.progress_line = self.progress_line.and_then(|i| i.checked_sub(shift));
         }
     }
 
     fn current_line(&mut self) -> &mut Line {
-        &mut self.lines[self.written % ROWS]
+        let len = self.lines.len();
+        &mut self.lines[self.written % len]
+    }
+
+    /// The screen row the next character goes to, and how many pixels into
+    /// it, if that is on screen.
+    fn cursor(&self) -> Option<(usize, usize)> {
+        if self.lines.is_empty() || self.scroll > 0 {
+            return None;
+        }
+
+        // The current line is the last one shown.
+        let mut row = self.len().min(self.rows) - 1;
+        let line = &self.lines[self.written % self.lines.len()];
+        let mut x = line.width;
+
+        // The next character starts a new line, if even the narrowest
+        // doesn't fit.
+        if self.advance_next || line.len == self.cols || x + self.font.narrowest > self.width {
+            row += 1;
+            x = 0;
+        }
+
+        (row < self.rows).then_some((row, x))
+    }
+
+    /// The index of visible `row`, counting from the very first line, if
+    /// there is a line there.
+    fn visible_index(&self, row: usize) -> Option<usize> {
+        let visible = self.visible();
+        let oldest = self.written + 1 - self.len();
+
+        (row < visible.end - visible.pos).then(|| oldest + visible.pos + row)
+    }
+
+    /// Overwrite line `index` (counting from the very first) from `col` on,
+    /// padding it with spaces if it is shorter. Lines no longer in the
+    /// history are left alone.
+    fn put<I: IntoIterator<Item = u8>>(&mut self, index: usize, col: usize, bytes: I) {
+        if self.lines.is_empty() || index > self.written || self.written - index >= self.len() {
+            return;
+        }
+
+        let (cols, font) = (self.cols, self.font);
+        let len = self.lines.len();
+        let line = &mut self.lines[index % len];
+
+        for (c, b) in (col..cols).zip(bytes) {
+            while line.len < c {
+                line.chars[line.len] = b' ';
+                line.len += 1;
+            }
+
+            line.chars[c] = b;
+            line.len = line.len.max(c + 1);
+        }
+
+        line.width = font.text_width(&line.chars[..line.len]);
+    }
+
+    /// Start a fresh line for `progress`, unless it already has one, and
+    /// return its index. The line is cleared.
+    fn reserve_progress_line(&mut self) -> Option<usize> {
+        if self.lines.is_empty() {
+            return None;
+        }
+
+        let reuse = self.advance_next && self.progress_line == Some(self.written);
+        if !reuse && (self.advance_next || self.current_line().len > 0) {
+            self.advance_next = false;
+            self.advance();
+        }
+
+        *self.current_line() = Line::new();
+        self.progress_line = Some(self.written);
+
+        // Anything printed next goes on a line of its own.
+        self.advance_next = true;
+
+        Some(self.written)
     }
 
     fn add(&mut self, c: u8) {
+        // Output before the history exists, e.g. from an interrupt handler,
+        // is dropped.
+        if self.lines.is_empty() {
+            return;
+        }
+
         if self.advance_next {
             self.advance_next = false;
             self.advance();
@@ -193,54 +1538,97 @@ impl CharBuffer {
             }
 
             _ => {
-                if self.current_line().len == COLS {
-                    self.advance();
-                }
+                // Keep room at the end for the marker.
+                let marker = self.wrap_marker.filter(|_| self.cols >= 3);
+                let limit = self.cols - marker.is_some() as usize;
+                let room = self
+                    .width
+                    .saturating_sub(marker.map_or(0, |m| self.font.advance(m)));
+                let advance = self.font.advance(c);
 
                 let line = self.current_line();
-                line.chars[line.len] = c;
-                line.len += 1;
+                if line.len >= limit || (line.len > 0 && line.width + advance > room) {
+                    self.wrap(marker);
+                }
+
+                self.push(c);
             }
         }
     }
 
+    /// Continue on a new line, marking both ends of the break with `marker`.
+    fn wrap(&mut self, marker: Option<u8>) {
+        if let Some(m) = marker {
+            self.push(m);
+            self.advance();
+            self.push(m);
+        } else {
+            self.advance();
+        }
+    }
+
+    fn push(&mut self, c: u8) {
+        let (color, advance) = (self.color, self.font.advance(c));
+        let line = self.current_line();
+
+        // A line takes the color it was started with.
+        if line.len == 0 {
+            line.color = color;
+        }
+
+        line.chars[line.len] = c;
+        line.len += 1;
+        line.width += advance;
+    }
+
+    /// All lines in the history, oldest first.
     fn lines(&self) -> LineIter<'_> {
-        LineIter { buf: self, pos: 0 }
+        LineIter {
+            buf: self,
+            pos: 0,
+            end: self.len(),
+        }
+    }
+
+    /// The lines currently on screen, top first.
+    fn visible(&self) -> LineIter<'_> {
+        let end = self.len() - self.scroll;
+
+        LineIter {
+            buf: self,
+            pos: end.saturating_sub(self.rows),
+            end,
+        }
     }
 }
 
+/// Never fails. Characters outside Latin-1 are written as blanks, and long
+/// lines wrap.
 impl fmt::Write for CharBuffer {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        unsafe {
-            for c in s.chars() {
-                match c as u32 {
-                    0..=255 => CHARS.add(c as u8),
-                    _ => CHARS.add(0),
-                }
-            }
+        for c in s.chars() {
+            self.add(to_byte(c));
         }
 
         Ok(())
     }
 }
 
+/// Iterates over a range of the history, `pos` and `end` counting from the
+/// oldest line.
 struct LineIter<'a> {
     buf: &'a CharBuffer,
     pos: usize,
+    end: usize,
 }
 
 impl<'a> Iterator for LineIter<'a> {
     type Item = Line;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos < core::cmp::min(self.buf.written + 1, ROWS) {
-            let idx = if self.buf.written > ROWS {
-                (self.buf.written + 1 + self.pos) % ROWS
-            } else {
-                self.pos
-            };
-
-            let line = self.buf.lines[idx];
+        if self.pos < self.end {
+            let oldest = self.buf.written + 1 - self.buf.len();
+            let line = self.buf.lines[(oldest + self.pos) % self.buf.lines.len()];
             self.pos += 1;
             Some(line)
         } else {
@@ -0,0 +1,173 @@
+//! The displayed framebuffer.
+
+use crate::sys::{self, DisplayPixelFormat, DisplaySetBufSync, IoOpenFlags, ThreadAttributes};
+use crate::{screenshot_argb_be, SCREEN_HEIGHT, SCREEN_WIDTH};
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::{mem, ptr};
+
+const TGA_HEADER_LEN: usize = 18;
+const TGA_TRUE_COLOR: u8 = 2;
+
+/// Above the usual 32 of the main thread, so the callback runs as soon as
+/// vblank starts.
+const VBLANK_THREAD_PRIORITY: i32 = 0x10;
+
+/// The `fn()` set by `set_vblank_callback`, or 0 for none.
+static VBLANK_CALLBACK: AtomicUsize = AtomicUsize::new(0);
+static VBLANK_THREAD_STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureError {
+    /// The path contains a NUL byte.
+    InvalidPath,
+    /// The file could not be opened, with the error code from `sceIoOpen`.
+    Open(i32),
+    /// Writing the file failed, with the error code from `sceIoWrite`, or 0
+    /// if it was cut short.
+    Write(i32),
+}
+
+/// The framebuffer set with `sceDisplaySetFrameBuf`, as its address, its
+/// stride in pixels, and its pixel format.
+///
+/// The address is null if no framebuffer has been set. It is as it was
+/// passed in, so reading through it goes through the data cache, which may
+/// not hold what the GE last drew.
+///
+/// If the buffer was set with `DisplaySetBufSync::NextFrame` and the frame
+/// hasn't ended yet, this may return that pending buffer, rather than the
+/// one still on screen.
+pub fn get_frame_buf() -> (*mut u8, usize, DisplayPixelFormat) {
+    let mut top_addr: *mut c_void = ptr::null_mut();
+    let mut buffer_width = 0;
+    let mut pixel_format = DisplayPixelFormat::Psm8888;
+
+    unsafe {
+        sys::sceDisplayGetFrameBuf(
+            &mut top_addr,
+            &mut buffer_width,
+            &mut pixel_format,
+            DisplaySetBufSync::Immediate,
+        );
+    }
+
+    (top_addr as *mut u8, buffer_width, pixel_format)
+}
+
+/// Call `f` at the start of every vblank, replacing any previous callback.
+///
+/// `f` runs on a thread of its own that waits for each vblank, rather than
+/// in the interrupt handler, so it can do anything a thread can, including
+/// printing and redrawing the debug console:
+///
+/// ```no_run
+/// psp::debug::set_cursor_visible(true);
+/// psp::display::set_vblank_callback(psp::debug::update_cursor);
+/// ```
+///
+/// It should still be quick: a vblank that starts while `f` runs is
+/// skipped.
+pub fn set_vblank_callback(f: fn()) {
+    VBLANK_CALLBACK.store(f as usize, Ordering::Release);
+
+    if VBLANK_THREAD_STARTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    unsafe {
+        let id = sys::sceKernelCreateThread(
+            b"vblank_callback\0".as_ptr(),
+            vblank_thread,
+            VBLANK_THREAD_PRIORITY,
+            32 * 1024,
+            ThreadAttributes::USER | ThreadAttributes::VFPU,
+            ptr::null_mut(),
+        );
+
+        sys::sceKernelStartThread(id, 0, ptr::null_mut());
+    }
+}
+
+/// Stop calling the callback set by `set_vblank_callback`. It may still run
+/// once more if it is running already.
+pub fn clear_vblank_callback() {
+    VBLANK_CALLBACK.store(0, Ordering::Release);
+}
+
+unsafe extern "C" fn vblank_thread(_args: usize, _argp: *mut c_void) -> i32 {
+    loop {
+        sys::sceDisplayWaitVblankStart();
+
+        match VBLANK_CALLBACK.load(Ordering::Acquire) {
+            0 => {}
+            f => mem::transmute::<usize, fn()>(f)(),
+        }
+    }
+}
+
+/// Save the displayed framebuffer to `path`, such as
+/// `"ms0:/PSP/PHOTO/capture.tga"`, as an uncompressed 24-bit TGA file.
+///
+/// This reads whichever buffer is being presented, so it includes everything
+/// on screen, such as the debug console. Any pixel format and stride set with
+/// `sceDisplaySetFrameBuf` is handled, and alpha is dropped.
+pub fn capture(path: &str) -> Result<(), CaptureError> {
+    if path.contains('\0') {
+        return Err(CaptureError::InvalidPath);
+    }
+
+    let mut c_path = Vec::with_capacity(path.len() + 1);
+    c_path.extend_from_slice(path.as_bytes());
+    c_path.push(0);
+
+    // Bottom row first, which is also TGA's default order.
+    let pixels = screenshot_argb_be();
+
+    let fd = unsafe {
+        sys::sceIoOpen(
+            c_path.as_ptr(),
+            IoOpenFlags::WR_ONLY | IoOpenFlags::CREAT | IoOpenFlags::TRUNC,
+            0o777,
+        )
+    };
+
+    if fd.0 < 0 {
+        return Err(CaptureError::Open(fd.0));
+    }
+
+    let write = |bytes: &[u8]| {
+        let written = unsafe { sys::sceIoWrite(fd, bytes.as_ptr() as *const c_void, bytes.len()) };
+
+        match written {
+            n if n < 0 => Err(CaptureError::Write(n)),
+            n if n as usize != bytes.len() => Err(CaptureError::Write(0)),
+            _ => Ok(()),
+        }
+    };
+
+    let mut header = [0; TGA_HEADER_LEN];
+    header[2] = TGA_TRUE_COLOR;
+    header[12..14].copy_from_slice(&(SCREEN_WIDTH as u16).to_le_bytes());
+    header[14..16].copy_from_slice(&(SCREEN_HEIGHT as u16).to_le_bytes());
+    header[16] = 24;
+
+    // Converted and written a row at a time.
+    let mut row = Vec::with_capacity(SCREEN_WIDTH as usize * 3);
+    let result = write(&header).and_then(|()| {
+        pixels.chunks(SCREEN_WIDTH as usize).try_for_each(|pixels| {
+            row.clear();
+
+            // 0xAARRGGBB is stored as B, G, R, A.
+            for pixel in pixels {
+                row.extend_from_slice(&pixel.to_le_bytes()[..3]);
+            }
+
+            write(&row)
+        })
+    });
+
+    unsafe { sys::sceIoClose(fd) };
+    result
+}
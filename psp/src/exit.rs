@@ -0,0 +1,151 @@
+//! Exiting, through the Home menu or `exit_game`.
+//!
+//! The kernel only shows the Home menu's "Exit" option, and only exits when
+//! it is chosen, if the program registered an exit callback from a thread
+//! that waits for callbacks. Without one, the program hangs when Home is
+//! pressed. `install` sets that up. `module!` calls it before `psp_main` for
+//! user mode modules, so the usual start of a program is:
+//!
+//! ```no_run
+//! #![no_std]
+//! #![no_main]
+//!
+//! psp::module!("my_game", 1, 1);
+//!
+//! fn psp_main() {
+//!     psp::exit::on_exit(|| psp::dprintln!("saving..."));
+//!
+//!     // ...
+//!
+//!     psp::exit::exit_game();
+//! }
+//! ```
+//!
+//! Hooks registered with `on_exit` run in the exit callback's thread, in the
+//! order they were registered, before the program is terminated with
+//! `sceKernelExitGame`. Other threads keep running meanwhile.
+
+use crate::critical;
+use crate::sys::{self, ThreadAttributes};
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::panic::AssertUnwindSafe;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Only accessed through `with_hooks`.
+static mut HOOKS: Hooks = Hooks {
+    head: ptr::null_mut(),
+    tail: ptr::null_mut(),
+};
+
+/// Registered hooks, as a list in registration order.
+struct Hooks {
+    head: *mut Hook,
+    tail: *mut Hook,
+}
+
+struct Hook {
+    f: Box<dyn FnOnce() + Send>,
+    next: *mut Hook,
+}
+
+/// Start the thread handling the Home menu's exit request.
+///
+/// Only the first call does anything, so this is safe to call from several
+/// places, and after `enable_home_button`.
+pub fn install() {
+    if INSTALLED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    unsafe {
+        let id = sys::sceKernelCreateThread(
+            &b"exit_thread\0"[0],
+            exit_thread,
+            32,
+            // Enough for the hooks.
+            64 * 1024,
+            ThreadAttributes::USER | ThreadAttributes::VFPU,
+            ptr::null_mut(),
+        );
+
+        sys::sceKernelStartThread(id, 0, ptr::null_mut());
+    }
+}
+
+/// Register `f` to run when the user exits through the Home menu, such as
+/// to flush save files or stop audio.
+///
+/// Hooks run in registration order. A hook that panics is skipped, and the
+/// rest still run. Hooks only run on Home if `install` has been called, and
+/// always on `exit_game`.
+pub fn on_exit<F: FnOnce() + Send + 'static>(f: F) {
+    // Allocated before suspending interrupts.
+    let hook = Box::into_raw(Box::new(Hook {
+        f: Box::new(f),
+        next: ptr::null_mut(),
+    }));
+
+    with_hooks(|hooks| {
+        match unsafe { hooks.tail.as_mut() } {
+            Some(tail) => tail.next = hook,
+            None => hooks.head = hook,
+        }
+
+        hooks.tail = hook;
+    });
+}
+
+/// Return to the XMB, after running the hooks registered with `on_exit`.
+///
+/// This works whether or not `install` has been called. Hooks run on the
+/// calling thread.
+pub fn exit_game() -> ! {
+    run_hooks();
+
+    unsafe { sys::sceKernelExitGame() };
+
+    // The kernel doesn't return from `sceKernelExitGame`.
+    loop {
+        unsafe { sys::sceKernelSleepThread() };
+    }
+}
+
+/// Run `f` with the hook list, with interrupts suspended.
+fn with_hooks<R, F: FnOnce(&mut Hooks) -> R>(f: F) -> R {
+    critical(|| f(unsafe { &mut *ptr::addr_of_mut!(HOOKS) }))
+}
+
+/// Run and free the registered hooks.
+fn run_hooks() {
+    let mut next = with_hooks(|hooks| {
+        hooks.tail = ptr::null_mut();
+        core::mem::replace(&mut hooks.head, ptr::null_mut())
+    });
+
+    while !next.is_null() {
+        let hook = unsafe { Box::from_raw(next) };
+        next = hook.next;
+
+        let _ = crate::catch_unwind(AssertUnwindSafe(hook.f));
+    }
+}
+
+unsafe extern "C" fn exit_thread(_args: usize, _argp: *mut c_void) -> i32 {
+    let id = sys::sceKernelCreateCallback(&b"exit_callback\0"[0], exit_callback, ptr::null_mut());
+
+    sys::sceKernelRegisterExitCallback(id);
+    sys::sceKernelSleepThreadCB();
+
+    0
+}
+
+unsafe extern "C" fn exit_callback(_arg1: i32, _arg2: i32, _arg: *mut c_void) -> i32 {
+    run_hooks();
+    sys::sceKernelExitGame();
+
+    0
+}
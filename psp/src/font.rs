@@ -0,0 +1,306 @@
+//! Bitmap font rendering with the GU.
+//!
+//! Fonts are loaded from the binary `.fnt` format written by AngelCode's
+//! BMFont (and compatible tools), along with their page texture.
+
+use crate::gu::{Frame, SpriteVertex};
+use crate::image::Texture;
+use crate::sys::GuPrimitive;
+use alloc::vec::Vec;
+
+/// Character id BMFont uses for the "invalid char" glyph, if it was exported.
+const MISSING_GLYPH_ID: u32 = 0xffff_ffff;
+
+const BLOCK_INFO: u8 = 1;
+const BLOCK_COMMON: u8 = 2;
+const BLOCK_PAGES: u8 = 3;
+const BLOCK_CHARS: u8 = 4;
+const BLOCK_KERNING: u8 = 5;
+
+const CHAR_LEN: usize = 20;
+const KERNING_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmFontError {
+    /// The file ended in the middle of a block.
+    Truncated,
+    /// The file does not start with the `BMF` magic, it may be a text or XML
+    /// font description.
+    BadMagic,
+    /// Only version 3 of the binary format is supported.
+    UnsupportedVersion(u8),
+    /// A required block (common or chars) is missing.
+    MissingBlock(u8),
+    /// The font uses more than one page texture, which is not supported.
+    MultiplePages(u16),
+    /// The page texture is not the size the font was generated for.
+    PageSizeMismatch,
+}
+
+/// A glyph's location in the page texture, and how to place it.
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub id: u32,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub x_offset: i16,
+    pub y_offset: i16,
+    pub x_advance: i16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KerningPair {
+    first: u32,
+    second: u32,
+    amount: i16,
+}
+
+/// A bitmap font with a single page texture.
+pub struct BmFont {
+    line_height: u16,
+    base: u16,
+    /// Sorted by id.
+    glyphs: Vec<Glyph>,
+    /// Sorted by (first, second).
+    kerning: Vec<KerningPair>,
+    missing: Option<Glyph>,
+    page: Texture,
+}
+
+impl BmFont {
+    /// Parse a binary `.fnt` file, using `page` as its page texture.
+    ///
+    /// The page is usually loaded with one of the `psp::image` loaders.
+    pub fn load(fnt: &[u8], page: Texture) -> Result<Self, BmFontError> {
+        if fnt.len() < 4 {
+            return Err(BmFontError::Truncated);
+        }
+
+        if &fnt[0..3] != b"BMF" {
+            return Err(BmFontError::BadMagic);
+        }
+
+        if fnt[3] != 3 {
+            return Err(BmFontError::UnsupportedVersion(fnt[3]));
+        }
+
+        let mut common = None;
+        let mut glyphs = Vec::new();
+        let mut kerning = Vec::new();
+
+        let mut pos = 4;
+        while pos < fnt.len() {
+            let header = fnt.get(pos..pos + 5).ok_or(BmFontError::Truncated)?;
+            let ty = header[0];
+            let len = read_u32(header, 1) as usize;
+            pos += 5;
+
+            let block = fnt.get(pos..pos + len).ok_or(BmFontError::Truncated)?;
+            pos += len;
+
+            match ty {
+                BLOCK_COMMON => {
+                    if block.len() < 10 {
+                        return Err(BmFontError::Truncated);
+                    }
+
+                    let pages = read_u16(block, 8);
+                    if pages != 1 {
+                        return Err(BmFontError::MultiplePages(pages));
+                    }
+
+                    common = Some((
+                        read_u16(block, 0),
+                        read_u16(block, 2),
+                        read_u16(block, 4),
+                        read_u16(block, 6),
+                    ));
+                }
+
+                BLOCK_CHARS => {
+                    for c in block.chunks_exact(CHAR_LEN) {
+                        // Byte 18 is the page, which is always 0 here.
+                        if c[18] != 0 {
+                            return Err(BmFontError::MultiplePages(c[18] as u16 + 1));
+                        }
+
+                        glyphs.push(Glyph {
+                            id: read_u32(c, 0),
+                            x: read_u16(c, 4),
+                            y: read_u16(c, 6),
+                            width: read_u16(c, 8),
+                            height: read_u16(c, 10),
+                            x_offset: read_u16(c, 12) as i16,
+                            y_offset: read_u16(c, 14) as i16,
+                            x_advance: read_u16(c, 16) as i16,
+                        });
+                    }
+                }
+
+                BLOCK_KERNING => {
+                    for k in block.chunks_exact(KERNING_LEN) {
+                        kerning.push(KerningPair {
+                            first: read_u32(k, 0),
+                            second: read_u32(k, 4),
+                            amount: read_u16(k, 8) as i16,
+                        });
+                    }
+                }
+
+                // Page file names are irrelevant, the texture is passed in.
+                BLOCK_INFO | BLOCK_PAGES => (),
+
+                // Unknown blocks are skipped for forward compatibility.
+                _ => (),
+            }
+        }
+
+        let (line_height, base, scale_w, scale_h) =
+            common.ok_or(BmFontError::MissingBlock(BLOCK_COMMON))?;
+
+        if glyphs.is_empty() {
+            return Err(BmFontError::MissingBlock(BLOCK_CHARS));
+        }
+
+        if page.width() != scale_w as u32 || page.height() != scale_h as u32 {
+            return Err(BmFontError::PageSizeMismatch);
+        }
+
+        glyphs.sort_unstable_by_key(|g| g.id);
+        kerning.sort_unstable_by_key(|k| (k.first, k.second));
+
+        let missing = glyphs.last().filter(|g| g.id == MISSING_GLYPH_ID).copied();
+
+        Ok(Self {
+            line_height,
+            base,
+            glyphs,
+            kerning,
+            missing,
+            page,
+        })
+    }
+
+    /// Distance between two lines of text, in pixels.
+    pub fn line_height(&self) -> u32 {
+        self.line_height as u32
+    }
+
+    /// Distance from the top of a line to the baseline, in pixels.
+    pub fn base(&self) -> u32 {
+        self.base as u32
+    }
+
+    /// The page texture.
+    pub fn page(&self) -> &Texture {
+        &self.page
+    }
+
+    /// Look up the glyph used to draw `c`.
+    ///
+    /// Characters the font does not contain are drawn with the font's "invalid
+    /// char" glyph if it has one, and are skipped otherwise.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        match self.glyphs.binary_search_by_key(&(c as u32), |g| g.id) {
+            Ok(i) => Some(&self.glyphs[i]),
+            Err(_) => self.missing.as_ref(),
+        }
+    }
+
+    /// Kerning adjustment to apply between `first` and `second`.
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        let key = (first as u32, second as u32);
+
+        match self
+            .kerning
+            .binary_search_by_key(&key, |k| (k.first, k.second))
+        {
+            Ok(i) => self.kerning[i].amount as i32,
+            Err(_) => 0,
+        }
+    }
+
+    /// Width of `s` in pixels, as `draw_text` would draw it.
+    pub fn measure(&self, s: &str) -> i32 {
+        self.layout(s, |_, _| ())
+    }
+
+    /// Walk through `s`, calling `f` with each glyph to draw and its pen
+    /// position. Returns the final pen position.
+    fn layout<F: FnMut(&Glyph, i32)>(&self, s: &str, mut f: F) -> i32 {
+        let mut pen = 0;
+        let mut prev = None;
+
+        for c in s.chars() {
+            let glyph = match self.glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+
+            if let Some(prev) = prev {
+                pen += self.kerning(prev, c);
+            }
+
+            f(glyph, pen);
+
+            pen += glyph.x_advance as i32;
+            prev = Some(c);
+        }
+
+        pen
+    }
+}
+
+/// Draw `s` with its top-left corner at (`x`, `y`), tinted by `color`.
+///
+/// Returns the width of the drawn text in pixels, which can be used to center
+/// or right-align it (see also `BmFont::measure`).
+///
+/// This binds the font's page texture, but leaves blending alone. Enable alpha
+/// blending with `Frame::set_alpha_blend` for anti-aliased glyph edges.
+pub fn draw_text<'a>(
+    frame: &mut Frame<'a>,
+    font: &'a BmFont,
+    x: i32,
+    y: i32,
+    color: u32,
+    s: &str,
+) -> i32 {
+    let count = s
+        .chars()
+        .filter(|c| font.glyph(*c).is_some_and(|g| g.width > 0))
+        .count();
+
+    let mut vertices = Vec::with_capacity(count * 2);
+    let width = font.layout(s, |glyph, pen| {
+        if glyph.width == 0 {
+            return;
+        }
+
+        let x = (x + pen + glyph.x_offset as i32) as i16;
+        let y = (y + glyph.y_offset as i32) as i16;
+        let (u, v) = (glyph.x as i16, glyph.y as i16);
+        let (w, h) = (glyph.width as i16, glyph.height as i16);
+
+        vertices.push(SpriteVertex::new(u, v, color, x, y));
+        vertices.push(SpriteVertex::new(u + w, v + h, color, x + w, y + h));
+    });
+
+    if !vertices.is_empty() {
+        frame.bind_texture(&font.page);
+        let vertices = frame.push_vertices(vertices);
+        frame.draw_array(GuPrimitive::Sprites, vertices);
+    }
+
+    width
+}
+
+fn read_u16(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
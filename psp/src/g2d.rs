@@ -0,0 +1,202 @@
+//! Immediate mode 2D drawing.
+//!
+//! A whole program can be a loop of `g2d` calls:
+//!
+//! ```no_run
+//! loop {
+//!     psp::g2d::begin_frame();
+//!     psp::g2d::rect(10, 10, 100, 50, 0xff00_00ff);
+//!     psp::g2d::end_frame();
+//! }
+//! ```
+//!
+//! Everything is drawn in screen coordinates, with alpha blending enabled.
+//! Draws are batched into sprite primitives, and `end_frame` swaps buffers
+//! on the next vertical blank.
+//!
+//! This is built on [`Gu`], which is initialized on the first `begin_frame`.
+//! An existing `Gu` can be handed over with [`init_with`] instead, and the
+//! current frame can be drawn to directly with [`with_frame`].
+//!
+//! The functions in this module must only be called from one thread.
+
+use crate::gu::{Blend, Frame, Gu, Rect, SpriteVertex};
+use crate::image::Texture;
+use crate::sys::GuPrimitive;
+use alloc::vec::Vec;
+use core::ptr;
+
+static mut GU: Option<Gu> = None;
+static mut FRAME: Option<Frame<'static>> = None;
+static mut CLEAR_COLOR: u32 = 0xff00_0000;
+
+/// Sprites waiting to be drawn, all using `BATCH_TEXTURE`.
+static mut BATCH: Vec<SpriteVertex> = Vec::new();
+static mut BATCH_TEXTURE: Option<&'static Texture> = None;
+
+/// Use `gu` for drawing, instead of initializing a new one.
+///
+/// # Panics
+///
+/// Panics if g2d already has a `Gu`.
+pub fn init_with(gu: Gu) {
+    unsafe {
+        let slot = &mut *ptr::addr_of_mut!(GU);
+        assert!(slot.is_none(), "g2d is already initialized");
+        *slot = Some(gu);
+    }
+}
+
+/// Take back the `Gu` used by g2d, for example to hand it to other code.
+///
+/// Returns `None` if g2d has not been initialized, or while a frame is in
+/// progress.
+pub fn take_gu() -> Option<Gu> {
+    unsafe {
+        if (*ptr::addr_of!(FRAME)).is_some() {
+            return None;
+        }
+
+        (*ptr::addr_of_mut!(GU)).take()
+    }
+}
+
+/// Set the color the screen is cleared to by `begin_frame`.
+pub fn set_clear_color(color: u32) {
+    unsafe { CLEAR_COLOR = color };
+}
+
+/// Start a new frame, clearing the screen.
+///
+/// # Panics
+///
+/// Panics if a frame is already in progress, or if the `Gu` has to be
+/// initialized and the VRAM allocator is already in use.
+pub fn begin_frame() {
+    unsafe {
+        let frame = &mut *ptr::addr_of_mut!(FRAME);
+        assert!(frame.is_none(), "g2d frame already in progress");
+
+        let gu = &mut *ptr::addr_of_mut!(GU);
+        if gu.is_none() {
+            *gu = Some(Gu::init().expect("VRAM allocator already in use"));
+        }
+
+        let mut f = gu.as_mut().unwrap().start_frame();
+        f.clear(CLEAR_COLOR);
+        f.set_blend(Some(Blend::ALPHA));
+        *frame = Some(f);
+    }
+}
+
+/// Draw everything, and show it on the next vertical blank.
+///
+/// # Panics
+///
+/// Panics if no frame is in progress.
+pub fn end_frame() {
+    flush();
+
+    unsafe {
+        let frame = (*ptr::addr_of_mut!(FRAME))
+            .take()
+            .expect("no g2d frame in progress");
+        frame.finish();
+
+        (*ptr::addr_of_mut!(GU))
+            .as_mut()
+            .unwrap()
+            .swap_buffers(true);
+    }
+}
+
+/// Fill a rectangle with `color`.
+pub fn rect(x: i32, y: i32, w: i32, h: i32, color: u32) {
+    push(None, Rect::new(0, 0, 0, 0), Rect::new(x, y, w, h), color);
+}
+
+/// Draw all of `texture` with its top-left corner at (`x`, `y`).
+///
+/// Textures are only read when the frame ends, which is why they must be
+/// `'static`. Textures loaded once at startup can be leaked with `Box::leak`.
+pub fn texture(texture: &'static Texture, x: i32, y: i32) {
+    let (w, h) = (texture.width() as i32, texture.height() as i32);
+    texture_part(texture, Rect::new(0, 0, w, h), Rect::new(x, y, w, h));
+}
+
+/// Draw the `src` part of `texture`, stretched to fill `dst`.
+pub fn texture_part(texture: &'static Texture, src: Rect, dst: Rect) {
+    push(Some(texture), src, dst, 0xffff_ffff);
+}
+
+/// Run `f` with the current frame, to draw with the lower-level `Gu` API.
+///
+/// Everything drawn with g2d so far is drawn first. State changed through
+/// the frame, such as blending, stays changed for the rest of the frame.
+///
+/// # Panics
+///
+/// Panics if no frame is in progress.
+pub fn with_frame<R, F: FnOnce(&mut Frame<'static>) -> R>(f: F) -> R {
+    flush();
+
+    unsafe {
+        let frame = (*ptr::addr_of_mut!(FRAME))
+            .as_mut()
+            .expect("no g2d frame in progress");
+        f(frame)
+    }
+}
+
+fn push(texture: Option<&'static Texture>, src: Rect, dst: Rect, color: u32) {
+    unsafe {
+        let same = match (BATCH_TEXTURE, texture) {
+            (Some(a), Some(b)) => ptr::eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+
+        if !same {
+            flush();
+            BATCH_TEXTURE = texture;
+        }
+
+        let batch = &mut *ptr::addr_of_mut!(BATCH);
+        batch.push(SpriteVertex::new(
+            src.x as i16,
+            src.y as i16,
+            color,
+            dst.x as i16,
+            dst.y as i16,
+        ));
+        batch.push(SpriteVertex::new(
+            (src.x + src.w) as i16,
+            (src.y + src.h) as i16,
+            color,
+            (dst.x + dst.w) as i16,
+            (dst.y + dst.h) as i16,
+        ));
+    }
+}
+
+/// Draw the pending batch.
+fn flush() {
+    unsafe {
+        let batch = &mut *ptr::addr_of_mut!(BATCH);
+        if batch.is_empty() {
+            return;
+        }
+
+        let frame = (*ptr::addr_of_mut!(FRAME))
+            .as_mut()
+            .expect("no g2d frame in progress");
+
+        match BATCH_TEXTURE {
+            Some(texture) => frame.bind_texture(texture),
+            None => frame.unbind_texture(),
+        }
+
+        let vertices = frame.push_vertices(batch.drain(..));
+        frame.draw_array(GuPrimitive::Sprites, vertices);
+    }
+}
@@ -0,0 +1,234 @@
+//! Conversion between the GE's direct color pixel formats.
+//!
+//! Pixels are stored little endian, with red in the lowest bits:
+//!
+//! | Format    | Layout (low to high bits) |
+//! |-----------|---------------------------|
+//! | `Psm8888` | R8 G8 B8 A8               |
+//! | `Psm5650` | R5 G6 B5                  |
+//! | `Psm5551` | R5 G5 B5 A1               |
+//! | `Psm4444` | R4 G4 B4 A4               |
+//!
+//! Channels are expanded by bit replication when converting to a wider
+//! format, so full intensity stays full intensity, and rounded to the nearest
+//! value when converting to a narrower one.
+
+use crate::image::{bytes_per_pixel, Texture};
+use crate::sys::TexturePixelFormat;
+
+/// 4x4 Bayer matrix, for ordered dithering.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Bias added before dividing when quantizing, to round to nearest.
+const ROUND: u32 = 127;
+
+/// A borrowed, unswizzled image in one of the direct color formats.
+#[derive(Clone, Copy)]
+pub struct ImageRef<'a> {
+    pub data: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    /// Length of a row in bytes, including any padding.
+    pub stride: usize,
+    pub format: TexturePixelFormat,
+}
+
+impl<'a> ImageRef<'a> {
+    /// # Panics
+    ///
+    /// Panics if `format` is not a direct color format, or if `data` is too
+    /// short for `height` rows of `stride` bytes.
+    pub fn new(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: TexturePixelFormat,
+    ) -> Self {
+        let bpp = bytes_per_pixel(format).expect("unsupported pixel format");
+        assert!(stride >= width as usize * bpp, "stride shorter than a row");
+        assert!(
+            height == 0 || data.len() >= (height as usize - 1) * stride + width as usize * bpp,
+            "image data too short"
+        );
+
+        Self {
+            data,
+            width,
+            height,
+            stride,
+            format,
+        }
+    }
+
+    /// View the image held by `texture`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture is swizzled.
+    pub fn from_texture(texture: &'a Texture) -> Self {
+        assert!(!texture.is_swizzled(), "cannot convert a swizzled texture");
+
+        Self::new(
+            texture.data(),
+            texture.width(),
+            texture.height(),
+            texture.stride(),
+            texture.format(),
+        )
+    }
+
+    /// Row `y`, without padding.
+    pub fn row(&self, y: u32) -> &'a [u8] {
+        let start = y as usize * self.stride;
+        let len = self.width as usize * bytes_per_pixel(self.format).unwrap();
+        &self.data[start..start + len]
+    }
+}
+
+/// Dithering applied when reducing color depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Round each channel to the nearest value.
+    None,
+    /// Ordered (4x4 Bayer) dithering of the color channels, which avoids
+    /// banding in gradients. Only used when converting from `Psm8888`.
+    Ordered,
+}
+
+/// Convert `src` into a new, unswizzled texture in `dst_format`.
+pub fn convert(src: &ImageRef<'_>, dst_format: TexturePixelFormat) -> Texture {
+    convert_with(src, dst_format, Dither::None)
+}
+
+/// Like `convert`, with the given dithering.
+pub fn convert_with(src: &ImageRef<'_>, dst_format: TexturePixelFormat, dither: Dither) -> Texture {
+    let mut texture = Texture::new(src.width, src.height, dst_format);
+
+    for y in 0..src.height {
+        convert_row(
+            src.row(y),
+            src.format,
+            texture.row_mut(y),
+            dst_format,
+            y,
+            dither,
+        );
+    }
+
+    texture
+}
+
+/// Convert one row of pixels from `src_format` to `dst_format`.
+///
+/// As many pixels as fit in both `src` and `dst` are converted. `y` is the
+/// row's position in the image, used to pick the dither pattern.
+///
+/// # Panics
+///
+/// Panics if either format is not a direct color format.
+pub fn convert_row(
+    src: &[u8],
+    src_format: TexturePixelFormat,
+    dst: &mut [u8],
+    dst_format: TexturePixelFormat,
+    y: u32,
+    dither: Dither,
+) {
+    let src_bpp = bytes_per_pixel(src_format).expect("unsupported source pixel format");
+    let dst_bpp = bytes_per_pixel(dst_format).expect("unsupported destination pixel format");
+
+    let dither = dither == Dither::Ordered && src_format == TexturePixelFormat::Psm8888;
+    let pixels = src.chunks_exact(src_bpp).zip(dst.chunks_exact_mut(dst_bpp));
+
+    for (x, (s, d)) in pixels.enumerate() {
+        let rgba = decode(s, src_format);
+
+        let bias = if dither {
+            let b = BAYER[y as usize % 4][x % 4] as u32;
+            // Spread the thresholds evenly around 127.5.
+            (b * 2 + 1) * 255 / 32
+        } else {
+            ROUND
+        };
+
+        encode(rgba, dst_format, bias, d);
+    }
+}
+
+/// Decode one pixel to 8-bit RGBA.
+fn decode(p: &[u8], format: TexturePixelFormat) -> [u8; 4] {
+    if format == TexturePixelFormat::Psm8888 {
+        return [p[0], p[1], p[2], p[3]];
+    }
+
+    let v = u16::from_le_bytes([p[0], p[1]]) as u32;
+
+    match format {
+        TexturePixelFormat::Psm5650 => [
+            expand(v & 0x1f, 5),
+            expand((v >> 5) & 0x3f, 6),
+            expand((v >> 11) & 0x1f, 5),
+            0xff,
+        ],
+        TexturePixelFormat::Psm5551 => [
+            expand(v & 0x1f, 5),
+            expand((v >> 5) & 0x1f, 5),
+            expand((v >> 10) & 0x1f, 5),
+            expand(v >> 15, 1),
+        ],
+        TexturePixelFormat::Psm4444 => [
+            expand(v & 0xf, 4),
+            expand((v >> 4) & 0xf, 4),
+            expand((v >> 8) & 0xf, 4),
+            expand(v >> 12, 4),
+        ],
+        _ => unreachable!(),
+    }
+}
+
+/// Encode 8-bit RGBA into one pixel. `bias` is added to the color channels
+/// before quantizing, alpha is always rounded.
+fn encode(rgba: [u8; 4], format: TexturePixelFormat, bias: u32, out: &mut [u8]) {
+    let [r, g, b, a] = rgba;
+
+    let v = match format {
+        TexturePixelFormat::Psm8888 => {
+            out.copy_from_slice(&rgba);
+            return;
+        }
+        TexturePixelFormat::Psm5650 => {
+            quantize(r, 5, bias) | quantize(g, 6, bias) << 5 | quantize(b, 5, bias) << 11
+        }
+        TexturePixelFormat::Psm5551 => {
+            quantize(r, 5, bias)
+                | quantize(g, 5, bias) << 5
+                | quantize(b, 5, bias) << 10
+                | quantize(a, 1, ROUND) << 15
+        }
+        TexturePixelFormat::Psm4444 => {
+            quantize(r, 4, bias)
+                | quantize(g, 4, bias) << 4
+                | quantize(b, 4, bias) << 8
+                | quantize(a, 4, ROUND) << 12
+        }
+        _ => unreachable!(),
+    };
+
+    out.copy_from_slice(&(v as u16).to_le_bytes());
+}
+
+/// Widen a `bits` wide channel to 8 bits by replicating its high bits.
+fn expand(v: u32, bits: u32) -> u8 {
+    match bits {
+        1 => (v * 0xff) as u8,
+        _ => ((v << (8 - bits)) | (v >> (2 * bits - 8))) as u8,
+    }
+}
+
+/// Narrow an 8-bit channel to `bits` bits. A `bias` of `ROUND` rounds to
+/// nearest.
+fn quantize(v: u8, bits: u32, bias: u32) -> u32 {
+    let max = (1 << bits) - 1;
+    (v as u32 * max + bias) / 255
+}
@@ -0,0 +1,257 @@
+//! The GE's four hardware lights.
+//!
+//! Lit vertices need a normal, and the GE uses it as given: it doesn't
+//! normalize it, before or after the model matrix. Normals that aren't unit
+//! length make lighting too bright or too dark, so they should be
+//! normalized when the mesh is built, and the model matrix shouldn't scale.
+
+use super::Frame;
+use crate::sys::{self, GuState, LightComponent, LightType, ScePspFVector3};
+
+/// Number of hardware lights.
+pub const MAX_LIGHTS: usize = 4;
+
+/// Returned when a light index is not below `MAX_LIGHTS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidLightIndex(pub usize);
+
+/// How a point or spot light fades with distance `d`:
+/// `1 / (constant + linear * d + quadratic * d²)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// No falloff with distance.
+    pub const NONE: Attenuation = Attenuation {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Light arriving from infinitely far away. `direction` points towards
+    /// the light.
+    Directional { direction: [f32; 3] },
+    /// Light spreading from `position` in all directions.
+    Point {
+        position: [f32; 3],
+        attenuation: Attenuation,
+    },
+    /// A cone of light from `position` towards `direction`.
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        attenuation: Attenuation,
+        /// How sharply the light falls off away from the cone's center.
+        exponent: f32,
+        /// Angle between the cone's center and its edge, in radians.
+        cutoff: f32,
+    },
+}
+
+/// A light's configuration, passed to `Frame::set_light`.
+///
+/// Colors are `0xBBGGRR`, like the material colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub ambient: u32,
+    pub diffuse: u32,
+    /// Specular color. The GE computes either the ambient or the specular
+    /// term of a light: if this is set, `ambient` is unused.
+    pub specular: Option<u32>,
+}
+
+impl Light {
+    pub fn directional(direction: [f32; 3], color: u32) -> Self {
+        Self::new(LightKind::Directional { direction }, color)
+    }
+
+    pub fn point(position: [f32; 3], color: u32, attenuation: Attenuation) -> Self {
+        Self::new(
+            LightKind::Point {
+                position,
+                attenuation,
+            },
+            color,
+        )
+    }
+
+    pub fn spot(
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: u32,
+        attenuation: Attenuation,
+        exponent: f32,
+        cutoff: f32,
+    ) -> Self {
+        Self::new(
+            LightKind::Spot {
+                position,
+                direction,
+                attenuation,
+                exponent,
+                cutoff,
+            },
+            color,
+        )
+    }
+
+    /// A light with the given diffuse `color`, and no ambient or specular
+    /// contribution.
+    pub fn new(kind: LightKind, color: u32) -> Self {
+        Self {
+            kind,
+            ambient: 0,
+            diffuse: color,
+            specular: None,
+        }
+    }
+
+    pub fn with_ambient(mut self, color: u32) -> Self {
+        self.ambient = color;
+        self
+    }
+
+    pub fn with_specular(mut self, color: u32) -> Self {
+        self.specular = Some(color);
+        self
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// Enable or disable lighting as a whole. Individual lights are enabled
+    /// with `enable_light`.
+    ///
+    /// Vertices drawn with lighting on need unit length normals.
+    pub fn set_lighting(&mut self, enabled: bool) {
+        set_state(GuState::Lighting, enabled);
+    }
+
+    /// Configure light `index`.
+    ///
+    /// Out of range indices panic in debug builds.
+    pub fn set_light(&mut self, index: usize, light: &Light) -> Result<(), InvalidLightIndex> {
+        let i = check_index(index)?;
+
+        let components = match light.specular {
+            Some(_) => LightComponent::DIFFUSE | LightComponent::SPECULAR,
+            None => LightComponent::AMBIENT | LightComponent::DIFFUSE,
+        };
+
+        unsafe {
+            match light.kind {
+                LightKind::Directional { direction } => {
+                    sys::sceGuLight(i, LightType::Directional, components, &vec3(direction));
+                }
+                LightKind::Point {
+                    position,
+                    attenuation,
+                } => {
+                    sys::sceGuLight(i, LightType::Pointlight, components, &vec3(position));
+                    set_attenuation(i, attenuation);
+                }
+                LightKind::Spot {
+                    position,
+                    direction,
+                    attenuation,
+                    exponent,
+                    cutoff,
+                } => {
+                    sys::sceGuLight(i, LightType::Spotlight, components, &vec3(position));
+                    set_attenuation(i, attenuation);
+
+                    // The GE compares against the cosine of the angle.
+                    sys::sceGuLightSpot(i, &vec3(direction), exponent, libm::cosf(cutoff));
+                }
+            }
+
+            sys::sceGuLightColor(i, LightComponent::AMBIENT, light.ambient);
+            sys::sceGuLightColor(i, LightComponent::DIFFUSE, light.diffuse);
+            if let Some(specular) = light.specular {
+                sys::sceGuLightColor(i, LightComponent::SPECULAR, specular);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Change one or more of light `index`'s colors, as `0xBBGGRR`, leaving
+    /// the rest of its configuration as it is.
+    ///
+    /// Out of range indices panic in debug builds.
+    pub fn set_light_color(
+        &mut self,
+        index: usize,
+        components: LightComponent,
+        color: u32,
+    ) -> Result<(), InvalidLightIndex> {
+        let i = check_index(index)?;
+        unsafe { sys::sceGuLightColor(i, components, color) };
+        Ok(())
+    }
+
+    /// Turn light `index` on or off.
+    ///
+    /// Out of range indices panic in debug builds.
+    pub fn enable_light(&mut self, index: usize, enabled: bool) -> Result<(), InvalidLightIndex> {
+        let state = match check_index(index)? {
+            0 => GuState::Light0,
+            1 => GuState::Light1,
+            2 => GuState::Light2,
+            _ => GuState::Light3,
+        };
+
+        set_state(state, enabled);
+        Ok(())
+    }
+
+    /// Set the scene's ambient light, as `0xAABBGGRR`.
+    pub fn set_ambient(&mut self, color: u32) {
+        unsafe { sys::sceGuAmbient(color) };
+    }
+}
+
+fn check_index(index: usize) -> Result<i32, InvalidLightIndex> {
+    debug_assert!(index < MAX_LIGHTS, "light index {} out of range", index);
+
+    if index < MAX_LIGHTS {
+        Ok(index as i32)
+    } else {
+        Err(InvalidLightIndex(index))
+    }
+}
+
+fn set_state(state: GuState, enabled: bool) {
+    unsafe {
+        if enabled {
+            sys::sceGuEnable(state);
+        } else {
+            sys::sceGuDisable(state);
+        }
+    }
+}
+
+unsafe fn set_attenuation(light: i32, att: Attenuation) {
+    sys::sceGuLightAtt(light, att.constant, att.linear, att.quadratic);
+}
+
+fn vec3(v: [f32; 3]) -> ScePspFVector3 {
+    ScePspFVector3 {
+        x: v[0],
+        y: v[1],
+        z: v[2],
+    }
+}
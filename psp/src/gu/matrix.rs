@@ -0,0 +1,110 @@
+//! The 4x4 matrices the GE transforms vertices with.
+//!
+//! A `Matrix4` is stored column by column, as `sceGum` and the GE expect:
+//! its `x`, `y`, `z` and `w` fields are columns, and a translation is in
+//! `w`. Textbooks write matrices row by row, so `from_rows` takes them that
+//! way and does the conversion.
+//!
+//! A vertex `v` is transformed to clip space by `projection * view * model
+//! * v`. The GE does that multiplication itself, from the three matrices
+//! set with `Frame::set_matrix`.
+
+use super::Frame;
+use crate::sys::{self, MatrixMode, ScePspFMatrix4, ScePspFVector4};
+use core::ops::Mul;
+
+/// A 4x4 matrix, as used by `sceGum`.
+pub type Matrix4 = ScePspFMatrix4;
+
+impl ScePspFMatrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4 {
+        x: vec4([1.0, 0.0, 0.0, 0.0]),
+        y: vec4([0.0, 1.0, 0.0, 0.0]),
+        z: vec4([0.0, 0.0, 1.0, 0.0]),
+        w: vec4([0.0, 0.0, 0.0, 1.0]),
+    };
+
+    /// A matrix from its rows, as it would be written on paper.
+    pub const fn from_rows(rows: [[f32; 4]; 4]) -> Matrix4 {
+        let [r0, r1, r2, r3] = rows;
+
+        Matrix4 {
+            x: vec4([r0[0], r1[0], r2[0], r3[0]]),
+            y: vec4([r0[1], r1[1], r2[1], r3[1]]),
+            z: vec4([r0[2], r1[2], r2[2], r3[2]]),
+            w: vec4([r0[3], r1[3], r2[3], r3[3]]),
+        }
+    }
+
+    /// The rows of the matrix, the inverse of `from_rows`.
+    pub fn to_rows(&self) -> [[f32; 4]; 4] {
+        let cols = self.cols();
+        let mut rows = [[0.0; 4]; 4];
+
+        for (r, row) in rows.iter_mut().enumerate() {
+            for (c, x) in row.iter_mut().enumerate() {
+                *x = cols[c][r];
+            }
+        }
+
+        rows
+    }
+
+    /// `self * v`, for a column vector `v`.
+    pub fn transform(&self, v: [f32; 4]) -> [f32; 4] {
+        let cols = self.cols();
+        let mut out = [0.0; 4];
+
+        for (col, &x) in cols.iter().zip(v.iter()) {
+            for (o, &c) in out.iter_mut().zip(col.iter()) {
+                *o += c * x;
+            }
+        }
+
+        out
+    }
+
+    fn cols(&self) -> [[f32; 4]; 4] {
+        let col = |v: &ScePspFVector4| [v.x, v.y, v.z, v.w];
+        [col(&self.x), col(&self.y), col(&self.z), col(&self.w)]
+    }
+}
+
+impl Mul for ScePspFMatrix4 {
+    type Output = Matrix4;
+
+    /// The matrix that applies `rhs`, then `self`.
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let col = |v: &ScePspFVector4| vec4(self.transform([v.x, v.y, v.z, v.w]));
+
+        Matrix4 {
+            x: col(&rhs.x),
+            y: col(&rhs.y),
+            z: col(&rhs.z),
+            w: col(&rhs.w),
+        }
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// Set the projection, view, model or texture matrix.
+    ///
+    /// This goes to the GE directly, not through the `sceGum` matrix
+    /// stack. A later `sceGumUpdateMatrix` sends the stack's matrices again,
+    /// replacing the ones set here.
+    ///
+    /// The GE only keeps the upper 3x4 part of the view, model and texture
+    /// matrices: the last component of each column is ignored.
+    pub fn set_matrix(&mut self, mode: MatrixMode, matrix: &Matrix4) {
+        unsafe { sys::sceGuSetMatrix(mode, matrix) };
+    }
+}
+
+const fn vec4(v: [f32; 4]) -> ScePspFVector4 {
+    ScePspFVector4 {
+        x: v[0],
+        y: v[1],
+        z: v[2],
+        w: v[3],
+    }
+}
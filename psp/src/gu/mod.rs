@@ -0,0 +1,402 @@
+//! A safe wrapper over the `sceGu` graphics API.
+//!
+//! [`Gu`] owns the display list and the VRAM holding the frame and depth
+//! buffers. Each frame is recorded through a [`Frame`], which finishes and
+//! syncs the display list when dropped.
+//!
+//! The raw functions in `psp::sys` can still be used while a frame is being
+//! recorded, for anything this wrapper does not cover.
+
+use crate::image::Texture;
+use crate::sys::{
+    self, ClearBuffer, DisplayPixelFormat, FrontFaceDirection, GuContextType, GuPrimitive, GuState,
+    GuSyncBehavior, GuSyncMode, MipmapLevel, ShadingModel, TextureColorComponent, TextureEffect,
+    TextureFilter, TexturePixelFormat, VertexType,
+};
+use crate::vram_alloc::{get_vram_allocator, SimpleVramAllocator, VramAllocatorInUseError};
+use crate::{Align16, BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use core::ffi::c_void;
+use core::{mem, ptr, slice};
+
+mod light;
+mod matrix;
+mod patch;
+mod skin;
+mod state;
+mod swizzle;
+mod validate;
+pub use light::{Attenuation, InvalidLightIndex, Light, LightKind, MAX_LIGHTS};
+pub use matrix::Matrix4;
+pub use patch::{PatchError, PatchKind, PatchMesh, MAX_PATCH_SIZE};
+pub use skin::{MAX_BONES, MAX_MORPH_TARGETS};
+use state::RenderState;
+pub use state::{Blend, DepthBuffer, Rect, StateGuard};
+pub use swizzle::{swizzle, unswizzle};
+pub(crate) use swizzle::{swizzle_into, unswizzle_into};
+
+/// Where the top-left corner of the screen is in the GE's 4096x4096
+/// coordinate space, which puts the screen's center in the middle.
+const OFFSET_X: i32 = 2048 - SCREEN_WIDTH as i32 / 2;
+const OFFSET_Y: i32 = 2048 - SCREEN_HEIGHT as i32 / 2;
+
+/// Size of the display list, in words.
+const LIST_LEN: usize = 0x40000;
+
+static mut LIST: Align16<[u32; LIST_LEN]> = Align16([0; LIST_LEN]);
+
+/// A vertex type that can be handed to the GE.
+///
+/// # Safety
+///
+/// `FORMAT` must exactly describe the memory layout of `Self`, including the
+/// padding the GE expects between components.
+pub unsafe trait Vertex: Copy {
+    const FORMAT: VertexType;
+}
+
+/// The initialized graphics engine.
+///
+/// Sets up a double buffered `Psm8888` framebuffer and a 16-bit depth buffer
+/// at the start of VRAM. Depth testing starts out disabled, see
+/// `Frame::set_depth_test`.
+pub struct Gu {
+    vram: SimpleVramAllocator,
+    state: RenderState,
+}
+
+impl Gu {
+    /// Initialize the GU and turn on the display.
+    ///
+    /// This takes the VRAM allocator, so it can only succeed once. Further VRAM
+    /// can be allocated through `Gu::vram`.
+    ///
+    /// The display is only turned on once the GE has run the setup list, so
+    /// the first frame seen is one drawn through the wrapper. Each frame is
+    /// then started, finished and synced, and swapped onto the screen:
+    ///
+    /// ```no_run
+    /// use psp::gu::Gu;
+    ///
+    /// let mut gu = Gu::init().unwrap();
+    ///
+    /// loop {
+    ///     let mut frame = gu.start_frame();
+    ///     frame.clear(0xff00_0000);
+    ///     // Draw.
+    ///     frame.finish();
+    ///
+    ///     gu.swap_buffers(true);
+    /// }
+    /// ```
+    pub fn init() -> Result<Self, VramAllocatorInUseError> {
+        let vram = get_vram_allocator()?;
+
+        // The chunks are never freed: `free_all` needs `&mut`, and the
+        // allocator is only ever lent out immutably.
+        let fbp0 = vram
+            .alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
+            .as_mut_ptr_from_zero();
+        let fbp1 = vram
+            .alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
+            .as_mut_ptr_from_zero();
+        let zbp = vram
+            .alloc_depth_buffer(BUF_WIDTH, SCREEN_HEIGHT)
+            .as_mut_ptr_from_zero();
+
+        unsafe {
+            sys::sceGuInit();
+
+            sys::sceGuStart(GuContextType::Direct, list_ptr());
+            sys::sceGuDrawBuffer(DisplayPixelFormat::Psm8888, fbp0 as _, BUF_WIDTH as i32);
+            sys::sceGuDispBuffer(
+                SCREEN_WIDTH as i32,
+                SCREEN_HEIGHT as i32,
+                fbp1 as _,
+                BUF_WIDTH as i32,
+            );
+            sys::sceGuDepthBuffer(zbp as _, BUF_WIDTH as i32);
+            sys::sceGuOffset(OFFSET_X as u32, OFFSET_Y as u32);
+            sys::sceGuViewport(2048, 2048, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+            sys::sceGuDepthRange(65535, 0);
+            sys::sceGuScissor(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+            sys::sceGuEnable(GuState::ScissorTest);
+            sys::sceGuFrontFace(FrontFaceDirection::Clockwise);
+            sys::sceGuShadeModel(ShadingModel::Smooth);
+            sys::sceGuEnable(GuState::ClipPlanes);
+            sys::sceGuDisable(GuState::Blend);
+            sys::sceGuDisable(GuState::DepthTest);
+            sys::sceGuFinish();
+            sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
+
+            sys::sceDisplayWaitVblankStart();
+            sys::sceGuDisplay(true);
+        }
+
+        Ok(Self {
+            vram,
+            state: RenderState::new(DepthBuffer {
+                zbp,
+                stride: BUF_WIDTH,
+            }),
+        })
+    }
+
+    /// Turn the display on or off. `Gu::init` turns it on.
+    ///
+    /// While it is off the screen is black, and `swap_buffers` keeps it
+    /// that way, so frames can be drawn, such as during loading, without
+    /// being shown. Turn it back on after `swap_buffers` has put a finished
+    /// frame in the display buffer.
+    pub fn set_display(&mut self, enabled: bool) {
+        unsafe { sys::sceGuDisplay(enabled) };
+    }
+
+    /// The VRAM allocator, for allocating textures or additional buffers.
+    pub fn vram(&self) -> &SimpleVramAllocator {
+        &self.vram
+    }
+
+    /// Start recording a new frame.
+    ///
+    /// Scissor, viewport, blending and depth test settings carry over from
+    /// the previous frame. Texturing starts out disabled, as last frame's
+    /// texture may not be alive anymore.
+    pub fn start_frame(&mut self) -> Frame<'_> {
+        unsafe {
+            sys::sceGuStart(GuContextType::Direct, list_ptr());
+            sys::sceGuDisable(GuState::Texture2D);
+        }
+
+        Frame {
+            gu: self,
+            texture: None,
+            finished: false,
+        }
+    }
+
+    /// Present the last finished frame.
+    ///
+    /// If `vsync` is set, this waits for the next vertical blank first.
+    pub fn swap_buffers(&mut self, vsync: bool) {
+        unsafe {
+            if vsync {
+                sys::sceDisplayWaitVblankStart();
+            }
+
+            sys::sceGuSwapBuffers();
+        }
+    }
+
+    /// Record a frame with `f`, then present it on the next vertical blank.
+    pub fn frame<F: FnOnce(&mut Frame<'_>)>(&mut self, f: F) {
+        let mut frame = self.start_frame();
+        f(&mut frame);
+        frame.finish();
+
+        self.swap_buffers(true);
+    }
+}
+
+/// A frame being recorded into the display list.
+///
+/// Anything borrowed for `'a` (textures, vertices) is guaranteed to outlive
+/// the execution of the display list, as the frame waits for the GE to finish
+/// before releasing the `Gu`.
+pub struct Frame<'a> {
+    gu: &'a mut Gu,
+    texture: Option<&'a Texture>,
+    finished: bool,
+}
+
+impl<'a> Frame<'a> {
+    /// The VRAM allocator, see `Gu::vram`.
+    pub fn vram(&self) -> &SimpleVramAllocator {
+        self.gu.vram()
+    }
+
+    /// Clear the color and depth buffers.
+    pub fn clear(&mut self, color: u32) {
+        self.clear_buffers(
+            ClearBuffer::COLOR_BUFFER_BIT | ClearBuffer::DEPTH_BUFFER_BIT,
+            color,
+            0,
+        );
+    }
+
+    /// Clear `buffers` within the scissor rectangle, the color buffer to
+    /// `color` and the depth buffer to `depth`.
+    ///
+    /// `Gu::init` maps the near plane to 65535 and the far plane to 0, so 0
+    /// is the farthest depth, behind everything drawn with
+    /// `DepthFunc::GreaterOrEqual`. Leaving out `DEPTH_BUFFER_BIT` keeps the
+    /// depth of what is already drawn, for drawing a scene in passes.
+    pub fn clear_buffers(&mut self, buffers: ClearBuffer, color: u32, depth: u16) {
+        unsafe {
+            sys::sceGuClearColor(color);
+            sys::sceGuClearDepth(depth as u32);
+            sys::sceGuClear(buffers);
+        }
+    }
+
+    /// Copy vertices into display list memory, where they stay valid for the
+    /// rest of the frame.
+    ///
+    /// This memory is uncached, so no cache writeback is needed before drawing.
+    pub fn push_vertices<T, I>(&mut self, vertices: I) -> &'a [T]
+    where
+        T: Vertex,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let vertices = vertices.into_iter();
+        let len = vertices.len();
+
+        if !validate::list_space(len * mem::size_of::<T>()) {
+            return &[];
+        }
+
+        unsafe {
+            let ptr = sys::sceGuGetMemory((len * mem::size_of::<T>()) as i32) as *mut T;
+
+            // Display list memory is only guaranteed to be 4 byte aligned.
+            debug_assert!(mem::align_of::<T>() <= 4);
+
+            for (i, v) in vertices.take(len).enumerate() {
+                ptr::write(ptr.add(i), v);
+            }
+
+            slice::from_raw_parts(ptr, len)
+        }
+    }
+
+    /// Draw `vertices` as `prim` primitives.
+    ///
+    /// Vertices not obtained through `push_vertices` must have been written
+    /// back from the data cache before the frame is finished.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is morphed or skinned, and its morph weights or bone
+    /// matrices haven't been set.
+    pub fn draw_array<T: Vertex>(&mut self, prim: GuPrimitive, vertices: &'a [T]) {
+        self.check_vertex_format(T::FORMAT);
+
+        if !validate::vertices(Some(prim), vertices) {
+            return;
+        }
+
+        unsafe {
+            sys::sceGuDrawArray(
+                prim,
+                T::FORMAT,
+                vertices.len() as i32,
+                ptr::null(),
+                vertices.as_ptr() as *const c_void,
+            );
+        }
+    }
+
+    /// Bind `texture` for subsequent draws, and enable texturing.
+    ///
+    /// The texture is modulated with the vertex color and uses its alpha.
+    pub fn bind_texture(&mut self, texture: &'a Texture) {
+        if !validate::texture(texture) {
+            return;
+        }
+
+        texture.writeback();
+        self.texture = Some(texture);
+
+        unsafe {
+            sys::sceGuEnable(GuState::Texture2D);
+            sys::sceGuTexMode(texture.format(), 0, 0, texture.is_swizzled() as i32);
+            sys::sceGuTexImage(
+                MipmapLevel::None,
+                texture.buffer_width() as i32,
+                texture.buffer_height() as i32,
+                texture.buffer_width() as i32,
+                texture.as_ptr() as *const c_void,
+            );
+            sys::sceGuTexFunc(TextureEffect::Modulate, TextureColorComponent::Rgba);
+            sys::sceGuTexFilter(TextureFilter::Nearest, TextureFilter::Nearest);
+            sys::sceGuTexScale(1.0, 1.0);
+            sys::sceGuTexOffset(0.0, 0.0);
+        }
+    }
+
+    /// Disable texturing.
+    pub fn unbind_texture(&mut self) {
+        self.texture = None;
+        unsafe { sys::sceGuDisable(GuState::Texture2D) };
+    }
+
+    /// Enable or disable standard alpha blending, see `Blend::ALPHA`.
+    pub fn set_alpha_blend(&mut self, enabled: bool) {
+        self.set_blend(if enabled { Some(Blend::ALPHA) } else { None });
+    }
+
+    /// Finish the display list and wait for the GE to execute it.
+    ///
+    /// This also happens when the frame is dropped.
+    pub fn finish(mut self) {
+        self.end();
+    }
+
+    fn end(&mut self) {
+        if !self.finished {
+            self.finished = true;
+
+            unsafe {
+                sys::sceGuFinish();
+                sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
+            }
+        }
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
+/// A vertex for textured 2D quads in through mode: texel coordinates, a color
+/// and screen coordinates.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpriteVertex {
+    pub u: i16,
+    pub v: i16,
+    pub color: u32,
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+    _pad: i16,
+}
+
+impl SpriteVertex {
+    pub const fn new(u: i16, v: i16, color: u32, x: i16, y: i16) -> Self {
+        Self {
+            u,
+            v,
+            color,
+            x,
+            y,
+            z: 0,
+            _pad: 0,
+        }
+    }
+}
+
+unsafe impl Vertex for SpriteVertex {
+    const FORMAT: VertexType = VertexType::from_bits_truncate(
+        VertexType::TEXTURE_16BIT.bits()
+            | VertexType::COLOR_8888.bits()
+            | VertexType::VERTEX_16BIT.bits()
+            | VertexType::TRANSFORM_2D.bits(),
+    );
+}
+
+fn list_ptr() -> *mut c_void {
+    unsafe { ptr::addr_of_mut!(LIST.0) as *mut c_void }
+}
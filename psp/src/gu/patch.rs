@@ -0,0 +1,141 @@
+//! Bezier and spline patches, tessellated by the GE.
+
+use super::{Frame, Vertex};
+use crate::sys::{self, VertexType};
+use core::ffi::c_void;
+use core::ptr;
+
+/// Most control points a patch can have in either direction.
+pub const MAX_PATCH_SIZE: usize = 255;
+
+/// Spline edges with both ends open, so the surface reaches its outer
+/// control points.
+const OPEN_EDGES: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchKind {
+    /// Bicubic Bezier patches. Neighbouring patches share their edge control
+    /// points.
+    Bezier,
+    /// A uniform cubic B-spline surface.
+    Spline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchError {
+    /// The vertex format has no positions.
+    NoPositions,
+    /// The number of vertices is not `u_count * v_count`.
+    CountMismatch,
+    /// The grid is not 3n+1 control points in each direction for Beziers,
+    /// at least 4 for splines, or is larger than `MAX_PATCH_SIZE`.
+    InvalidSize,
+}
+
+/// A grid of control points, stored row by row: `u_count` points along U,
+/// repeated for each of the `v_count` rows.
+#[derive(Debug, Clone, Copy)]
+pub struct PatchMesh<'a, T: Vertex> {
+    kind: PatchKind,
+    vertices: &'a [T],
+    u_count: usize,
+    v_count: usize,
+}
+
+impl<'a, T: Vertex> PatchMesh<'a, T> {
+    pub fn bezier(vertices: &'a [T], u_count: usize, v_count: usize) -> Result<Self, PatchError> {
+        Self::new(PatchKind::Bezier, vertices, u_count, v_count)
+    }
+
+    pub fn spline(vertices: &'a [T], u_count: usize, v_count: usize) -> Result<Self, PatchError> {
+        Self::new(PatchKind::Spline, vertices, u_count, v_count)
+    }
+
+    pub fn new(
+        kind: PatchKind,
+        vertices: &'a [T],
+        u_count: usize,
+        v_count: usize,
+    ) -> Result<Self, PatchError> {
+        // `VERTEX_32BITF` covers all the position format bits.
+        if !T::FORMAT.intersects(VertexType::VERTEX_32BITF) {
+            return Err(PatchError::NoPositions);
+        }
+
+        let valid = |n: usize| {
+            n <= MAX_PATCH_SIZE
+                && match kind {
+                    PatchKind::Bezier => n >= 4 && (n - 1) % 3 == 0,
+                    PatchKind::Spline => n >= 4,
+                }
+        };
+
+        if !valid(u_count) || !valid(v_count) {
+            return Err(PatchError::InvalidSize);
+        }
+
+        if vertices.len() != u_count * v_count {
+            return Err(PatchError::CountMismatch);
+        }
+
+        Ok(Self {
+            kind,
+            vertices,
+            u_count,
+            v_count,
+        })
+    }
+
+    pub fn kind(&self) -> PatchKind {
+        self.kind
+    }
+
+    pub fn u_count(&self) -> usize {
+        self.u_count
+    }
+
+    pub fn v_count(&self) -> usize {
+        self.v_count
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// Tessellate and draw `mesh`, which can be a Bezier or a spline patch.
+    /// Each patch is divided into `divisions` steps in both directions.
+    ///
+    /// As with `draw_array`, vertices not obtained through `push_vertices`
+    /// must have been written back from the data cache.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `divisions` is not in `1..=64`, or on the same vertex format
+    /// mismatches as `draw_array`.
+    pub fn draw_bezier<T: Vertex>(&mut self, mesh: &PatchMesh<'a, T>, divisions: u32) {
+        assert!((1..=64).contains(&divisions), "invalid patch divisions");
+        self.check_vertex_format(T::FORMAT);
+
+        if !super::validate::vertices(None, mesh.vertices) {
+            return;
+        }
+
+        let vertices = mesh.vertices.as_ptr() as *const c_void;
+        let (u, v) = (mesh.u_count as i32, mesh.v_count as i32);
+
+        unsafe {
+            sys::sceGuPatchDivide(divisions, divisions);
+
+            match mesh.kind {
+                PatchKind::Bezier => sys::sceGuDrawBezier(T::FORMAT, u, v, ptr::null(), vertices),
+                PatchKind::Spline => sys::sceGuDrawSpline(
+                    T::FORMAT,
+                    u,
+                    v,
+                    OPEN_EDGES,
+                    OPEN_EDGES,
+                    ptr::null(),
+                    vertices,
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Vertex morphing and skinning.
+//!
+//! A morphed vertex type holds the same vertex several times, once per morph
+//! target, and the GE blends them with the weights set by
+//! `Frame::set_morph_weights`. Its `FORMAT` uses
+//! `VertexType::with_morph_targets`.
+//!
+//! A skinned vertex type starts with up to 8 weights, each applied to the bone
+//! matrix with the same index. Its `FORMAT` uses one of the `WEIGHT_*` formats
+//! and `VertexType::with_weights`.
+
+use super::{Frame, Matrix4};
+use crate::sys::{self, VertexType};
+
+/// Most morph targets or bone matrices a vertex can use.
+pub const MAX_MORPH_TARGETS: usize = 8;
+pub const MAX_BONES: usize = 8;
+
+impl<'a> Frame<'a> {
+    /// Set the weights of the morph targets, in order. Weights for targets
+    /// past the end of `weights` are left as they were.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `MAX_MORPH_TARGETS` weights.
+    pub fn set_morph_weights(&mut self, weights: &[f32]) {
+        assert!(weights.len() <= MAX_MORPH_TARGETS, "too many morph weights");
+
+        for (i, &weight) in weights.iter().enumerate() {
+            unsafe { sys::sceGuMorphWeight(i as i32, weight) };
+        }
+
+        let state = &mut self.gu.state;
+        state.morph_weights = state.morph_weights.max(weights.len());
+    }
+
+    /// Set bone matrix `index`.
+    ///
+    /// The GE only keeps the upper 3x4 part of the matrix: the last
+    /// component of each column is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not below `MAX_BONES`.
+    pub fn set_bone_matrix(&mut self, index: usize, matrix: &Matrix4) {
+        assert!(index < MAX_BONES, "bone index {} out of range", index);
+
+        unsafe { sys::sceGuBoneMatrix(index as u32, matrix) };
+        self.gu.state.bones |= 1 << index;
+    }
+
+    /// Check that the morph weights and bone matrices `format` uses have
+    /// been set.
+    pub(super) fn check_vertex_format(&self, format: VertexType) {
+        let state = &self.gu.state;
+
+        let morphs = format.morph_count();
+        assert!(
+            morphs == 1 || state.morph_weights >= morphs,
+            "vertex format has {} morph targets, but only {} morph weights are set",
+            morphs,
+            state.morph_weights,
+        );
+
+        let bones = (1u16 << format.weight_count()) - 1;
+        assert!(
+            state.bones as u16 & bones == bones,
+            "vertex format has {} weights, but not all of their bone matrices are set",
+            format.weight_count(),
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! Shadowed GE state, and guards that restore it.
+//!
+//! The GE's state can't be read back, so `Gu` keeps a copy of everything set
+//! through the wrapper. This lets the `scoped_*` methods on `Frame` put the
+//! previous value back when their guard is dropped.
+
+use super::Frame;
+use crate::image::Texture;
+use crate::sys::{self, BlendFactor, BlendOp, DepthFunc, GuState};
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use core::ffi::c_void;
+use core::ops::{Deref, DerefMut};
+
+/// A rectangle in screen coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    /// The whole screen.
+    pub const SCREEN: Rect = Rect::new(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+
+    pub const fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self { x, y, w, h }
+    }
+}
+
+/// Blending equation, as passed to `sceGuBlendFunc`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Blend {
+    pub op: BlendOp,
+    pub src: BlendFactor,
+    pub dest: BlendFactor,
+    /// Fixed source value, used with `BlendFactor::Fix`.
+    pub src_fix: u32,
+    /// Fixed destination value, used with `BlendFactor::Fix`.
+    pub dest_fix: u32,
+}
+
+impl Blend {
+    /// Standard alpha blending: `src * a + dest * (1 - a)`.
+    pub const ALPHA: Blend = Blend {
+        op: BlendOp::Add,
+        src: BlendFactor::SrcAlpha,
+        dest: BlendFactor::OneMinusSrcAlpha,
+        src_fix: 0,
+        dest_fix: 0,
+    };
+
+    /// Additive blending: `src * a + dest`.
+    pub const ADD: Blend = Blend {
+        op: BlendOp::Add,
+        src: BlendFactor::SrcAlpha,
+        dest: BlendFactor::Fix,
+        src_fix: 0,
+        dest_fix: 0xffffff,
+    };
+}
+
+/// A depth buffer in VRAM, as passed to `sceGuDepthBuffer`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DepthBuffer {
+    /// The start of the buffer, as an offset into VRAM, such as from
+    /// `VramMemChunk::as_mut_ptr_from_zero`.
+    pub zbp: *mut u8,
+    /// Width of a row, in pixels.
+    pub stride: u32,
+}
+
+/// The state that persists in the GE between frames.
+pub(crate) struct RenderState {
+    pub(crate) scissor: Rect,
+    pub(crate) depth_buffer: DepthBuffer,
+    pub(crate) viewport: Rect,
+    pub(crate) blend: Option<Blend>,
+    pub(crate) depth_test: Option<DepthFunc>,
+    /// Number of morph weights set, counting from the first.
+    pub(crate) morph_weights: usize,
+    /// Bit `i` is set once bone matrix `i` has been set.
+    pub(crate) bones: u8,
+}
+
+impl RenderState {
+    /// The state `Gu::init` leaves the GE in.
+    pub(crate) const fn new(depth_buffer: DepthBuffer) -> Self {
+        Self {
+            scissor: Rect::SCREEN,
+            depth_buffer,
+            viewport: Rect::SCREEN,
+            blend: None,
+            depth_test: None,
+            morph_weights: 0,
+            bones: 0,
+        }
+    }
+}
+
+/// Restores a piece of state on drop. Returned by the `Frame::scoped_*`
+/// methods.
+///
+/// The guard dereferences to the frame, so drawing (and nesting further
+/// guards) continues through it. Borrowing guarantees nested guards are
+/// dropped, and so restore, in reverse order.
+#[must_use = "the previous state is restored as soon as the guard is dropped"]
+pub struct StateGuard<'f, 'a> {
+    frame: &'f mut Frame<'a>,
+    restore: Restore<'a>,
+}
+
+enum Restore<'a> {
+    Scissor(Rect),
+    Viewport(Rect),
+    Blend(Option<Blend>),
+    DepthTest(Option<DepthFunc>),
+    Texture(Option<&'a Texture>),
+}
+
+impl<'a> Deref for StateGuard<'_, 'a> {
+    type Target = Frame<'a>;
+
+    fn deref(&self) -> &Frame<'a> {
+        self.frame
+    }
+}
+
+impl<'a> DerefMut for StateGuard<'_, 'a> {
+    fn deref_mut(&mut self) -> &mut Frame<'a> {
+        self.frame
+    }
+}
+
+impl Drop for StateGuard<'_, '_> {
+    fn drop(&mut self) {
+        match self.restore {
+            Restore::Scissor(rect) => self.frame.set_scissor(rect),
+            Restore::Viewport(rect) => self.frame.set_viewport(rect),
+            Restore::Blend(blend) => self.frame.set_blend(blend),
+            Restore::DepthTest(func) => self.frame.set_depth_test(func),
+            Restore::Texture(Some(texture)) => self.frame.bind_texture(texture),
+            Restore::Texture(None) => self.frame.unbind_texture(),
+        }
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// The current scissor rectangle.
+    pub fn scissor(&self) -> Rect {
+        self.gu.state.scissor
+    }
+
+    /// Restrict drawing to `rect`.
+    ///
+    /// The rectangle is in draw buffer pixels, so it must lie within the
+    /// `BUF_WIDTH` x `SCREEN_HEIGHT` buffer `Gu` draws to. Only the first
+    /// `SCREEN_WIDTH` columns of it are shown. Clearing is limited to it too,
+    /// so a corner of the screen can be cleared and drawn to while the rest
+    /// keeps the previous frame.
+    pub fn set_scissor(&mut self, rect: Rect) {
+        if !super::validate::scissor(rect) {
+            return;
+        }
+
+        self.gu.state.scissor = rect;
+
+        // `sceGuScissor` takes the bottom-right corner, not the size.
+        unsafe { sys::sceGuScissor(rect.x, rect.y, rect.x + rect.w, rect.y + rect.h) };
+    }
+
+    /// The current viewport.
+    pub fn viewport(&self) -> Rect {
+        self.gu.state.viewport
+    }
+
+    /// Map clip space onto `rect` of the screen, so a 3D scene fills it
+    /// rather than the whole screen.
+    ///
+    /// The viewport only places geometry, it doesn't clip it: anything the
+    /// projection puts outside of `rect` is still drawn, up to the scissor
+    /// rectangle. Set that to the same `rect` to keep a split screen view to
+    /// its part.
+    pub fn set_viewport(&mut self, rect: Rect) {
+        if !super::validate::viewport(rect) {
+            return;
+        }
+
+        self.gu.state.viewport = rect;
+
+        // `sceGuViewport` takes the center, in the coordinates `Gu::init`
+        // offsets the screen to the middle of.
+        unsafe {
+            sys::sceGuViewport(
+                super::OFFSET_X + rect.x + rect.w / 2,
+                super::OFFSET_Y + rect.y + rect.h / 2,
+                rect.w,
+                rect.h,
+            )
+        };
+    }
+
+    /// The current blending equation, `None` if blending is disabled.
+    pub fn blend(&self) -> Option<Blend> {
+        self.gu.state.blend
+    }
+
+    /// Set the blending equation, or disable blending with `None`.
+    pub fn set_blend(&mut self, blend: Option<Blend>) {
+        self.gu.state.blend = blend;
+
+        unsafe {
+            match blend {
+                Some(b) => {
+                    sys::sceGuBlendFunc(b.op, b.src, b.dest, b.src_fix, b.dest_fix);
+                    sys::sceGuEnable(GuState::Blend);
+                }
+                None => sys::sceGuDisable(GuState::Blend),
+            }
+        }
+    }
+
+    /// The current depth buffer. Until `set_depth_buffer` is called, this
+    /// is the one `Gu::init` allocated.
+    pub fn depth_buffer(&self) -> DepthBuffer {
+        self.gu.state.depth_buffer
+    }
+
+    /// Draw with `buffer` as the depth buffer, such as one allocated with
+    /// `SimpleVramAllocator::alloc_depth_buffer`. It stays in use for later
+    /// frames, until this is called again.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be at least `stride` x `SCREEN_HEIGHT` 16-bit pixels of
+    /// VRAM, and stay allocated while the GE uses it.
+    pub unsafe fn set_depth_buffer(&mut self, buffer: DepthBuffer) {
+        self.gu.state.depth_buffer = buffer;
+        sys::sceGuDepthBuffer(buffer.zbp as *mut c_void, buffer.stride as i32);
+    }
+
+    /// The current depth test function, `None` if depth testing is disabled.
+    pub fn depth_test(&self) -> Option<DepthFunc> {
+        self.gu.state.depth_test
+    }
+
+    /// Set the depth test function, or disable depth testing with `None`.
+    pub fn set_depth_test(&mut self, func: Option<DepthFunc>) {
+        self.gu.state.depth_test = func;
+
+        unsafe {
+            match func {
+                Some(func) => {
+                    sys::sceGuDepthFunc(func);
+                    sys::sceGuEnable(GuState::DepthTest);
+                }
+                None => sys::sceGuDisable(GuState::DepthTest),
+            }
+        }
+    }
+
+    /// The currently bound texture.
+    pub fn texture(&self) -> Option<&'a Texture> {
+        self.texture
+    }
+
+    /// Set the scissor rectangle until the guard is dropped.
+    pub fn scoped_scissor(&mut self, rect: Rect) -> StateGuard<'_, 'a> {
+        let prev = self.scissor();
+        self.set_scissor(rect);
+        self.guard(Restore::Scissor(prev))
+    }
+
+    /// Set the viewport until the guard is dropped.
+    pub fn scoped_viewport(&mut self, rect: Rect) -> StateGuard<'_, 'a> {
+        let prev = self.viewport();
+        self.set_viewport(rect);
+        self.guard(Restore::Viewport(prev))
+    }
+
+    /// Set the blending equation until the guard is dropped.
+    pub fn scoped_blend(&mut self, blend: Option<Blend>) -> StateGuard<'_, 'a> {
+        let prev = self.blend();
+        self.set_blend(blend);
+        self.guard(Restore::Blend(prev))
+    }
+
+    /// Set the depth test function until the guard is dropped.
+    pub fn scoped_depth_test(&mut self, func: Option<DepthFunc>) -> StateGuard<'_, 'a> {
+        let prev = self.depth_test();
+        self.set_depth_test(func);
+        self.guard(Restore::DepthTest(prev))
+    }
+
+    /// Bind `texture` (or disable texturing with `None`) until the guard is
+    /// dropped.
+    pub fn scoped_texture(&mut self, texture: Option<&'a Texture>) -> StateGuard<'_, 'a> {
+        let prev = self.texture();
+
+        match texture {
+            Some(texture) => self.bind_texture(texture),
+            None => self.unbind_texture(),
+        }
+
+        self.guard(Restore::Texture(prev))
+    }
+
+    fn guard(&mut self, restore: Restore<'a>) -> StateGuard<'_, 'a> {
+        StateGuard {
+            frame: self,
+            restore,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Swizzling, the block order the GE reads textures fastest in.
+//!
+//! A swizzled texture is split into blocks of 16 bytes by 8 rows, stored
+//! one after another, left to right and then top to bottom, each block
+//! holding its rows in order.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Width of a swizzle block in bytes.
+const BLOCK_WIDTH: usize = 16;
+/// Height of a swizzle block in rows.
+const BLOCK_HEIGHT: usize = 8;
+
+/// Swizzle `width` x `height` pixels of `bpp` bytes each, stored row by row
+/// with no padding, into a new buffer.
+///
+/// Set the `swizzle` argument of `sceGuTexMode` to 1 when binding the
+/// result. A `Texture` can be swizzled in place with `Texture::swizzle`.
+///
+/// # Panics
+///
+/// Panics if a row isn't a multiple of 16 bytes, `height` isn't a multiple
+/// of 8, or `src` isn't exactly `width * height * bpp` bytes. Power-of-two
+/// texture sizes are, once at least 16 bytes wide and 8 rows high.
+pub fn swizzle(src: &[u8], width: u32, height: u32, bpp: usize) -> Vec<u8> {
+    let stride = checked_stride(src, width, height, bpp);
+    let mut out = vec![0; src.len()];
+    swizzle_into(src, &mut out, stride);
+    out
+}
+
+/// Undo `swizzle`, returning pixels row by row.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `swizzle`.
+pub fn unswizzle(src: &[u8], width: u32, height: u32, bpp: usize) -> Vec<u8> {
+    let stride = checked_stride(src, width, height, bpp);
+    let mut out = vec![0; src.len()];
+    unswizzle_into(src, &mut out, stride);
+    out
+}
+
+/// Row length in bytes, after checking the buffer can be swizzled.
+fn checked_stride(src: &[u8], width: u32, height: u32, bpp: usize) -> usize {
+    let stride = width as usize * bpp;
+
+    assert!(
+        stride % BLOCK_WIDTH == 0 && height as usize % BLOCK_HEIGHT == 0,
+        "swizzled textures must be a multiple of 16 bytes wide and 8 rows high"
+    );
+    assert_eq!(
+        src.len(),
+        stride * height as usize,
+        "pixel data must be width * height * bpp bytes"
+    );
+
+    stride
+}
+
+/// Swizzle rows of `stride` bytes from `src` into `dst`, of the same length.
+pub(crate) fn swizzle_into(src: &[u8], dst: &mut [u8], stride: usize) {
+    for (linear, swizzled) in runs(stride, src.len()) {
+        dst[swizzled..swizzled + BLOCK_WIDTH].copy_from_slice(&src[linear..linear + BLOCK_WIDTH]);
+    }
+}
+
+/// Undo `swizzle_into`.
+pub(crate) fn unswizzle_into(src: &[u8], dst: &mut [u8], stride: usize) {
+    for (linear, swizzled) in runs(stride, src.len()) {
+        dst[linear..linear + BLOCK_WIDTH].copy_from_slice(&src[swizzled..swizzled + BLOCK_WIDTH]);
+    }
+}
+
+/// The offsets of each 16 byte run of a row, in row order and in swizzled
+/// order.
+fn runs(stride: usize, len: usize) -> impl Iterator<Item = (usize, usize)> {
+    let row_blocks = stride / BLOCK_WIDTH;
+
+    // A zero sized texture has no rows.
+    let rows = len.checked_div(stride).unwrap_or(0);
+
+    (0..rows).flat_map(move |y| {
+        (0..row_blocks).map(move |block_x| {
+            let block = block_x + y / BLOCK_HEIGHT * row_blocks;
+            let swizzled = (block * BLOCK_HEIGHT + y % BLOCK_HEIGHT) * BLOCK_WIDTH;
+            (y * stride + block_x * BLOCK_WIDTH, swizzled)
+        })
+    })
+}
@@ -0,0 +1,169 @@
+//! Argument checks for the wrapper's GE calls.
+//!
+//! The GE doesn't report bad arguments, it draws garbage or hangs. With the
+//! `gu-validation` feature enabled in a debug build, a call that would hand
+//! it something bad prints what is wrong with `dprintln!` and is skipped
+//! instead. Otherwise every check is a constant `true`, and compiles away.
+
+use super::{Rect, LIST_LEN};
+use crate::image::{Texture, MAX_TEXTURE_SIZE};
+use crate::sys::{self, GuPrimitive};
+use crate::{BUF_WIDTH, SCREEN_HEIGHT};
+use core::mem;
+
+pub(super) const ENABLED: bool = cfg!(all(feature = "gu-validation", debug_assertions));
+
+/// List space kept free for the commands around a draw call, and for
+/// finishing the list.
+const LIST_SLACK: usize = 256;
+
+/// Most vertices a single draw call can take.
+const MAX_VERTICES: usize = 0xffff;
+
+/// Evaluates to `$cond`, printing the message if it is false.
+macro_rules! check {
+    ($cond:expr, $($arg:tt)*) => {
+        if $cond {
+            true
+        } else {
+            crate::dprintln!("gu: {}", format_args!($($arg)*));
+            false
+        }
+    };
+}
+
+pub(super) fn texture(texture: &Texture) -> bool {
+    if !ENABLED {
+        return true;
+    }
+
+    let (w, h) = (texture.buffer_width(), texture.buffer_height());
+
+    check!(
+        w.is_power_of_two() && h.is_power_of_two(),
+        "texture buffer is {}x{}, not a power of two",
+        w,
+        h
+    ) && check!(
+        w <= MAX_TEXTURE_SIZE && h <= MAX_TEXTURE_SIZE,
+        "texture buffer is {}x{}, larger than {}",
+        w,
+        h,
+        MAX_TEXTURE_SIZE
+    ) && check!(
+        texture.stride() == w as usize * texture.bytes_per_pixel(),
+        "texture stride {} doesn't match its width {}",
+        texture.stride(),
+        w
+    ) && address(texture.as_ptr() as usize, 16, "texture data")
+}
+
+/// Check the vertex data for drawing `prim` primitives, or a patch if `prim`
+/// is `None`.
+pub(super) fn vertices<T>(prim: Option<GuPrimitive>, vertices: &[T]) -> bool {
+    if !ENABLED {
+        return true;
+    }
+
+    let len = vertices.len();
+    let (min, multiple) = match prim {
+        Some(GuPrimitive::Points) | None => (1, 1),
+        Some(GuPrimitive::Lines) | Some(GuPrimitive::Sprites) => (2, 2),
+        Some(GuPrimitive::LineStrip) => (2, 1),
+        Some(GuPrimitive::Triangles) => (3, 3),
+        Some(GuPrimitive::TriangleStrip) | Some(GuPrimitive::TriangleFan) => (3, 1),
+    };
+
+    check!(
+        len <= MAX_VERTICES,
+        "{} vertices, more than the {} one draw can take",
+        len,
+        MAX_VERTICES
+    ) && check!(
+        len >= min && len % multiple == 0,
+        "{} vertices don't make whole {:?} primitives",
+        len,
+        prim
+    ) && address(
+        vertices.as_ptr() as usize,
+        mem::align_of::<T>(),
+        "vertex data",
+    ) && list_space(0)
+}
+
+/// The scissor rectangle lies within the draw buffer.
+pub(super) fn scissor(rect: Rect) -> bool {
+    if !ENABLED {
+        return true;
+    }
+
+    check!(
+        rect.x >= 0
+            && rect.y >= 0
+            && rect.w >= 0
+            && rect.h >= 0
+            && rect.x + rect.w <= BUF_WIDTH as i32
+            && rect.y + rect.h <= SCREEN_HEIGHT as i32,
+        "scissor {:?} is outside the {}x{} draw buffer",
+        rect,
+        BUF_WIDTH,
+        SCREEN_HEIGHT
+    )
+}
+
+/// The viewport has a size, and its center is within the GE's coordinate
+/// space.
+pub(super) fn viewport(rect: Rect) -> bool {
+    if !ENABLED {
+        return true;
+    }
+
+    let (cx, cy) = (
+        super::OFFSET_X + rect.x + rect.w / 2,
+        super::OFFSET_Y + rect.y + rect.h / 2,
+    );
+
+    check!(rect.w > 0 && rect.h > 0, "viewport {:?} is empty", rect)
+        && check!(
+            (0..4096).contains(&cx) && (0..4096).contains(&cy),
+            "viewport {:?} is outside the GE's 4096x4096 coordinate space",
+            rect
+        )
+}
+
+/// The display list has room for `bytes` more, plus a draw call.
+pub(super) fn list_space(bytes: usize) -> bool {
+    if !ENABLED {
+        return true;
+    }
+
+    let used = unsafe { sys::sceGuCheckList() } as usize;
+
+    check!(
+        used + bytes + LIST_SLACK <= LIST_LEN * 4,
+        "display list full: {} bytes used, {} more requested",
+        used,
+        bytes
+    )
+}
+
+/// `addr` is aligned to `align` and points into main memory or VRAM.
+fn address(addr: usize, align: usize, what: &str) -> bool {
+    // Without the uncached and kernel segment bits.
+    let phys = addr & 0x1fff_ffff;
+    let vram = 0x0400_0000..0x0400_0000 + crate::vram_alloc::total();
+    let ram = 0x0800_0000..0x0c00_0000;
+
+    check!(
+        vram.contains(&phys) || ram.contains(&phys),
+        "{} at {:#x} is outside of memory the GE can read",
+        what,
+        addr
+    ) && check!(
+        addr % align == 0,
+        "{} at {:#x} is not {} byte aligned",
+        what,
+        addr,
+        align
+    )
+}
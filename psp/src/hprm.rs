@@ -0,0 +1,135 @@
+//! The headphone remote's buttons.
+//!
+//! ```no_run
+//! use psp::hprm::{RemoteKey, RemoteState};
+//!
+//! let mut remote = RemoteState::new();
+//!
+//! loop {
+//!     remote.update();
+//!
+//!     if remote.pressed(RemoteKey::PLAY_PAUSE) {
+//!         psp::dprintln!("play/pause");
+//!     }
+//!
+//!     unsafe { psp::sys::sceDisplayWaitVblankStart() };
+//! }
+//! ```
+//!
+//! The remote's contacts bounce, so a single press can read as several. Keys
+//! are only reported once they have stayed the same for the debounce window.
+
+use crate::sys;
+use core::time::Duration;
+
+/// A key on the remote, or a set of keys.
+pub type RemoteKey = sys::HprmKey;
+
+/// How long keys must stay the same before they are reported, by default.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// The remote's state for the current frame.
+#[derive(Debug, Clone, Copy)]
+pub struct RemoteState {
+    debounce_us: u64,
+    remote: bool,
+    headphones: bool,
+    /// Debounced keys, this update and the previous one.
+    keys: RemoteKey,
+    previous: RemoteKey,
+    /// The raw keys, and since when they have read the same.
+    raw: RemoteKey,
+    raw_since: u64,
+}
+
+impl RemoteState {
+    pub fn new() -> Self {
+        Self::with_debounce(DEFAULT_DEBOUNCE)
+    }
+
+    pub fn with_debounce(window: Duration) -> Self {
+        let mut state = Self {
+            debounce_us: 0,
+            remote: false,
+            headphones: false,
+            keys: RemoteKey::empty(),
+            previous: RemoteKey::empty(),
+            raw: RemoteKey::empty(),
+            raw_since: 0,
+        };
+
+        state.set_debounce(window);
+        state
+    }
+
+    /// Set how long keys must stay the same before they are reported. Zero
+    /// reports them as soon as they are read.
+    pub fn set_debounce(&mut self, window: Duration) {
+        self.debounce_us = window.as_micros() as u64;
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_micros(self.debounce_us)
+    }
+
+    /// Read the remote. Call this once per frame.
+    pub fn update(&mut self) {
+        self.previous = self.keys;
+        self.headphones = unsafe { sys::sceHprmIsHeadphoneExist() } == 1;
+        self.remote = unsafe { sys::sceHprmIsRemoteExist() } == 1;
+
+        let mut raw = RemoteKey::empty();
+        if !self.remote || unsafe { sys::sceHprmPeekCurrentKey(&mut raw) } < 0 {
+            // Keys held when the remote is unplugged read as released.
+            self.remote = false;
+            self.keys = RemoteKey::empty();
+            self.raw = RemoteKey::empty();
+            return;
+        }
+
+        let now = unsafe { sys::sceKernelGetSystemTimeWide() } as u64;
+
+        if raw != self.raw {
+            self.raw = raw;
+            self.raw_since = now;
+        }
+
+        if now.saturating_sub(self.raw_since) >= self.debounce_us {
+            self.keys = self.raw;
+        }
+    }
+
+    /// The debounced keys held down, or `None` if no remote is plugged in.
+    pub fn keys(&self) -> Option<RemoteKey> {
+        self.remote.then_some(self.keys)
+    }
+
+    /// Whether all of `keys` are held down.
+    pub fn held(&self, keys: RemoteKey) -> bool {
+        self.remote && self.keys.contains(keys)
+    }
+
+    /// Whether any of `keys` went down since the previous update.
+    pub fn pressed(&self, keys: RemoteKey) -> bool {
+        (self.keys - self.previous).intersects(keys)
+    }
+
+    /// Whether any of `keys` went up since the previous update.
+    pub fn released(&self, keys: RemoteKey) -> bool {
+        (self.previous - self.keys).intersects(keys)
+    }
+
+    pub fn remote_present(&self) -> bool {
+        self.remote
+    }
+
+    pub fn headphones_present(&self) -> bool {
+        self.headphones
+    }
+}
+
+impl Default for RemoteState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,457 @@
+//! Deflate decompression (RFC 1951), wrapped in zlib (RFC 1950) for PNG
+//! image data, or raw for ZIP entries.
+//!
+//! Codes are decoded a bit at a time, which is slow next to a table based
+//! decoder, but small, and fast enough for images that fit in a texture.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InflateError {
+    /// The data ended in the middle of the stream.
+    Truncated,
+    /// The data is not a valid zlib stream, or decompresses to more than
+    /// the limit.
+    Corrupt,
+}
+
+/// The longest Huffman code, in bits.
+const MAX_BITS: usize = 15;
+
+/// Literal/length and distance symbols, including two unused of each.
+const MAX_LITERALS: usize = 288;
+const MAX_DISTANCES: usize = 30;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order code lengths of the code length code are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Bytes of output kept for back references.
+const WINDOW_LEN: usize = 32 * 1024;
+
+/// Decompress a zlib stream, checking its checksum. Fails rather than
+/// growing past `limit` bytes.
+pub(crate) fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    if data.len() < 2 {
+        return Err(InflateError::Truncated);
+    }
+
+    // Deflate, with a valid header check, and no preset dictionary.
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || u16::from_be_bytes([cmf, flg]) % 31 != 0 || flg & 0x20 != 0 {
+        return Err(InflateError::Corrupt);
+    }
+
+    let mut inflater = Inflater::new(&data[2..]);
+    // Deflate expands data at most 1032 times, so a bogus `limit` doesn't
+    // reserve more than the data could hold.
+    let mut out = Vec::with_capacity(limit.min(data.len().saturating_mul(1032)));
+
+    loop {
+        let len = out.len();
+        out.resize(len + WINDOW_LEN, 0);
+
+        let read = inflater.read(&mut out[len..])?;
+        out.truncate(len + read);
+
+        if out.len() > limit {
+            return Err(InflateError::Corrupt);
+        }
+
+        if read == 0 {
+            break;
+        }
+    }
+
+    // The stream ends on a byte boundary, so the checksum follows it.
+    let mut input = inflater.into_input();
+    let mut checksum = [0; 4];
+    for byte in checksum.iter_mut() {
+        *byte = input.next_byte()?;
+    }
+
+    if u32::from_be_bytes(checksum) != adler32(&out) {
+        return Err(InflateError::Corrupt);
+    }
+
+    Ok(out)
+}
+
+/// Where compressed data is read from, a byte at a time.
+pub(crate) trait Input {
+    /// The next byte, or `InflateError::Truncated` at the end.
+    fn next_byte(&mut self) -> Result<u8, InflateError>;
+}
+
+impl Input for &[u8] {
+    fn next_byte(&mut self) -> Result<u8, InflateError> {
+        let (&byte, rest) = self.split_first().ok_or(InflateError::Truncated)?;
+        *self = rest;
+        Ok(byte)
+    }
+}
+
+/// A raw deflate stream (RFC 1951), decompressed as it is read, so only the
+/// last 32 KiB of output is kept.
+pub(crate) struct Inflater<I> {
+    bits: Bits<I>,
+    state: State,
+    /// Set once the last block has started.
+    last: bool,
+    /// The most recent output, as a ring buffer.
+    window: Vec<u8>,
+    /// Bytes output so far.
+    total: usize,
+    /// A back reference still being copied out: bytes left, and distance.
+    copy: (usize, usize),
+}
+
+enum State {
+    /// Before a block's header.
+    Header,
+    /// In an uncompressed block, with this many bytes left.
+    Stored(usize),
+    /// In a compressed block, with its literal/length and distance codes,
+    /// boxed as they are much larger than the other states.
+    Codes(Box<(Huffman, Huffman)>),
+    Done,
+}
+
+impl<I: Input> Inflater<I> {
+    pub(crate) fn new(input: I) -> Self {
+        Self {
+            bits: Bits::new(input),
+            state: State::Header,
+            last: false,
+            window: alloc::vec![0; WINDOW_LEN],
+            total: 0,
+            copy: (0, 0),
+        }
+    }
+
+    /// Decompress into `buf`, returning how much was written, which is only
+    /// 0 at the end of the stream.
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> Result<usize, InflateError> {
+        let mut len = 0;
+
+        while len < buf.len() {
+            if self.copy.0 > 0 {
+                let (left, distance) = self.copy;
+                buf[len] = self.push(self.window[(self.total - distance) % WINDOW_LEN]);
+                len += 1;
+                self.copy.0 = left - 1;
+                continue;
+            }
+
+            match &mut self.state {
+                State::Header if self.last => self.state = State::Done,
+                State::Header => {
+                    self.last = self.bits.take(1)? == 1;
+                    self.state = match self.bits.take(2)? {
+                        0 => State::Stored(stored_len(&mut self.bits)?),
+                        1 => State::Codes(Box::new(fixed_codes())),
+                        2 => State::Codes(Box::new(dynamic_codes(&mut self.bits)?)),
+                        _ => return Err(InflateError::Corrupt),
+                    };
+                }
+                State::Stored(0) => self.state = State::Header,
+                State::Stored(left) => {
+                    *left -= 1;
+                    let byte = self.bits.input.next_byte()?;
+                    buf[len] = self.push(byte);
+                    len += 1;
+                }
+                State::Codes(codes) => {
+                    let (literals, distances) = &**codes;
+                    match code(&mut self.bits, literals, distances)? {
+                        Code::Literal(byte) => {
+                            buf[len] = self.push(byte);
+                            len += 1;
+                        }
+                        Code::End => self.state = State::Header,
+                        Code::Copy(_, distance) if distance > self.total => {
+                            return Err(InflateError::Corrupt)
+                        }
+                        Code::Copy(left, distance) => self.copy = (left, distance),
+                    }
+                }
+                State::Done => break,
+            }
+        }
+
+        Ok(len)
+    }
+
+    #[cfg(feature = "zip")]
+    pub(crate) fn input(&self) -> &I {
+        &self.bits.input
+    }
+
+    /// The input, positioned after the end of the stream once `read` has
+    /// returned 0.
+    pub(crate) fn into_input(self) -> I {
+        self.bits.input
+    }
+
+    fn push(&mut self, byte: u8) -> u8 {
+        self.window[self.total % WINDOW_LEN] = byte;
+        self.total += 1;
+        byte
+    }
+}
+
+/// Reads bits from the least significant end of each byte, as deflate
+/// stores them.
+struct Bits<I> {
+    input: I,
+    buf: u32,
+    /// Bits left in `buf`, always under 8 between reads.
+    count: u32,
+}
+
+impl<I: Input> Bits<I> {
+    fn new(input: I) -> Self {
+        Self {
+            input,
+            buf: 0,
+            count: 0,
+        }
+    }
+
+    /// The next `n` bits, up to 16.
+    fn take(&mut self, n: u32) -> Result<u32, InflateError> {
+        while self.count < n {
+            let byte = self.input.next_byte()?;
+            self.buf |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skip to the next byte boundary. No whole bytes are buffered, so the
+    /// input continues from there.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length, and
+/// the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: [u16; MAX_LITERALS],
+}
+
+impl Huffman {
+    /// Build the code from the code length of each symbol, 0 for unused
+    /// symbols.
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // More codes of a length than fit is invalid. Fewer is allowed, as
+        // a single distance code is stored that way.
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(InflateError::Corrupt);
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = [0u16; MAX_LITERALS];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode<I: Input>(&self, bits: &mut Bits<I>) -> Result<u16, InflateError> {
+        // The first code of the current length, and the index of its symbol.
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(InflateError::Corrupt)
+    }
+}
+
+/// Read the header of an uncompressed block, returning its length.
+fn stored_len<I: Input>(bits: &mut Bits<I>) -> Result<usize, InflateError> {
+    bits.align();
+
+    let mut header = [0; 4];
+    for byte in header.iter_mut() {
+        *byte = bits.input.next_byte()?;
+    }
+
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(InflateError::Corrupt);
+    }
+
+    Ok(len as usize)
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; MAX_LITERALS];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    // Neither has more codes of a length than fit.
+    let literals = Huffman::new(&lengths).unwrap();
+    let distances = Huffman::new(&[5; MAX_DISTANCES]).unwrap();
+    (literals, distances)
+}
+
+/// Read the codes stored at the start of a dynamic block.
+fn dynamic_codes<I: Input>(bits: &mut Bits<I>) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > MAX_DISTANCES {
+        return Err(InflateError::Corrupt);
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = bits.take(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    // Both sets of lengths are stored as one, so repeats may cross over.
+    let total = literal_count + distance_count;
+    let mut lengths = [0u8; 286 + MAX_DISTANCES];
+    let mut i = 0;
+
+    while i < total {
+        let (len, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 if i == 0 => return Err(InflateError::Corrupt),
+            16 => (lengths[i - 1], 3 + bits.take(2)? as usize),
+            17 => (0, 3 + bits.take(3)? as usize),
+            _ => (0, 11 + bits.take(7)? as usize),
+        };
+
+        if i + repeat > total {
+            return Err(InflateError::Corrupt);
+        }
+
+        lengths[i..i + repeat].fill(len);
+        i += repeat;
+    }
+
+    // A block that can't end is invalid.
+    if lengths[256] == 0 {
+        return Err(InflateError::Corrupt);
+    }
+
+    let literals = Huffman::new(&lengths[..literal_count])?;
+    let distances = Huffman::new(&lengths[literal_count..total])?;
+    Ok((literals, distances))
+}
+
+enum Code {
+    Literal(u8),
+    End,
+    /// Copy this many bytes from this far back.
+    Copy(usize, usize),
+}
+
+/// Decode the next code of a compressed block.
+fn code<I: Input>(
+    bits: &mut Bits<I>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<Code, InflateError> {
+    let symbol = literals.decode(bits)? as usize;
+    let symbol = match symbol {
+        0..=255 => return Ok(Code::Literal(symbol as u8)),
+        256 => return Ok(Code::End),
+        _ => symbol - 257,
+    };
+
+    if symbol >= LENGTH_BASE.len() {
+        return Err(InflateError::Corrupt);
+    }
+    let len = LENGTH_BASE[symbol] as usize + bits.take(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+    let symbol = distances.decode(bits)? as usize;
+    if symbol >= DISTANCE_BASE.len() {
+        return Err(InflateError::Corrupt);
+    }
+    let distance =
+        DISTANCE_BASE[symbol] as usize + bits.take(DISTANCE_EXTRA[symbol] as u32)? as usize;
+
+    // The copy may overlap what it writes, repeating it.
+    Ok(Code::Copy(len, distance))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // The most bytes that can be summed before `b` could overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
@@ -0,0 +1,285 @@
+//! Image loading into GE-ready textures.
+//!
+//! Loaders in this module decode an in-memory image file into a [`Texture`],
+//! which holds pixel data laid out the way `sceGuTexImage` expects it: a
+//! power-of-two sized, 16-byte aligned buffer. Some can also decode into a
+//! plain [`Image`], for pixels that aren't going straight to the GE.
+
+use crate::sys::TexturePixelFormat;
+use crate::{cpu, gu};
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+pub(crate) mod inflate;
+pub mod png;
+pub mod tga;
+
+/// The largest texture dimension the GE can sample from.
+pub const MAX_TEXTURE_SIZE: u32 = 512;
+
+/// A decoded image, as 8-bit RGBA pixels, row by row from the top.
+///
+/// Unlike a `Texture`, rows aren't padded, and the size isn't limited to
+/// what the GE can sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// A texture in main memory, ready to be bound with `sceGuTexImage`.
+///
+/// The backing buffer is padded to power-of-two dimensions. `width` and
+/// `height` describe the part of the buffer that holds the actual image, the
+/// rest is zeroed.
+pub struct Texture {
+    width: u32,
+    height: u32,
+    buffer_width: u32,
+    buffer_height: u32,
+    format: TexturePixelFormat,
+    swizzled: bool,
+    data: TextureData,
+    /// Set when the CPU may have pixel data in its cache that the GE can't see.
+    dirty: Cell<bool>,
+}
+
+impl Texture {
+    /// Create a new zeroed texture with room for a `width` x `height` image.
+    ///
+    /// Only the direct color formats (`Psm5650`, `Psm5551`, `Psm4444` and
+    /// `Psm8888`) are supported.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either dimension is zero or larger than `MAX_TEXTURE_SIZE`,
+    /// or if `format` is not a direct color format.
+    pub fn new(width: u32, height: u32, format: TexturePixelFormat) -> Self {
+        assert!(
+            (1..=MAX_TEXTURE_SIZE).contains(&width) && (1..=MAX_TEXTURE_SIZE).contains(&height),
+            "texture dimensions must be within 1..={}",
+            MAX_TEXTURE_SIZE
+        );
+
+        let bpp = bytes_per_pixel(format).expect("unsupported texture pixel format");
+
+        // Rows must span at least one 16 byte swizzle block, and there must be
+        // at least one block worth (8) of rows.
+        let buffer_width = width.next_power_of_two().max(16 / bpp as u32);
+        let buffer_height = height.next_power_of_two().max(8);
+
+        let len = buffer_width as usize * buffer_height as usize * bpp;
+
+        Self {
+            width,
+            height,
+            buffer_width,
+            buffer_height,
+            format,
+            swizzled: false,
+            data: TextureData::new(len),
+            dirty: Cell::new(true),
+        }
+    }
+
+    /// Create a `Psm8888` texture from `width` x `height` RGBA pixels, row by
+    /// row from the top, such as `Image::rgba`, and swizzle it if `swizzled`
+    /// is set. `Gu` binds it in whichever order it ends up in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either dimension is zero or larger than `MAX_TEXTURE_SIZE`,
+    /// or if `rgba` isn't exactly 4 bytes per pixel.
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8], swizzled: bool) -> Self {
+        assert_eq!(
+            rgba.len(),
+            width as usize * height as usize * 4,
+            "RGBA data must be 4 bytes per pixel"
+        );
+
+        let mut texture = Self::new(width, height, TexturePixelFormat::Psm8888);
+        for (y, src) in rgba.chunks_exact(width as usize * 4).enumerate() {
+            texture.row_mut(y as u32)[..src.len()].copy_from_slice(src);
+        }
+
+        if swizzled {
+            texture.swizzle();
+        }
+
+        texture
+    }
+
+    /// Width of the image, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Width of the backing buffer, in pixels. This is what should be passed
+    /// as both the width and the buffer width to `sceGuTexImage`.
+    pub fn buffer_width(&self) -> u32 {
+        self.buffer_width
+    }
+
+    /// Height of the backing buffer, in pixels.
+    pub fn buffer_height(&self) -> u32 {
+        self.buffer_height
+    }
+
+    pub fn format(&self) -> TexturePixelFormat {
+        self.format
+    }
+
+    /// Size of one pixel in bytes.
+    pub fn bytes_per_pixel(&self) -> usize {
+        // Checked in `Texture::new`.
+        bytes_per_pixel(self.format).unwrap()
+    }
+
+    /// Length in bytes of one row of the backing buffer.
+    pub fn stride(&self) -> usize {
+        self.buffer_width as usize * self.bytes_per_pixel()
+    }
+
+    /// Whether the pixel data is currently swizzled. This must be passed on to
+    /// `sceGuTexMode`.
+    pub fn is_swizzled(&self) -> bool {
+        self.swizzled
+    }
+
+    /// The raw backing buffer.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The raw backing buffer, mutably.
+    ///
+    /// If the texture is swizzled, this data is in swizzled order.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.dirty.set(true);
+        &mut self.data
+    }
+
+    /// A mutable view of row `y` of the backing buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture is swizzled, or if `y` is outside the buffer.
+    pub fn row_mut(&mut self, y: u32) -> &mut [u8] {
+        assert!(!self.swizzled, "cannot access rows of a swizzled texture");
+
+        let stride = self.stride();
+        let start = y as usize * stride;
+        self.dirty.set(true);
+        &mut self.data[start..start + stride]
+    }
+
+    /// Swizzle the pixel data in place for faster GE texture reads.
+    ///
+    /// Does nothing if the texture is already swizzled.
+    pub fn swizzle(&mut self) {
+        if self.swizzled {
+            return;
+        }
+
+        let mut out = TextureData::new(self.data.len());
+        gu::swizzle_into(&self.data, &mut out, self.stride());
+
+        self.data = out;
+        self.swizzled = true;
+        self.dirty.set(true);
+    }
+
+    /// Undo `swizzle`, restoring linear row order.
+    ///
+    /// Does nothing if the texture is not swizzled.
+    pub fn unswizzle(&mut self) {
+        if !self.swizzled {
+            return;
+        }
+
+        let mut out = TextureData::new(self.data.len());
+        gu::unswizzle_into(&self.data, &mut out, self.stride());
+
+        self.data = out;
+        self.swizzled = false;
+        self.dirty.set(true);
+    }
+
+    /// Pointer to the pixel data, for `sceGuTexImage`.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    /// Write the pixel data back from the data cache, if it was modified since
+    /// the last writeback. Must be done before the GE samples the texture.
+    pub fn writeback(&self) {
+        if self.dirty.replace(false) {
+            cpu::dcache_writeback_range(self.data.as_ptr(), self.data.len());
+        }
+    }
+}
+
+/// Size of a pixel in bytes, for direct color formats.
+pub(crate) fn bytes_per_pixel(format: TexturePixelFormat) -> Option<usize> {
+    match format {
+        TexturePixelFormat::Psm5650 | TexturePixelFormat::Psm5551 | TexturePixelFormat::Psm4444 => {
+            Some(2)
+        }
+        TexturePixelFormat::Psm8888 => Some(4),
+        _ => None,
+    }
+}
+
+/// A zero-initialized heap buffer aligned to 16 bytes, as the GE requires for
+/// texture data.
+struct TextureData {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl TextureData {
+    const ALIGN: usize = 16;
+
+    fn new(len: usize) -> Self {
+        let layout = Self::layout(len);
+        let ptr = unsafe { alloc_zeroed(layout) };
+
+        match NonNull::new(ptr) {
+            Some(ptr) => Self { ptr, len },
+            None => handle_alloc_error(layout),
+        }
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len, Self::ALIGN).unwrap()
+    }
+}
+
+impl Deref for TextureData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for TextureData {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for TextureData {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr(), Self::layout(self.len)) }
+    }
+}
@@ -0,0 +1,280 @@
+//! PNG loader.
+//!
+//! Supports non-interlaced images with 8 bits per channel, in any color
+//! type: greyscale, RGB, palette, and greyscale or RGB with alpha. Palette
+//! transparency from the `tRNS` chunk is applied. Other ancillary chunks,
+//! like gamma, are ignored, and chunk checksums aren't checked, though the
+//! image data's checksum is.
+
+use super::inflate::{self, InflateError};
+use super::{Image, Texture, MAX_TEXTURE_SIZE};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+const COLOR_GREY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GREY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngError {
+    /// The data doesn't start with the PNG signature.
+    NotPng,
+    /// The file ended before all of the image data was read.
+    Truncated,
+    /// A chunk is malformed or missing, or the image data can't be
+    /// decompressed.
+    Corrupt,
+    /// The color type is not one defined by PNG.
+    UnsupportedColorType(u8),
+    /// Only 8 bits per channel are supported.
+    UnsupportedBitDepth(u8),
+    /// Interlaced images are not supported.
+    Interlaced,
+    /// The image is empty, or too large. For `load`, that is larger than
+    /// `MAX_TEXTURE_SIZE` in a dimension.
+    InvalidDimensions { width: u32, height: u32 },
+}
+
+impl From<InflateError> for PngError {
+    fn from(e: InflateError) -> Self {
+        match e {
+            InflateError::Truncated => PngError::Truncated,
+            InflateError::Corrupt => PngError::Corrupt,
+        }
+    }
+}
+
+/// Decode a PNG file into RGBA pixels.
+pub fn decode(data: &[u8]) -> Result<Image, PngError> {
+    decode_up_to(data, u32::MAX)
+}
+
+/// Decode a PNG file into a `Psm8888` texture.
+///
+/// The returned texture is not swizzled, call `Texture::swizzle` on it if
+/// needed.
+pub fn load(data: &[u8]) -> Result<Texture, PngError> {
+    let image = decode_up_to(data, MAX_TEXTURE_SIZE)?;
+    Ok(Texture::from_rgba(
+        image.width,
+        image.height,
+        &image.rgba,
+        false,
+    ))
+}
+
+/// Decode an image no larger than `max_size` in either dimension, which is
+/// checked before anything is decompressed.
+fn decode_up_to(data: &[u8], max_size: u32) -> Result<Image, PngError> {
+    let mut rest = match data.strip_prefix(&SIGNATURE[..]) {
+        Some(rest) => rest,
+        None if SIGNATURE.starts_with(data) => return Err(PngError::Truncated),
+        None => return Err(PngError::NotPng),
+    };
+
+    let header = match next_chunk(&mut rest)? {
+        (b"IHDR", body) => Header::parse(body)?,
+        _ => return Err(PngError::Corrupt),
+    };
+
+    let Header { width, height, .. } = header;
+    if width == 0 || height == 0 || width > max_size || height > max_size {
+        return Err(PngError::InvalidDimensions { width, height });
+    }
+
+    let channels = match header.color_type {
+        COLOR_GREY | COLOR_PALETTE => 1,
+        COLOR_GREY_ALPHA => 2,
+        COLOR_RGB => 3,
+        COLOR_RGBA => 4,
+        other => return Err(PngError::UnsupportedColorType(other)),
+    };
+
+    if header.bit_depth != 8 {
+        return Err(PngError::UnsupportedBitDepth(header.bit_depth));
+    }
+
+    if header.interlaced {
+        return Err(PngError::Interlaced);
+    }
+
+    // The pixels must fit in memory as RGBA, and as stored, where each row
+    // starts with its filter type.
+    let too_large = PngError::InvalidDimensions { width, height };
+    let pixel_count = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|n| n.checked_mul(4).is_some())
+        .ok_or(too_large)?;
+    let stride = width as usize * channels;
+    let raw_len = (pixel_count * channels)
+        .checked_add(height as usize)
+        .ok_or(too_large)?;
+
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut compressed = Vec::new();
+
+    loop {
+        match next_chunk(&mut rest)? {
+            (b"PLTE", body) => {
+                if body.len() % 3 != 0 || body.len() > 256 * 3 {
+                    return Err(PngError::Corrupt);
+                }
+
+                palette = body
+                    .chunks_exact(3)
+                    .map(|c| [c[0], c[1], c[2], 0xff])
+                    .collect();
+            }
+            (b"tRNS", body) if header.color_type == COLOR_PALETTE => {
+                for (entry, &alpha) in palette.iter_mut().zip(body) {
+                    entry[3] = alpha;
+                }
+            }
+            (b"IDAT", body) => compressed.extend_from_slice(body),
+            (b"IEND", _) => break,
+            _ => {}
+        }
+    }
+
+    let raw = inflate::decompress(&compressed, raw_len)?;
+    if raw.len() != raw_len {
+        return Err(PngError::Corrupt);
+    }
+
+    let pixels = unfilter(&raw, stride, channels)?;
+
+    let rgba = match header.color_type {
+        COLOR_GREY => pixels.iter().flat_map(|&v| [v, v, v, 0xff]).collect(),
+        COLOR_GREY_ALPHA => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        COLOR_RGB => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 0xff])
+            .collect(),
+        COLOR_PALETTE => {
+            let mut rgba = Vec::with_capacity(pixels.len() * 4);
+            for &index in &pixels {
+                let entry = palette.get(index as usize).ok_or(PngError::Corrupt)?;
+                rgba.extend_from_slice(entry);
+            }
+            rgba
+        }
+        _ => pixels,
+    };
+
+    Ok(Image {
+        width,
+        height,
+        rgba,
+    })
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> Result<Self, PngError> {
+        if body.len() != 13 {
+            return Err(PngError::Corrupt);
+        }
+
+        // Compression and filter methods other than 0 aren't defined.
+        if body[10] != 0 || body[11] != 0 {
+            return Err(PngError::Corrupt);
+        }
+
+        Ok(Self {
+            width: u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+            height: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+            bit_depth: body[8],
+            color_type: body[9],
+            interlaced: body[12] != 0,
+        })
+    }
+}
+
+/// Split the next chunk off `rest`, as its type and body.
+fn next_chunk<'a>(rest: &mut &'a [u8]) -> Result<(&'a [u8; 4], &'a [u8]), PngError> {
+    if rest.len() < 8 {
+        return Err(PngError::Truncated);
+    }
+
+    let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+    let kind = rest[4..8].try_into().unwrap();
+
+    // The body is followed by a 4 byte checksum.
+    if rest.len() - 8 < len || rest.len() - 8 - len < 4 {
+        return Err(PngError::Truncated);
+    }
+
+    let body = &rest[8..8 + len];
+    *rest = &rest[8 + len + 4..];
+    Ok((kind, body))
+}
+
+/// Undo the filter each row was stored with, returning the pixel bytes.
+fn unfilter(raw: &[u8], stride: usize, bpp: usize) -> Result<Vec<u8>, PngError> {
+    let rows = raw.len() / (stride + 1);
+    let mut pixels = vec![0u8; stride * rows];
+    // What the first row is filtered against.
+    let zeros = vec![0u8; stride];
+
+    for (y, line) in raw.chunks_exact(stride + 1).enumerate() {
+        let (above, rest) = pixels.split_at_mut(y * stride);
+        let prior = match y {
+            0 => &zeros[..],
+            _ => &above[(y - 1) * stride..],
+        };
+        let row = &mut rest[..stride];
+        let filter = line[0];
+
+        for i in 0..stride {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = prior[i];
+            let c = if i >= bpp { prior[i - bpp] } else { 0 };
+
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(PngError::Corrupt),
+            };
+
+            row[i] = line[1 + i].wrapping_add(predicted);
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Whichever of left, above and upper left is closest to `a + b - c`.
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
@@ -0,0 +1,174 @@
+//! Truevision TGA loader.
+//!
+//! Supports uncompressed (type 2) and RLE compressed (type 10) true-color
+//! images with 24 or 32 bits per pixel. The result is always a `Psm8888`
+//! texture.
+
+use super::{Texture, MAX_TEXTURE_SIZE};
+use crate::sys::TexturePixelFormat;
+
+const HEADER_LEN: usize = 18;
+
+const TYPE_COLOR_MAPPED: u8 = 1;
+const TYPE_TRUE_COLOR: u8 = 2;
+const TYPE_RLE_COLOR_MAPPED: u8 = 9;
+const TYPE_RLE_TRUE_COLOR: u8 = 10;
+
+/// Image descriptor bit set when pixels are stored right-to-left.
+const DESCRIPTOR_RIGHT_TO_LEFT: u8 = 1 << 4;
+/// Image descriptor bit set when rows are stored top-to-bottom.
+const DESCRIPTOR_TOP_TO_BOTTOM: u8 = 1 << 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TgaError {
+    /// The file ended before all of the image data was read.
+    Truncated,
+    /// Color-mapped (palette) images are not supported.
+    ColorMapped,
+    /// The image type is not one of the supported true-color types.
+    UnsupportedImageType(u8),
+    /// Only 24 and 32 bits per pixel are supported.
+    UnsupportedBitDepth(u8),
+    /// The image is empty, or larger than `MAX_TEXTURE_SIZE` in a dimension.
+    InvalidDimensions { width: u16, height: u16 },
+}
+
+/// Decode a TGA file into a `Psm8888` texture.
+///
+/// The returned texture is not swizzled, call `Texture::swizzle` on it if
+/// needed.
+pub fn load(data: &[u8]) -> Result<Texture, TgaError> {
+    if data.len() < HEADER_LEN {
+        return Err(TgaError::Truncated);
+    }
+
+    let id_len = data[0] as usize;
+    let color_map_type = data[1];
+    let image_type = data[2];
+    let color_map_len = u16::from_le_bytes([data[5], data[6]]) as usize;
+    let color_map_entry_bits = data[7] as usize;
+    let width = u16::from_le_bytes([data[12], data[13]]);
+    let height = u16::from_le_bytes([data[14], data[15]]);
+    let bits = data[16];
+    let descriptor = data[17];
+
+    let rle = match image_type {
+        TYPE_TRUE_COLOR => false,
+        TYPE_RLE_TRUE_COLOR => true,
+        TYPE_COLOR_MAPPED | TYPE_RLE_COLOR_MAPPED => return Err(TgaError::ColorMapped),
+        other => return Err(TgaError::UnsupportedImageType(other)),
+    };
+
+    let bpp = match bits {
+        24 => 3,
+        32 => 4,
+        other => return Err(TgaError::UnsupportedBitDepth(other)),
+    };
+
+    if width == 0
+        || height == 0
+        || width as u32 > MAX_TEXTURE_SIZE
+        || height as u32 > MAX_TEXTURE_SIZE
+    {
+        return Err(TgaError::InvalidDimensions { width, height });
+    }
+
+    // True-color images may still carry a (unused) color map, skip over it.
+    let color_map_bytes = if color_map_type != 0 {
+        color_map_len * color_map_entry_bits.div_ceil(8)
+    } else {
+        0
+    };
+
+    let start = HEADER_LEN + id_len + color_map_bytes;
+    let pixels = data.get(start..).ok_or(TgaError::Truncated)?;
+
+    let mut texture = Texture::new(width as u32, height as u32, TexturePixelFormat::Psm8888);
+    let mut reader = PixelReader::new(pixels, bpp, rle);
+
+    let flip_x = descriptor & DESCRIPTOR_RIGHT_TO_LEFT != 0;
+    let top_to_bottom = descriptor & DESCRIPTOR_TOP_TO_BOTTOM != 0;
+
+    for i in 0..height as u32 {
+        let y = if top_to_bottom {
+            i
+        } else {
+            height as u32 - 1 - i
+        };
+        let row = texture.row_mut(y);
+
+        for j in 0..width as usize {
+            let x = if flip_x { width as usize - 1 - j } else { j };
+            let bgra = reader.next()?;
+
+            // Stored as BGR(A), the GE wants RGBA.
+            row[x * 4..x * 4 + 4].copy_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+        }
+    }
+
+    Ok(texture)
+}
+
+/// Reads BGRA pixels, expanding RLE packets if needed.
+struct PixelReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bpp: usize,
+    rle: bool,
+    /// Pixels left in the current RLE packet.
+    remaining: u8,
+    /// Whether the current RLE packet repeats a single pixel.
+    repeat: bool,
+    pixel: [u8; 4],
+}
+
+impl<'a> PixelReader<'a> {
+    fn new(data: &'a [u8], bpp: usize, rle: bool) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bpp,
+            rle,
+            remaining: 0,
+            repeat: false,
+            pixel: [0; 4],
+        }
+    }
+
+    fn read_pixel(&mut self) -> Result<[u8; 4], TgaError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + self.bpp)
+            .ok_or(TgaError::Truncated)?;
+        self.pos += self.bpp;
+
+        let alpha = if self.bpp == 4 { bytes[3] } else { 0xff };
+        Ok([bytes[0], bytes[1], bytes[2], alpha])
+    }
+
+    fn next(&mut self) -> Result<[u8; 4], TgaError> {
+        if !self.rle {
+            return self.read_pixel();
+        }
+
+        if self.remaining == 0 {
+            let header = *self.data.get(self.pos).ok_or(TgaError::Truncated)?;
+            self.pos += 1;
+
+            self.remaining = (header & 0x7f) + 1;
+            self.repeat = header & 0x80 != 0;
+
+            if self.repeat {
+                self.pixel = self.read_pixel()?;
+            }
+        }
+
+        self.remaining -= 1;
+
+        if self.repeat {
+            Ok(self.pixel)
+        } else {
+            self.read_pixel()
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Replacing files without leaving them half written.
+
+use super::{c_path, File, IoError};
+use crate::sys;
+use alloc::boxed::Box;
+use alloc::format;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomicWriteError {
+    /// Writing the temporary file failed. The destination is untouched.
+    Io(IoError),
+    /// The new contents are complete in `path.tmp`, but couldn't be moved
+    /// over the destination.
+    ///
+    /// Firmware that can't rename over an existing file gets the
+    /// destination removed first. If the rename still fails, the
+    /// destination is gone, and `path.tmp` is the only copy. A power loss
+    /// between removing and renaming leaves the same state, so a program
+    /// loading a save should fall back to `path.tmp` when `path` is
+    /// missing.
+    Rename(IoError),
+}
+
+impl From<IoError> for AtomicWriteError {
+    fn from(e: IoError) -> Self {
+        AtomicWriteError::Io(e)
+    }
+}
+
+/// Replace the file at `path` with `bytes`, so that a crash or power loss
+/// leaves either the old contents or the new ones, never a mix.
+///
+/// This is `AtomicWriter` writing everything at once.
+///
+/// ```no_run
+/// psp::io::write_atomic("ms0:/PSP/SAVEDATA/MYGAME/progress.bin", &[3, 1, 4]).unwrap();
+/// ```
+///
+/// To check a program's saving, loop this with a counter in the contents,
+/// and pull the battery with no charger plugged in while the Memory Stick
+/// light is blinking. After booting again, the file should hold the full
+/// contents of one of the writes, never a shorter or mixed file. Repeat a
+/// few dozen times, as most pulls land between writes.
+pub fn write_atomic(path: &str, bytes: &[u8]) -> Result<(), AtomicWriteError> {
+    let mut writer = AtomicWriter::create(path)?;
+    writer.write_all(bytes)?;
+    writer.commit()
+}
+
+/// A file written to `path.tmp`, then moved over `path` by `commit`.
+///
+/// Until then the file at `path` is untouched. Dropping the writer without
+/// committing deletes the temporary file.
+#[derive(Debug)]
+pub struct AtomicWriter {
+    /// Only taken by `commit`.
+    file: Option<File>,
+    /// Both NUL terminated.
+    path: Box<[u8]>,
+    tmp_path: Box<[u8]>,
+}
+
+impl AtomicWriter {
+    /// Start replacing the file at `path`. A temporary file left by an
+    /// earlier write that didn't finish is deleted first.
+    pub fn create(path: &str) -> Result<Self, IoError> {
+        let tmp = format!("{}.tmp", path);
+        let dest_path = c_path(path)?;
+        let tmp_path = c_path(&tmp)?;
+
+        // Fails if there is none, which is fine.
+        unsafe { sys::sceIoRemove(tmp_path.as_ptr()) };
+
+        Ok(Self {
+            file: Some(File::create(&tmp)?),
+            path: dest_path.into_boxed_slice(),
+            tmp_path: tmp_path.into_boxed_slice(),
+        })
+    }
+
+    /// Write from `buf`, returning how many bytes were written.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        self.file().write(buf)
+    }
+
+    /// Write all of `buf`.
+    pub fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError> {
+        self.file().write_all(buf)
+    }
+
+    /// Close the temporary file, which writes it out, and move it over the
+    /// destination.
+    pub fn commit(mut self) -> Result<(), AtomicWriteError> {
+        self.file.take().unwrap().close()?;
+
+        let rename = || unsafe { sys::sceIoRename(self.tmp_path.as_ptr(), self.path.as_ptr()) };
+
+        let mut ret = rename();
+        if ret < 0 && super::stat(&self.path).is_ok() {
+            unsafe { sys::sceIoRemove(self.path.as_ptr()) };
+            ret = rename();
+        }
+
+        match ret {
+            e if e < 0 => Err(AtomicWriteError::Rename(IoError::on_path(&self.path, e))),
+            _ => Ok(()),
+        }
+    }
+
+    fn file(&mut self) -> &mut File {
+        // Only `commit` takes it, which consumes the writer.
+        self.file.as_mut().unwrap()
+    }
+}
+
+impl Drop for AtomicWriter {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            drop(file);
+            unsafe { sys::sceIoRemove(self.tmp_path.as_ptr()) };
+        }
+    }
+}
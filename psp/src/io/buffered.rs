@@ -0,0 +1,345 @@
+//! Buffering, so small reads and writes don't each go to the kernel.
+
+use super::{File, IoError, SeekFrom};
+use crate::sync::Mutex;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::{mem, ptr};
+
+/// Buffer size of `BufReader::new` and `BufWriter::new`.
+pub const DEFAULT_BUF_LEN: usize = 8 * 1024;
+
+/// A `File` read through a buffer.
+///
+/// Each `File::read` is a system call, which makes reading a few bytes at
+/// a time, as parsers tend to, slow. This reads a buffer's worth at a time
+/// instead, and hands it out from memory.
+///
+/// ```no_run
+/// use psp::io::{BufReader, File};
+///
+/// let mut reader = BufReader::new(File::open("ms0:/level.txt").unwrap());
+/// let mut lines = 0;
+///
+/// loop {
+///     let buf = reader.fill_buf().unwrap();
+///     if buf.is_empty() {
+///         break;
+///     }
+///
+///     let len = buf.len();
+///     lines += buf.iter().filter(|&&b| b == b'\n').count();
+///     reader.consume(len);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BufReader {
+    file: File,
+    buf: Box<[u8]>,
+    /// The next byte of `buf` to hand out.
+    pos: usize,
+    /// How much of `buf` holds data from the file.
+    filled: usize,
+}
+
+impl BufReader {
+    pub fn new(file: File) -> Self {
+        Self::with_capacity(DEFAULT_BUF_LEN, file)
+    }
+
+    /// A reader with a buffer of `capacity` bytes. A multiple of
+    /// `mem_stick::cluster_size` reads whole clusters at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(capacity: usize, file: File) -> Self {
+        assert!(capacity > 0, "BufReader capacity must not be 0");
+
+        Self {
+            file,
+            buf: alloc::vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// The file, which reading from directly skips what is buffered.
+    pub fn get_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// The file, dropping what is buffered. Its position is past the
+    /// buffered data.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The buffered data, not yet read.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// The buffered data, reading more from the file first if there is
+    /// none. Empty at the end of the file.
+    ///
+    /// Nothing is taken out of the buffer until `consume` is called.
+    pub fn fill_buf(&mut self) -> Result<&[u8], IoError> {
+        if self.pos == self.filled {
+            self.filled = self.file.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+
+        Ok(self.buffer())
+    }
+
+    /// Mark `len` bytes of the buffer as read.
+    pub fn consume(&mut self, len: usize) {
+        self.pos = (self.pos + len).min(self.filled);
+    }
+
+    /// Read into `buf`, returning how many bytes were read, which is 0 at
+    /// the end of the file.
+    ///
+    /// Reads at least as large as the buffer go straight to the file when
+    /// nothing is buffered.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if self.pos == self.filled && buf.len() >= self.buf.len() {
+            return self.file.read(buf);
+        }
+
+        let data = self.fill_buf()?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+
+    /// Move to `pos`, returning the new position from the start.
+    ///
+    /// Moves within the buffer keep it. Any other move drops it, and the
+    /// next read fills it again from the new position.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, IoError> {
+        let buffered = (self.filled - self.pos) as i64;
+
+        if let SeekFrom::Current(offset) = pos {
+            let target = self.pos as i64 + offset;
+
+            if (0..=self.filled as i64).contains(&target) {
+                // Where the file is, less what hasn't been handed out.
+                let end = self.file.seek(SeekFrom::Current(0))?;
+                self.pos = target as usize;
+                return Ok(end - (self.filled - self.pos) as u64);
+            }
+
+            // The file is ahead of the reader by what is buffered.
+            let pos = self.file.seek(SeekFrom::Current(offset - buffered))?;
+            self.discard();
+            return Ok(pos);
+        }
+
+        let pos = self.file.seek(pos)?;
+        self.discard();
+        Ok(pos)
+    }
+
+    fn discard(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+}
+
+/// A `File` written through a buffer.
+///
+/// Small writes are gathered in memory and written a buffer's worth at a
+/// time. `flush` writes what is gathered so far.
+///
+/// The buffer is flushed on drop too, but there is no way to return an
+/// error from there. Flush before dropping, or keep the writer's
+/// `drop_error` to check after.
+#[derive(Debug)]
+pub struct BufWriter {
+    file: File,
+    buf: Vec<u8>,
+    /// Where to leave the error of the flush on drop, once asked for.
+    drop_error: Option<DropError>,
+}
+
+impl BufWriter {
+    pub fn new(file: File) -> Self {
+        Self::with_capacity(DEFAULT_BUF_LEN, file)
+    }
+
+    /// A writer with a buffer of `capacity` bytes. A multiple of
+    /// `mem_stick::cluster_size` writes whole clusters at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(capacity: usize, file: File) -> Self {
+        assert!(capacity > 0, "BufWriter capacity must not be 0");
+
+        Self {
+            file,
+            buf: Vec::with_capacity(capacity),
+            drop_error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// The file, which writing to directly skips ahead of what is
+    /// buffered.
+    pub fn get_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Flush the buffer and return the file.
+    ///
+    /// If flushing fails, the buffered data is lost along with the error.
+    pub fn into_inner(mut self) -> Result<File, IoError> {
+        let flushed = self.flush_buf();
+
+        // Moved out without running `Drop`, which would flush again.
+        drop(mem::take(&mut self.buf));
+        drop(self.drop_error.take());
+        let file = unsafe { ptr::read(&self.file) };
+        mem::forget(self);
+
+        flushed.map(|()| file)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// The data written but not yet flushed.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Write from `buf`, returning how many bytes were taken.
+    ///
+    /// Writes that don't fit in the buffer flush it first, and those at
+    /// least as large as the buffer then go straight to the file.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        if self.buf.len() + buf.len() > self.buf.capacity() {
+            self.flush_buf()?;
+        }
+
+        if buf.len() >= self.buf.capacity() {
+            self.file.write(buf)
+        } else {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// Write all of `buf`.
+    pub fn write_all(&mut self, mut buf: &[u8]) -> Result<(), IoError> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(IoError::WriteZero),
+                written => buf = &buf[written..],
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write out everything buffered.
+    pub fn flush(&mut self) -> Result<(), IoError> {
+        self.flush_buf()
+    }
+
+    /// Flush the buffer, then move to `pos`, returning the new position
+    /// from the start.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, IoError> {
+        self.flush_buf()?;
+        self.file.seek(pos)
+    }
+
+    /// A handle to the error of this writer's flush when it is dropped,
+    /// which it can't return itself.
+    ///
+    /// ```no_run
+    /// use psp::io::{BufWriter, File};
+    ///
+    /// let mut writer = BufWriter::new(File::create("ms0:/save.dat").unwrap());
+    /// let drop_error = writer.drop_error();
+    ///
+    /// writer.write_all(b"progress").unwrap();
+    /// drop(writer);
+    ///
+    /// if let Some(e) = drop_error.take_error() {
+    ///     psp::dprintln!("saving failed: {:?}", e);
+    /// }
+    /// ```
+    pub fn drop_error(&mut self) -> DropError {
+        self.drop_error.get_or_insert_with(DropError::new).clone()
+    }
+
+    /// Write out the buffer. On an error, what was written is still taken
+    /// out of it.
+    fn flush_buf(&mut self) -> Result<(), IoError> {
+        let mut written = 0;
+
+        let result = loop {
+            if written == self.buf.len() {
+                break Ok(());
+            }
+
+            match self.file.write(&self.buf[written..]) {
+                Ok(0) => break Err(IoError::WriteZero),
+                Ok(n) => written += n,
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.buf.drain(..written);
+        result
+    }
+}
+
+impl Drop for BufWriter {
+    fn drop(&mut self) {
+        if let (Err(e), Some(drop_error)) = (self.flush_buf(), &self.drop_error) {
+            *drop_error.error.lock() = Some(e);
+        }
+    }
+}
+
+/// The error of a `BufWriter`'s flush on drop, from `BufWriter::drop_error`.
+///
+/// It outlives the writer, and can be sent to another thread to check it
+/// there.
+#[derive(Debug, Clone)]
+pub struct DropError {
+    error: Arc<Mutex<Option<IoError>>>,
+}
+
+impl DropError {
+    fn new() -> Self {
+        Self {
+            error: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The error, if the writer has been dropped and failed to flush,
+    /// clearing it.
+    pub fn take_error(&self) -> Option<IoError> {
+        self.error.lock().take()
+    }
+}
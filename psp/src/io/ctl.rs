@@ -0,0 +1,186 @@
+//! Device specific commands, sent with `sceIoIoctl` to an open file or
+//! with `sceIoDevctl` to a device.
+//!
+//! The kernel takes these as a command number and untyped buffers, which
+//! some commands read pointers out of. The functions here check the
+//! buffers and give the results a type. `ioctl` and `devctl` send any
+//! other command, unsafely.
+//!
+//! Reading a game's files by sector, as an ISO dumper does:
+//!
+//! ```no_run
+//! use psp::io::{ctl, File};
+//!
+//! let mut file = File::open("disc0:/PSP_GAME/USRDIR/data.bin").unwrap();
+//! ctl::umd_seek_sector(&mut file, 16).unwrap();
+//!
+//! let mut sectors = alloc::vec![0; 4 * ctl::SECTOR_LEN];
+//! ctl::umd_read_sectors(&mut file, &mut sectors).unwrap();
+//! ```
+
+use super::{c_path, File, IoError};
+use crate::sys;
+use core::ffi::c_void;
+use core::mem;
+
+/// Size of a UMD sector, in bytes.
+pub const SECTOR_LEN: usize = 2048;
+
+/// `sceIoIoctl` command moving a UMD file to a sector, counted from its
+/// start.
+const IOCTL_UMD_SEEK_SECTOR: u32 = 0x0101_0005;
+
+/// `sceIoIoctl` command getting the sector a UMD file starts at.
+const IOCTL_UMD_START_SECTOR: u32 = 0x0102_0006;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtlError {
+    Io(IoError),
+    /// The device doesn't know the command.
+    Unsupported,
+    /// The buffer isn't a size the command can take.
+    BadBufferLen(usize),
+}
+
+impl From<IoError> for CtlError {
+    fn from(e: IoError) -> Self {
+        CtlError::Io(e)
+    }
+}
+
+impl CtlError {
+    fn from_code(path: &[u8], code: i32) -> Self {
+        match code as u32 {
+            // `ENOTSUP`, and the kernel's own unsupported operation error.
+            0x8001_0086 | 0x8002_0325 => CtlError::Unsupported,
+            _ => CtlError::Io(IoError::on_path(path, code)),
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// The kind of device a file is on, from `device_type`.
+    pub struct DeviceType: u32 {
+        /// Read and written a byte at a time, like the console.
+        const CHAR = 0x01;
+        /// Read and written in blocks, like a raw disc.
+        const BLOCK = 0x04;
+        /// Holds files and directories.
+        const FS = 0x10;
+        /// Another name for a device.
+        const ALIAS = 0x20;
+        const MOUNT_POINT = 0x40;
+    }
+}
+
+/// The kind of device `file` is on.
+pub fn device_type(file: &File) -> Result<DeviceType, CtlError> {
+    match unsafe { sys::sceIoGetDevType(file.fd) } {
+        e if e < 0 => Err(CtlError::from_code(&file.path, e)),
+        ty => Ok(DeviceType::from_bits_truncate(ty as u32)),
+    }
+}
+
+/// The sector of the disc that `file`, on `umd0:/` or `disc0:/`, starts at.
+pub fn umd_start_sector(file: &File) -> Result<u32, CtlError> {
+    let mut sector = 0u32;
+    unsafe { ioctl_raw(file, IOCTL_UMD_START_SECTOR, &mut [], as_bytes(&mut sector))? };
+    Ok(sector)
+}
+
+/// Move `file`, on `umd0:/` or `disc0:/`, to `sector` sectors from its
+/// start.
+pub fn umd_seek_sector(file: &mut File, sector: u32) -> Result<(), CtlError> {
+    let mut sector = sector;
+    unsafe { ioctl_raw(file, IOCTL_UMD_SEEK_SECTOR, as_bytes(&mut sector), &mut [])? };
+    Ok(())
+}
+
+/// Read whole sectors from `file` into `buf`, whose length must be a
+/// multiple of `SECTOR_LEN`, returning how many bytes were read.
+///
+/// Fewer bytes are read only at the end of the file, where the last
+/// sector can be partly filled.
+pub fn umd_read_sectors(file: &mut File, buf: &mut [u8]) -> Result<usize, CtlError> {
+    if buf.is_empty() || buf.len() % SECTOR_LEN != 0 {
+        return Err(CtlError::BadBufferLen(buf.len()));
+    }
+
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+
+    Ok(len)
+}
+
+/// Send `cmd` to the device `file` is on, returning the kernel's
+/// non-negative result.
+///
+/// # Safety
+///
+/// The command decides how `input` and `output` are used. Some read
+/// addresses out of `input`, or write past the buffers' lengths, so the
+/// buffers must be what `cmd` expects.
+pub unsafe fn ioctl(
+    file: &mut File,
+    cmd: u32,
+    input: &mut [u8],
+    output: &mut [u8],
+) -> Result<i32, CtlError> {
+    ioctl_raw(file, cmd, input, output)
+}
+
+/// Send `cmd` to `device`, such as `ms0:`, returning the kernel's
+/// non-negative result.
+///
+/// # Safety
+///
+/// As with `ioctl`, the buffers must be what `cmd` expects.
+pub unsafe fn devctl(
+    device: &str,
+    cmd: u32,
+    input: &mut [u8],
+    output: &mut [u8],
+) -> Result<i32, CtlError> {
+    let device = c_path(device)?;
+
+    match sys::sceIoDevctl(
+        device.as_ptr(),
+        cmd,
+        input.as_mut_ptr() as *mut c_void,
+        input.len() as i32,
+        output.as_mut_ptr() as *mut c_void,
+        output.len() as i32,
+    ) {
+        e if e < 0 => Err(CtlError::from_code(&device, e)),
+        ret => Ok(ret),
+    }
+}
+
+/// `ioctl` on a shared file, for commands that only read from it.
+unsafe fn ioctl_raw(
+    file: &File,
+    cmd: u32,
+    input: &mut [u8],
+    output: &mut [u8],
+) -> Result<i32, CtlError> {
+    match sys::sceIoIoctl(
+        file.fd,
+        cmd,
+        input.as_mut_ptr() as *mut c_void,
+        input.len() as i32,
+        output.as_mut_ptr() as *mut c_void,
+        output.len() as i32,
+    ) {
+        e if e < 0 => Err(CtlError::from_code(&file.path, e)),
+        ret => Ok(ret),
+    }
+}
+
+fn as_bytes(x: &mut u32) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(x as *mut u32 as *mut u8, mem::size_of::<u32>()) }
+}
@@ -0,0 +1,129 @@
+//! Implementations of the `embedded-io` traits.
+//!
+//! Like the methods they call, these block until the kernel is done: `read`
+//! returns once at least one byte was read, or at the end of the file, and
+//! `write` once at least one byte was written. Only `BufWriter` buffers,
+//! so `flush` does nothing for the others. Errors are `IoError`, with the
+//! closest `ErrorKind`.
+
+use super::{BufReader, BufWriter, File, IoError, SeekFrom, Stdout};
+use embedded_io::{BufRead, ErrorKind, ErrorType, Read, Seek, Write};
+
+impl embedded_io::Error for IoError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            IoError::InvalidPath | IoError::IsDirectory => ErrorKind::InvalidInput,
+            IoError::NotFound => ErrorKind::NotFound,
+            IoError::AlreadyExists => ErrorKind::AlreadyExists,
+            IoError::PermissionDenied | IoError::ReadOnly => ErrorKind::PermissionDenied,
+            IoError::WriteZero => ErrorKind::WriteZero,
+            IoError::NoDevice
+            | IoError::MediumRemoved
+            | IoError::NoSpace
+            | IoError::TooManyOpen
+            | IoError::Kernel(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl ErrorType for File {
+    type Error = IoError;
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        File::read(self, buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        nonzero_write(buf, File::write(self, buf))
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, IoError> {
+        File::seek(self, seek_from(pos))
+    }
+}
+
+impl ErrorType for BufReader {
+    type Error = IoError;
+}
+
+impl Read for BufReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        BufReader::read(self, buf)
+    }
+}
+
+impl BufRead for BufReader {
+    fn fill_buf(&mut self) -> Result<&[u8], IoError> {
+        BufReader::fill_buf(self)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        BufReader::consume(self, amt)
+    }
+}
+
+impl Seek for BufReader {
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, IoError> {
+        BufReader::seek(self, seek_from(pos))
+    }
+}
+
+impl ErrorType for BufWriter {
+    type Error = IoError;
+}
+
+impl Write for BufWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        nonzero_write(buf, BufWriter::write(self, buf))
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        BufWriter::flush(self)
+    }
+}
+
+impl Seek for BufWriter {
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, IoError> {
+        BufWriter::seek(self, seek_from(pos))
+    }
+}
+
+impl ErrorType for Stdout {
+    type Error = IoError;
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        nonzero_write(buf, Stdout::write(self, buf))
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+fn seek_from(pos: embedded_io::SeekFrom) -> SeekFrom {
+    match pos {
+        embedded_io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+        embedded_io::SeekFrom::End(offset) => SeekFrom::End(offset),
+        embedded_io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
+    }
+}
+
+/// `embedded_io::Write::write` may only return 0 for an empty `buf`.
+fn nonzero_write(buf: &[u8], written: Result<usize, IoError>) -> Result<usize, IoError> {
+    match written? {
+        0 if !buf.is_empty() => Err(IoError::WriteZero),
+        written => Ok(written),
+    }
+}
@@ -0,0 +1,265 @@
+//! Memory Stick insertion and removal.
+//!
+//! `is_inserted` can be checked at any time, such as before saving.
+//! `on_change` runs a hook whenever the stick is inserted or ejected, so a
+//! game can pause and ask for it back:
+//!
+//! ```no_run
+//! use core::sync::atomic::{AtomicBool, Ordering};
+//! use psp::io::mem_stick::{self, Event};
+//!
+//! static EJECTED: AtomicBool = AtomicBool::new(false);
+//!
+//! mem_stick::on_change(|event| EJECTED.store(event == Event::Ejected, Ordering::Relaxed))
+//!     .unwrap();
+//! ```
+//!
+//! Once the stick is gone, file operations on `ms0:/` fail with
+//! `IoError::MediumRemoved`.
+//!
+//! `free_space` tells whether a save will fit. On a PSP Go started from its
+//! internal storage, it measures that instead.
+
+use super::IoError;
+use crate::critical;
+use crate::sys::{self, MsCbEvent, ThreadAttributes};
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::ptr;
+use core::sync::atomic::{AtomicI32, Ordering};
+
+/// `WATCHER` before the watcher thread is started.
+const IDLE: i32 = 0;
+/// `WATCHER` while the watcher thread registers its callback.
+const STARTING: i32 = 1;
+/// `WATCHER` once the callback is registered. A failure to start is stored
+/// as its negative error code.
+const WATCHING: i32 = 2;
+
+static WATCHER: AtomicI32 = AtomicI32::new(IDLE);
+
+/// `sceIoDevctl` command filling in a `DeviceSize`.
+const DEVCTL_GET_DEVICE_SIZE: u32 = 0x0242_5818;
+
+/// Only accessed through `critical`, apart from the watcher thread reading
+/// the list. Hooks are never removed, so it can do that at any time.
+static mut HOOKS: Hooks = Hooks {
+    head: ptr::null_mut(),
+    tail: ptr::null_mut(),
+};
+
+/// Registered hooks, as a list in registration order.
+struct Hooks {
+    head: *mut Hook,
+    tail: *mut Hook,
+}
+
+struct Hook {
+    f: Box<dyn FnMut(Event) + Send>,
+    next: *mut Hook,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Inserted,
+    Ejected,
+}
+
+/// Whether a Memory Stick is in the slot, asked of the driver each time, so
+/// this works without `on_change`.
+pub fn is_inserted() -> bool {
+    unsafe { sys::MScmIsMediumInserted() == 1 }
+}
+
+/// Free space on the Memory Stick, in bytes.
+///
+/// This is the device the program was started from: the PSP Go's internal
+/// storage, `ef0:/`, if it was started from there, or else `ms0:/`. If that
+/// isn't mounted, the other one is tried. Fails with `IoError::NoDevice` if
+/// neither is.
+pub fn free_space() -> Result<u64, IoError> {
+    let size = device_size()?;
+    Ok(size.free_clusters as u64 * size.cluster_size() as u64)
+}
+
+/// Total space on the Memory Stick, in bytes, from the same device as
+/// `free_space`.
+pub fn total_space() -> Result<u64, IoError> {
+    let size = device_size()?;
+    Ok(size.max_clusters as u64 * size.cluster_size() as u64)
+}
+
+/// The size of a cluster in bytes, from the same device as `free_space`.
+/// Every file takes up a whole number of clusters, so a save of many small
+/// files needs more than the sum of their sizes.
+pub fn cluster_size() -> Result<u32, IoError> {
+    Ok(device_size()?.cluster_size())
+}
+
+/// Register `f` to run whenever a Memory Stick is inserted or ejected.
+///
+/// The first call starts a thread that waits for the driver's
+/// notifications, and hooks run on it, in registration order. They should
+/// be quick, such as setting a flag for the main loop. A stick already in
+/// the slot is not reported.
+pub fn on_change<F: FnMut(Event) + Send + 'static>(f: F) -> Result<(), IoError> {
+    watch()?;
+
+    // Allocated before suspending interrupts.
+    let hook = Box::into_raw(Box::new(Hook {
+        f: Box::new(f),
+        next: ptr::null_mut(),
+    }));
+
+    critical(|| unsafe {
+        match HOOKS.tail.as_mut() {
+            Some(tail) => tail.next = hook,
+            None => HOOKS.head = hook,
+        }
+
+        HOOKS.tail = hook;
+    });
+
+    Ok(())
+}
+
+/// Start the watcher thread, if it isn't already, and wait for its
+/// callback to be registered.
+fn watch() -> Result<(), IoError> {
+    if WATCHER
+        .compare_exchange(IDLE, STARTING, Ordering::Acquire, Ordering::Acquire)
+        .is_ok()
+    {
+        let thread = unsafe {
+            sys::sceKernelCreateThread(
+                b"mem_stick_watcher\0".as_ptr(),
+                watcher_thread,
+                32,
+                // Enough for the hooks.
+                32 * 1024,
+                ThreadAttributes::USER,
+                ptr::null_mut(),
+            )
+        };
+
+        if thread.0 < 0 {
+            WATCHER.store(thread.0, Ordering::Release);
+        } else {
+            unsafe { sys::sceKernelStartThread(thread, 0, ptr::null_mut()) };
+        }
+    }
+
+    loop {
+        match WATCHER.load(Ordering::Acquire) {
+            STARTING => unsafe {
+                sys::sceKernelDelayThread(1000);
+            },
+            WATCHING => return Ok(()),
+            e => return Err(IoError::from_code(e)),
+        }
+    }
+}
+
+unsafe extern "C" fn watcher_thread(_args: usize, _argp: *mut c_void) -> i32 {
+    // Callbacks only run on the thread that created them, while it waits.
+    let id =
+        sys::sceKernelCreateCallback(b"mem_stick_callback\0".as_ptr(), changed, ptr::null_mut());
+    if id.0 < 0 {
+        WATCHER.store(id.0, Ordering::Release);
+        return 0;
+    }
+
+    let ret = sys::MScmRegisterMSInsertEjectCallback(id);
+    if ret < 0 {
+        sys::sceKernelDeleteCallback(id);
+        WATCHER.store(ret, Ordering::Release);
+        return 0;
+    }
+
+    WATCHER.store(WATCHING, Ordering::Release);
+
+    loop {
+        sys::sceKernelSleepThreadCB();
+    }
+}
+
+unsafe extern "C" fn changed(_count: i32, event: i32, _arg: *mut c_void) -> i32 {
+    let event = match event {
+        e if e == MsCbEvent::Inserted as i32 => Event::Inserted,
+        e if e == MsCbEvent::Ejected as i32 => Event::Ejected,
+        _ => return 0,
+    };
+
+    let mut next = critical(|| HOOKS.head);
+    while let Some(hook) = next.as_mut() {
+        (hook.f)(event);
+        next = critical(|| hook.next);
+    }
+
+    0
+}
+
+/// Filled in by `DEVCTL_GET_DEVICE_SIZE`.
+#[repr(C)]
+#[derive(Default)]
+struct DeviceSize {
+    max_clusters: u32,
+    free_clusters: u32,
+    max_sectors: u32,
+    sector_size: u32,
+    sectors_per_cluster: u32,
+}
+
+impl DeviceSize {
+    fn cluster_size(&self) -> u32 {
+        self.sector_size * self.sectors_per_cluster
+    }
+}
+
+fn device_size() -> Result<DeviceSize, IoError> {
+    // The PSP Go's internal storage first, if the program was started
+    // from there.
+    let internal = super::base_path()
+        .get(..4)
+        .is_some_and(|device| device.eq_ignore_ascii_case("ef0:"));
+    let devices: [&[u8]; 2] = match internal {
+        true => [b"ef0:\0", b"ms0:\0"],
+        false => [b"ms0:\0", b"ef0:\0"],
+    };
+
+    devices
+        .iter()
+        .find_map(|device| query_size(device).ok())
+        .ok_or(IoError::NoDevice)
+}
+
+/// Free space on `c_device`, a NUL terminated device name such as `ms0:`,
+/// in bytes.
+pub(crate) fn device_free_space(c_device: &[u8]) -> Result<u64, IoError> {
+    let size = query_size(c_device).map_err(|e| IoError::on_path(c_device, e))?;
+    Ok(size.free_clusters as u64 * size.cluster_size() as u64)
+}
+
+/// Ask `c_device` for its size, returning the kernel's error code on
+/// failure.
+fn query_size(c_device: &[u8]) -> Result<DeviceSize, i32> {
+    let mut size = DeviceSize::default();
+    // The command takes the address of the struct to fill in.
+    let mut size_ptr = &mut size as *mut DeviceSize;
+
+    let ret = unsafe {
+        sys::sceIoDevctl(
+            c_device.as_ptr(),
+            DEVCTL_GET_DEVICE_SIZE,
+            &mut size_ptr as *mut _ as *mut c_void,
+            core::mem::size_of::<*mut DeviceSize>() as i32,
+            ptr::null_mut(),
+            0,
+        )
+    };
+
+    match ret {
+        e if e < 0 => Err(e),
+        _ => Ok(size),
+    }
+}
@@ -0,0 +1,825 @@
+//! Files, read and written through `sceIo`.
+//!
+//! ```no_run
+//! use psp::io::File;
+//!
+//! let mut save = File::create("ms0:/PSP/SAVEDATA/MYGAME/progress.bin").unwrap();
+//! save.write_all(&[3, 1, 4]).unwrap();
+//!
+//! let mut level = File::open("umd0:/PSP_GAME/USRDIR/level1.dat").unwrap();
+//! let mut data = alloc::vec::Vec::new();
+//! level.read_to_end(&mut data).unwrap();
+//! ```
+//!
+//! `read_dir` lists the contents of a directory, `create_dir_all` makes
+//! one along with its parents, `free_space` tells whether a save will fit,
+//! and `mem_stick` tells when the Memory Stick is taken out.
+//! `write_atomic` replaces a file such that a power loss can't leave it
+//! half written, for saves, and `slots` builds numbered save slots on it.
+//!
+//! With the `embedded-io` feature, `File` and `Stdout` implement that
+//! crate's `Read`, `Write` and `Seek` traits, for parsers generic over them.
+//!
+//! `ctl` sends device specific commands, such as reading the disc by
+//! sector.
+//!
+//! `BufReader` and `BufWriter` gather small reads and writes into larger
+//! ones, which saves a system call each.
+//!
+//! `File::read_async` reads in the background, so that loading doesn't
+//! hold up the main loop:
+//!
+//! ```no_run
+//! use psp::io::{AsyncStatus, File};
+//!
+//! let mut file = File::open("ms0:/PSP/GAME/MYGAME/music.at3").unwrap();
+//! let mut read = file.read_async(alloc::vec![0; 256 * 1024]);
+//!
+//! while read.poll() == Ok(AsyncStatus::Pending) {
+//!     // Draw a frame.
+//! }
+//!
+//! let data = read.wait().unwrap();
+//! ```
+//!
+//! `File::open_async` opens a file in the background the same way, since
+//! that can take a while too, as on the disc while it spins up. A file has
+//! one background operation at a time: it is borrowed until the read is
+//! done, so starting another doesn't compile.
+//!
+//! Paths start with a device: `ms0:/` for the Memory Stick (or the PSP
+//! Go's internal storage), `umd0:/` or `disc0:/` for the disc, once
+//! `umd::wait_ready` has mounted it, and `host0:/` for the PC running
+//! PSPLink. Paths without one are relative to the directory the program
+//! was started from.
+//!
+//! `resolve` turns a path relative to that directory into a full one, so
+//! assets load from `host0:/` during development and from `ms0:/` in
+//! release without changing the path.
+
+use crate::sys::{
+    self, IoOpenFlags, IoStatMode, IoWhence, SceIoDirent, SceIoStat, ScePspDateTime, SceUid,
+};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::mem;
+
+mod atomic;
+mod buffered;
+pub mod ctl;
+#[cfg(feature = "embedded-io")]
+mod embedded_io;
+pub mod mem_stick;
+mod path;
+pub mod slots;
+
+pub use atomic::{write_atomic, AtomicWriteError, AtomicWriter};
+pub use buffered::{BufReader, BufWriter, DropError, DEFAULT_BUF_LEN};
+pub(crate) use path::set_program_path;
+pub use path::{base_path, host_available, resolve};
+
+/// Bytes `read_to_end` reads at a time, past what it expects the file to
+/// hold.
+const READ_CHUNK_LEN: usize = 16 * 1024;
+
+/// Permissions for created files. The Memory Stick doesn't keep them.
+const CREATE_PERMISSIONS: i32 = 0o777;
+
+/// Bytes `copy` moves at a time.
+const COPY_BUF_LEN: usize = 32 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoError {
+    /// The path contains a NUL byte.
+    InvalidPath,
+    /// There is no file or directory at the path.
+    NotFound,
+    /// The file already exists.
+    AlreadyExists,
+    /// The file can't be accessed this way.
+    PermissionDenied,
+    /// The path's device doesn't exist, or has no media in it, such as
+    /// `umd0:/` without a disc.
+    NoDevice,
+    /// The path is on the Memory Stick, which isn't inserted, or was taken
+    /// out while the file was open. Files opened before then stay unusable
+    /// after it is put back.
+    MediumRemoved,
+    /// The device is full.
+    NoSpace,
+    /// The device can't be written to, such as the disc, or a locked
+    /// Memory Stick.
+    ReadOnly,
+    /// The path is a directory.
+    IsDirectory,
+    /// Too many files are open.
+    TooManyOpen,
+    /// Nothing could be written, though there was data to write.
+    WriteZero,
+    /// The kernel returned another error code.
+    Kernel(i32),
+}
+
+impl IoError {
+    /// The error for a negative return value of a `sceIo` function.
+    pub(crate) fn from_code(code: i32) -> Self {
+        match code as u32 {
+            0x8001_0002 => IoError::NotFound,
+            0x8001_000d => IoError::PermissionDenied,
+            0x8001_0011 => IoError::AlreadyExists,
+            0x8001_0013 | 0x8002_0321 => IoError::NoDevice,
+            0x8001_0015 => IoError::IsDirectory,
+            0x8001_0018 | 0x8002_0320 => IoError::TooManyOpen,
+            0x8001_001c => IoError::NoSpace,
+            0x8001_001e => IoError::ReadOnly,
+            _ => IoError::Kernel(code),
+        }
+    }
+
+    /// The error for a negative return value of a `sceIo` function called
+    /// on `c_path`, telling apart a Memory Stick that has been taken out.
+    fn on_path(c_path: &[u8], code: i32) -> Self {
+        let error = Self::from_code(code);
+
+        // Errors that the path itself is to blame for stay as they are.
+        let device_error = matches!(error, IoError::NoDevice | IoError::Kernel(_));
+        let on_mem_stick = c_path.starts_with(b"ms0:") || c_path.starts_with(b"fatms0:");
+
+        if device_error && on_mem_stick && !mem_stick::is_inserted() {
+            IoError::MediumRemoved
+        } else {
+            error
+        }
+    }
+}
+
+/// A position in a file, for `File::seek`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// Bytes from the start.
+    Start(u64),
+    /// Bytes from the end, usually negative.
+    End(i64),
+    /// Bytes from the current position.
+    Current(i64),
+}
+
+/// Information about a file or directory.
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    /// Size in bytes.
+    pub len: u64,
+    pub is_dir: bool,
+    pub created: ScePspDateTime,
+    pub accessed: ScePspDateTime,
+    pub modified: ScePspDateTime,
+}
+
+impl From<&SceIoStat> for Metadata {
+    fn from(stat: &SceIoStat) -> Self {
+        Self {
+            len: stat.st_size as u64,
+            is_dir: stat.st_mode.contains(IoStatMode::IFDIR),
+            created: stat.st_ctime,
+            accessed: stat.st_atime,
+            modified: stat.st_mtime,
+        }
+    }
+}
+
+/// Information about the file or directory at `path`.
+pub fn metadata(path: &str) -> Result<Metadata, IoError> {
+    stat(&c_path(path)?)
+}
+
+/// Create the directory at `path`, and any of its parents that are
+/// missing. Directories that already exist are left as they are.
+///
+/// Fails with `AlreadyExists` if part of the path is a file.
+pub fn create_dir_all(path: &str) -> Result<(), IoError> {
+    let path = c_path(path)?;
+    let path = &path[..path.len() - 1];
+
+    // The device, such as `ms0:/`, is never created.
+    let start = match path.iter().position(|&b| b == b':') {
+        Some(colon) => colon + 1,
+        None => 0,
+    };
+
+    let mut dir = Vec::with_capacity(path.len() + 1);
+    let mut end = start;
+
+    for name in path[start..].split(|&b| b == b'/') {
+        let dir_end = end + name.len();
+        end = dir_end + 1;
+
+        // Between leading, repeated or trailing slashes.
+        if name.is_empty() {
+            continue;
+        }
+
+        dir.clear();
+        dir.extend_from_slice(&path[..dir_end]);
+        dir.push(0);
+
+        let ret = unsafe { sys::sceIoMkdir(dir.as_ptr(), CREATE_PERMISSIONS) };
+        if ret < 0 {
+            // Some devices report an existing directory as another error,
+            // so check what is there rather than trusting the code.
+            match stat(&dir) {
+                Ok(m) if m.is_dir => {}
+                Ok(_) => return Err(IoError::AlreadyExists),
+                Err(_) => return Err(IoError::on_path(&dir, ret)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the file at `path`.
+pub fn remove_file(path: &str) -> Result<(), IoError> {
+    let path = c_path(path)?;
+
+    match unsafe { sys::sceIoRemove(path.as_ptr()) } {
+        e if e < 0 => Err(IoError::on_path(&path, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Copy the file at `src` to `dst`, which is created or emptied first,
+/// returning how many bytes were copied. The two can be on different
+/// devices, such as `umd0:/` and `ms0:/`.
+///
+/// The file is streamed through a fixed size buffer, so it doesn't need to
+/// fit in memory. On an error, such as `NoSpace` when the destination
+/// fills up, whatever was written to `dst` is left there, for the caller
+/// to delete with `remove_file`.
+pub fn copy(src: &str, dst: &str) -> Result<u64, IoError> {
+    let mut src = File::open(src)?;
+    let mut dst = File::create(dst)?;
+    let mut buf = alloc::vec![0; COPY_BUF_LEN];
+    let mut copied = 0;
+
+    loop {
+        match src.read(&mut buf)? {
+            0 => break,
+            read => {
+                dst.write_all(&buf[..read])?;
+                copied += read as u64;
+            }
+        }
+    }
+
+    // Closing writes out what the device buffered, which can fail too.
+    dst.close()?;
+
+    Ok(copied)
+}
+
+/// Free space on `device`, in bytes, such as before saving to check that
+/// the save will fit.
+///
+/// `device` is a device name with its colon: `ms0:` for the Memory Stick,
+/// or `ef0:` for the PSP Go's internal storage. Anything after the colon is
+/// ignored, so a path on the device works too. `mem_stick::free_space`
+/// picks the device the program was started from instead.
+///
+/// Only those devices can measure their free space. The flash partitions,
+/// `flash0:` to `flash3:`, and the disc fail with an error.
+///
+/// ```no_run
+/// let save = [0u8; 64 * 1024];
+///
+/// if psp::io::free_space("ms0:").unwrap_or(0) < save.len() as u64 {
+///     psp::dprintln!("not enough space on the Memory Stick to save");
+/// }
+/// ```
+pub fn free_space(device: &str) -> Result<u64, IoError> {
+    let device = match device.find(':') {
+        Some(colon) => &device[..=colon],
+        None => device,
+    };
+
+    mem_stick::device_free_space(&c_path(device)?)
+}
+
+/// An open file, closed on drop.
+///
+/// The descriptor isn't exposed, so it can't be closed twice or used after
+/// the file is.
+#[derive(Debug)]
+pub struct File {
+    fd: SceUid,
+    /// NUL terminated, for `metadata`.
+    path: Box<[u8]>,
+}
+
+impl File {
+    /// Open an existing file for reading.
+    pub fn open(path: &str) -> Result<Self, IoError> {
+        Self::open_with(path, IoOpenFlags::RD_ONLY)
+    }
+
+    /// Open a file for writing, creating it if it doesn't exist, and
+    /// emptying it if it does.
+    pub fn create(path: &str) -> Result<Self, IoError> {
+        Self::open_with(
+            path,
+            IoOpenFlags::WR_ONLY | IoOpenFlags::CREAT | IoOpenFlags::TRUNC,
+        )
+    }
+
+    /// Start opening an existing file for reading, without waiting for it
+    /// to open.
+    ///
+    /// Opening can take a while on the disc, which spins up first, and on
+    /// `host0:/`. `AsyncOpen::wait` returns the file once it is open.
+    pub fn open_async(path: &str) -> Result<AsyncOpen, IoError> {
+        let path = c_path(path)?;
+
+        let fd =
+            unsafe { sys::sceIoOpenAsync(path.as_ptr(), IoOpenFlags::RD_ONLY, CREATE_PERMISSIONS) };
+        if fd.0 < 0 {
+            return Err(IoError::on_path(&path, fd.0));
+        }
+
+        Ok(AsyncOpen {
+            fd,
+            path: path.into_boxed_slice(),
+            result: None,
+        })
+    }
+
+    fn open_with(path: &str, flags: IoOpenFlags) -> Result<Self, IoError> {
+        let path = c_path(path)?;
+
+        let fd = unsafe { sys::sceIoOpen(path.as_ptr(), flags, CREATE_PERMISSIONS) };
+        if fd.0 < 0 {
+            return Err(IoError::on_path(&path, fd.0));
+        }
+
+        Ok(Self {
+            fd,
+            path: path.into_boxed_slice(),
+        })
+    }
+
+    /// Read into `buf`, returning how many bytes were read, which is 0 at
+    /// the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let read =
+            unsafe { sys::sceIoRead(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len() as u32) };
+
+        match read {
+            e if e < 0 => Err(IoError::on_path(&self.path, e)),
+            read => Ok(read as usize),
+        }
+    }
+
+    /// Read everything up to the end of the file onto the end of `buf`,
+    /// returning how many bytes were read.
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, IoError> {
+        let start = buf.len();
+
+        // Reserving the rest of the file up front saves growing `buf` as it's
+        // read. Files can still grow while being read, so this isn't relied on.
+        let pos = self.seek(SeekFrom::Current(0))?;
+        let end = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(pos))?;
+        buf.reserve(end.saturating_sub(pos) as usize);
+
+        loop {
+            if buf.len() == buf.capacity() {
+                buf.reserve(READ_CHUNK_LEN);
+            }
+
+            let len = buf.len();
+            buf.resize(buf.capacity(), 0);
+
+            match self.read(&mut buf[len..]) {
+                Ok(0) => {
+                    buf.truncate(len);
+                    return Ok(len - start);
+                }
+                Ok(read) => buf.truncate(len + read),
+                Err(e) => {
+                    buf.truncate(len);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Write from `buf`, returning how many bytes were written.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        let written = unsafe { sys::sceIoWrite(self.fd, buf.as_ptr() as *const c_void, buf.len()) };
+
+        match written {
+            e if e < 0 => Err(IoError::on_path(&self.path, e)),
+            written => Ok(written as usize),
+        }
+    }
+
+    /// Write all of `buf`.
+    pub fn write_all(&mut self, mut buf: &[u8]) -> Result<(), IoError> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(IoError::WriteZero),
+                written => buf = &buf[written..],
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move to `pos`, returning the new position from the start.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, IoError> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (offset as i64, IoWhence::Set),
+            SeekFrom::End(offset) => (offset, IoWhence::End),
+            SeekFrom::Current(offset) => (offset, IoWhence::Cur),
+        };
+
+        let pos = unsafe { sys::sceIoLseek(self.fd, offset, whence) };
+        if pos < 0 {
+            return Err(IoError::on_path(&self.path, pos as i32));
+        }
+
+        Ok(pos as u64)
+    }
+
+    /// Start reading into `buf`, filling as much of it as the file holds,
+    /// without waiting for the read to finish.
+    ///
+    /// The buffer is given back by `AsyncRead::wait`. The file can't be
+    /// used until then.
+    pub fn read_async(&mut self, mut buf: Vec<u8>) -> AsyncRead<'_> {
+        let ret = unsafe {
+            sys::sceIoReadAsync(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len() as u32)
+        };
+
+        AsyncRead {
+            file: self,
+            buf,
+            result: (ret < 0).then(|| Err(IoError::on_path(&self.path, ret))),
+        }
+    }
+
+    /// Information about the file, looked up by the path it was opened
+    /// with.
+    pub fn metadata(&self) -> Result<Metadata, IoError> {
+        stat(&self.path)
+    }
+
+    /// Close the file, returning any error that dropping it would ignore,
+    /// such as written data failing to reach the device.
+    pub fn close(mut self) -> Result<(), IoError> {
+        let fd = self.fd;
+        let path = mem::take(&mut self.path);
+        mem::forget(self);
+
+        match unsafe { sys::sceIoClose(fd) } {
+            e if e < 0 => Err(IoError::on_path(&path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        unsafe { sys::sceIoClose(self.fd) };
+    }
+}
+
+/// The program's standard output, from `stdout`. PSPLink shows it on the
+/// PC.
+#[derive(Debug)]
+pub struct Stdout {
+    fd: SceUid,
+}
+
+/// A handle to the program's standard output.
+pub fn stdout() -> Stdout {
+    Stdout {
+        fd: unsafe { sys::sceKernelStdout() },
+    }
+}
+
+impl Stdout {
+    /// Write from `buf`, returning how many bytes were written.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        let written = unsafe { sys::sceIoWrite(self.fd, buf.as_ptr() as *const c_void, buf.len()) };
+
+        match written {
+            e if e < 0 => Err(IoError::from_code(e)),
+            written => Ok(written as usize),
+        }
+    }
+
+    /// Write all of `buf`.
+    pub fn write_all(&mut self, mut buf: &[u8]) -> Result<(), IoError> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(IoError::WriteZero),
+                written => buf = &buf[written..],
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The entries of the directory at `path`, in the order the device lists
+/// them. `.` and `..` are left out.
+pub fn read_dir(path: &str) -> Result<ReadDir, IoError> {
+    let path = c_path(path)?;
+
+    let fd = unsafe { sys::sceIoDopen(path.as_ptr()) };
+    if fd.0 < 0 {
+        return Err(IoError::on_path(&path, fd.0));
+    }
+
+    Ok(ReadDir {
+        fd,
+        path: path.into_boxed_slice(),
+        // Boxed, so `ReadDir` is cheap to move.
+        dirent: Box::new(unsafe { mem::zeroed() }),
+        done: false,
+    })
+}
+
+/// An iterator over the entries of a directory, from `read_dir`. The
+/// directory is closed on drop, whether or not all entries were read.
+///
+/// Iteration ends after an error.
+pub struct ReadDir {
+    fd: SceUid,
+    /// NUL terminated, for errors.
+    path: Box<[u8]>,
+    dirent: Box<SceIoDirent>,
+    done: bool,
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry, IoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            // Some devices write through `d_private` unless it's null.
+            *self.dirent = unsafe { mem::zeroed() };
+
+            match unsafe { sys::sceIoDread(self.fd, &mut *self.dirent) } {
+                0 => self.done = true,
+                e if e < 0 => {
+                    self.done = true;
+                    return Some(Err(IoError::on_path(&self.path, e)));
+                }
+                _ => {
+                    let name = &self.dirent.d_name;
+                    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                    let name = &name[..len];
+
+                    if name != b"." && name != b".." {
+                        return Some(Ok(DirEntry {
+                            name: name.into(),
+                            metadata: Metadata::from(&self.dirent.d_stat),
+                        }));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        unsafe { sys::sceIoDclose(self.fd) };
+    }
+}
+
+/// A file or directory listed by `read_dir`.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    name: Box<[u8]>,
+    metadata: Metadata,
+}
+
+impl DirEntry {
+    /// The name, as stored. Names written by other software may be in
+    /// Shift-JIS rather than UTF-8.
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// The name, if it is valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        core::str::from_utf8(&self.name).ok()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir
+    }
+
+    /// The size, timestamps, and whether it is a directory.
+    pub fn metadata(&self) -> Metadata {
+        self.metadata
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncStatus {
+    /// The read hasn't finished.
+    Pending,
+    /// The read is done, with this many bytes read.
+    Done(usize),
+}
+
+/// A read from `File::read_async`, running in the background.
+///
+/// Dropping it before the read is done cancels the read, or waits for it,
+/// if it has already started.
+#[must_use]
+#[derive(Debug)]
+pub struct AsyncRead<'a> {
+    file: &'a File,
+    /// Owned, rather than borrowed, so that the kernel can't write to it
+    /// after it's freed, even if this is forgotten.
+    buf: Vec<u8>,
+    /// Set once the read is done.
+    result: Option<Result<usize, IoError>>,
+}
+
+impl AsyncRead<'_> {
+    /// Check whether the read is done, without waiting.
+    pub fn poll(&mut self) -> Result<AsyncStatus, IoError> {
+        if self.result.is_none() {
+            let mut res = 0;
+
+            match unsafe { sys::sceIoPollAsync(self.file.fd, &mut res) } {
+                // Still reading.
+                1 => return Ok(AsyncStatus::Pending),
+                e if e < 0 => self.result = Some(Err(self.error(e))),
+                _ => self.result = Some(self.read_result(res)),
+            }
+        }
+
+        self.result.unwrap().map(AsyncStatus::Done)
+    }
+
+    /// Wait for the read to be done, and return the buffer, shortened to
+    /// what was read.
+    pub fn wait(mut self) -> Result<Vec<u8>, IoError> {
+        let read = self.finish()?;
+
+        let mut buf = mem::take(&mut self.buf);
+        buf.truncate(read);
+        Ok(buf)
+    }
+
+    fn finish(&mut self) -> Result<usize, IoError> {
+        if self.result.is_none() {
+            let mut res = 0;
+
+            self.result = Some(
+                match unsafe { sys::sceIoWaitAsync(self.file.fd, &mut res) } {
+                    e if e < 0 => Err(self.error(e)),
+                    _ => self.read_result(res),
+                },
+            );
+        }
+
+        self.result.unwrap()
+    }
+
+    /// The result of the read, as bytes read or an error code.
+    fn read_result(&self, res: i64) -> Result<usize, IoError> {
+        match res {
+            e if e < 0 => Err(self.error(e as i32)),
+            read => Ok(read as usize),
+        }
+    }
+
+    fn error(&self, code: i32) -> IoError {
+        IoError::on_path(&self.file.path, code)
+    }
+}
+
+impl Drop for AsyncRead<'_> {
+    fn drop(&mut self) {
+        if self.result.is_none() && unsafe { sys::sceIoCancel(self.file.fd) } < 0 {
+            let _ = self.finish();
+        }
+    }
+}
+
+/// A file from `File::open_async`, being opened in the background.
+///
+/// Dropping it before it is open cancels the open, or waits for it, if it
+/// can't be cancelled.
+#[must_use]
+#[derive(Debug)]
+pub struct AsyncOpen {
+    /// Given out by the kernel before the file is open, and closed on drop
+    /// unless `wait` hands it over.
+    fd: SceUid,
+    path: Box<[u8]>,
+    /// Set once the open is done.
+    result: Option<Result<(), IoError>>,
+}
+
+impl AsyncOpen {
+    /// Check whether the file is open, without waiting.
+    pub fn poll(&mut self) -> Result<bool, IoError> {
+        if self.result.is_none() {
+            let mut res = 0;
+
+            match unsafe { sys::sceIoPollAsync(self.fd, &mut res) } {
+                // Still opening.
+                1 => return Ok(false),
+                e if e < 0 => self.result = Some(Err(self.error(e))),
+                _ => self.result = Some(self.open_result(res)),
+            }
+        }
+
+        self.result.unwrap().map(|()| true)
+    }
+
+    /// Wait for the file to be open, and return it.
+    pub fn wait(mut self) -> Result<File, IoError> {
+        self.finish()?;
+
+        // Moved out without running `Drop`, which would close the file.
+        let file = File {
+            fd: self.fd,
+            path: mem::take(&mut self.path),
+        };
+        mem::forget(self);
+
+        Ok(file)
+    }
+
+    fn finish(&mut self) -> Result<(), IoError> {
+        if self.result.is_none() {
+            let mut res = 0;
+
+            self.result = Some(match unsafe { sys::sceIoWaitAsync(self.fd, &mut res) } {
+                e if e < 0 => Err(self.error(e)),
+                _ => self.open_result(res),
+            });
+        }
+
+        self.result.unwrap()
+    }
+
+    /// The result of the open, from its result code.
+    fn open_result(&self, res: i64) -> Result<(), IoError> {
+        match res {
+            e if e < 0 => Err(self.error(e as i32)),
+            _ => Ok(()),
+        }
+    }
+
+    fn error(&self, code: i32) -> IoError {
+        IoError::on_path(&self.path, code)
+    }
+}
+
+impl Drop for AsyncOpen {
+    fn drop(&mut self) {
+        // Closing a descriptor with a pending operation blocks.
+        if self.result.is_none() && unsafe { sys::sceIoCancel(self.fd) } < 0 {
+            let _ = self.finish();
+        }
+
+        // The descriptor needs closing even if opening failed.
+        unsafe { sys::sceIoClose(self.fd) };
+    }
+}
+
+/// `path` with a NUL terminator.
+fn c_path(path: &str) -> Result<Vec<u8>, IoError> {
+    if path.contains('\0') {
+        return Err(IoError::InvalidPath);
+    }
+
+    let mut c_path = Vec::with_capacity(path.len() + 1);
+    c_path.extend_from_slice(path.as_bytes());
+    c_path.push(0);
+
+    Ok(c_path)
+}
+
+fn stat(c_path: &[u8]) -> Result<Metadata, IoError> {
+    let mut stat: SceIoStat = unsafe { mem::zeroed() };
+
+    let ret = unsafe { sys::sceIoGetstat(c_path.as_ptr(), &mut stat) };
+    if ret < 0 {
+        return Err(IoError::on_path(c_path, ret));
+    }
+
+    Ok(Metadata::from(&stat))
+}
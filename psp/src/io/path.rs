@@ -0,0 +1,156 @@
+//! Finding the program's files, wherever it was started from.
+
+use super::IoError;
+use crate::sys::{self, IoOpenFlags};
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::str;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// How long `host_available` waits for PSPLink to answer, in microseconds.
+const HOST_PROBE_TIMEOUT: u32 = 100_000;
+
+/// How often `host_available` checks for an answer, in microseconds.
+const HOST_PROBE_INTERVAL: u32 = 1_000;
+
+/// A name that isn't expected to exist on the PC. Opening it only has to
+/// reach PSPLink, not succeed.
+const HOST_PROBE_PATH: &[u8] = b"host0:/.psp-host-probe\0";
+
+const UNKNOWN: u8 = 0;
+const ABSENT: u8 = 1;
+const PRESENT: u8 = 2;
+
+static HOST: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// The directory the program was started from, with a trailing `/`. Set
+/// before `psp_main` starts, and not changed after.
+static mut PROGRAM_DIR: Option<&'static str> = None;
+
+/// Remember the directory of `argv[0]`, for `base_path`.
+pub(crate) unsafe fn set_program_path(arg0: *const u8) {
+    let mut len = 0;
+    while *arg0.add(len) != 0 {
+        len += 1;
+    }
+
+    let path = core::slice::from_raw_parts(arg0, len);
+    let dir = match path.iter().rposition(|&b| b == b'/') {
+        Some(slash) => &path[..=slash],
+        None => return,
+    };
+
+    if let Ok(dir) = str::from_utf8(dir) {
+        PROGRAM_DIR = Some(Box::leak(dir.into()));
+    }
+}
+
+/// The directory the program was started from, such as
+/// `ms0:/PSP/GAME/MYGAME/`, or `host0:/` when PSPLink started it from the
+/// PC. Ends with a `/`.
+///
+/// If the loader didn't pass the program's path, this is `host0:/` when
+/// `host_available`, and otherwise empty, leaving paths relative to the
+/// current directory.
+pub fn base_path() -> &'static str {
+    match unsafe { PROGRAM_DIR } {
+        Some(dir) => dir,
+        None if host_available() => "host0:/",
+        None => "",
+    }
+}
+
+/// `path` relative to `base_path`, so the same path finds the program's
+/// assets in development and in release.
+///
+/// ```no_run
+/// let path = psp::io::resolve("assets/ferris.bmp");
+/// let file = psp::io::File::open(&path).unwrap();
+/// ```
+///
+/// Paths that already start with a device, such as `ms0:/`, are returned
+/// as they are.
+pub fn resolve(path: &str) -> String {
+    if has_device(path) {
+        return path.into();
+    }
+
+    let base = base_path();
+    let path = match base.is_empty() {
+        true => path,
+        false => path.trim_start_matches('/'),
+    };
+
+    let mut resolved = String::with_capacity(base.len() + path.len());
+    resolved.push_str(base);
+    resolved.push_str(path);
+    resolved
+}
+
+/// Whether `host0:/` reaches a PC through PSPLink.
+///
+/// With PSPLink loaded but its PC side not running, `host0:/` waits for an
+/// answer that never comes. This gives up after a tenth of a second, and
+/// remembers the answer, so only the first call can take that long.
+pub fn host_available() -> bool {
+    match HOST.load(Ordering::Relaxed) {
+        UNKNOWN => {
+            let present = probe_host();
+            HOST.store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+            present
+        }
+        state => state == PRESENT,
+    }
+}
+
+fn probe_host() -> bool {
+    // Without the device, this fails right away.
+    let fd = unsafe { sys::sceIoOpenAsync(HOST_PROBE_PATH.as_ptr(), IoOpenFlags::RD_ONLY, 0) };
+    if fd.0 < 0 {
+        return false;
+    }
+
+    let mut waited = 0;
+    let mut res = 0;
+
+    loop {
+        match unsafe { sys::sceIoPollAsync(fd, &mut res) } {
+            // Still waiting.
+            1 if waited < HOST_PROBE_TIMEOUT => {
+                unsafe { sys::sceKernelDelayThread(HOST_PROBE_INTERVAL) };
+                waited += HOST_PROBE_INTERVAL;
+            }
+            1 => {
+                // Closing a descriptor with a pending operation blocks, so
+                // it is leaked if the open can't be cancelled.
+                if unsafe { sys::sceIoCancel(fd) } >= 0 {
+                    unsafe { sys::sceIoClose(fd) };
+                }
+                return false;
+            }
+            e if e < 0 => {
+                unsafe { sys::sceIoClose(fd) };
+                return false;
+            }
+            _ => break,
+        }
+    }
+
+    unsafe { sys::sceIoClose(fd) };
+
+    // Any answer but a missing device means PSPLink is there, most likely
+    // that the file doesn't exist.
+    res >= 0
+        || !matches!(
+            IoError::from_code(res as i32),
+            IoError::NoDevice | IoError::Kernel(_)
+        )
+}
+
+/// Whether `path` starts with a device name, such as `ms0:`.
+fn has_device(path: &str) -> bool {
+    match path.find(':') {
+        Some(colon) => !path[..colon].contains('/'),
+        None => false,
+    }
+}
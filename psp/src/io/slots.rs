@@ -0,0 +1,178 @@
+//! Numbered save slots, as plain files.
+//!
+//! Each slot is a file in one directory, holding the saved bytes behind a
+//! header with their length and CRC-32, so a damaged save is caught when
+//! loading instead of being handed to the game.
+//!
+//! ```no_run
+//! use psp::io::slots::SlotStore;
+//!
+//! let saves = SlotStore::new("ms0:/PSP/SAVEDATA/MYGAME", 3);
+//! saves.save(2, b"level 4").unwrap();
+//!
+//! match saves.load(2) {
+//!     Ok(Some(data)) => { /* Continue from `data`. */ }
+//!     Ok(None) => { /* An empty slot. */ }
+//!     Err(e) => psp::dprintln!("save 2 is damaged: {:?}", e),
+//! }
+//! ```
+//!
+//! Saving goes through `write_atomic`, so a crash or power loss leaves
+//! either the previous save or the new one.
+
+use super::{AtomicWriteError, File, IoError};
+use crate::crc32;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Start of every slot file.
+const MAGIC: [u8; 4] = *b"PSAV";
+
+/// The format `save` writes. Slots with a newer version can't be loaded.
+pub const VERSION: u16 = 1;
+
+/// Magic, version, 2 reserved bytes, payload length, CRC-32.
+const HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotError {
+    /// The slot is not below the store's slot count.
+    InvalidSlot(usize),
+    Io(IoError),
+    /// Saving failed. The previous save is kept, see `AtomicWriteError`.
+    Write(AtomicWriteError),
+    /// The slot's file is damaged: its header or CRC-32 doesn't match its
+    /// contents.
+    Corrupt,
+    /// The slot was saved in a newer format.
+    UnsupportedVersion(u16),
+}
+
+impl From<IoError> for SlotError {
+    fn from(e: IoError) -> Self {
+        SlotError::Io(e)
+    }
+}
+
+impl From<AtomicWriteError> for SlotError {
+    fn from(e: AtomicWriteError) -> Self {
+        SlotError::Write(e)
+    }
+}
+
+/// A directory of `slot_count` save slots, numbered from 0.
+#[derive(Debug, Clone)]
+pub struct SlotStore {
+    /// Without a trailing `/`.
+    base_dir: String,
+    slot_count: usize,
+}
+
+impl SlotStore {
+    /// Slots in `base_dir`, which is created on the first save.
+    pub fn new(base_dir: &str, slot_count: usize) -> Self {
+        Self {
+            base_dir: base_dir.trim_end_matches('/').into(),
+            slot_count,
+        }
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+
+    /// The file `slot` is saved to.
+    pub fn path(&self, slot: usize) -> Result<String, SlotError> {
+        if slot >= self.slot_count {
+            return Err(SlotError::InvalidSlot(slot));
+        }
+
+        Ok(format!("{}/slot{}.sav", self.base_dir, slot))
+    }
+
+    /// Save `data` to `slot`, replacing what it held.
+    pub fn save(&self, slot: usize, data: &[u8]) -> Result<(), SlotError> {
+        let path = self.path(slot)?;
+        super::create_dir_all(&self.base_dir)?;
+
+        let mut file = Vec::with_capacity(HEADER_LEN + data.len());
+        file.extend_from_slice(&MAGIC);
+        file.extend_from_slice(&VERSION.to_le_bytes());
+        file.extend_from_slice(&[0; 2]);
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&crc32::checksum(data).to_le_bytes());
+        file.extend_from_slice(data);
+
+        super::write_atomic(&path, &file)?;
+        Ok(())
+    }
+
+    /// The data saved in `slot`, or `None` if it is empty.
+    pub fn load(&self, slot: usize) -> Result<Option<Vec<u8>>, SlotError> {
+        let path = self.path(slot)?;
+
+        if let Some(file) = read(&path)? {
+            return parse(file).map(Some);
+        }
+
+        // A save interrupted between removing the old file and renaming
+        // the new one into place leaves only the temporary file, complete.
+        // One interrupted while writing leaves a damaged temporary file,
+        // and the slot was empty before.
+        match read(&format!("{}.tmp", path))? {
+            Some(file) => Ok(parse(file).ok()),
+            None => Ok(None),
+        }
+    }
+
+    /// Empty `slot`. Emptying an empty slot succeeds.
+    pub fn delete(&self, slot: usize) -> Result<(), SlotError> {
+        let path = self.path(slot)?;
+
+        for path in [format!("{}.tmp", path), path].iter() {
+            match super::remove_file(path) {
+                Ok(()) | Err(IoError::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The contents of the file at `path`, or `None` if there isn't one.
+fn read(path: &str) -> Result<Option<Vec<u8>>, IoError> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(IoError::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(Some(data))
+}
+
+/// The payload of a slot file, checked against its header.
+fn parse(mut file: Vec<u8>) -> Result<Vec<u8>, SlotError> {
+    if file.len() < HEADER_LEN || file[..4] != MAGIC {
+        return Err(SlotError::Corrupt);
+    }
+
+    let u32_at =
+        |at: usize| u32::from_le_bytes([file[at], file[at + 1], file[at + 2], file[at + 3]]);
+
+    let version = u16::from_le_bytes([file[4], file[5]]);
+    if version == 0 || version > VERSION {
+        return Err(SlotError::UnsupportedVersion(version));
+    }
+
+    let (len, crc) = (u32_at(8) as usize, u32_at(12));
+    if file.len() - HEADER_LEN != len || crc32::checksum(&file[HEADER_LEN..]) != crc {
+        return Err(SlotError::Corrupt);
+    }
+
+    file.drain(..HEADER_LEN);
+    Ok(file)
+}
@@ -29,6 +29,14 @@ extern crate alloc;
 #[cfg(not(feature = "stub-only"))]
 extern crate panic_unwind;
 
+#[cfg(not(feature = "stub-only"))]
+pub mod alloc_sys;
+#[cfg(not(feature = "stub-only"))]
+pub mod audio;
+#[cfg(not(feature = "stub-only"))]
+pub mod cpu;
+#[cfg(not(feature = "stub-only"))]
+pub mod ctrl;
 #[macro_use]
 #[doc(hidden)]
 #[cfg(not(feature = "stub-only"))]
@@ -36,17 +44,51 @@ pub mod debug;
 
 #[macro_use]
 mod vfpu;
+#[cfg(not(feature = "stub-only"))]
+pub mod display;
 mod eabi;
+#[cfg(not(feature = "stub-only"))]
+pub mod exit;
+#[cfg(not(feature = "stub-only"))]
+pub mod font;
+#[cfg(not(feature = "stub-only"))]
+pub mod g2d;
+#[cfg(not(feature = "stub-only"))]
+pub mod graphics;
+#[cfg(not(feature = "stub-only"))]
+pub mod gu;
+#[cfg(not(feature = "stub-only"))]
+pub mod hprm;
+#[cfg(not(feature = "stub-only"))]
+pub mod image;
+#[cfg(not(feature = "stub-only"))]
+pub mod io;
 pub mod math;
+#[cfg(not(feature = "stub-only"))]
+pub mod mem;
+#[cfg(not(feature = "stub-only"))]
+pub mod rand;
+#[cfg(not(feature = "stub-only"))]
+pub mod sfo;
+#[cfg(not(feature = "stub-only"))]
+pub mod sync;
 pub mod sys;
 #[cfg(not(feature = "stub-only"))]
 pub mod test_runner;
 #[cfg(not(feature = "stub-only"))]
+pub mod thread;
+#[cfg(not(feature = "stub-only"))]
+pub mod umd;
+#[cfg(not(feature = "stub-only"))]
+pub mod utility;
+#[cfg(not(feature = "stub-only"))]
 pub mod vram_alloc;
 
 #[cfg(not(feature = "stub-only"))]
 mod alloc_impl;
 #[cfg(not(feature = "stub-only"))]
+mod crc32;
+#[cfg(not(feature = "stub-only"))]
 pub mod panic;
 
 #[cfg(not(feature = "stub-only"))]
@@ -64,6 +106,23 @@ mod constants;
 #[cfg(not(feature = "stub-only"))]
 pub use constants::*;
 
+/// Run `f` with interrupts suspended, which keeps out both other threads
+/// and interrupt handlers. Keep `f` short.
+#[cfg(not(feature = "stub-only"))]
+pub(crate) fn critical<R, F: FnOnce() -> R>(f: F) -> R {
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+        let ret = f();
+        sys::sceKernelCpuResumeIntr(flags);
+
+        ret
+    }
+}
+
+/// A kernel wait function, such as `sceKernelWaitSema`, gave up waiting.
+#[cfg(not(feature = "stub-only"))]
+pub(crate) const ERROR_WAIT_TIMEOUT: i32 = 0x8002_01a8_u32 as i32;
+
 #[doc(hidden)]
 pub use unstringify::unstringify;
 
@@ -93,6 +152,12 @@ pub use panic::catch_unwind;
 #[cfg(feature = "embedded-graphics")]
 pub mod embedded_graphics;
 
+#[cfg(all(feature = "log-compat", not(feature = "stub-only")))]
+pub mod log_compat;
+
+#[cfg(all(feature = "zip", not(feature = "stub-only")))]
+pub mod zip;
+
 #[repr(align(16))]
 #[derive(Copy, Clone)]
 pub struct Align16<T>(pub T);
@@ -166,7 +231,10 @@ macro_rules! _start {
         }
 
         if $argc > 0 {
-            unsafe { init_cwd($argv as *mut u8) };
+            unsafe {
+                $crate::_set_program_path($argv as *const u8);
+                init_cwd($argv as *mut u8);
+            }
         }
 
         // TODO: Maybe print any error to debug screen?
@@ -179,10 +247,34 @@ macro_rules! _start {
 /// Declare a PSP module.
 ///
 /// You must also define a `fn psp_main() { ... }` function in conjunction with
-/// this macro.
+/// this macro. It runs in a thread of its own, with the Home menu's exit
+/// callback already installed as with `exit::install`.
+///
+/// ```ignore
+/// psp::module!("my_game", 1, 0);
+///
+/// fn psp_main() {
+///     psp::dprintln!("Hello");
+/// }
+/// ```
+///
+/// The name is at most 27 bytes, and is followed by the major and minor
+/// version. Modules are user mode by default. Kernel mode, for plugins and
+/// other modules loaded by a kernel mode loader, is selected with a trailing
+/// `kernel`:
+///
+/// ```ignore
+/// psp::module!("my_plugin", 1, 0, kernel);
+/// ```
+///
+/// Kernel mode modules don't get an exit callback, and their main thread
+/// runs in kernel mode.
 #[macro_export]
 macro_rules! module {
-    ($name:expr, $version_major:expr, $version_minor: expr) => {
+    (
+        @module $name:expr, $version_major:expr, $version_minor:expr,
+        $mod_attr:expr, $thread_attr:expr, $exit_callback:expr
+    ) => {
         #[doc(hidden)]
         mod __psp_module {
             #[no_mangle]
@@ -190,7 +282,7 @@ macro_rules! module {
             #[used]
             static MODULE_INFO: $crate::Align16<$crate::sys::SceModuleInfo> =
                 $crate::Align16($crate::sys::SceModuleInfo {
-                    mod_attribute: 0,
+                    mod_attribute: $mod_attr as u16,
                     mod_version: [$version_major, $version_minor],
                     mod_name: $crate::sys::SceModuleInfo::name($name),
                     terminal: 0,
@@ -239,6 +331,10 @@ macro_rules! module {
             #[no_mangle]
             extern "C" fn module_start(argc_bytes: usize, argv: *mut c_void) -> isize {
                 extern "C" fn main_thread(argc: usize, argv: *mut c_void) -> i32 {
+                    if $exit_callback {
+                        $crate::_install_exit_callback();
+                    }
+
                     $crate::_start!(super::psp_main, argc, argv)
                 }
 
@@ -250,7 +346,7 @@ macro_rules! module {
                         32,
                         // 256kb stack
                         256 * 1024,
-                        $crate::sys::ThreadAttributes::USER | $crate::sys::ThreadAttributes::VFPU,
+                        $thread_attr,
                         core::ptr::null_mut(),
                     );
 
@@ -261,45 +357,52 @@ macro_rules! module {
             }
         }
     };
+    ($name:expr, $version_major:expr, $version_minor:expr) => {
+        $crate::module!($name, $version_major, $version_minor, user);
+    };
+    ($name:expr, $version_major:expr, $version_minor:expr, user) => {
+        $crate::module!(
+            @module $name, $version_major, $version_minor,
+            $crate::sys::ModuleInfoAttr::User,
+            $crate::sys::ThreadAttributes::USER | $crate::sys::ThreadAttributes::VFPU,
+            true
+        );
+    };
+    ($name:expr, $version_major:expr, $version_minor:expr, kernel) => {
+        $crate::module!(
+            @module $name, $version_major, $version_minor,
+            $crate::sys::ModuleInfoAttr::Kernel,
+            $crate::sys::ThreadAttributes::VFPU,
+            false
+        );
+    };
 }
 
 /// Enable the home button.
 ///
-/// This API does not have destructor support yet. You can manually setup an
-/// exit callback if you need this, see the source code of this function.
+/// This is `exit::install`. Cleanup code can be run on exit with
+/// `exit::on_exit`. User mode modules declared with `module!` already have
+/// it enabled, so this only matters for modules set up by hand.
+#[cfg(not(feature = "stub-only"))]
 pub fn enable_home_button() {
-    use core::{ffi::c_void, ptr};
-    use sys::ThreadAttributes;
-
-    unsafe {
-        unsafe extern "C" fn exit_thread(_args: usize, _argp: *mut c_void) -> i32 {
-            unsafe extern "C" fn exit_callback(_arg1: i32, _arg2: i32, _arg: *mut c_void) -> i32 {
-                sys::sceKernelExitGame();
-                0
-            }
-
-            let id = sys::sceKernelCreateCallback(
-                &b"exit_callback\0"[0],
-                exit_callback,
-                ptr::null_mut(),
-            );
-
-            sys::sceKernelRegisterExitCallback(id);
-            sys::sceKernelSleepThreadCB();
-
-            0
-        }
+    exit::install();
+}
 
-        // Enable the home button.
-        let id = sys::sceKernelCreateThread(
-            &b"exit_thread\0"[0],
-            exit_thread,
-            32,
-            0x1000,
-            ThreadAttributes::empty(),
-            ptr::null_mut(),
-        );
+// Called by `module!`, which can't check the crate's features itself.
+#[doc(hidden)]
+pub fn _install_exit_callback() {
+    #[cfg(not(feature = "stub-only"))]
+    exit::install();
+}
 
-        sys::sceKernelStartThread(id, 0, ptr::null_mut());
+// Called by `_start` with the path the program was started from, for the
+// same reason.
+#[doc(hidden)]
+pub unsafe fn _set_program_path(arg0: *const u8) {
+    #[cfg(not(feature = "stub-only"))]
+    {
+        io::set_program_path(arg0);
     }
+    #[cfg(feature = "stub-only")]
+    let _ = arg0;
 }
@@ -0,0 +1,134 @@
+//! A `log` crate backend printing to the debug console.
+//!
+//! Call `init` once at startup, before anything logs:
+//!
+//! ```no_run
+//! psp::log_compat::init().unwrap();
+//! psp::log_compat::set_max_level(log::LevelFilter::Info);
+//!
+//! log::info!("hello from {}", "log");
+//! ```
+//!
+//! Records are shown like `psp::log!` messages, prefixed and colored by
+//! level. Each record is formatted into a fixed buffer on the stack first,
+//! as there may not be a heap to format into. Records longer than
+//! `MAX_RECORD_LEN` bytes are cut short, and end with `...`.
+
+use crate::debug::{self, Level};
+use core::fmt;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Longest record shown in full, in bytes.
+pub const MAX_RECORD_LEN: usize = 256;
+
+const ELLIPSIS: &str = "...";
+
+static LOGGER: Logger = Logger;
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        debug::level_enabled(level(metadata.level()))
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        use fmt::Write;
+
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut buf = StackBuf::new();
+        let _ = write!(buf, "{}", record.args());
+        debug::log_args(level(record.level()), format_args!("{}", buf.as_str()));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the console as the `log` logger, showing all levels.
+///
+/// This can only succeed once, and fails if another logger has already been
+/// installed.
+pub fn init() -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    set_max_level(LevelFilter::Trace);
+    Ok(())
+}
+
+/// Set the most verbose level shown, for both `log` records and
+/// `psp::log!` messages. `LevelFilter::Off` hides both.
+pub fn set_max_level(filter: LevelFilter) {
+    log::set_max_level(filter);
+
+    match filter.to_level() {
+        Some(filter) => debug::set_min_level(level(filter)),
+        None => debug::set_log_off(),
+    }
+}
+
+fn level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::Error,
+        log::Level::Warn => Level::Warn,
+        log::Level::Info => Level::Info,
+        log::Level::Debug => Level::Debug,
+        log::Level::Trace => Level::Trace,
+    }
+}
+
+/// A record formatted on the stack, cut short if it doesn't fit.
+struct StackBuf {
+    /// With room for the ellipsis after a full record.
+    buf: [u8; MAX_RECORD_LEN + ELLIPSIS.len()],
+    len: usize,
+    truncated: bool,
+}
+
+impl StackBuf {
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_RECORD_LEN + ELLIPSIS.len()],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole `str`s or characters are ever copied in.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+
+    fn push(&mut self, s: &str) {
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+    }
+}
+
+impl fmt::Write for StackBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Err(fmt::Error);
+        }
+
+        if self.len + s.len() <= MAX_RECORD_LEN {
+            self.push(s);
+            return Ok(());
+        }
+
+        for c in s.chars() {
+            if self.len + c.len_utf8() > MAX_RECORD_LEN {
+                break;
+            }
+
+            self.push(c.encode_utf8(&mut [0; 4]));
+        }
+
+        self.push(ELLIPSIS);
+        self.truncated = true;
+
+        // Stops formatting the rest of the record.
+        Err(fmt::Error)
+    }
+}
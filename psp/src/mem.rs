@@ -0,0 +1,112 @@
+//! Fast copies and fills, for moving pixels in and out of VRAM.
+//!
+//! ```no_run
+//! // Clear a 512x272 framebuffer at the start of VRAM to opaque black.
+//! let vram = 0x4400_0000 as *mut u32;
+//! unsafe { psp::mem::fast_fill32(vram, 0xff00_0000, 512 * 272) };
+//! ```
+//!
+//! Both work a 64-byte cache line at a time, with unrolled word stores,
+//! once the destination is aligned to one. They don't use the VFPU, whose
+//! registers may hold the caller's matrices, and which threads created
+//! without `ThreadAttributes::VFPU` can't use.
+//!
+//! The stores are volatile, so they aren't turned back into a call to
+//! `memset` or `memcpy`, and none are left out. On VRAM's uncached mirror,
+//! at `0x4400_0000`, they reach VRAM directly, so nothing needs writing
+//! back from the cache after. On cached memory that the GE reads, write
+//! back the cache first, as for any other CPU write.
+
+use core::ptr;
+
+/// Words in a cache line, stored by each pass of the unrolled loops.
+const LINE_WORDS: usize = 16;
+
+const LINE_LEN: usize = LINE_WORDS * 4;
+
+/// Set `count` words from `dst` to `value`.
+///
+/// # Safety
+///
+/// `dst` must be aligned to 4 bytes, and valid for writing `count` words.
+pub unsafe fn fast_fill32(dst: *mut u32, value: u32, count: usize) {
+    let mut dst = dst;
+    let end = dst.add(count);
+
+    // Single words up to a line boundary.
+    while dst < end && dst as usize % LINE_LEN != 0 {
+        ptr::write_volatile(dst, value);
+        dst = dst.add(1);
+    }
+
+    while end as usize - dst as usize >= LINE_LEN {
+        for i in 0..LINE_WORDS {
+            ptr::write_volatile(dst.add(i), value);
+        }
+
+        dst = dst.add(LINE_WORDS);
+    }
+
+    while dst < end {
+        ptr::write_volatile(dst, value);
+        dst = dst.add(1);
+    }
+}
+
+/// Copy `len` bytes from `src` to `dst`.
+///
+/// When `src` and `dst` are equally far from a word boundary, which they
+/// are when both are aligned, the copy goes a word at a time. Otherwise it
+/// falls back to `ptr::copy_nonoverlapping`.
+///
+/// # Safety
+///
+/// `src` must be valid for reading `len` bytes, `dst` valid for writing
+/// them, and the two must not overlap.
+pub unsafe fn fast_copy(dst: *mut u8, src: *const u8, len: usize) {
+    if (dst as usize ^ src as usize) % 4 != 0 {
+        ptr::copy_nonoverlapping(src, dst, len);
+        return;
+    }
+
+    let (mut dst, mut src) = (dst, src);
+    let end = dst.add(len);
+
+    // Bytes up to a word boundary, then words up to a line boundary.
+    while dst < end && dst as usize % 4 != 0 {
+        ptr::write_volatile(dst, *src);
+        dst = dst.add(1);
+        src = src.add(1);
+    }
+
+    let (mut dst_word, mut src_word) = (dst as *mut u32, src as *const u32);
+
+    while end as usize - dst_word as usize >= 4 && dst_word as usize % LINE_LEN != 0 {
+        ptr::write_volatile(dst_word, *src_word);
+        dst_word = dst_word.add(1);
+        src_word = src_word.add(1);
+    }
+
+    while end as usize - dst_word as usize >= LINE_LEN {
+        for i in 0..LINE_WORDS {
+            ptr::write_volatile(dst_word.add(i), *src_word.add(i));
+        }
+
+        dst_word = dst_word.add(LINE_WORDS);
+        src_word = src_word.add(LINE_WORDS);
+    }
+
+    while end as usize - dst_word as usize >= 4 {
+        ptr::write_volatile(dst_word, *src_word);
+        dst_word = dst_word.add(1);
+        src_word = src_word.add(1);
+    }
+
+    let (mut dst, mut src) = (dst_word as *mut u8, src_word as *const u8);
+
+    while dst < end {
+        ptr::write_volatile(dst, *src);
+        dst = dst.add(1);
+        src = src.add(1);
+    }
+}
@@ -0,0 +1,79 @@
+//! Random numbers, from the kernel's Mersenne Twister.
+//!
+//! ```no_run
+//! use psp::rand::Rng;
+//!
+//! let mut rng = Rng::new();
+//! let damage = rng.next_range(10, 20);
+//! let critical = rng.next_f32() < 0.1;
+//! ```
+//!
+//! The numbers are not suitable for cryptography.
+
+use crate::sys::{self, SceKernelUtilsMt19937Context};
+use alloc::boxed::Box;
+
+/// A random number generator.
+///
+/// Two generators with the same seed produce the same numbers, and a clone
+/// continues with the same numbers as the original.
+#[derive(Clone)]
+pub struct Rng {
+    /// Boxed, as the state is 2.5 KiB.
+    ctx: Box<SceKernelUtilsMt19937Context>,
+}
+
+impl Rng {
+    /// A generator seeded from the system timer, so different on each run.
+    pub fn new() -> Self {
+        Self::with_seed(unsafe { sys::sceKernelGetSystemTimeLow() })
+    }
+
+    pub fn with_seed(seed: u32) -> Self {
+        let mut ctx = Box::new(SceKernelUtilsMt19937Context {
+            count: 0,
+            state: [0; 624],
+        });
+
+        // Only fails on a null context.
+        unsafe { sys::sceKernelUtilsMt19937Init(&mut *ctx, seed) };
+
+        Self { ctx }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        unsafe { sys::sceKernelUtilsMt19937UInt(&mut *self.ctx) }
+    }
+
+    /// A number from `lo` up to but not including `hi`, or `lo` if the range
+    /// is empty. Every number in the range is equally likely.
+    pub fn next_range(&mut self, lo: i32, hi: i32) -> i32 {
+        if hi <= lo {
+            return lo;
+        }
+
+        let span = hi.wrapping_sub(lo) as u32;
+
+        // Numbers past the last whole multiple of `span` would make the
+        // lower part of the range more likely, so they are drawn again.
+        let limit = u32::MAX - (u32::MAX - span + 1) % span;
+        loop {
+            let n = self.next_u32();
+            if n <= limit {
+                return lo.wrapping_add((n % span) as i32);
+            }
+        }
+    }
+
+    /// A number from 0.0 up to but not including 1.0.
+    pub fn next_f32(&mut self) -> f32 {
+        // All the precision an `f32` has.
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
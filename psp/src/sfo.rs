@@ -0,0 +1,337 @@
+//! PARAM.SFO files, which hold the title and other details of games and
+//! save data.
+//!
+//! ```no_run
+//! use psp::io::File;
+//!
+//! let mut data = alloc::vec::Vec::new();
+//! File::open("disc0:/PSP_GAME/PARAM.SFO")
+//!     .unwrap()
+//!     .read_to_end(&mut data)
+//!     .unwrap();
+//!
+//! let sfo = psp::sfo::parse(&data).unwrap();
+//! psp::dprintln!("{:?}", sfo.get_str("TITLE"));
+//! ```
+//!
+//! Entries keep the order and space they were stored with, so a file laid
+//! out like those written by Sony's tools and `mksfo`, with the keys and
+//! then the values packed in entry order, is written back byte for byte by
+//! `Sfo::to_bytes`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const MAGIC: &[u8; 4] = b"\0PSF";
+const VERSION: u32 = 0x0101;
+const HEADER_LEN: usize = 20;
+const INDEX_ENTRY_LEN: usize = 16;
+
+/// Entry formats, as stored.
+const FORMAT_BINARY: u16 = 0x0004;
+const FORMAT_STR: u16 = 0x0204;
+const FORMAT_INT: u16 = 0x0404;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SfoError {
+    /// The data doesn't start with the SFO signature.
+    NotSfo,
+    /// A table or value runs past the end of the data.
+    Truncated,
+    /// An entry's format is not a string, integer or binary value.
+    UnknownFormat(u16),
+    /// A string doesn't fit its entry's `max_len` bytes, including its NUL
+    /// terminator, in a parsed file, or when setting an existing entry.
+    Overlong { max_len: u32 },
+    /// A key or string to set contains a NUL byte.
+    InvalidString,
+    /// A key isn't valid UTF-8, an integer isn't 4 bytes, or binary data
+    /// doesn't fit its entry.
+    Corrupt,
+}
+
+/// A value in an SFO file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    /// A string, without its NUL terminator. `None` if it isn't valid
+    /// UTF-8.
+    Str(Option<&'a str>),
+    Int(u32),
+    /// Raw data, such as `SAVEDATA_PARAMS`.
+    Binary(&'a [u8]),
+}
+
+/// The entries of an SFO file, from `parse` or `Sfo::new`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sfo {
+    version: u32,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    key: String,
+    format: u16,
+    /// Bytes of `data` in use, including a string's NUL terminator.
+    len: u32,
+    /// The space reserved for the value, which is `max_len` when stored.
+    data: Vec<u8>,
+}
+
+impl Entry {
+    fn value(&self) -> Value<'_> {
+        let data = &self.data[..self.len as usize];
+
+        match self.format {
+            FORMAT_INT => Value::Int(u32::from_le_bytes([data[0], data[1], data[2], data[3]])),
+            FORMAT_STR => {
+                let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                Value::Str(core::str::from_utf8(&data[..end]).ok())
+            }
+            _ => Value::Binary(data),
+        }
+    }
+}
+
+/// Parse an SFO file.
+pub fn parse(data: &[u8]) -> Result<Sfo, SfoError> {
+    if data.len() < HEADER_LEN {
+        return Err(match MAGIC.starts_with(&data[..data.len().min(4)]) {
+            true => SfoError::Truncated,
+            false => SfoError::NotSfo,
+        });
+    }
+
+    if &data[..4] != MAGIC {
+        return Err(SfoError::NotSfo);
+    }
+
+    let word = |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let version = word(4);
+    let key_table = word(8) as usize;
+    let data_table = word(12) as usize;
+    let count = word(16) as usize;
+
+    let index_end = count
+        .checked_mul(INDEX_ENTRY_LEN)
+        .and_then(|len| len.checked_add(HEADER_LEN))
+        .ok_or(SfoError::Truncated)?;
+
+    if index_end > data.len() || key_table > data.len() || data_table > data.len() {
+        return Err(SfoError::Truncated);
+    }
+
+    let mut entries = Vec::with_capacity(count);
+
+    for index in data[HEADER_LEN..index_end].chunks_exact(INDEX_ENTRY_LEN) {
+        let half = |at: usize| u16::from_le_bytes([index[at], index[at + 1]]);
+        let word = |at: usize| {
+            u32::from_le_bytes([index[at], index[at + 1], index[at + 2], index[at + 3]])
+        };
+
+        let key_start = key_table + half(0) as usize;
+        let format = half(2);
+        let len = word(4);
+        let max_len = word(8);
+        let value_start = data_table
+            .checked_add(word(12) as usize)
+            .ok_or(SfoError::Truncated)?;
+
+        let key = data.get(key_start..).ok_or(SfoError::Truncated)?;
+        let key_len = key
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(SfoError::Truncated)?;
+        let key = core::str::from_utf8(&key[..key_len]).map_err(|_| SfoError::Corrupt)?;
+
+        let value = value_start
+            .checked_add(max_len as usize)
+            .and_then(|end| data.get(value_start..end))
+            .ok_or(SfoError::Truncated)?;
+
+        match format {
+            FORMAT_INT if len != 4 || max_len < 4 => return Err(SfoError::Corrupt),
+            FORMAT_STR if len > max_len || !value[..len as usize].ends_with(&[0]) => {
+                return Err(SfoError::Overlong { max_len })
+            }
+            FORMAT_BINARY if len > max_len => return Err(SfoError::Corrupt),
+            FORMAT_INT | FORMAT_STR | FORMAT_BINARY => {}
+            other => return Err(SfoError::UnknownFormat(other)),
+        }
+
+        entries.push(Entry {
+            key: key.into(),
+            format,
+            len,
+            data: value.into(),
+        });
+    }
+
+    Ok(Sfo { version, entries })
+}
+
+impl Sfo {
+    /// An SFO file with no entries.
+    pub fn new() -> Self {
+        Self {
+            version: VERSION,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The value of `key`.
+    pub fn get(&self, key: &str) -> Option<Value<'_>> {
+        self.entry(key).map(Entry::value)
+    }
+
+    /// The string value of `key`, such as `"TITLE"` or `"DISC_ID"`. `None`
+    /// if it isn't a string, or isn't valid UTF-8.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Value::Str(s) => s,
+            _ => None,
+        }
+    }
+
+    /// The integer value of `key`, such as `"PARENTAL_LEVEL"`.
+    pub fn get_int(&self, key: &str) -> Option<u32> {
+        match self.get(key)? {
+            Value::Int(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The keys and values, in the order they are stored.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Value<'_>)> {
+        self.entries.iter().map(|e| (e.key.as_str(), e.value()))
+    }
+
+    /// Set `key` to the string `value`.
+    ///
+    /// An existing string keeps the space it was stored with, which some
+    /// keys, like `TITLE`, need to keep, so a longer value fails with
+    /// `SfoError::Overlong`. A new key gets just enough space.
+    pub fn set_str(&mut self, key: &str, value: &str) -> Result<(), SfoError> {
+        if value.contains('\0') {
+            return Err(SfoError::InvalidString);
+        }
+
+        let len = value.len() as u32 + 1;
+        let entry = self.entry_for(key, FORMAT_STR, len)?;
+
+        if len as usize > entry.data.len() {
+            return Err(SfoError::Overlong {
+                max_len: entry.data.len() as u32,
+            });
+        }
+
+        entry.data.fill(0);
+        entry.data[..value.len()].copy_from_slice(value.as_bytes());
+        entry.len = len;
+        Ok(())
+    }
+
+    /// Set `key` to the integer `value`.
+    pub fn set_int(&mut self, key: &str, value: u32) -> Result<(), SfoError> {
+        let entry = self.entry_for(key, FORMAT_INT, 4)?;
+
+        entry.data.fill(0);
+        entry.data[..4].copy_from_slice(&value.to_le_bytes());
+        entry.len = 4;
+        Ok(())
+    }
+
+    /// Serialize to the SFO format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let index_end = HEADER_LEN + self.entries.len() * INDEX_ENTRY_LEN;
+        let keys_len: usize = self.entries.iter().map(|e| e.key.len() + 1).sum();
+        let data_table = align4(index_end + keys_len);
+        let data_len: usize = self.entries.iter().map(|e| e.data.len()).sum();
+
+        let mut out = Vec::with_capacity(data_table + data_len);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&(index_end as u32).to_le_bytes());
+        out.extend_from_slice(&(data_table as u32).to_le_bytes());
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+
+        let (mut key_offset, mut data_offset) = (0, 0);
+        for entry in &self.entries {
+            out.extend_from_slice(&(key_offset as u16).to_le_bytes());
+            out.extend_from_slice(&entry.format.to_le_bytes());
+            out.extend_from_slice(&entry.len.to_le_bytes());
+            out.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data_offset as u32).to_le_bytes());
+
+            key_offset += entry.key.len() + 1;
+            data_offset += entry.data.len();
+        }
+
+        for entry in &self.entries {
+            out.extend_from_slice(entry.key.as_bytes());
+            out.push(0);
+        }
+        out.resize(data_table, 0);
+
+        for entry in &self.entries {
+            out.extend_from_slice(&entry.data);
+        }
+
+        out
+    }
+
+    fn entry(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.key == key)
+    }
+
+    /// The entry for `key`, converted to `format`, or added with `len`
+    /// bytes of space, rounded up to a multiple of 4.
+    fn entry_for(&mut self, key: &str, format: u16, len: u32) -> Result<&mut Entry, SfoError> {
+        if key.contains('\0') {
+            return Err(SfoError::InvalidString);
+        }
+
+        let index = match self.entries.iter().position(|e| e.key == key) {
+            Some(index) => index,
+            None => {
+                // Keys are stored sorted, so keep them that way.
+                let index = self
+                    .entries
+                    .iter()
+                    .position(|e| e.key.as_str() > key)
+                    .unwrap_or(self.entries.len());
+
+                self.entries.insert(
+                    index,
+                    Entry {
+                        key: key.into(),
+                        format,
+                        len: 0,
+                        data: alloc::vec![0; align4(len as usize)],
+                    },
+                );
+                index
+            }
+        };
+
+        let entry = &mut self.entries[index];
+        if entry.format != format {
+            entry.format = format;
+            entry
+                .data
+                .resize(entry.data.len().max(align4(len as usize)), 0);
+        }
+
+        Ok(entry)
+    }
+}
+
+impl Default for Sfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
@@ -0,0 +1,334 @@
+use super::{with_timeout, KernelObject, Semaphore};
+use crate::sys::{self, SceUid};
+use crate::ERROR_WAIT_TIMEOUT;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::Cell;
+use core::ffi::c_void;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+/// The user memory partition, which the pipe's buffer is taken from.
+const USER_PARTITION: i32 = 2;
+
+/// Sent by the last sender to drop, after everything it sent.
+const DISCONNECTED: usize = 0;
+
+/// A channel holding up to `capacity` values, sent from any number of
+/// `Sender`s (it can be cloned) to one `Receiver`.
+///
+/// Values are boxed, and the boxes sent through a kernel message pipe. Once
+/// `capacity` values wait to be received, sending waits for room. A
+/// capacity of 0 is taken as 1.
+///
+/// # Panics
+///
+/// Panics if the kernel can't create the pipe, or the semaphore senders
+/// wait on, which only happens when it is out of memory for them.
+pub fn channel<T: Send>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        pipe: KernelObject::new(sys::sceKernelDeleteMsgPipe),
+        capacity,
+        slots: Semaphore::new(capacity as i32, i32::MAX),
+        senders: AtomicUsize::new(1),
+        receiver_dropped: AtomicBool::new(false),
+        _values: PhantomData,
+    });
+
+    // Created now, so that failing to create them panics here rather than
+    // in the middle of sending.
+    shared.pipe();
+    shared.slots.create();
+
+    let receiver = Receiver {
+        shared: shared.clone(),
+        disconnected: Cell::new(false),
+    };
+
+    (Sender { shared }, receiver)
+}
+
+struct Shared<T> {
+    /// Carries `Box<T>` pointers, with room for one more than `capacity`:
+    /// the last sender's `DISCONNECTED`.
+    pipe: KernelObject,
+    capacity: usize,
+    /// Free places in the pipe, which senders take before sending, and the
+    /// receiver gives back.
+    slots: Semaphore,
+    senders: AtomicUsize,
+    receiver_dropped: AtomicBool,
+    _values: PhantomData<T>,
+}
+
+// The pipe hands each value to one thread.
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn pipe(&self) -> SceUid {
+        self.pipe.get_or_create(|| unsafe {
+            sys::sceKernelCreateMsgPipe(
+                b"rust_channel\0".as_ptr(),
+                USER_PARTITION,
+                0,
+                // The buffer size, in bytes.
+                ((self.capacity + 1) * mem::size_of::<usize>()) as *mut c_void,
+                ptr::null_mut(),
+            )
+        })
+    }
+
+    /// Put `message` in the pipe, which must have room for it.
+    fn put(&self, message: usize) {
+        let mut message = message;
+        let ret = unsafe {
+            sys::sceKernelTrySendMsgPipe(
+                self.pipe(),
+                &mut message as *mut usize as *mut c_void,
+                mem::size_of::<usize>() as u32,
+                0,
+                ptr::null_mut(),
+            )
+        };
+
+        assert!(ret >= 0, "failed to send to message pipe: {:#x}", ret);
+    }
+
+    /// Wait for up to `timeout` for a message, or not at all with
+    /// `Some(Duration::ZERO)`.
+    fn take(&self, timeout: Option<Duration>) -> Result<usize, i32> {
+        let mut message = 0usize;
+        let message_ptr = &mut message as *mut usize as *mut c_void;
+        let size = mem::size_of::<usize>() as u32;
+
+        let ret = match timeout {
+            Some(Duration::ZERO) => unsafe {
+                sys::sceKernelTryReceiveMsgPipe(self.pipe(), message_ptr, size, 0, ptr::null_mut())
+            },
+            _ => with_timeout(timeout, |timeout| unsafe {
+                sys::sceKernelReceiveMsgPipe(
+                    self.pipe(),
+                    message_ptr,
+                    size,
+                    0,
+                    ptr::null_mut(),
+                    timeout,
+                )
+            }),
+        };
+
+        match ret {
+            e if e < 0 => Err(e),
+            _ => Ok(message),
+        }
+    }
+
+    /// Drop the values waiting in the pipe.
+    fn drain(&self) {
+        while let Ok(message) = self.take(Some(Duration::ZERO)) {
+            if message != DISCONNECTED {
+                drop(unsafe { Box::from_raw(message as *mut T) });
+            }
+        }
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        // Values sent as the receiver was dropped, after it drained the
+        // pipe.
+        self.drain();
+    }
+}
+
+/// The sending half of a `channel`. Clone it to send from more threads.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Send> Sender<T> {
+    /// Send `value`, waiting for room if the channel is full.
+    ///
+    /// Fails if the `Receiver` has been dropped, giving `value` back.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.send_for(value, None).map_err(|e| match e {
+            SendTimeoutError::Timeout(value) | SendTimeoutError::Disconnected(value) => {
+                SendError(value)
+            }
+        })
+    }
+
+    /// As `send`, giving up if there is still no room after `timeout`.
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.send_for(value, Some(timeout))
+    }
+
+    fn send_for(&self, value: T, timeout: Option<Duration>) -> Result<(), SendTimeoutError<T>> {
+        let shared = &*self.shared;
+
+        if shared.receiver_dropped.load(Ordering::Acquire) {
+            return Err(SendTimeoutError::Disconnected(value));
+        }
+
+        if !shared.slots.wait_for(timeout) {
+            return Err(SendTimeoutError::Timeout(value));
+        }
+
+        // Woken by the receiver being dropped. Wake the next waiting
+        // sender in turn.
+        if shared.receiver_dropped.load(Ordering::Acquire) {
+            shared.slots.signal(1);
+            return Err(SendTimeoutError::Disconnected(value));
+        }
+
+        shared.put(Box::into_raw(Box::new(value)) as usize);
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // No sender is left to fill the place kept for this.
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.put(DISCONNECTED);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sender").finish()
+    }
+}
+
+/// The receiving half of a `channel`.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// Whether `DISCONNECTED` has been received.
+    disconnected: Cell<bool>,
+}
+
+impl<T: Send> Receiver<T> {
+    /// Wait for a value.
+    ///
+    /// Fails once every `Sender` has been dropped and the values they sent
+    /// have been received.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.recv_for(None).map_err(|_| RecvError)
+    }
+
+    /// Receive a value if one is waiting.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.recv_for(Some(Duration::ZERO)).map_err(|e| match e {
+            RecvTimeoutError::Timeout => TryRecvError::Empty,
+            RecvTimeoutError::Disconnected => TryRecvError::Disconnected,
+        })
+    }
+
+    /// As `recv`, giving up if no value arrives within `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.recv_for(Some(timeout))
+    }
+
+    fn recv_for(&self, timeout: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        if self.disconnected.get() {
+            return Err(RecvTimeoutError::Disconnected);
+        }
+
+        let message = match self.shared.take(timeout) {
+            Ok(message) => message,
+            Err(e) if e == ERROR_WAIT_TIMEOUT || timeout == Some(Duration::ZERO) => {
+                return Err(RecvTimeoutError::Timeout)
+            }
+            Err(e) => panic!("failed to receive from message pipe: {:#x}", e),
+        };
+
+        if message == DISCONNECTED {
+            self.disconnected.set(true);
+            return Err(RecvTimeoutError::Disconnected);
+        }
+
+        self.shared.slots.signal(1);
+        Ok(*unsafe { Box::from_raw(message as *mut T) })
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_dropped.store(true, Ordering::Release);
+
+        // Wake a waiting sender, which wakes the next.
+        self.shared.slots.signal(1);
+        self.shared.drain();
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Receiver").finish()
+    }
+}
+
+/// The `Receiver` was dropped. Holds the value that wasn't sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+/// Why `Sender::send_timeout` failed. Holds the value that wasn't sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SendTimeoutError<T> {
+    /// The channel stayed full.
+    Timeout(T),
+    /// The `Receiver` was dropped.
+    Disconnected(T),
+}
+
+impl<T> fmt::Debug for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => f.write_str("Timeout(..)"),
+            SendTimeoutError::Disconnected(_) => f.write_str("Disconnected(..)"),
+        }
+    }
+}
+
+/// Every `Sender` was dropped, and the values they sent received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No value is waiting.
+    Empty,
+    /// Every `Sender` was dropped, and the values they sent received.
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    /// No value arrived in time.
+    Timeout,
+    /// Every `Sender` was dropped, and the values they sent received.
+    Disconnected,
+}
@@ -0,0 +1,146 @@
+use super::{with_timeout, KernelObject};
+use crate::sys::{self, EventFlagAttributes, EventFlagWaitTypes, SceUid};
+use crate::ERROR_WAIT_TIMEOUT;
+use core::fmt;
+use core::ptr;
+use core::time::Duration;
+
+/// The flag was deleted while the thread waited on it.
+const ERROR_WAIT_DELETE: i32 = 0x8002_01b5_u32 as i32;
+
+/// The flag's UID doesn't exist, because it was deleted before the wait.
+const ERROR_UNKNOWN_EVFID: i32 = 0x8002_019a_u32 as i32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitError {
+    /// The bits weren't set before the timeout.
+    Timeout,
+    /// The flag was deleted, before or while waiting.
+    Deleted,
+    /// The kernel returned another error code, such as for waiting on no
+    /// bits.
+    Kernel(i32),
+}
+
+impl WaitError {
+    fn from_code(code: i32) -> Self {
+        match code {
+            ERROR_WAIT_TIMEOUT => WaitError::Timeout,
+            ERROR_WAIT_DELETE | ERROR_UNKNOWN_EVFID => WaitError::Deleted,
+            _ => WaitError::Kernel(code),
+        }
+    }
+}
+
+/// 32 bits that threads set, clear, and wait on, for signaling each other.
+///
+/// Any number of threads can wait at once, each for any or all of the bits
+/// it asks for. Setting bits wakes every thread whose wait they satisfy.
+///
+/// ```no_run
+/// use psp::sync::EventFlag;
+///
+/// const LOADED: u32 = 1 << 0;
+/// const CANCELLED: u32 = 1 << 1;
+///
+/// static LOADER: EventFlag = EventFlag::new(0);
+///
+/// psp::thread::spawn(|| {
+///     // Load the level.
+///     LOADER.set(LOADED);
+/// });
+///
+/// match LOADER.wait_any(LOADED | CANCELLED, None) {
+///     Ok(bits) if bits & LOADED != 0 => { /* Start the level. */ }
+///     _ => { /* Back to the menu. */ }
+/// }
+/// ```
+///
+/// The kernel object is created on first use, so `new` can be used for a
+/// `static`, and deleted on drop. Code still waiting on it through its
+/// `id` then wakes up with an error, `WaitError::Deleted` in Rust.
+pub struct EventFlag {
+    object: KernelObject,
+    initial: u32,
+}
+
+impl EventFlag {
+    /// A flag with the bits of `initial` set.
+    pub const fn new(initial: u32) -> Self {
+        Self {
+            object: KernelObject::new(sys::sceKernelDeleteEventFlag),
+            initial,
+        }
+    }
+
+    /// Set `bits`, waking the threads waiting for them.
+    pub fn set(&self, bits: u32) {
+        unsafe { sys::sceKernelSetEventFlag(self.uid(), bits) };
+    }
+
+    /// Clear `bits`, leaving the others as they are.
+    pub fn clear(&self, bits: u32) {
+        // The kernel keeps the bits it is given, and clears the rest.
+        unsafe { sys::sceKernelClearEventFlag(self.uid(), !bits) };
+    }
+
+    /// Wait until any of `bits` is set, or `timeout` passes, and return all
+    /// of the bits as they were when the wait ended. `None` waits forever.
+    ///
+    /// The bits stay set. Use `clear` to take them back.
+    pub fn wait_any(&self, bits: u32, timeout: Option<Duration>) -> Result<u32, WaitError> {
+        self.wait(bits, EventFlagWaitTypes::OR, timeout)
+    }
+
+    /// Wait until all of `bits` are set, or `timeout` passes, and return all
+    /// of the bits as they were when the wait ended. `None` waits forever.
+    pub fn wait_all(&self, bits: u32, timeout: Option<Duration>) -> Result<u32, WaitError> {
+        self.wait(bits, EventFlagWaitTypes::AND, timeout)
+    }
+
+    /// The kernel's UID for the flag, for passing to code that uses it
+    /// directly.
+    pub fn id(&self) -> i32 {
+        self.uid().0
+    }
+
+    fn wait(
+        &self,
+        bits: u32,
+        mode: EventFlagWaitTypes,
+        timeout: Option<Duration>,
+    ) -> Result<u32, WaitError> {
+        let mut out = 0;
+        let ret = with_timeout(timeout, |timeout| unsafe {
+            sys::sceKernelWaitEventFlag(self.uid(), bits, mode, &mut out, timeout)
+        });
+
+        match ret {
+            e if e < 0 => Err(WaitError::from_code(e)),
+            _ => Ok(out),
+        }
+    }
+
+    fn uid(&self) -> SceUid {
+        self.object.get_or_create(|| unsafe {
+            sys::sceKernelCreateEventFlag(
+                b"rust_event_flag\0".as_ptr(),
+                EventFlagAttributes::WAIT_MULTIPLE,
+                self.initial as i32,
+                ptr::null_mut(),
+            )
+        })
+    }
+}
+
+impl Default for EventFlag {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl fmt::Debug for EventFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventFlag").field("id", &self.id()).finish()
+    }
+}
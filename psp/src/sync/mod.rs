@@ -0,0 +1,237 @@
+//! Sharing data between threads.
+//!
+//! `Mutex` lets one thread at a time reach the data it holds. It can be
+//! put in a `static`, for state shared with threads started by
+//! `thread::spawn`:
+//!
+//! ```no_run
+//! use psp::sync::Mutex;
+//!
+//! static LOADED: Mutex<usize> = Mutex::new(0);
+//!
+//! let loader = psp::thread::spawn(|| {
+//!     for _ in 0..10 {
+//!         // Load a file.
+//!         *LOADED.lock() += 1;
+//!     }
+//! });
+//!
+//! psp::dprintln!("{} files loaded", *LOADED.lock());
+//! loader.join().unwrap();
+//! ```
+//!
+//! `RwLock` lets any number of threads read at once, for data that is
+//! mostly read, such as a loaded configuration.
+//!
+//! `Lazy` builds a `static` the first time it is used, exactly once, even
+//! with threads racing to use it first, and `Once` runs any code that way:
+//!
+//! ```no_run
+//! use psp::sync::Lazy;
+//!
+//! static TABLE: Lazy<[u8; 256]> = Lazy::new(|| {
+//!     let mut table = [0; 256];
+//!     for (i, x) in table.iter_mut().enumerate() {
+//!         *x = (i as u8).reverse_bits();
+//!     }
+//!     table
+//! });
+//!
+//! let reversed = TABLE[0x01];
+//! ```
+//!
+//! `EventFlag` signals other threads, which wait for some or all of its
+//! bits to be set.
+//!
+//! `channel` hands values from any number of threads to one other, such as
+//! assets from a loading thread to the one drawing:
+//!
+//! ```no_run
+//! use alloc::vec::Vec;
+//!
+//! let (sender, receiver) = psp::sync::channel::<Vec<u8>>(4);
+//!
+//! psp::thread::spawn(move || {
+//!     for level in 0..3 {
+//!         // Load the level's data.
+//!         let data = alloc::vec![level; 1024];
+//!         sender.send(data).unwrap();
+//!     }
+//! });
+//!
+//! // Ends once the loading thread is done, and its sender dropped.
+//! while let Ok(data) = receiver.recv() {
+//!     psp::dprintln!("loaded {} bytes", data.len());
+//! }
+//! ```
+//!
+//! Statics can't make system calls, so the kernel objects these wait on
+//! are created when first needed.
+
+use crate::sys::{self, SceUid};
+use crate::{critical, ERROR_WAIT_TIMEOUT};
+use core::cell::UnsafeCell;
+use core::ptr;
+use core::time::Duration;
+
+mod channel;
+mod event_flag;
+mod mutex;
+mod once;
+mod rwlock;
+
+pub use channel::{
+    channel, Receiver, RecvError, RecvTimeoutError, SendError, SendTimeoutError, Sender,
+    TryRecvError,
+};
+pub use event_flag::{EventFlag, WaitError};
+pub use mutex::{Mutex, MutexGuard};
+pub use once::{Lazy, Once};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// `KernelObject::uid` before the object is created.
+const NOT_CREATED: i32 = 0;
+
+/// A kernel object, created on first use, and deleted on drop.
+struct KernelObject {
+    /// The object's UID, or `NOT_CREATED`. Only changed through
+    /// `critical`.
+    uid: UnsafeCell<i32>,
+    delete: unsafe extern "C" fn(SceUid) -> i32,
+}
+
+// The UID is only reached through `critical`.
+unsafe impl Send for KernelObject {}
+unsafe impl Sync for KernelObject {}
+
+impl KernelObject {
+    const fn new(delete: unsafe extern "C" fn(SceUid) -> i32) -> Self {
+        Self {
+            uid: UnsafeCell::new(NOT_CREATED),
+            delete,
+        }
+    }
+
+    /// The object, created with `create` first if this is its first use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `create` fails, which for the objects here only happens
+    /// when the kernel is out of memory for them.
+    fn get_or_create(&self, create: impl FnOnce() -> SceUid) -> SceUid {
+        match critical(|| unsafe { *self.uid.get() }) {
+            NOT_CREATED => {}
+            uid => return SceUid(uid),
+        }
+
+        // Created outside of `critical`, which would hold off interrupts
+        // for the whole system call.
+        let created = create();
+        assert!(
+            created.0 >= 0,
+            "failed to create kernel object: {:#x}",
+            created.0
+        );
+
+        let uid = critical(|| unsafe {
+            if *self.uid.get() == NOT_CREATED {
+                *self.uid.get() = created.0;
+            }
+
+            *self.uid.get()
+        });
+
+        // Another thread's first use got there first.
+        if uid != created.0 {
+            unsafe { (self.delete)(created) };
+        }
+
+        SceUid(uid)
+    }
+}
+
+impl Drop for KernelObject {
+    fn drop(&mut self) {
+        match *self.uid.get_mut() {
+            NOT_CREATED => {}
+            uid => unsafe {
+                (self.delete)(SceUid(uid));
+            },
+        }
+    }
+}
+
+/// A kernel semaphore, created on first use.
+struct Semaphore {
+    object: KernelObject,
+    initial: i32,
+    max: i32,
+}
+
+impl Semaphore {
+    const fn new(initial: i32, max: i32) -> Self {
+        Self {
+            object: KernelObject::new(sys::sceKernelDeleteSema),
+            initial,
+            max,
+        }
+    }
+
+    /// Create the semaphore now, if it hasn't been yet.
+    fn create(&self) {
+        self.uid();
+    }
+
+    /// Wait for the semaphore and take one from its count.
+    fn wait(&self) {
+        self.wait_for(None);
+    }
+
+    /// As `wait`, giving up after `timeout`. Returns whether one was taken.
+    fn wait_for(&self, timeout: Option<Duration>) -> bool {
+        let ret = with_timeout(timeout, |timeout| unsafe {
+            sys::sceKernelWaitSema(self.uid(), 1, timeout)
+        });
+
+        match ret {
+            ERROR_WAIT_TIMEOUT => false,
+            e => {
+                assert!(e >= 0, "failed to wait for semaphore: {:#x}", e);
+                true
+            }
+        }
+    }
+
+    /// Take one from the count if it isn't 0, without waiting.
+    fn poll(&self) -> bool {
+        unsafe { sys::sceKernelPollSema(self.uid(), 1) >= 0 }
+    }
+
+    fn signal(&self, count: i32) {
+        unsafe { sys::sceKernelSignalSema(self.uid(), count) };
+    }
+
+    fn uid(&self) -> SceUid {
+        self.object.get_or_create(|| unsafe {
+            sys::sceKernelCreateSema(
+                b"rust_sync\0".as_ptr(),
+                0,
+                self.initial,
+                self.max,
+                ptr::null_mut(),
+            )
+        })
+    }
+}
+
+/// Run `f` with `timeout` as the kernel's wait functions take it: a count
+/// of microseconds, which they count down in place, or null to wait
+/// forever.
+fn with_timeout<R>(timeout: Option<Duration>, f: impl FnOnce(*mut u32) -> R) -> R {
+    let mut micros = timeout.map(|t| t.as_micros().min(u32::MAX as u128) as u32);
+
+    f(match &mut micros {
+        Some(micros) => micros,
+        None => ptr::null_mut(),
+    })
+}
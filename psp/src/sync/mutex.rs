@@ -0,0 +1,133 @@
+use super::Semaphore;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+/// A lock around `T`, for sharing it between threads.
+///
+/// Waiting threads sleep on a kernel semaphore, rather than spinning, so
+/// a thread of lower priority holding the lock still gets to run and
+/// release it. The semaphore is created on the first `lock` or
+/// `try_lock`, so `new` can be used for a `static`.
+///
+/// Locking a mutex the calling thread already holds waits forever.
+///
+/// There is no poisoning: a thread that panics while holding the lock
+/// releases it as the guard is dropped, and the data is left as it was.
+pub struct Mutex<T: ?Sized> {
+    /// Counts 1 while the lock is free.
+    sema: Semaphore,
+    data: UnsafeCell<T>,
+}
+
+// The data is only reached through a guard, which one thread holds at a
+// time.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            sema: Semaphore::new(1, 1),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Wait until no other thread holds the lock, and take it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the kernel can't create the semaphore, which only happens
+    /// when it is out of memory for kernel objects.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.sema.wait();
+        MutexGuard::new(self)
+    }
+
+    /// Take the lock if no other thread holds it, without waiting.
+    ///
+    /// # Panics
+    ///
+    /// As `lock`.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        match self.sema.poll() {
+            true => Some(MutexGuard::new(self)),
+            false => None,
+        }
+    }
+
+    /// The data, without locking, since `&mut self` means no other thread
+    /// can hold the lock.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+
+        d.finish()
+    }
+}
+
+/// The lock on a `Mutex`, released on drop. Dereferences to the data.
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    /// Shares the data like a `&mut T`, so the guard is only `Sync` if the
+    /// data is.
+    _data: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    fn new(mutex: &'a Mutex<T>) -> Self {
+        Self {
+            mutex,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.sema.signal(1);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,133 @@
+use super::Mutex;
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Runs code exactly once, such as to set up global state, however many
+/// threads call it.
+///
+/// ```no_run
+/// use psp::sync::Once;
+///
+/// static INIT: Once = Once::new();
+///
+/// INIT.call_once(|| psp::dprintln!("only printed once"));
+/// INIT.call_once(|| unreachable!());
+/// ```
+pub struct Once {
+    done: AtomicBool,
+    /// Held while `call_once` runs its closure, so racing threads wait for
+    /// it to finish.
+    running: Mutex<()>,
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            done: AtomicBool::new(false),
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Run `f`, unless a call on this `Once` already has. A call made while
+    /// another thread runs its closure waits for that to finish.
+    ///
+    /// If `f` panics, the next call runs its closure instead. A call from
+    /// inside `f` on the same `Once` waits forever.
+    pub fn call_once<F: FnOnce()>(&self, f: F) {
+        if self.is_completed() {
+            return;
+        }
+
+        let _running = self.running.lock();
+        if !self.done.load(Ordering::Relaxed) {
+            f();
+            self.done.store(true, Ordering::Release);
+        }
+    }
+
+    /// Whether a closure given to `call_once` has finished.
+    pub fn is_completed(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Once {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Once")
+            .field("completed", &self.is_completed())
+            .finish()
+    }
+}
+
+/// A value built the first time it is used, for a `static` that can't be
+/// built in a constant.
+///
+/// The first use runs the function given to `new`, as with `Once`, and
+/// later ones return what it built.
+pub struct Lazy<T, F = fn() -> T> {
+    once: Once,
+    /// Taken by the first use.
+    init: Cell<Option<F>>,
+    /// Set once `once` completes, and not changed after.
+    value: UnsafeCell<Option<T>>,
+}
+
+// `init` is only taken while holding `once`, and `value` only written
+// then. Threads share `&T` after, and the thread using it first runs `F`.
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F> Lazy<T, F> {
+    pub const fn new(init: F) -> Self {
+        Self {
+            once: Once::new(),
+            init: Cell::new(Some(init)),
+            value: UnsafeCell::new(None),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// The value, built first if this is the first use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an earlier first use panicked while building the value.
+    pub fn force(this: &Self) -> &T {
+        this.once.call_once(|| {
+            let init = this.init.take().expect("Lazy's initializer panicked");
+            unsafe { *this.value.get() = Some(init()) };
+        });
+
+        // Set by the closure above, or by an earlier `force`.
+        unsafe { (*this.value.get()).as_ref().unwrap() }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("Lazy");
+
+        match self.once.is_completed() {
+            true => d.field("value", unsafe { (*self.value.get()).as_ref().unwrap() }),
+            false => d.field("value", &format_args!("<uninit>")),
+        };
+
+        d.finish()
+    }
+}
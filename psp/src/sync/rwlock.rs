@@ -0,0 +1,310 @@
+use super::Semaphore;
+use crate::critical;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+/// A lock around `T` that any number of threads can hold for reading at
+/// once, or one for writing.
+///
+/// Writers come first: once a writer is waiting, new readers wait behind
+/// it, and a writer releasing the lock hands it to the next waiting writer
+/// before any waiting reader. So readers can't keep a writer out, but
+/// writers that keep coming can keep readers waiting.
+///
+/// Uncontended locking doesn't wait on the kernel. Threads that have to
+/// wait sleep on a semaphore, created the first time one does, so `new`
+/// can be used for a `static`.
+///
+/// Taking the lock again on a thread that already holds it can wait
+/// forever, even for reading, if a writer is waiting in between. As with
+/// `Mutex`, there is no poisoning.
+pub struct RwLock<T: ?Sized> {
+    /// Only reached through `critical`.
+    state: UnsafeCell<State>,
+    /// Waiting readers sleep on this, and are counted as holding the lock
+    /// before they are woken.
+    readers: Semaphore,
+    /// Waiting writers sleep on this, and likewise.
+    writers: Semaphore,
+    data: UnsafeCell<T>,
+}
+
+// Readers share `&T` between threads, and writers can send the data to
+// another thread through `&mut T`.
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+#[derive(Clone, Copy)]
+struct State {
+    /// Threads holding the lock for reading.
+    readers: u32,
+    /// Whether a thread holds the lock for writing.
+    writer: bool,
+    waiting_readers: u32,
+    waiting_writers: u32,
+}
+
+impl State {
+    fn try_read(&mut self) -> bool {
+        let free = !self.writer && self.waiting_writers == 0;
+        self.readers += free as u32;
+        free
+    }
+
+    fn try_write(&mut self) -> bool {
+        let free = !self.writer && self.readers == 0;
+        self.writer |= free;
+        free
+    }
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: UnsafeCell::new(State {
+                readers: 0,
+                writer: false,
+                waiting_readers: 0,
+                waiting_writers: 0,
+            }),
+            readers: Semaphore::new(0, i32::MAX),
+            writers: Semaphore::new(0, i32::MAX),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Wait until no thread holds or waits for the lock for writing, and
+    /// take it for reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the kernel can't create the semaphore to wait on, which
+    /// only happens when it is out of memory for kernel objects.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.acquire(&self.readers, State::try_read, |state| {
+            state.waiting_readers += 1
+        });
+
+        RwLockReadGuard::new(self)
+    }
+
+    /// Take the lock for reading if that doesn't need waiting.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        match critical(|| unsafe { (*self.state.get()).try_read() }) {
+            true => Some(RwLockReadGuard::new(self)),
+            false => None,
+        }
+    }
+
+    /// Wait until no other thread holds the lock, and take it for writing.
+    ///
+    /// # Panics
+    ///
+    /// As `read`.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.acquire(&self.writers, State::try_write, |state| {
+            state.waiting_writers += 1
+        });
+
+        RwLockWriteGuard::new(self)
+    }
+
+    /// Take the lock for writing if no other thread holds it.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        match critical(|| unsafe { (*self.state.get()).try_write() }) {
+            true => Some(RwLockWriteGuard::new(self)),
+            false => None,
+        }
+    }
+
+    /// The data, without locking, since `&mut self` means no other thread
+    /// can hold the lock.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Take the lock with `take`, or else count the thread as waiting with
+    /// `queue` and sleep on `sema` until a releasing thread hands it the
+    /// lock.
+    fn acquire(&self, sema: &Semaphore, take: fn(&mut State) -> bool, queue: fn(&mut State)) {
+        let mut created = false;
+
+        loop {
+            let queued = critical(|| unsafe {
+                let state = &mut *self.state.get();
+
+                if take(state) {
+                    Some(false)
+                } else if created {
+                    queue(state);
+                    Some(true)
+                } else {
+                    None
+                }
+            });
+
+            match queued {
+                Some(false) => return,
+                Some(true) => return sema.wait(),
+                // The semaphore is created before the thread counts as
+                // waiting, so that releasing threads never have to. That
+                // can't be done in `critical`, so the lock is tried again
+                // after.
+                None => {
+                    sema.create();
+                    created = true;
+                }
+            }
+        }
+    }
+
+    fn read_unlock(&self) {
+        let wake_writer = critical(|| unsafe {
+            let state = &mut *self.state.get();
+            state.readers -= 1;
+
+            let wake = state.readers == 0 && state.waiting_writers > 0;
+            if wake {
+                state.waiting_writers -= 1;
+                state.writer = true;
+            }
+
+            wake
+        });
+
+        if wake_writer {
+            self.writers.signal(1);
+        }
+    }
+
+    fn write_unlock(&self) {
+        let (wake_writer, wake_readers) = critical(|| unsafe {
+            let state = &mut *self.state.get();
+
+            if state.waiting_writers > 0 {
+                state.waiting_writers -= 1;
+                return (true, 0);
+            }
+
+            state.writer = false;
+            state.readers = state.waiting_readers;
+            state.waiting_readers = 0;
+
+            (false, state.readers)
+        });
+
+        if wake_writer {
+            self.writers.signal(1);
+        } else if wake_readers > 0 {
+            self.readers.signal(wake_readers as i32);
+        }
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+
+        d.finish()
+    }
+}
+
+/// The lock on a `RwLock` for reading, released on drop. Dereferences to
+/// the data.
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    /// Shares the data like a `&T`.
+    _data: PhantomData<&'a T>,
+}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    fn new(lock: &'a RwLock<T>) -> Self {
+        Self {
+            lock,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The lock on a `RwLock` for writing, released on drop. Dereferences to
+/// the data.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    /// Shares the data like a `&mut T`, so the guard is only `Sync` if the
+    /// data is.
+    _data: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    fn new(lock: &'a RwLock<T>) -> Self {
+        Self {
+            lock,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
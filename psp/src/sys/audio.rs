@@ -339,7 +339,7 @@ psp_extern! {
     /// # Return value
     ///
     /// 0 on success, <0 on error.
-    pub fn sceAudioInputBlocking(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void);
+    pub fn sceAudioInputBlocking(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void) -> i32;
 
     #[psp(0x6D4BEC68)]
     /// Perform audio input
@@ -353,7 +353,7 @@ psp_extern! {
     /// # Return value
     ///
     /// 0 on success, <0 on error.
-    pub fn sceAudioInput(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void);
+    pub fn sceAudioInput(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void) -> i32;
 
     #[psp(0xA708C6A6)]
     /// Get the number of samples that were acquired
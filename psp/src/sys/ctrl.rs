@@ -83,9 +83,13 @@ pub struct SceCtrlData {
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct SceCtrlLatch {
+    /// Buttons that went down.
     pub ui_make: u32,
+    /// Buttons that went up.
     pub ui_break: u32,
+    /// Buttons currently down.
     pub ui_press: u32,
+    /// Buttons currently up.
     pub ui_release: u32,
 }
 
@@ -161,9 +165,31 @@ psp_extern! {
     pub fn sceCtrlReadBufferNegative(pad_data: *mut SceCtrlData, count: i32) -> i32;
 
     #[psp(0xB1D0E5CD)]
+    /// Read the button transitions recorded since the latch was last reset,
+    /// without resetting it.
+    ///
+    /// # Parameters
+    ///
+    /// - `latch_data`: Pointer to a `SceCtrlLatch` to hold the latch data.
+    ///
+    /// # Return value
+    ///
+    /// The number of samples taken since the latch was last reset, < 0 on
+    /// error.
     pub fn sceCtrlPeekLatch(latch_data: *mut SceCtrlLatch) -> i32;
 
     #[psp(0x0B588501)]
+    /// Read the button transitions recorded since the latch was last reset,
+    /// and reset it.
+    ///
+    /// # Parameters
+    ///
+    /// - `latch_data`: Pointer to a `SceCtrlLatch` to hold the latch data.
+    ///
+    /// # Return value
+    ///
+    /// The number of samples taken since the latch was last reset, < 0 on
+    /// error.
     pub fn sceCtrlReadLatch(latch_data: *mut SceCtrlLatch) -> i32;
 
     #[psp(0xA7144800)]
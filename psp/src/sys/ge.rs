@@ -343,6 +343,18 @@ pub enum GeCommand {
     NopFF = 0xff,
 }
 
+/// Address bit that makes accesses bypass the CPU cache.
+pub const UNCACHED_BIT: usize = 0x4000_0000;
+
+/// The uncached alias of `ptr`, for memory the GE or display reads directly,
+/// such as VRAM from `sceGeEdramGetAddr`.
+///
+/// Writes through it are seen without flushing the data cache. This is a
+/// no-op for a pointer that is already uncached.
+pub fn uncached<T>(ptr: *mut T) -> *mut T {
+    (ptr as usize | UNCACHED_BIT) as *mut T
+}
+
 psp_extern! {
     #![name = "sceGe_user"]
     #![flags = 0x4001]
@@ -354,7 +366,7 @@ psp_extern! {
     /// # Return value
     ///
     /// The size of VRAM (in bytes).
-    pub fn sceGeEdramGetSize() -> u32;
+    pub fn sceGeEdramGetSize() -> usize;
 
     #[psp(0xE47E40E4)]
     /// Get the eDRAM address.
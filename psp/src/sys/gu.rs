@@ -164,11 +164,37 @@ impl VertexType {
     const fn num_vertices(n: u32) -> i32 {
         (((n - 1) & 7) << 18) as i32
     }
+
+    /// This type with `n` (1-8) skinning weights per vertex, replacing any
+    /// previous weight count. The weight format must be set separately.
+    pub const fn with_weights(self, n: u32) -> Self {
+        Self::from_bits_truncate((self.bits() & !(7 << 14)) | Self::num_weights(n))
+    }
+
+    /// This type with `n` (1-8) morph targets per vertex, replacing any
+    /// previous morph count.
+    pub const fn with_morph_targets(self, n: u32) -> Self {
+        Self::from_bits_truncate((self.bits() & !(7 << 18)) | Self::num_vertices(n))
+    }
+
+    /// Number of skinning weights per vertex, 0 if there is no weight format.
+    pub const fn weight_count(self) -> usize {
+        if self.bits() & Self::WEIGHT_32BITF.bits() == 0 {
+            0
+        } else {
+            ((self.bits() >> 14) & 7) as usize + 1
+        }
+    }
+
+    /// Number of morph targets per vertex, 1 if the vertices aren't morphed.
+    pub const fn morph_count(self) -> usize {
+        ((self.bits() >> 18) & 7) as usize + 1
+    }
 }
 
 /// Texture pixel formats
 // TODO: Better documentation
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum TexturePixelFormat {
     /// Hicolor, 16-bit.
@@ -324,7 +350,7 @@ pub enum ColorFunc {
 }
 
 /// Test function for depth test
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum DepthFunc {
     /// No pixels pass the depth-test
@@ -425,6 +451,7 @@ pub enum MipmapLevel {
 /// - `Cd`: Destination color
 /// - `Bs`: Blend function for source fragment
 /// - `Bd`: Blend function for destination fragment
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum BlendOp {
     /// `(Cs*Bs) + (Cd*Bd)`
@@ -442,6 +469,7 @@ pub enum BlendOp {
 }
 
 /// Blending factor
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum BlendFactor {
     Color = 0,
@@ -1648,7 +1676,7 @@ pub unsafe extern "C" fn sceGuGetMemory(mut size: i32) -> *mut c_void {
 #[no_mangle]
 pub unsafe extern "C" fn sceGuStart(context_type: GuContextType, list: *mut c_void) {
     let context = &mut CONTEXTS[context_type as usize];
-    let local_list = ((list as u32) | 0x4000_0000) as *mut u32;
+    let local_list = sys::uncached(list.cast::<u32>());
 
     // setup display list
     context.list.start = local_list;
@@ -645,6 +645,14 @@ psp_extern! {
         sub_intr_code: SceUid,
         data: *mut IntrHandlerOptionParam,
     ) -> i32;
+
+    #[psp(0xFE28C6D9)]
+    /// Determine whether the caller is running in an interrupt handler.
+    ///
+    /// # Return Value
+    ///
+    /// 1 inside an interrupt handler, 0 otherwise.
+    pub fn sceKernelIsIntrContext() -> i32;
 }
 
 psp_extern! {
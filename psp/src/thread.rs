@@ -0,0 +1,426 @@
+//! Sleeping, and the calling thread's stack and status.
+//!
+//! `spawn` runs a closure on a new thread, and `Builder` sets that thread's
+//! name, priority and stack size. `sleep` and `delay` sleep, `yield_now`
+//! lets other threads run without sleeping, and `delay_precise` waits
+//! without letting them.
+//!
+//! A stack overflow on the PSP doesn't fault, it overwrites whatever is
+//! below the stack, and crashes later somewhere unrelated. Watching
+//! `stack_free` during development catches a stack running low first, such
+//! as on a status line of the debug console:
+//!
+//! ```no_run
+//! let free = psp::thread::stack_free();
+//! psp::debug::print_at(0, 40, &alloc::format!("stack {:>6}", free));
+//! ```
+
+use crate::sys::{self, SceKernelThreadInfo, SceUid, ThreadAttributes};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::mem::{self, MaybeUninit};
+use core::panic::AssertUnwindSafe;
+use core::ptr;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::time::Duration;
+
+/// Priority of threads from `spawn`, the same as the main thread's.
+pub const DEFAULT_PRIORITY: i32 = 32;
+
+/// Stack size of threads from `spawn`, in bytes.
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// Name of threads from `spawn`.
+const DEFAULT_NAME: &str = "rust_thread";
+
+// The states of a `Packet`.
+const RUNNING: u8 = 0;
+const FINISHED: u8 = 1;
+const DETACHED: u8 = 2;
+
+bitflags::bitflags! {
+    /// What a thread is doing. A thread can be both waiting and suspended.
+    pub struct ThreadStatus: i32 {
+        const RUNNING = 1;
+        const READY = 2;
+        const WAITING = 4;
+        const SUSPENDED = 8;
+        const STOPPED = 16;
+        const KILLED = 32;
+    }
+}
+
+/// Information about a thread, from `current_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    /// The thread's UID.
+    pub id: i32,
+    pub name: String,
+    pub status: ThreadStatus,
+    /// Lower is more urgent. The main thread starts at 32.
+    pub priority: i32,
+    /// The priority the thread was created with.
+    pub initial_priority: i32,
+    /// Stack size in bytes.
+    pub stack_size: usize,
+    /// Stack bytes never used, as `stack_free`.
+    pub stack_free: usize,
+}
+
+/// Sleep for at least `micros` microseconds, letting other threads run.
+///
+/// A `u32` of microseconds only reaches about 71 minutes. `sleep` and
+/// `sleep_us` take longer ones.
+pub fn delay(micros: u32) {
+    unsafe { sys::sceKernelDelayThread(micros) };
+}
+
+/// Sleep for at least `duration`, rounded up to whole microseconds, letting
+/// other threads run.
+pub fn sleep(duration: Duration) {
+    sleep_us(micros(duration).min(u64::MAX as u128) as u64);
+}
+
+/// Sleep for at least `micros` microseconds, letting other threads run.
+/// Waits too long for one `delay` are split into several.
+pub fn sleep_us(micros: u64) {
+    let mut left = micros;
+
+    loop {
+        let step = left.min(u32::MAX as u64) as u32;
+        delay(step);
+
+        left -= step as u64;
+        if left == 0 {
+            break;
+        }
+    }
+}
+
+/// Wait for `duration` by spinning on the system clock, without sleeping.
+///
+/// Sleeping wakes up on the kernel's scheduler, which can overshoot a
+/// short wait many times over, so this is for waits of up to about 100
+/// microseconds that need to be close to exact, such as between writes to
+/// hardware. The clock counts whole microseconds. Other threads of the
+/// same or lower priority (higher numbers) don't run meanwhile, so use
+/// `sleep` for anything longer.
+pub fn delay_precise(duration: Duration) {
+    let micros = micros(duration).min(i64::MAX as u128) as i64;
+
+    let start = unsafe { sys::sceKernelGetSystemTimeWide() };
+    while unsafe { sys::sceKernelGetSystemTimeWide() } - start < micros {
+        core::hint::spin_loop();
+    }
+}
+
+/// Sleep like `delay`, but run the calling thread's pending callbacks
+/// meanwhile, and as they arrive.
+///
+/// Callbacks only run on the thread that created them, while it waits in a
+/// `CB` function like this one. `module!` handles the Home button's exit
+/// callback on a thread of its own, so plain `delay` is fine for most
+/// programs. Kernel modules get no exit callback from `module!`, so one
+/// that registers its own on a thread that also does long waits needs
+/// this there, or exiting through the Home menu stalls until the wait
+/// ends.
+pub fn delay_cb(micros: u32) {
+    unsafe { sys::sceKernelDelayThreadCB(micros) };
+}
+
+/// `duration` in microseconds, rounded up.
+fn micros(duration: Duration) -> u128 {
+    duration.as_nanos().div_ceil(1000)
+}
+
+/// Let other ready threads of the same priority run before continuing.
+///
+/// The calling thread stays ready, so threads of lower priority (higher
+/// numbers) still don't get to run. Use `delay` to let them.
+pub fn yield_now() {
+    unsafe { sys::sceKernelRotateThreadReadyQueue(0) };
+}
+
+/// Bytes of the calling thread's stack that have never been used.
+///
+/// The kernel fills stacks with a pattern when threads are created, and
+/// this counts how much of it is left, so it is the low water mark rather
+/// than the current depth. It reads 0 for threads created with
+/// `ThreadAttributes::NO_FILLSTACK`.
+pub fn stack_free() -> usize {
+    unsafe {
+        let id = sys::sceKernelGetThreadId();
+        sys::sceKernelGetThreadStackFreeSize(sys::SceUid(id)).max(0) as usize
+    }
+}
+
+/// Information about the calling thread.
+pub fn current_info() -> ThreadInfo {
+    let id = unsafe { sys::sceKernelGetThreadId() };
+
+    let mut info = MaybeUninit::<SceKernelThreadInfo>::uninit();
+    let info = unsafe {
+        ptr::addr_of_mut!((*info.as_mut_ptr()).size).write(mem::size_of::<SceKernelThreadInfo>());
+        let ret = sys::sceKernelReferThreadStatus(sys::SceUid(id), info.as_mut_ptr());
+        assert!(ret >= 0, "failed to read thread status: {:#x}", ret);
+
+        info.assume_init()
+    };
+
+    let len = info.name.iter().position(|&b| b == 0).unwrap_or(32);
+
+    ThreadInfo {
+        id,
+        name: String::from_utf8_lossy(&info.name[..len]).into_owned(),
+        status: ThreadStatus::from_bits_truncate(info.status),
+        priority: info.current_priority,
+        initial_priority: info.init_priority,
+        stack_size: info.stack_size.max(0) as usize,
+        stack_free: stack_free(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The name contains a NUL byte.
+    InvalidName,
+    /// The kernel couldn't create or start the thread, such as for lack of
+    /// memory for its stack, or a priority outside 8 to 119.
+    Kernel(i32),
+}
+
+/// Settings for a new thread, started with `spawn`.
+///
+/// ```no_run
+/// let loader = psp::thread::Builder::new()
+///     .name("loader")
+///     .priority(40)
+///     .stack_size(0x4000)
+///     .spawn(|| load_level(3))
+///     .unwrap();
+///
+/// // Draw a loading screen meanwhile.
+/// let level = loader.join().unwrap();
+/// # fn load_level(_: u32) {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    name: String,
+    priority: i32,
+    stack_size: usize,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            name: DEFAULT_NAME.into(),
+            priority: DEFAULT_PRIORITY,
+            stack_size: DEFAULT_STACK_SIZE,
+        }
+    }
+
+    /// The thread's name, as `current_info` and PSPLink show it. The kernel
+    /// keeps the first 31 bytes.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Lower is more urgent. The kernel takes 8 to 119.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Stack size in bytes. Stacks don't grow, see `stack_free`.
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = stack_size;
+        self
+    }
+
+    /// Run `f` on a new thread, which can use the VFPU.
+    ///
+    /// The thread is deleted when `f` returns, once the `JoinHandle` is
+    /// joined or dropped. A panic in `f` ends the thread, and is returned
+    /// by `join`.
+    pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if self.name.contains('\0') {
+            return Err(SpawnError::InvalidName);
+        }
+
+        let mut name: Vec<u8> = self.name.into();
+        name.push(0);
+
+        let packet = Arc::new(Packet {
+            state: AtomicU8::new(RUNNING),
+            result: UnsafeCell::new(None),
+        });
+
+        let start = Box::into_raw(Box::new(Start {
+            f,
+            packet: packet.clone(),
+        }));
+
+        unsafe {
+            let thread = sys::sceKernelCreateThread(
+                name.as_ptr(),
+                trampoline::<F, T>,
+                self.priority,
+                self.stack_size as i32,
+                ThreadAttributes::USER | ThreadAttributes::VFPU,
+                ptr::null_mut(),
+            );
+
+            if thread.0 < 0 {
+                drop(Box::from_raw(start));
+                return Err(SpawnError::Kernel(thread.0));
+            }
+
+            // The kernel copies the pointer onto the thread's stack.
+            let ret = sys::sceKernelStartThread(
+                thread,
+                mem::size_of::<*mut Start<F, T>>(),
+                &start as *const *mut Start<F, T> as *mut c_void,
+            );
+
+            if ret < 0 {
+                sys::sceKernelDeleteThread(thread);
+                drop(Box::from_raw(start));
+                return Err(SpawnError::Kernel(ret));
+            }
+
+            Ok(JoinHandle { thread, packet })
+        }
+    }
+}
+
+/// Run `f` on a new thread, with the `Builder` defaults.
+///
+/// # Panics
+///
+/// Panics if the kernel can't create the thread, see `Builder::spawn`.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Builder::new().spawn(f).expect("failed to spawn thread")
+}
+
+/// A thread started by `spawn`, and the value it returns.
+///
+/// Dropping the handle detaches the thread: it keeps running, and deletes
+/// itself when it ends.
+pub struct JoinHandle<T> {
+    thread: SceUid,
+    packet: Arc<Packet<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// The thread's UID.
+    pub fn id(&self) -> i32 {
+        self.thread.0
+    }
+
+    /// Whether the thread's closure has returned or panicked, so `join`
+    /// won't wait.
+    pub fn is_finished(&self) -> bool {
+        self.packet.state.load(Ordering::Acquire) == FINISHED
+    }
+
+    /// Wait for the thread to end and delete it, returning what its closure
+    /// returned, or the panic that ended it.
+    ///
+    /// Joining the thread from itself waits forever.
+    pub fn join(self) -> Result<T, Box<dyn Any + Send>> {
+        unsafe {
+            sys::sceKernelWaitThreadEnd(self.thread, ptr::null_mut());
+            sys::sceKernelDeleteThread(self.thread);
+        }
+
+        // Moved out without running `Drop`, which would detach the thread.
+        let packet = unsafe { ptr::read(&self.packet) };
+        mem::forget(self);
+
+        // The thread has ended, so the result is no longer shared.
+        unsafe { (*packet.result.get()).take() }.expect("thread ended without a result")
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        // A thread that finished first has nobody left to delete it. One
+        // still running sees `DETACHED` when it finishes, and deletes
+        // itself.
+        if self.packet.state.swap(DETACHED, Ordering::AcqRel) == FINISHED {
+            unsafe {
+                sys::sceKernelWaitThreadEnd(self.thread, ptr::null_mut());
+                sys::sceKernelDeleteThread(self.thread);
+            }
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("id", &self.thread.0)
+            .finish()
+    }
+}
+
+/// State shared between a `JoinHandle` and its thread.
+struct Packet<T> {
+    /// `RUNNING`, until the thread stores its result and sets `FINISHED`,
+    /// or the handle is dropped and sets `DETACHED`.
+    state: AtomicU8,
+    /// Written by the thread before it sets `FINISHED`, and read by the
+    /// handle after the thread has ended.
+    result: UnsafeCell<Option<Result<T, Box<dyn Any + Send>>>>,
+}
+
+// The result is only written by the thread, then read after it ends.
+unsafe impl<T: Send> Send for Packet<T> {}
+unsafe impl<T: Send> Sync for Packet<T> {}
+
+/// What a new thread is given, through its `argp`.
+struct Start<F, T> {
+    f: F,
+    packet: Arc<Packet<T>>,
+}
+
+unsafe extern "C" fn trampoline<F, T>(_args: usize, argp: *mut c_void) -> i32
+where
+    F: FnOnce() -> T,
+{
+    let start = Box::from_raw(*(argp as *mut *mut Start<F, T>));
+    let Start { f, packet } = *start;
+
+    let result = crate::catch_unwind(AssertUnwindSafe(f));
+    *packet.result.get() = Some(result);
+
+    let detached = packet.state.swap(FINISHED, Ordering::AcqRel) == DETACHED;
+    drop(packet);
+
+    if detached {
+        // Frees the kernel thread. The handle is gone, so nothing else will.
+        sys::sceKernelExitDeleteThread(0);
+    }
+
+    0
+}
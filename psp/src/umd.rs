@@ -0,0 +1,163 @@
+//! The UMD drive.
+//!
+//! ```no_run
+//! use psp::umd::{self, UmdError};
+//!
+//! match umd::mount() {
+//!     Ok(disc) => {
+//!         let level = disc.open("PSP_GAME/USRDIR/level1.dat");
+//!         // ...
+//!     }
+//!     Err(UmdError::NoDisc) => psp::dprintln!("Insert the game disc."),
+//!     Err(UmdError::WrongDisc(_)) => psp::dprintln!("That's not a game disc."),
+//!     Err(e) => psp::dprintln!("Can't read the disc: {:?}", e),
+//! }
+//! ```
+//!
+//! While the guard returned by `mount` is alive, files can be read from
+//! `disc0:/` or `umd0:/` with `io::File`, as well as through the guard.
+//! `wait_ready` mounts the disc for the rest of the program instead.
+//!
+//! The PSP Go has no drive, and neither do emulators unless they were
+//! started from a disc image. There, `is_present` is false, and `wait_ready`
+//! fails with `UmdError::NoDisc` right away, rather than waiting for a disc
+//! that can't be inserted. Apps installed to the Memory Stick don't need the
+//! drive at all.
+
+use crate::io::{File, IoError};
+use crate::sys::{self, UmdInfo, UmdStateFlags};
+use crate::ERROR_WAIT_TIMEOUT;
+use alloc::format;
+use core::mem;
+use core::time::Duration;
+
+/// How long `wait_ready` waits for the disc to spin up.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The device the disc is mounted as.
+const DRIVE: &[u8] = b"disc0:\0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UmdError {
+    /// There is no disc in the drive, or no drive.
+    NoDisc,
+    /// The disc wasn't ready within `READY_TIMEOUT`.
+    Timeout,
+    /// The disc is not a game disc, such as a UMD Video, so its files
+    /// aren't the program's.
+    WrongDisc(DiscKind),
+    /// The kernel returned an error code.
+    Kernel(i32),
+}
+
+/// What a disc holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscKind {
+    Game,
+    Video,
+    Audio,
+    /// A type the kernel reported that isn't one of the above.
+    Other(u32),
+}
+
+/// Information about the disc in the drive, from `UmdGuard::disc_info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscInfo {
+    pub kind: DiscKind,
+}
+
+/// Whether there is a disc in the drive.
+pub fn is_present() -> bool {
+    unsafe { sys::sceUmdCheckMedium() != 0 }
+}
+
+/// Mount the disc at `disc0:`, and wait for it to be ready to read.
+///
+/// This fails with `UmdError::NoDisc` if there is no disc, rather than
+/// waiting for one.
+pub fn wait_ready() -> Result<(), UmdError> {
+    if !is_present() {
+        return Err(UmdError::NoDisc);
+    }
+
+    let ret = unsafe { sys::sceUmdActivate(1, DRIVE.as_ptr()) };
+    if ret < 0 {
+        return Err(UmdError::Kernel(ret));
+    }
+
+    let ret = unsafe {
+        sys::sceUmdWaitDriveStatWithTimer(UmdStateFlags::READY, READY_TIMEOUT.as_micros() as u32)
+    };
+
+    match ret {
+        ERROR_WAIT_TIMEOUT => Err(UmdError::Timeout),
+        // The disc was taken out while waiting.
+        _ if !is_present() => Err(UmdError::NoDisc),
+        e if e < 0 => Err(UmdError::Kernel(e)),
+        _ => Ok(()),
+    }
+}
+
+/// Mount a game disc at `disc0:`, and wait for it to be ready to read. The
+/// disc is unmounted when the returned guard is dropped.
+///
+/// This fails with `UmdError::NoDisc` if there is no disc, rather than
+/// waiting for one, and with `UmdError::WrongDisc` if it isn't a game disc.
+pub fn mount() -> Result<UmdGuard, UmdError> {
+    wait_ready()?;
+
+    // Unmounts on any error below.
+    let mut guard = UmdGuard {
+        info: DiscInfo {
+            kind: DiscKind::Other(0),
+        },
+    };
+
+    // Read as a number, as the kernel may report a type `UmdType` lacks.
+    let mut info = [mem::size_of::<UmdInfo>() as u32, 0];
+    let ret = unsafe { sys::sceUmdGetDiscInfo(info.as_mut_ptr() as *mut UmdInfo) };
+    if ret < 0 {
+        return Err(UmdError::Kernel(ret));
+    }
+
+    let kind = match info[1] {
+        0x10 => DiscKind::Game,
+        0x20 => DiscKind::Video,
+        0x40 => DiscKind::Audio,
+        other => DiscKind::Other(other),
+    };
+
+    if kind != DiscKind::Game {
+        return Err(UmdError::WrongDisc(kind));
+    }
+
+    guard.info.kind = kind;
+    Ok(guard)
+}
+
+/// A mounted game disc, from `mount`. Unmounted on drop.
+///
+/// Files opened on the disc should be dropped first, as reading them fails
+/// once it is unmounted.
+#[derive(Debug)]
+pub struct UmdGuard {
+    info: DiscInfo,
+}
+
+impl UmdGuard {
+    pub fn disc_info(&self) -> DiscInfo {
+        self.info
+    }
+
+    /// Open the file at `path` on the disc, relative to its root, such as
+    /// `"PSP_GAME/USRDIR/level1.dat"`.
+    pub fn open(&self, path: &str) -> Result<File, IoError> {
+        File::open(&format!("disc0:/{}", path.trim_start_matches('/')))
+    }
+}
+
+impl Drop for UmdGuard {
+    fn drop(&mut self) {
+        unsafe { sys::sceUmdDeactivate(1, DRIVE.as_ptr()) };
+    }
+}
@@ -0,0 +1,385 @@
+//! Running system utility dialogs (messages, on-screen keyboard, network
+//! configuration and savedata) on top of the [`Gu`] wrapper.
+//!
+//! Dialogs draw over whatever the application renders, and must be updated
+//! once per frame, after the display list has finished and before the buffers
+//! are swapped. [`run_dialog`] takes care of this; the dialog types in this
+//! module all go through it.
+
+use crate::gu::{Frame, Gu};
+use crate::sys::{
+    self, PspUtilityDialogState, SceUtilityOskData, SceUtilityOskInputLanguage,
+    SceUtilityOskInputType, SceUtilityOskParams, SceUtilityOskResult, SceUtilitySavedataParam,
+    SystemParamId, SystemParamLanguage, UtilityDialogButtonAccept, UtilityDialogCommon,
+    UtilityMsgDialogMode, UtilityMsgDialogOption, UtilityMsgDialogParams, UtilityMsgDialogPressed,
+    UtilityNetconfAction, UtilityNetconfData,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::{mem, ptr};
+
+/// Vertical blanks to wait after a dialog has shut down, before another one
+/// can be started.
+const SHUTDOWN_VBLANKS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogError {
+    /// The `InitStart` call failed with this error code. Usually another
+    /// dialog is still active.
+    Init(i32),
+    /// `GetStatus` returned this error code while the dialog was running.
+    Status(i32),
+}
+
+/// One of the `sceUtility` dialogs, driven by [`run_dialog`].
+///
+/// Each method maps to the dialog's `InitStart`, `GetStatus`, `Update` and
+/// `ShutdownStart` functions.
+pub trait DialogState {
+    /// What the dialog returns once it has shut down.
+    type Output;
+
+    /// Start the dialog, returning the raw result of `InitStart`.
+    ///
+    /// # Safety
+    ///
+    /// The parameters handed to the system may point into `self`, so `self`
+    /// must not move or be dropped until the dialog has shut down.
+    /// `run_dialog` upholds this.
+    unsafe fn init_start(&mut self) -> i32;
+
+    /// The raw result of `GetStatus`.
+    fn status(&self) -> i32;
+
+    fn update(&mut self);
+
+    fn shutdown_start(&mut self);
+
+    /// Collect the result, after the dialog has shut down.
+    fn result(&mut self) -> Self::Output;
+}
+
+/// Run `dialog` until it has shut down, rendering a frame with `per_frame`
+/// each time it is updated.
+///
+/// `per_frame` draws what should show behind the dialog. It should not swap
+/// buffers or finish the frame.
+pub fn run_dialog<D, F>(
+    gu: &mut Gu,
+    dialog: &mut D,
+    mut per_frame: F,
+) -> Result<D::Output, DialogError>
+where
+    D: DialogState,
+    F: FnMut(&mut Frame<'_>),
+{
+    let ret = unsafe { dialog.init_start() };
+    if ret < 0 {
+        return Err(DialogError::Init(ret));
+    }
+
+    loop {
+        let mut frame = gu.start_frame();
+        per_frame(&mut frame);
+
+        // The dialog renders on top of the finished list.
+        frame.finish();
+
+        let status = dialog.status();
+        match PspUtilityDialogState::try_from(status as u32) {
+            Ok(PspUtilityDialogState::Visible) => dialog.update(),
+            Ok(PspUtilityDialogState::Quit) => dialog.shutdown_start(),
+            Ok(PspUtilityDialogState::None) => break,
+            Ok(PspUtilityDialogState::Init) | Ok(PspUtilityDialogState::Finished) => (),
+            Err(_) => return Err(DialogError::Status(status)),
+        }
+
+        gu.swap_buffers(true);
+    }
+
+    // The utility keeps its resources for a few frames after reporting that
+    // it is done, and fails to start the next dialog until they are freed.
+    for _ in 0..SHUTDOWN_VBLANKS {
+        unsafe { sys::sceDisplayWaitVblankStart() };
+    }
+
+    Ok(dialog.result())
+}
+
+/// Common dialog parameters for a parameter struct of type `T`, using the
+/// system language.
+pub fn dialog_common<T>() -> UtilityDialogCommon {
+    let mut language = 0;
+    unsafe { sys::sceUtilityGetSystemParamInt(SystemParamId::Language, &mut language) };
+
+    UtilityDialogCommon {
+        size: mem::size_of::<T>() as u32,
+        language: SystemParamLanguage::try_from(language as u32)
+            .unwrap_or(SystemParamLanguage::English),
+        button_accept: UtilityDialogButtonAccept::Cross,
+        // Thread priorities used by the PSPSDK samples.
+        graphics_thread: 0x11,
+        access_thread: 0x13,
+        font_thread: 0x12,
+        sound_thread: 0x10,
+        result: 0,
+        reserved: [0; 4],
+    }
+}
+
+/// A message dialog, showing either a text or a system error message.
+pub struct MsgDialog {
+    params: UtilityMsgDialogParams,
+}
+
+impl MsgDialog {
+    /// A dialog showing `message`. It is truncated to 511 bytes.
+    pub fn text(message: &str) -> Self {
+        let mut buf = [0; 512];
+        let len = message.len().min(buf.len() - 1);
+        buf[..len].copy_from_slice(&message.as_bytes()[..len]);
+
+        Self::new(UtilityMsgDialogMode::Text, 0, buf)
+    }
+
+    /// A dialog showing the system's description of `error`.
+    pub fn error(error: u32) -> Self {
+        Self::new(UtilityMsgDialogMode::Error, error, [0; 512])
+    }
+
+    fn new(mode: UtilityMsgDialogMode, error_value: u32, message: [u8; 512]) -> Self {
+        let options = match mode {
+            UtilityMsgDialogMode::Error => UtilityMsgDialogOption::ERROR,
+            UtilityMsgDialogMode::Text => UtilityMsgDialogOption::TEXT,
+        };
+
+        Self {
+            params: UtilityMsgDialogParams {
+                base: dialog_common::<UtilityMsgDialogParams>(),
+                unknown: 0,
+                mode,
+                error_value,
+                message,
+                options,
+                button_pressed: UtilityMsgDialogPressed::Unknown1,
+            },
+        }
+    }
+
+    /// Show yes and no buttons instead of a single one. If `default_no` is
+    /// set, "no" is selected initially.
+    pub fn yes_no(mut self, default_no: bool) -> Self {
+        self.params.options |= UtilityMsgDialogOption::YES_NO_BUTTONS;
+
+        if default_no {
+            self.params.options |= UtilityMsgDialogOption::DEFAULT_NO;
+        }
+
+        self
+    }
+}
+
+impl DialogState for MsgDialog {
+    /// The button the user closed the dialog with.
+    type Output = UtilityMsgDialogPressed;
+
+    unsafe fn init_start(&mut self) -> i32 {
+        sys::sceUtilityMsgDialogInitStart(&mut self.params)
+    }
+
+    fn status(&self) -> i32 {
+        unsafe { sys::sceUtilityMsgDialogGetStatus() }
+    }
+
+    fn update(&mut self) {
+        unsafe { sys::sceUtilityMsgDialogUpdate(1) }
+    }
+
+    fn shutdown_start(&mut self) {
+        unsafe { sys::sceUtilityMsgDialogShutdownStart() }
+    }
+
+    fn result(&mut self) -> UtilityMsgDialogPressed {
+        self.params.button_pressed
+    }
+}
+
+/// The on-screen keyboard, with a single input field.
+pub struct OskDialog {
+    params: SceUtilityOskParams,
+    data: SceUtilityOskData,
+    desc: Vec<u16>,
+    initial: Vec<u16>,
+    out: Vec<u16>,
+}
+
+impl OskDialog {
+    /// A keyboard titled `desc`, starting out with `initial` and accepting up
+    /// to `max_len` characters.
+    pub fn new(desc: &str, initial: &str, max_len: usize) -> Self {
+        let desc = utf16_nul(desc);
+        let initial = utf16_nul(initial);
+        let out = alloc::vec![0; max_len + 1];
+
+        Self {
+            params: SceUtilityOskParams {
+                base: dialog_common::<SceUtilityOskParams>(),
+                datacount: 1,
+                data: ptr::null_mut(),
+                state: PspUtilityDialogState::None,
+                unk_60: 0,
+            },
+            data: SceUtilityOskData {
+                unk_00: 0,
+                unk_04: 0,
+                language: SceUtilityOskInputLanguage::Default,
+                unk_12: 0,
+                inputtype: SceUtilityOskInputType::All,
+                lines: 1,
+                unk_24: 0,
+                desc: ptr::null_mut(),
+                intext: ptr::null_mut(),
+                outtextlength: out.len() as i32,
+                outtext: ptr::null_mut(),
+                result: SceUtilityOskResult::Unchanged,
+                outtextlimit: max_len as i32,
+            },
+            desc,
+            initial,
+            out,
+        }
+    }
+}
+
+impl DialogState for OskDialog {
+    /// The entered text, or `None` if the user cancelled.
+    type Output = Option<String>;
+
+    unsafe fn init_start(&mut self) -> i32 {
+        // Only now is `self` known not to move until shutdown.
+        self.data.desc = self.desc.as_mut_ptr();
+        self.data.intext = self.initial.as_mut_ptr();
+        self.data.outtext = self.out.as_mut_ptr();
+        self.params.data = &mut self.data;
+
+        sys::sceUtilityOskInitStart(&mut self.params)
+    }
+
+    fn status(&self) -> i32 {
+        unsafe { sys::sceUtilityOskGetStatus() }
+    }
+
+    fn update(&mut self) {
+        unsafe { sys::sceUtilityOskUpdate(1) };
+    }
+
+    fn shutdown_start(&mut self) {
+        unsafe { sys::sceUtilityOskShutdownStart() };
+    }
+
+    fn result(&mut self) -> Option<String> {
+        match self.data.result {
+            SceUtilityOskResult::Cancelled => None,
+            _ => {
+                let len = self
+                    .out
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or(self.out.len());
+                Some(String::from_utf16_lossy(&self.out[..len]))
+            }
+        }
+    }
+}
+
+/// The network configuration dialog.
+///
+/// The `NetCommon` and `NetInet` modules must be loaded, and the network
+/// libraries initialized, before running it.
+pub struct NetconfDialog {
+    params: UtilityNetconfData,
+}
+
+impl NetconfDialog {
+    pub fn new(action: UtilityNetconfAction) -> Self {
+        Self {
+            params: UtilityNetconfData {
+                base: dialog_common::<UtilityNetconfData>(),
+                action,
+                adhocparam: ptr::null_mut(),
+                hotspot: 0,
+                hotspot_connected: 0,
+                wifisp: 0,
+            },
+        }
+    }
+}
+
+impl DialogState for NetconfDialog {
+    /// Whether a connection was established.
+    type Output = bool;
+
+    unsafe fn init_start(&mut self) -> i32 {
+        sys::sceUtilityNetconfInitStart(&mut self.params)
+    }
+
+    fn status(&self) -> i32 {
+        unsafe { sys::sceUtilityNetconfGetStatus() }
+    }
+
+    fn update(&mut self) {
+        unsafe { sys::sceUtilityNetconfUpdate(1) };
+    }
+
+    fn shutdown_start(&mut self) {
+        unsafe { sys::sceUtilityNetconfShutdownStart() };
+    }
+
+    fn result(&mut self) -> bool {
+        self.params.base.result == 0
+    }
+}
+
+/// The savedata utility, for loading, saving or deleting saves.
+///
+/// The parameters are passed through as is; `base` can be filled in with
+/// [`dialog_common`].
+pub struct SavedataDialog {
+    pub params: SceUtilitySavedataParam,
+}
+
+impl SavedataDialog {
+    pub fn new(params: SceUtilitySavedataParam) -> Self {
+        Self { params }
+    }
+}
+
+impl DialogState for SavedataDialog {
+    /// The utility's result code, 0 on success.
+    type Output = i32;
+
+    unsafe fn init_start(&mut self) -> i32 {
+        sys::sceUtilitySavedataInitStart(&mut self.params)
+    }
+
+    fn status(&self) -> i32 {
+        unsafe { sys::sceUtilitySavedataGetStatus() }
+    }
+
+    fn update(&mut self) {
+        unsafe { sys::sceUtilitySavedataUpdate(1) };
+    }
+
+    fn shutdown_start(&mut self) {
+        unsafe { sys::sceUtilitySavedataShutdownStart() };
+    }
+
+    fn result(&mut self) -> i32 {
+        self.params.base.result
+    }
+}
+
+fn utf16_nul(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(core::iter::once(0)).collect()
+}
@@ -1,34 +1,119 @@
+use crate::sync::Once;
 use crate::sys::TexturePixelFormat;
-use crate::sys::{sceGeEdramGetAddr, sceGeEdramGetSize};
+use crate::sys::{sceGeEdramGetAddr, sceGeEdramGetSize, sceGeEdramSetAddrTranslation};
 use core::marker::PhantomData;
 use core::mem::size_of;
 use core::ptr::null_mut;
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 type VramAllocator = SimpleVramAllocator;
 
 #[derive(Debug)]
 pub struct VramAllocatorInUseError {}
 
-static mut VRAM_ALLOCATOR: VramAllocatorSingleton = VramAllocatorSingleton {
-    alloc: Some(VramAllocator::new()),
-};
+/// Returned when an allocation does not fit in the remaining VRAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VramOutOfMemoryError {
+    pub requested: usize,
+    pub remaining: usize,
+}
 
-pub fn get_vram_allocator() -> Result<VramAllocator, VramAllocatorInUseError> {
-    let opt_alloc = unsafe { VRAM_ALLOCATOR.get_vram_alloc() };
-    opt_alloc.ok_or(VramAllocatorInUseError {})
+/// Size of VRAM in bytes, queried once. 0 until then.
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+/// Start of the next allocation. Shared by all allocator handles, so that
+/// VRAM reserved before the allocator is handed out stays reserved.
+static OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+/// Where `free_all` rewinds to, the end of the reserved VRAM.
+static BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// Total size of VRAM in bytes, as reported by the GE.
+///
+/// This is 2 MiB on most models, but is queried rather than assumed.
+pub fn total() -> usize {
+    match TOTAL.load(Ordering::Relaxed) {
+        0 => {
+            let size = unsafe { sceGeEdramGetSize() };
+            TOTAL.store(size, Ordering::Relaxed);
+            size
+        }
+        size => size,
+    }
 }
 
-pub struct VramAllocatorSingleton {
-    alloc: Option<VramAllocator>,
+/// VRAM in bytes not yet handed out by the allocator.
+pub fn remaining() -> usize {
+    total().saturating_sub(OFFSET.load(Ordering::Relaxed))
+}
+
+/// Set the eDRAM address translation width, returning the previous width.
+///
+/// `width` must be 0 (leave unchanged), 512, 1024, 2048 or 4096; other values
+/// are rejected with `Err(None)`. Errors from the GE are returned as
+/// `Err(Some(code))`.
+///
+/// Translation only changes how the eDRAM is addressed, not its size: the
+/// allocator never hands out memory past `total()`.
+pub fn set_addr_translation(width: u32) -> Result<u32, Option<i32>> {
+    match width {
+        0 | 512 | 1024 | 2048 | 4096 => (),
+        _ => return Err(None),
+    }
+
+    match unsafe { sceGeEdramSetAddrTranslation(width as i32) } {
+        e if e < 0 => Err(Some(e)),
+        prev => Ok(prev as u32),
+    }
 }
 
-impl VramAllocatorSingleton {
-    pub fn get_vram_alloc(&mut self) -> Option<VramAllocator> {
-        self.alloc.take()
+/// Reserve `size` bytes for the debug console's framebuffer, returning its
+/// offset into VRAM.
+///
+/// If the allocator has not been handed out yet, the memory is taken from it
+/// permanently. Otherwise, the allocator's owner is in charge of VRAM, and
+/// the console shares the start of VRAM (usually the first framebuffer) with
+/// it.
+pub(crate) fn reserve_console(size: usize) -> usize {
+    if HANDED_OUT.is_completed() {
+        return 0;
+    }
+
+    match reserve(size) {
+        Ok(offset) => {
+            BASE.store(offset + size, Ordering::Relaxed);
+            offset
+        }
+        Err(_) => 0,
     }
 }
 
+/// Bump `OFFSET` by `size`, returning the old offset.
+fn reserve(size: usize) -> Result<usize, VramOutOfMemoryError> {
+    let total = total();
+
+    OFFSET
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+            offset.checked_add(size).filter(|end| *end <= total)
+        })
+        .map_err(|offset| VramOutOfMemoryError {
+            requested: size,
+            remaining: total.saturating_sub(offset),
+        })
+}
+
+/// Completed once the allocator has been handed out.
+static HANDED_OUT: Once = Once::new();
+
+/// Take the allocator, which is only handed out once.
+pub fn get_vram_allocator() -> Result<VramAllocator, VramAllocatorInUseError> {
+    total();
+
+    let mut alloc = None;
+    HANDED_OUT.call_once(|| alloc = Some(VramAllocator::new()));
+    alloc.ok_or(VramAllocatorInUseError {})
+}
+
 pub struct VramMemChunk<'a> {
     start: u32,
     len: u32,
@@ -66,14 +151,12 @@ impl VramMemChunk<'_> {
 // TODO: pin?
 #[derive(Debug)]
 pub struct SimpleVramAllocator {
-    offset: AtomicU32,
+    _private: (),
 }
 
 impl SimpleVramAllocator {
     const fn new() -> Self {
-        Self {
-            offset: AtomicU32::new(0),
-        }
+        Self { _private: () }
     }
 
     /// Frees all previously allocated VRAM chunks.
@@ -82,25 +165,31 @@ impl SimpleVramAllocator {
     /// VRAM. Since this method requires `&mut Self`, it cannot overlap with any
     /// previously allocated `VramMemChunk`s since they have the lifetime of the
     /// `&Self` that allocated them.
+    ///
+    /// VRAM reserved by the debug console stays reserved.
     pub fn free_all(&mut self) {
-        self.offset.store(0, Ordering::Relaxed);
+        OFFSET.store(BASE.load(Ordering::Relaxed), Ordering::Relaxed);
     }
 
-    // TODO: return a Result instead of panicking
     /// Allocates `size` bytes of VRAM
     ///
     /// The returned VRAM chunk has the same lifetime as the
     /// `SimpleVramAllocator` borrow (i.e. `&self`) that allocated it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not enough VRAM left, see `try_alloc`.
     pub fn alloc(&self, size: u32) -> VramMemChunk<'_> {
-        let old_offset = self.offset.load(Ordering::Relaxed);
-        let new_offset = old_offset + size;
-        self.offset.store(new_offset, Ordering::Relaxed);
-
-        if new_offset > self.total_mem() {
-            panic!("Total VRAM size exceeded!");
+        match self.try_alloc(size) {
+            Ok(chunk) => chunk,
+            Err(_) => panic!("Total VRAM size exceeded!"),
         }
+    }
 
-        VramMemChunk::new(old_offset, size)
+    /// Allocates `size` bytes of VRAM, failing if that would go past the end
+    /// of VRAM as reported by `total()`.
+    pub fn try_alloc(&self, size: u32) -> Result<VramMemChunk<'_>, VramOutOfMemoryError> {
+        reserve(size as usize).map(|offset| VramMemChunk::new(offset as u32, size))
     }
 
     // TODO: ensure 16-bit alignment?
@@ -119,6 +208,13 @@ impl SimpleVramAllocator {
         self.alloc(size)
     }
 
+    /// Allocates a 16-bit depth buffer for `width` x `height` pixels, for
+    /// `sceGuDepthBuffer`. `width` is the buffer's stride, such as
+    /// `BUF_WIDTH`, not the visible width.
+    pub fn alloc_depth_buffer(&self, width: u32, height: u32) -> VramMemChunk<'_> {
+        self.alloc(2 * width * height)
+    }
+
     // TODO: write, or write_volatile?
     // TODO: result instead of unwrap?
     // TODO: Keep track of the allocated chunk
@@ -129,14 +225,6 @@ impl SimpleVramAllocator {
         ptr.write(obj);
         ptr.as_mut().unwrap()
     }
-
-    fn total_mem(&self) -> u32 {
-        total_vram_size()
-    }
-}
-
-fn total_vram_size() -> u32 {
-    unsafe { sceGeEdramGetSize() }
 }
 
 // NOTE: VRAM actually starts at 0x4000000, as returned by sceGeEdramGetAddr.
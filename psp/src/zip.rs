@@ -0,0 +1,411 @@
+//! Reading ZIP archives, so assets can ship as one file instead of many
+//! small ones, which are slow to open on a Memory Stick.
+//!
+//! ```no_run
+//! use psp::io::File;
+//! use psp::zip::Archive;
+//!
+//! let mut archive = Archive::open(File::open("data.zip").unwrap()).unwrap();
+//!
+//! let mut png = alloc::vec::Vec::new();
+//! archive.by_name("textures/ferris.png").unwrap().read_to_end(&mut png).unwrap();
+//! let texture = psp::image::png::load(&png).unwrap();
+//! ```
+//!
+//! Only the central directory, the list of entries at the end of the
+//! archive, is kept in memory. Entries are read from the file as they are
+//! decompressed, and only stored and deflate compressed entries are
+//! supported, which is what most tools write. ZIP64 archives, for more than
+//! 4 GiB or 65535 entries, aren't supported.
+
+use crate::crc32;
+use crate::image::inflate::{InflateError, Inflater, Input};
+use crate::io::{File, IoError, SeekFrom};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_SIG: u32 = 0x0605_4b50;
+const ZIP64_LOCATOR_SIG: u32 = 0x0706_4b50;
+
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_LEN: usize = 22;
+const ZIP64_LOCATOR_LEN: usize = 20;
+/// The archive comment after the end record is at most this long.
+const MAX_COMMENT_LEN: usize = 0xffff;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+const FLAG_ENCRYPTED: u16 = 1;
+
+/// Compressed data read from the file at a time.
+const READ_BUF_LEN: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipError {
+    Io(IoError),
+    /// The file has no end of central directory record, so it isn't a ZIP
+    /// archive, or has been cut short.
+    NotZip,
+    /// The archive is in the ZIP64 format.
+    Zip64,
+    /// The archive is split across several files.
+    MultiDisk,
+    /// No entry has the name.
+    NotFound,
+    /// The entry is compressed with a method other than stored (0) or
+    /// deflate (8).
+    UnsupportedMethod(u16),
+    /// The entry is encrypted.
+    Encrypted,
+    /// A record or an entry's compressed data is invalid, or an entry
+    /// doesn't match its size or checksum.
+    Corrupt,
+}
+
+impl From<IoError> for ZipError {
+    fn from(error: IoError) -> Self {
+        ZipError::Io(error)
+    }
+}
+
+/// An open ZIP archive.
+pub struct Archive {
+    file: File,
+    entries: Vec<EntryInfo>,
+}
+
+/// An entry's record from the central directory.
+struct EntryInfo {
+    name: String,
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    size: u32,
+    header_offset: u32,
+}
+
+impl Archive {
+    /// Read the central directory of the archive in `file`.
+    pub fn open(mut file: File) -> Result<Self, ZipError> {
+        // The end record is at the very end, but for the comment after it.
+        let len = file.seek(SeekFrom::End(0))?;
+        let tail_len = len.min((END_LEN + MAX_COMMENT_LEN) as u64) as usize;
+        let mut tail = vec![0; tail_len];
+        file.seek(SeekFrom::Start(len - tail_len as u64))?;
+        read_exact(&mut file, &mut tail)?;
+
+        let end = (0..=tail_len.checked_sub(END_LEN).ok_or(ZipError::NotZip)?)
+            .rev()
+            .find(|&i| u32_at(&tail, i) == END_SIG)
+            .ok_or(ZipError::NotZip)?;
+
+        let locator = end.checked_sub(ZIP64_LOCATOR_LEN);
+        if locator.is_some_and(|i| u32_at(&tail, i) == ZIP64_LOCATOR_SIG) {
+            return Err(ZipError::Zip64);
+        }
+
+        let record = &tail[end..];
+        let (disk, directory_disk) = (u16_at(record, 4), u16_at(record, 6));
+        let (disk_count, count) = (u16_at(record, 8), u16_at(record, 10));
+        let directory_len = u32_at(record, 12);
+        let directory_offset = u32_at(record, 16);
+
+        if count == 0xffff || directory_len == 0xffff_ffff || directory_offset == 0xffff_ffff {
+            return Err(ZipError::Zip64);
+        }
+
+        if disk != 0 || directory_disk != 0 || disk_count != count {
+            return Err(ZipError::MultiDisk);
+        }
+
+        drop(tail);
+
+        // Checked before allocating, the length could be anything.
+        let end_offset = len - tail_len as u64 + end as u64;
+        if directory_offset as u64 + directory_len as u64 > end_offset {
+            return Err(ZipError::Corrupt);
+        }
+
+        let mut directory = vec![0; directory_len as usize];
+        file.seek(SeekFrom::Start(directory_offset as u64))?;
+        read_exact(&mut file, &mut directory)?;
+
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut rest = &directory[..];
+
+        for _ in 0..count {
+            if rest.len() < CENTRAL_HEADER_LEN || u32_at(rest, 0) != CENTRAL_HEADER_SIG {
+                return Err(ZipError::Corrupt);
+            }
+
+            let name_len = u16_at(rest, 28) as usize;
+            let extra_len = u16_at(rest, 30) as usize;
+            let comment_len = u16_at(rest, 32) as usize;
+            let record_len = CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+            let name = rest
+                .get(CENTRAL_HEADER_LEN..CENTRAL_HEADER_LEN + name_len)
+                .filter(|_| rest.len() >= record_len)
+                .ok_or(ZipError::Corrupt)?;
+
+            entries.push(EntryInfo {
+                name: String::from_utf8_lossy(name).into(),
+                flags: u16_at(rest, 8),
+                method: u16_at(rest, 10),
+                crc32: u32_at(rest, 16),
+                compressed_size: u32_at(rest, 20),
+                size: u32_at(rest, 24),
+                header_offset: u32_at(rest, 42),
+            });
+
+            rest = &rest[record_len..];
+        }
+
+        Ok(Self { file, entries })
+    }
+
+    /// The number of entries, including directories.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The names of the entries, in the order they are stored. Directories
+    /// end in `/`.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.name.as_str())
+    }
+
+    /// Start reading the entry at `path`, such as `"textures/ferris.png"`.
+    pub fn by_name(&mut self, path: &str) -> Result<Entry<'_>, ZipError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.name == path)
+            .ok_or(ZipError::NotFound)?;
+
+        self.by_index(index)
+    }
+
+    /// Start reading entry `index`, in the order of `names`.
+    pub fn by_index(&mut self, index: usize) -> Result<Entry<'_>, ZipError> {
+        let info = self.entries.get(index).ok_or(ZipError::NotFound)?;
+
+        if info.flags & FLAG_ENCRYPTED != 0 {
+            return Err(ZipError::Encrypted);
+        }
+
+        if info.method != METHOD_STORED && info.method != METHOD_DEFLATE {
+            return Err(ZipError::UnsupportedMethod(info.method));
+        }
+
+        // The data follows the local header, which has its own name and
+        // extra field lengths.
+        let mut header = [0; LOCAL_HEADER_LEN];
+        self.file.seek(SeekFrom::Start(info.header_offset as u64))?;
+        read_exact(&mut self.file, &mut header)?;
+
+        if u32_at(&header, 0) != LOCAL_HEADER_SIG {
+            return Err(ZipError::Corrupt);
+        }
+
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        self.file.seek(SeekFrom::Current(skip))?;
+
+        let data = Compressed {
+            file: &mut self.file,
+            buf: vec![0; READ_BUF_LEN],
+            pos: 0,
+            len: 0,
+            left: info.compressed_size,
+            error: None,
+        };
+
+        let reader = match info.method {
+            METHOD_STORED => Reader::Stored(data),
+            _ => Reader::Deflated(Inflater::new(data)),
+        };
+
+        Ok(Entry {
+            name: &info.name,
+            size: info.size,
+            expected_crc32: info.crc32,
+            reader,
+            crc32: !0,
+            read: 0,
+        })
+    }
+}
+
+/// An entry being read from an `Archive`.
+pub struct Entry<'a> {
+    name: &'a str,
+    size: u32,
+    expected_crc32: u32,
+    reader: Reader<'a>,
+    /// The checksum of what has been read so far, inverted.
+    crc32: u32,
+    read: u32,
+}
+
+enum Reader<'a> {
+    Stored(Compressed<'a>),
+    Deflated(Inflater<Compressed<'a>>),
+}
+
+impl Entry<'_> {
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// The size of the entry's contents, once decompressed.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Read and decompress into `buf`, returning how many bytes were read,
+    /// which is only 0 at the end of the entry.
+    ///
+    /// The size and checksum are checked at the end, so an entry that is
+    /// read to the end without failing with `ZipError::Corrupt` is intact.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, ZipError> {
+        let len = match &mut self.reader {
+            Reader::Stored(data) => data.read(buf)?,
+            Reader::Deflated(inflater) => inflater.read(buf).map_err(|e| match e {
+                InflateError::Truncated => inflater
+                    .input()
+                    .error
+                    .map_or(ZipError::Corrupt, ZipError::Io),
+                InflateError::Corrupt => ZipError::Corrupt,
+            })?,
+        };
+
+        self.crc32 = crc32::update(self.crc32, &buf[..len]);
+        self.read = self.read.saturating_add(len as u32);
+
+        let done = len == 0 && !buf.is_empty();
+        if self.read > self.size
+            || done && (self.read != self.size || !self.crc32 != self.expected_crc32)
+        {
+            return Err(ZipError::Corrupt);
+        }
+
+        Ok(len)
+    }
+
+    /// Read the rest of the entry, appending it to `buf`. Returns the number
+    /// of bytes read.
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, ZipError> {
+        let start = buf.len();
+        buf.reserve(self.size.saturating_sub(self.read) as usize);
+
+        loop {
+            let len = buf.len();
+            buf.resize(len + READ_BUF_LEN.max(buf.capacity() - len), 0);
+
+            match self.read(&mut buf[len..]) {
+                Ok(0) => {
+                    buf.truncate(len);
+                    return Ok(len - start);
+                }
+                Ok(read) => buf.truncate(len + read),
+                Err(e) => {
+                    buf.truncate(len);
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// An entry's data, read from the archive a block at a time.
+struct Compressed<'a> {
+    file: &'a mut File,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    /// Bytes of the entry not yet read from the file.
+    left: u32,
+    /// Set if reading failed, as `Input` can only report the end of the data.
+    error: Option<IoError>,
+}
+
+impl Compressed<'_> {
+    /// Refill the buffer once it has been used up. Returns false at the end
+    /// of the entry.
+    fn fill(&mut self) -> Result<bool, IoError> {
+        if self.pos < self.len {
+            return Ok(true);
+        }
+
+        let want = (self.left as usize).min(self.buf.len());
+        if want == 0 {
+            return Ok(false);
+        }
+
+        let len = self.file.read(&mut self.buf[..want])?;
+        // The file ends before the entry does.
+        if len == 0 {
+            return Ok(false);
+        }
+
+        self.left -= len as u32;
+        self.pos = 0;
+        self.len = len;
+        Ok(true)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if buf.is_empty() || !self.fill()? {
+            return Ok(0);
+        }
+
+        let len = buf.len().min(self.len - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl Input for Compressed<'_> {
+    fn next_byte(&mut self) -> Result<u8, InflateError> {
+        match self.fill() {
+            Ok(true) => {
+                self.pos += 1;
+                Ok(self.buf[self.pos - 1])
+            }
+            Ok(false) => Err(InflateError::Truncated),
+            Err(e) => {
+                self.error = Some(e);
+                Err(InflateError::Truncated)
+            }
+        }
+    }
+}
+
+fn read_exact(file: &mut File, mut buf: &mut [u8]) -> Result<(), ZipError> {
+    while !buf.is_empty() {
+        match file.read(buf)? {
+            0 => return Err(ZipError::Corrupt),
+            len => buf = &mut buf[len..],
+        }
+    }
+
+    Ok(())
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}